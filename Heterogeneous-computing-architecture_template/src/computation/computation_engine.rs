@@ -1,56 +1,356 @@
 // Computation engine implementation
 
-use crate::hardware::abstract_device::DeviceManager;
-use crate::scheduling::{TaskScheduler, ResourceAllocator, AllocationStrategy};
-use crate::hardware::abstract_device::HardwareDevice;
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::{self, CancelToken, Capacity, HardwareDevice, HardwareManager, TaskResult};
+use crate::scheduling::Task;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
 
+/// Computation engine error types
+#[derive(Debug, Clone)]
+pub enum ComputationError {
+    HardwareNotAvailable(String),
+    TaskExecutionFailed(String),
+    ResourceAllocationFailed(String),
+    /// The task can never succeed as submitted, e.g. an unsupported operation
+    InvalidTask(String),
+    /// The task ran past its `TaskRequirements.timeout` and was cancelled
+    Timeout(String),
+    /// The task was cancelled while it ran
+    Cancelled(String),
+}
+
+impl ComputationError {
+    /// Whether running the task again could succeed
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, ComputationError::InvalidTask(_) | ComputationError::Cancelled(_))
+    }
+
+    fn from_device(error: HardwareError) -> Self {
+        if error.is_retryable() {
+            ComputationError::TaskExecutionFailed(error.to_string())
+        } else {
+            ComputationError::InvalidTask(error.to_string())
+        }
+    }
+}
+
+impl std::fmt::Display for ComputationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComputationError::HardwareNotAvailable(msg) => write!(f, "Hardware not available: {}", msg),
+            ComputationError::TaskExecutionFailed(msg) => write!(f, "Task execution failed: {}", msg),
+            ComputationError::ResourceAllocationFailed(msg) => write!(f, "Resource allocation failed: {}", msg),
+            ComputationError::InvalidTask(msg) => write!(f, "Invalid task: {}", msg),
+            ComputationError::Timeout(msg) => write!(f, "Task timed out: {}", msg),
+            ComputationError::Cancelled(msg) => write!(f, "Task cancelled: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ComputationError {}
+
+/// Computation engine structure
 pub struct ComputationEngine {
-    device_manager: DeviceManager,
-    task_scheduler: TaskScheduler,
-    resource_allocator: ResourceAllocator,
+    hardware_manager: Arc<dyn HardwareManager>,
 }
 
 impl ComputationEngine {
-    pub fn new(device_manager: DeviceManager, task_scheduler: TaskScheduler) -> Self {
-        let resource_allocator = ResourceAllocator::new(AllocationStrategy::BestFit);
-        Self {
-            device_manager,
-            task_scheduler,
-            resource_allocator,
-        }
-    }
-
-    pub async fn execute_task(&self, task: scheduling::Task) -> Result<computation::TaskResult, Box<dyn std::error::Error>> {
-        // Get available devices
-        let devices = self.device_manager.get_all_devices().await;
-        let device_refs: Vec<&dyn HardwareDevice> = devices.iter().map(|d| d.as_ref()).collect();
-        
-        // Allocate resources for the task
-        let requirements = &task.required_resources;
-        let device = self.resource_allocator.allocate_resources(requirements, &device_refs);
-        
-        if let Some(device) = device {
-            // Convert scheduling task to hardware task
-            let hw_task = crate::hardware::abstract_device::Task {
-                id: task.id,
-                data: vec![], // In a real implementation, this would contain actual data
-                operation: "compute".to_string(),
-            };
-            
-            // Execute the task on the allocated device
-            let result = device.execute_task(&hw_task).await?;
-            
-            // Convert hardware result to computation result
-            let comp_result = computation::TaskResult {
-                task_id: result.task_id,
-                data: result.data,
-                execution_time: result.execution_time,
+    pub fn new(hardware_manager: Arc<dyn HardwareManager>) -> Self {
+        Self { hardware_manager }
+    }
+
+    /// Execute a task on hardware chosen by the device manager
+    ///
+    /// A task with a non-zero `timeout` is cancelled once that many milliseconds pass.
+    pub async fn execute_task(&self, task: &Task) -> Result<TaskResult, ComputationError> {
+        self.execute_cancellable(task, std::future::pending()).await
+    }
+
+    /// Execute a task, stopping it early once `cancelled` resolves
+    ///
+    /// The task's lease is released however the run ends, but only after its devices
+    /// have stopped working on it.
+    pub async fn execute_cancellable(
+        &self,
+        task: &Task,
+        cancelled: impl Future<Output = ()>,
+    ) -> Result<TaskResult, ComputationError> {
+        debug!("Executing task '{}' on {:?}", task.name, task.requirements.hardware_type);
+
+        // Waiting for capacity cannot help if no device of the type runs the operation
+        for member in task.requirements.per_device() {
+            let devices = self.hardware_manager.get_devices_by_type(member.hardware_type);
+            if !devices.is_empty() && !devices.iter().any(|device| device.capabilities().supports(&task.operation)) {
+                return Err(ComputationError::InvalidTask(format!(
+                    "no {} device supports {}",
+                    member.hardware_type, task.operation
+                )));
+            }
+        }
+
+        // Reserve capacity for the task, on its pinned device if a plan placed it
+        let leases = match &task.device_id {
+            Some(device_id) if !task.requirements.is_gang() => self
+                .hardware_manager
+                .reserve(device_id, &task.id, &task.requirements)
+                .map(|lease| vec![lease]),
+            _ => self.hardware_manager.allocate_resources(&task.id, &task.requirements),
+        }
+        .map_err(|e| ComputationError::ResourceAllocationFailed(e.to_string()))?;
+
+        let cancel = CancelToken::new();
+        let device_ids: Vec<String> = leases.iter().map(|lease| lease.device_id.clone()).collect();
+        // Devices hold the run to its slice or share, not just the lease ledger
+        let limits: Vec<Option<Capacity>> = leases.iter().map(|lease| Some(lease.limit)).collect();
+        let run = async {
+            if task.requirements.is_gang() {
+                self.run_gang(task, &device_ids, &limits, &cancel).await
+            } else {
+                self.run_single(task, &device_ids[0], limits[0], &cancel).await
+            }
+        };
+        tokio::pin!(run);
+        let deadline = async {
+            match task.requirements.timeout {
+                0 => std::future::pending().await,
+                timeout => tokio::time::sleep(Duration::from_millis(timeout)).await,
+            }
+        };
+        let stopped = tokio::select! {
+            result = &mut run => Ok(result),
+            _ = deadline => Err(ComputationError::Timeout(format!(
+                "{} after {} ms",
+                task.name, task.requirements.timeout
+            ))),
+            _ = cancelled => Err(ComputationError::Cancelled(task.name.clone())),
+        };
+        let result = match stopped {
+            Ok(result) => result,
+            Err(error) => {
+                // Dropping the run would leave its pool jobs computing on released capacity
+                cancel.cancel();
+                let _ = run.await;
+                Err(error)
+            }
+        };
+
+        let lease_ids: Vec<String> = leases.into_iter().map(|lease| lease.id).collect();
+        match self.hardware_manager.release_resources(&lease_ids) {
+            Ok(()) => {}
+            // The capacity was already reclaimed; the task just ran past its timeout
+            Err(HardwareError::LeaseExpired(id)) => warn!("Task '{}' outlived its lease {}", task.name, id),
+            // The run is over either way; failing it here would throw finished work away
+            Err(e) => warn!("Cannot release the leases of task '{}': {}", task.name, e),
+        }
+
+        result
+    }
+
+    /// Execute a task on a specific device
+    pub async fn execute_task_with_hardware(
+        &self,
+        task: &Task,
+        hardware_id: &str,
+    ) -> Result<TaskResult, ComputationError> {
+        self.run_single(task, hardware_id, None, &CancelToken::new()).await
+    }
+
+    async fn run_single(
+        &self,
+        task: &Task,
+        hardware_id: &str,
+        limit: Option<Capacity>,
+        cancel: &CancelToken,
+    ) -> Result<TaskResult, ComputationError> {
+        debug!("Executing task '{}' on hardware {}", task.name, hardware_id);
+
+        // The handle keeps the device from finishing a drain while the task runs
+        let device = self
+            .hardware_manager
+            .acquire_device(hardware_id)
+            .map_err(|e| ComputationError::HardwareNotAvailable(e.to_string()))?;
+
+        let mut hw_task = task.to_device_task();
+        hw_task.cancel = cancel.clone();
+        hw_task.limit = limit;
+        let outcome = Self::run_on_device(&*device, &hw_task).await;
+        self.hardware_manager
+            .report_task_outcome(hardware_id, outcome.as_ref().map(|_| ()));
+        outcome.map_err(ComputationError::from_device)
+    }
+
+    /// Execute a gang task on every device of its set at once
+    ///
+    /// Each device is handed the whole device set. Outputs are joined in device order,
+    /// energy is summed, and the first device to fail stops the others.
+    pub async fn execute_task_with_gang(
+        &self,
+        task: &Task,
+        device_ids: &[String],
+    ) -> Result<TaskResult, ComputationError> {
+        self.run_gang(task, device_ids, &vec![None; device_ids.len()], &CancelToken::new()).await
+    }
+
+    async fn run_gang(
+        &self,
+        task: &Task,
+        device_ids: &[String],
+        limits: &[Option<Capacity>],
+        cancel: &CancelToken,
+    ) -> Result<TaskResult, ComputationError> {
+        debug!("Executing task '{}' on gang {:?}", task.name, device_ids);
+
+        let devices = device_ids
+            .iter()
+            .map(|id| self.hardware_manager.acquire_device(id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ComputationError::HardwareNotAvailable(e.to_string()))?;
+
+        let mut hw_task = task.to_device_task();
+        hw_task.devices = device_ids.to_vec();
+        hw_task.cancel = cancel.clone();
+        let mut runs = JoinSet::new();
+        for (rank, device) in devices.into_iter().enumerate() {
+            let mut hw_task = hw_task.clone();
+            hw_task.limit = limits[rank];
+            runs.spawn(async move { (rank, Self::run_on_device(&*device, &hw_task).await) });
+        }
+
+        // The first failure cancels the other members, which are still waited for
+        let mut outputs = vec![None; device_ids.len()];
+        let mut failure = None;
+        while let Some(joined) = runs.join_next().await {
+            let outcome = match joined {
+                Ok((rank, outcome)) => {
+                    self.hardware_manager
+                        .report_task_outcome(&device_ids[rank], outcome.as_ref().map(|_| ()));
+                    outcome.map(|output| outputs[rank] = Some(output)).map_err(ComputationError::from_device)
+                }
+                Err(e) => Err(ComputationError::TaskExecutionFailed(e.to_string())),
             };
-            
-            Ok(comp_result)
-        } else {
-            Err("No suitable device found for task".into())
+            if let Err(error) = outcome {
+                cancel.cancel();
+                failure.get_or_insert(error);
+            }
         }
+        if let Some(error) = failure {
+            return Err(error);
+        }
+
+        let outputs: Vec<TaskResult> = outputs.into_iter().flatten().collect();
+        Ok(TaskResult {
+            task_id: task.id.clone(),
+            data: outputs.iter().flat_map(|output| output.data.iter().copied()).collect(),
+            execution_time: outputs.iter().map(|output| output.execution_time).max().unwrap_or(0),
+            energy: outputs.iter().map(|output| output.energy).sum(),
+        })
     }
-}
\ No newline at end of file
+
+    /// Execute a task on an already selected device
+    pub async fn execute_on_device(device: &dyn HardwareDevice, task: &Task) -> Result<TaskResult, ComputationError> {
+        if !device.is_available() {
+            return Err(ComputationError::HardwareNotAvailable(format!("Device {} is not available", device.id())));
+        }
+
+        Self::run_on_device(device, &task.to_device_task())
+            .await
+            .map_err(ComputationError::from_device)
+    }
+
+    async fn run_on_device(device: &dyn HardwareDevice, task: &abstract_device::Task) -> Result<TaskResult, HardwareError> {
+        let result = device.execute_task(task).await?;
+
+        debug!(
+            "Task '{}' executed on {} in {} ms using {:.3} J",
+            task.id,
+            device.id(),
+            result.execution_time,
+            result.energy
+        );
+        Ok(result)
+    }
+
+    /// Batch execute multiple tasks
+    pub async fn execute_batch(&self, tasks: &[Task]) -> Vec<Result<TaskResult, ComputationError>> {
+        debug!("Executing batch of {} tasks", tasks.len());
+
+        let mut results = Vec::new();
+
+        for task in tasks {
+            let result = self.execute_task(task).await;
+            results.push(result);
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{DeviceManager, HardwareType, TaskRequirements};
+    use crate::hardware::cpu::CpuDevice;
+    use crate::scheduling::TaskPriority;
+
+    #[test]
+    fn test_computation_error_display() {
+        let error = ComputationError::HardwareNotAvailable("GPU not available".to_string());
+        assert_eq!(format!("{}", error), "Hardware not available: GPU not available");
+    }
+
+    #[tokio::test]
+    async fn test_execute_task_dispatches_through_device_manager() {
+        let manager = DeviceManager::new();
+        let cpu = CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string());
+        cpu.initialize_cpu().unwrap();
+        manager.register_device(Arc::new(cpu)).unwrap();
+
+        let engine = ComputationEngine::new(Arc::new(manager));
+        let requirements = TaskRequirements::for_test(HardwareType::Cpu);
+        let task = Task::new("task-1".to_string(), "Echo".to_string(), requirements, TaskPriority::Normal)
+            .with_operation("compute", vec![1, 2, 3]);
+
+        let result = engine.execute_task(&task).await.unwrap();
+        assert_eq!(result.task_id, "task-1");
+        assert_eq!(result.data, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_stops_the_device_before_releasing_the_lease() {
+        use crate::hardware::abstract_device::Tensor;
+
+        let manager = Arc::new(DeviceManager::new());
+        let cpu = Arc::new(CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string()));
+        cpu.initialize_cpu().unwrap();
+        manager.register_device(cpu.clone()).unwrap();
+
+        let engine = ComputationEngine::new(manager.clone());
+        let requirements = TaskRequirements {
+            timeout: 20,
+            ..TaskRequirements::for_test(HardwareType::Cpu)
+        };
+        let n = 1024;
+        let a = Tensor::new(vec![n, n], vec![1.0; n * n]).unwrap();
+        let task = Task::new("task-1".to_string(), "Gemm".to_string(), requirements, TaskPriority::Normal)
+            .with_operation("gemm", Tensor::encode_all(&[a.clone(), a]));
+
+        let result = engine.execute_task(&task).await;
+        assert!(matches!(result, Err(ComputationError::Timeout(_))));
+        assert!(manager.leases().is_empty());
+        assert!(cpu.get_metrics().core_reservations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execute_task_without_matching_device() {
+        let engine = ComputationEngine::new(Arc::new(DeviceManager::new()));
+        let requirements = TaskRequirements::for_test(HardwareType::Fpga);
+        let task = Task::new("task-1".to_string(), "Echo".to_string(), requirements, TaskPriority::Normal);
+
+        let result = engine.execute_task(&task).await;
+        assert!(matches!(result, Err(ComputationError::ResourceAllocationFailed(_))));
+    }
+}
@@ -3,12 +3,18 @@
 pub mod computation_engine;
 pub mod task_executor;
 
+use crate::hardware::abstract_device::HardwareManager;
+use std::sync::Arc;
+
 // Re-export key types
-pub use computation_engine::ComputationEngine;
+pub use computation_engine::{ComputationEngine, ComputationError};
 pub use task_executor::TaskExecutor;
+pub use crate::hardware::abstract_device::TaskResult;
 
-pub struct TaskResult {
-    pub task_id: String,
-    pub data: Vec<u8>,
-    pub execution_time: u64,
-}
\ No newline at end of file
+/// Initialize the computation engine
+pub fn init_computation_engine(
+    _config: &serde_yaml::Value,
+    hardware_manager: Arc<dyn HardwareManager>,
+) -> Result<Arc<ComputationEngine>, Box<dyn std::error::Error>> {
+    Ok(Arc::new(ComputationEngine::new(hardware_manager)))
+}
@@ -1,29 +1,166 @@
 // Task executor implementation
 
-use crate::hardware::abstract_device::HardwareDevice;
-use crate::scheduling::Task;
+use crate::computation::computation_engine::{ComputationEngine, ComputationError};
+use crate::hardware::abstract_device::{HardwareManager, HardwareType, TaskResult};
+use crate::scheduling::{Task, TaskScheduler};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
 
-pub struct TaskExecutor;
+/// Task executor structure
+pub struct TaskExecutor {
+    computation_engine: Arc<ComputationEngine>,
+    hardware_manager: Arc<dyn HardwareManager>,
+}
 
 impl TaskExecutor {
-    pub async fn execute_task(device: &dyn HardwareDevice, task: &Task) -> Result<computation::TaskResult, crate::errors::HardwareError> {
-        // Convert scheduling task to hardware task
-        let hw_task = crate::hardware::abstract_device::Task {
-            id: task.id.clone(),
-            data: vec![], // In a real implementation, this would contain actual data
-            operation: "compute".to_string(),
-        };
-        
-        // Execute the task on the device
-        let result = device.execute_task(&hw_task).await?;
-        
-        // Convert hardware result to computation result
-        let comp_result = computation::TaskResult {
-            task_id: result.task_id,
-            data: result.data,
-            execution_time: result.execution_time,
-        };
-        
-        Ok(comp_result)
+    /// Create a new task executor
+    pub fn new(
+        computation_engine: Arc<ComputationEngine>,
+        hardware_manager: Arc<dyn HardwareManager>,
+    ) -> Self {
+        Self {
+            computation_engine,
+            hardware_manager,
+        }
     }
-}
\ No newline at end of file
+
+    /// Execute a single task
+    pub async fn execute_task(&self, task: &Task) -> Result<TaskResult, ComputationError> {
+        debug!("Executing task: {}", task.name);
+
+        let result = self.computation_engine.execute_task(task).await?;
+
+        debug!("Task '{}' completed successfully", task.name);
+        Ok(result)
+    }
+
+    /// Execute a batch of tasks
+    pub async fn execute_batch(&self, tasks: &[Task]) -> Vec<Result<TaskResult, ComputationError>> {
+        debug!("Executing batch of {} tasks", tasks.len());
+
+        let mut results = Vec::new();
+
+        for task in tasks {
+            let result = self.execute_task(task).await;
+            results.push(result);
+        }
+
+        results
+    }
+
+    /// Run queued tasks concurrently until the scheduler has nothing left
+    ///
+    /// Each outcome is reported back to the scheduler, which queues the graph tasks it
+    /// unblocks and decides whether a failed task is retried. A task cancelled through
+    /// the scheduler is stopped mid-run. Waits for pending retries
+    /// before returning. Returns the number of attempts executed.
+    pub async fn run_until_idle(&self, scheduler: &TaskScheduler) -> usize {
+        let mut running = JoinSet::new();
+        let mut task_ids = HashMap::new();
+        let mut executed = 0;
+
+        loop {
+            while let Some(task) = scheduler.schedule_next_task() {
+                let engine = self.computation_engine.clone();
+                let task_id = task.id.clone();
+                let cancel = scheduler.start_task(&task.id);
+                let handle = running.spawn(async move {
+                    let result = engine.execute_cancellable(&task, cancel.cancelled()).await;
+                    (task, result)
+                });
+                task_ids.insert(handle.id(), task_id);
+            }
+
+            // Wake up for whichever comes first: a finished task or a retry falling due
+            let retry = scheduler.next_retry_in();
+            let joined = match (running.is_empty(), retry) {
+                (true, None) => break,
+                (true, Some(delay)) => {
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                (false, None) => running.join_next_with_id().await,
+                (false, Some(delay)) => tokio::select! {
+                    joined = running.join_next_with_id() => joined,
+                    _ = tokio::time::sleep(delay) => continue,
+                },
+            };
+            let Some(joined) = joined else {
+                continue;
+            };
+            executed += 1;
+            match joined {
+                Ok((id, (task, result))) => {
+                    task_ids.remove(&id);
+                    match result {
+                        Ok(result) => scheduler.complete_task(&task.id, result),
+                        Err(e) => {
+                            let task_name = task.name.clone();
+                            let action = scheduler.report_failure(task, &e.to_string(), e.is_retryable());
+                            debug!("Task '{}' failed ({}): {:?}", task_name, e, action);
+                        }
+                    }
+                }
+                Err(e) => {
+                    let task_id = task_ids.remove(&e.id()).unwrap_or_default();
+                    warn!("Task '{}' panicked: {}", task_id, e);
+                    scheduler.fail_task(&task_id, &format!("task panicked: {}", e));
+                }
+            }
+        }
+
+        executed
+    }
+
+    /// Execute task with specific hardware selection
+    pub async fn execute_task_on_hardware(
+        &self,
+        task: &Task,
+        hardware_type: HardwareType,
+    ) -> Result<TaskResult, ComputationError> {
+        debug!("Executing task '{}' on specific hardware: {:?}", task.name, hardware_type);
+
+        // Find available devices of the specified type
+        let devices = self.hardware_manager.get_devices_by_type(hardware_type);
+        if devices.is_empty() {
+            return Err(ComputationError::HardwareNotAvailable(format!("No {} devices available", hardware_type)));
+        }
+
+        let mut retargeted = task.clone();
+        retargeted.requirements.hardware_type = hardware_type;
+
+        let result = self.computation_engine.execute_task(&retargeted).await?;
+
+        debug!("Task '{}' completed successfully on {:?}", task.name, hardware_type);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{DeviceManager, TaskRequirements};
+    use crate::hardware::gpu::GpuDevice;
+    use crate::scheduling::TaskPriority;
+
+    #[tokio::test]
+    async fn test_execute_task_on_hardware_retargets_task() {
+        let manager = DeviceManager::new();
+        let gpu = GpuDevice::new("gpu-0".to_string(), "Test GPU".to_string());
+        gpu.initialize_gpu().unwrap();
+        manager.register_device(Arc::new(gpu)).unwrap();
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+
+        let engine = Arc::new(ComputationEngine::new(manager.clone()));
+        let executor = TaskExecutor::new(engine, manager);
+
+        let requirements = TaskRequirements::for_test(HardwareType::Cpu);
+        let task = Task::new("task-1".to_string(), "Echo".to_string(), requirements, TaskPriority::Normal);
+
+        assert!(executor.execute_task(&task).await.is_err());
+        let result = executor.execute_task_on_hardware(&task, HardwareType::Gpu).await.unwrap();
+        assert_eq!(result.task_id, "task-1");
+    }
+}
@@ -0,0 +1,420 @@
+//! In-memory columnar tables
+//!
+//! Every format conversion goes through a `Table`: a `Schema` naming and typing the
+//! columns, and one typed `Column` per field. Readers hand tables over in batches, so
+//! a file never has to fit in memory as a whole.
+//!
+//! A schema is either given or inferred from the first batch of records. Values from
+//! text formats (CSV, XML) arrive as strings and are inferred as booleans, integers or
+//! floats when every value in the column parses as one; JSON values keep their type.
+//! An inferred schema widens when a later record does not fit it, which changes the
+//! schema of the batches after that record.
+
+use crate::errors::DataError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Type of the values in a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Boolean,
+    Integer,
+    Float,
+    Text,
+}
+
+/// A named, typed column of a schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub column_type: ColumnType,
+
+    /// Whether the column may hold missing values
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+impl Field {
+    /// A nullable field
+    pub fn new(name: &str, column_type: ColumnType) -> Self {
+        Self {
+            name: name.to_string(),
+            column_type,
+            nullable: true,
+        }
+    }
+}
+
+/// Columns of a table, in order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    pub fields: Vec<Field>,
+}
+
+impl Schema {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self { fields }
+    }
+
+    /// Position of the field called `name`
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name == name)
+    }
+
+    /// Infer a schema from records, keeping fields in order of first appearance
+    ///
+    /// With `textual`, string values are typed by what every one of them parses as.
+    /// A column with mixed types is text, and a column with no values at all is
+    /// nullable text.
+    pub fn infer(records: &[Vec<(String, Value)>], textual: bool) -> Self {
+        let mut fields: Vec<Field> = Vec::new();
+        let mut types: Vec<Option<ColumnType>> = Vec::new();
+        let mut counts: Vec<usize> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for record in records {
+            for (name, value) in record {
+                let index = *positions.entry(name.clone()).or_insert_with(|| {
+                    fields.push(Field::new(name, ColumnType::Text));
+                    types.push(None);
+                    counts.push(0);
+                    fields.len() - 1
+                });
+                let Some(kind) = value.kind(textual) else {
+                    continue;
+                };
+                counts[index] += 1;
+                types[index] = Some(match types[index] {
+                    None => kind,
+                    Some(seen) => widen(seen, kind),
+                });
+            }
+        }
+        for (index, field) in fields.iter_mut().enumerate() {
+            field.column_type = types[index].unwrap_or(ColumnType::Text);
+            field.nullable = counts[index] < records.len();
+        }
+        Self { fields }
+    }
+
+    /// Widen the fields so `record` fits them, as `infer` would have with the record
+    /// among its input: types widen, and fields left out or null become nullable.
+    /// Returns whether any field changed. Names the schema lacks are not added.
+    pub fn widen_to(&mut self, record: &[(String, Value)], textual: bool) -> bool {
+        let mut changed = false;
+        for field in &mut self.fields {
+            let value = record.iter().find(|(name, _)| *name == field.name).map(|(_, value)| value);
+            match value.and_then(|value| value.kind(textual)) {
+                Some(kind) => {
+                    let widened = widen(field.column_type, kind);
+                    changed |= widened != field.column_type;
+                    field.column_type = widened;
+                }
+                None => {
+                    changed |= !field.nullable;
+                    field.nullable = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// Narrowest type holding values of both types
+fn widen(a: ColumnType, b: ColumnType) -> ColumnType {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => ColumnType::Float,
+        _ => ColumnType::Text,
+    }
+}
+
+/// One value of a record
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Value {
+    /// Type the value would infer as; `None` for a null
+    fn kind(&self, textual: bool) -> Option<ColumnType> {
+        match self {
+            Value::Null => None,
+            Value::Boolean(_) => Some(ColumnType::Boolean),
+            Value::Integer(_) => Some(ColumnType::Integer),
+            Value::Float(_) => Some(ColumnType::Float),
+            Value::Text(text) if textual => Some(if parse_bool(text).is_some() {
+                ColumnType::Boolean
+            } else if text.trim().parse::<i64>().is_ok() {
+                ColumnType::Integer
+            } else if parse_float(text).is_some() {
+                ColumnType::Float
+            } else {
+                ColumnType::Text
+            }),
+            Value::Text(_) => Some(ColumnType::Text),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            // Debug keeps the decimal point of whole numbers, so they read back as floats
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Text(value) => f.write_str(value),
+        }
+    }
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse a decimal number; words such as "inf" or "nan" stay text
+fn parse_float(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if !text.bytes().any(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Values of one column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Boolean(Vec<Option<bool>>),
+    Integer(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+}
+
+impl Column {
+    /// An empty column of the given type
+    pub fn new(column_type: ColumnType) -> Self {
+        match column_type {
+            ColumnType::Boolean => Column::Boolean(Vec::new()),
+            ColumnType::Integer => Column::Integer(Vec::new()),
+            ColumnType::Float => Column::Float(Vec::new()),
+            ColumnType::Text => Column::Text(Vec::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Boolean(values) => values.len(),
+            Column::Integer(values) => values.len(),
+            Column::Float(values) => values.len(),
+            Column::Text(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Value at `row`
+    pub fn get(&self, row: usize) -> Value {
+        let value = match self {
+            Column::Boolean(values) => values[row].map(Value::Boolean),
+            Column::Integer(values) => values[row].map(Value::Integer),
+            Column::Float(values) => values[row].map(Value::Float),
+            Column::Text(values) => values[row].clone().map(Value::Text),
+        };
+        value.unwrap_or(Value::Null)
+    }
+
+    /// Append a value, converting it to the column's type
+    ///
+    /// Returns the value back if it cannot be converted.
+    fn push(&mut self, value: Value) -> Result<(), Value> {
+        match (self, value) {
+            (Column::Boolean(values), Value::Null) => values.push(None),
+            (Column::Integer(values), Value::Null) => values.push(None),
+            (Column::Float(values), Value::Null) => values.push(None),
+            (Column::Text(values), Value::Null) => values.push(None),
+
+            (Column::Boolean(values), Value::Boolean(value)) => values.push(Some(value)),
+            (Column::Boolean(values), Value::Text(text)) => values.push(Some(parse_bool(&text).ok_or(Value::Text(text))?)),
+
+            (Column::Integer(values), Value::Integer(value)) => values.push(Some(value)),
+            (Column::Integer(values), Value::Float(value)) if value.fract() == 0.0 && value.abs() < i64::MAX as f64 => {
+                values.push(Some(value as i64))
+            }
+            (Column::Integer(values), Value::Text(text)) => match text.trim().parse() {
+                Ok(value) => values.push(Some(value)),
+                Err(_) => return Err(Value::Text(text)),
+            },
+
+            (Column::Float(values), Value::Float(value)) => values.push(Some(value)),
+            (Column::Float(values), Value::Integer(value)) => values.push(Some(value as f64)),
+            (Column::Float(values), Value::Text(text)) => values.push(Some(parse_float(&text).ok_or(Value::Text(text))?)),
+
+            (Column::Text(values), value) => values.push(Some(value.to_string())),
+            (_, value) => return Err(value),
+        }
+        Ok(())
+    }
+}
+
+/// A batch of rows held column by column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    schema: Schema,
+    columns: Vec<Column>,
+}
+
+impl Table {
+    /// An empty table with the given columns
+    pub fn new(schema: Schema) -> Self {
+        let columns = schema.fields.iter().map(|field| Column::new(field.column_type)).collect();
+        Self { schema, columns }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Column of the field called `name`
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.schema.index_of(name).map(|index| &self.columns[index])
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map_or(0, Column::len)
+    }
+
+    /// Values of one row, in schema order
+    pub fn row(&self, row: usize) -> Vec<Value> {
+        self.columns.iter().map(|column| column.get(row)).collect()
+    }
+
+    /// The same rows under `schema`, whose columns must hold every value
+    pub fn cast(&self, schema: Schema) -> Result<Table, DataError> {
+        let mut table = Table::new(schema);
+        for row in 0..self.num_rows() {
+            table.push_row(self.row(row))?;
+        }
+        Ok(table)
+    }
+
+    /// Append a record, matching its values to columns by name
+    ///
+    /// Columns the record does not mention get a null. With `strict`, a name the
+    /// schema does not have is an error; otherwise it is dropped.
+    pub fn push_record(&mut self, record: Vec<(String, Value)>, strict: bool) -> Result<(), DataError> {
+        let mut row = vec![Value::Null; self.columns.len()];
+        for (name, value) in record {
+            match self.schema.index_of(&name) {
+                Some(index) => row[index] = value,
+                None if strict => {
+                    return Err(DataError::Schema(format!(
+                        "field {} is not in the schema; give an explicit schema that includes it",
+                        name
+                    )))
+                }
+                None => {}
+            }
+        }
+        self.push_row(row)
+    }
+
+    /// Append a row of values in schema order
+    pub fn push_row(&mut self, row: Vec<Value>) -> Result<(), DataError> {
+        if row.len() != self.columns.len() {
+            return Err(DataError::Schema(format!(
+                "row has {} values for {} columns",
+                row.len(),
+                self.columns.len()
+            )));
+        }
+        // Check every value first so a bad row leaves the columns the same length
+        for (field, value) in self.schema.fields.iter().zip(&row) {
+            if *value == Value::Null && !field.nullable {
+                return Err(DataError::Schema(format!("field {} is missing and not nullable", field.name)));
+            }
+            let mut probe = Column::new(field.column_type);
+            if let Err(value) = probe.push(value.clone()) {
+                return Err(DataError::Schema(format!(
+                    "value {:?} of field {} is not {:?}",
+                    value, field.name, field.column_type
+                )));
+            }
+        }
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value).expect("value was checked against the column type");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(values: &[(&str, Value)]) -> Vec<(String, Value)> {
+        values.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_inference_and_coercion() {
+        let records = vec![
+            record(&[("id", text("1")), ("score", text("2")), ("ok", text("true")), ("name", text("007"))]),
+            record(&[("id", text("2")), ("score", text("2.5")), ("ok", text("False")), ("name", Value::Null)]),
+            record(&[("id", text("3")), ("score", text("3")), ("note", text("late"))]),
+        ];
+        let schema = Schema::infer(&records, true);
+        let types: Vec<(&str, ColumnType, bool)> =
+            schema.fields.iter().map(|field| (field.name.as_str(), field.column_type, field.nullable)).collect();
+        assert_eq!(
+            types,
+            vec![
+                ("id", ColumnType::Integer, false),
+                ("score", ColumnType::Float, false),
+                ("ok", ColumnType::Boolean, true),
+                ("name", ColumnType::Integer, true),
+                ("note", ColumnType::Text, true),
+            ]
+        );
+        // JSON strings keep their type
+        assert_eq!(Schema::infer(&records, false).fields[0].column_type, ColumnType::Text);
+
+        let mut table = Table::new(schema);
+        for record in records {
+            table.push_record(record, true).unwrap();
+        }
+        assert_eq!(table.num_rows(), 3);
+        assert_eq!(table.row(1), vec![Value::Integer(2), Value::Float(2.5), Value::Boolean(false), Value::Null, Value::Null]);
+
+        assert!(table.push_record(record(&[("id", text("x"))]), true).is_err());
+        assert!(table.push_record(record(&[("score", text("1"))]), true).is_err(), "id is not nullable");
+        assert!(table.push_record(record(&[("id", text("4")), ("extra", text("1"))]), true).is_err());
+        table.push_record(record(&[("id", text("4")), ("score", Value::Integer(1)), ("extra", text("1"))]), false).unwrap();
+        assert_eq!(table.num_rows(), 4);
+        assert!(table.columns().iter().all(|column| column.len() == 4));
+    }
+}
@@ -0,0 +1,220 @@
+// Data manager configuration
+
+use crate::data::pipeline::{Cipher, Codec, Encrypt, Key, Keyring, Pipeline, Transform};
+use crate::errors::DataError;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The `data` section of application.yaml
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataConfig {
+    /// Directory every stored file lives under
+    pub path: String,
+
+    /// Size of each chunk read or written, in KiB
+    pub buffer_size: usize,
+
+    /// Compress whole files as they are saved
+    pub compression_enabled: bool,
+
+    pub compression: CompressionConfig,
+
+    /// Encrypt whole files as they are saved, after compressing them
+    pub encryption_enabled: bool,
+
+    pub encryption: EncryptionConfig,
+}
+
+impl Default for DataConfig {
+    fn default() -> Self {
+        Self {
+            path: "data".to_string(),
+            buffer_size: 1024,
+            compression_enabled: false,
+            compression: CompressionConfig::default(),
+            encryption_enabled: false,
+            encryption: EncryptionConfig::default(),
+        }
+    }
+}
+
+/// The `data.compression` section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub codec: Codec,
+
+    /// Zstd compression level; lz4 has none
+    pub level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            codec: Codec::Zstd,
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+/// The `data.encryption` section
+///
+/// The key is given as 64 hexadecimal digits in `key`, or read from `key_file`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub cipher: Cipher,
+
+    /// Id recorded with encrypted files to name the key they need
+    pub key_id: String,
+
+    pub key: Option<String>,
+
+    pub key_file: Option<String>,
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            cipher: Cipher::Aes256Gcm,
+            key_id: "default".to_string(),
+            key: None,
+            key_file: None,
+        }
+    }
+}
+
+impl DataConfig {
+    /// Parse the `data` section of an application.yaml document
+    pub fn from_yaml(application: &serde_yaml::Value) -> Result<Self, DataError> {
+        let config: Self = match application.get("data") {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| DataError::InvalidConfiguration(format!("data: {}", e)))?,
+            None => Self::default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), DataError> {
+        let mut problems = Vec::new();
+        if self.path.is_empty() {
+            problems.push("data.path must not be empty".to_string());
+        }
+        if self.buffer_size == 0 {
+            problems.push("data.buffer_size must be at least 1 KiB".to_string());
+        }
+        if self.compression.codec == Codec::Zstd && !zstd::compression_level_range().contains(&self.compression.level) {
+            problems.push(format!(
+                "data.compression.level must be within {:?} for zstd",
+                zstd::compression_level_range()
+            ));
+        }
+        let encryption = &self.encryption;
+        if !Keyring::valid_id(&encryption.key_id) {
+            problems.push("data.encryption.key_id may only hold letters, digits, '-', '_' and '.'".to_string());
+        }
+        match (&encryption.key, &encryption.key_file) {
+            (Some(_), Some(_)) => problems.push("data.encryption takes a key or a key_file, not both".to_string()),
+            (None, None) if self.encryption_enabled => {
+                problems.push("data.encryption needs a key or a key_file when encryption is enabled".to_string())
+            }
+            _ => {}
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(DataError::InvalidConfiguration(problems.join("; ")))
+        }
+    }
+
+    /// Chunk size in bytes
+    pub fn chunk_size(&self) -> usize {
+        self.buffer_size * 1024
+    }
+
+    /// The configured compression, whether or not it is enabled
+    pub fn compression_stage(&self) -> Arc<dyn Transform> {
+        self.compression.codec.stage(self.compression.level)
+    }
+
+    /// The configured encryption, whether or not it is enabled; `None` without a key
+    pub fn encryption_stage(&self) -> Result<Option<Arc<dyn Transform>>, DataError> {
+        let stage = self
+            .key()?
+            .map(|key| Arc::new(Encrypt::new(self.encryption.cipher, &self.encryption.key_id, key)) as Arc<dyn Transform>);
+        Ok(stage)
+    }
+
+    /// Keys for reading back what the configured encryption wrote
+    pub fn keyring(&self) -> Result<Keyring, DataError> {
+        let mut keyring = Keyring::new();
+        if let Some(key) = self.key()? {
+            keyring.insert(&self.encryption.key_id, key);
+        }
+        Ok(keyring)
+    }
+
+    fn key(&self) -> Result<Option<Key>, DataError> {
+        match (&self.encryption.key, &self.encryption.key_file) {
+            (Some(hex), _) => Key::from_hex(hex).map(Some),
+            (None, Some(file)) => Key::from_file(file).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Stages every saved file goes through: compression, then encryption
+    pub fn pipeline(&self) -> Result<Pipeline, DataError> {
+        let mut pipeline = Pipeline::new();
+        if self.compression_enabled {
+            pipeline = pipeline.then(self.compression_stage());
+        }
+        if self.encryption_enabled {
+            if let Some(stage) = self.encryption_stage()? {
+                pipeline = pipeline.then(stage);
+            }
+        }
+        Ok(pipeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_repository_data_config() {
+        let content = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config/application.yaml")).unwrap();
+        let config = DataConfig::from_yaml(&serde_yaml::from_str(&content).unwrap()).unwrap();
+        assert_eq!(config.path, "data");
+        assert_eq!(config.chunk_size(), 1024 * 1024);
+        assert_eq!(config.pipeline().unwrap().describe(), "zstd");
+
+        let zero: serde_yaml::Value = serde_yaml::from_str("data: {buffer_size: 0}").unwrap();
+        assert!(matches!(DataConfig::from_yaml(&zero), Err(DataError::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn test_encryption_needs_one_key() {
+        let parse = |yaml: &str| DataConfig::from_yaml(&serde_yaml::from_str(yaml).unwrap());
+        assert!(parse("data: {encryption_enabled: true}").is_err());
+        assert!(parse("data: {encryption: {key: aa, key_file: k}}").is_err());
+        assert!(parse("data: {encryption: {key_id: 'a,b'}}").is_err());
+        assert!(parse("data: {compression: {level: 99}}").is_err());
+
+        let key = "11".repeat(32);
+        let config = parse(&format!(
+            "data: {{compression_enabled: true, compression: {{codec: lz4}}, encryption_enabled: true, \
+             encryption: {{cipher: chacha20-poly1305, key_id: k1, key: '{}'}}}}",
+            key
+        ))
+        .unwrap();
+        assert_eq!(config.pipeline().unwrap().describe(), "lz4,chacha20-poly1305:k1");
+        assert!(config.keyring().unwrap().get("k1").is_some());
+
+        let short = parse("data: {encryption: {key: abcd}}").unwrap();
+        assert!(matches!(short.pipeline(), Ok(pipeline) if pipeline.is_empty()), "encryption is off");
+        assert!(short.keyring().is_err());
+    }
+}
@@ -1,27 +1,830 @@
 // Data manager implementation
 
+use crate::data::data_config::DataConfig;
+use crate::data::pipeline::{Keyring, Pipeline, Transform};
+use crate::errors::DataError;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+/// Start of a file written by `DataManager`, ahead of its header
+const HEADER_MAGIC: [u8; 8] = *b"HCADATA\x01";
+
+/// Distinguishes temporary files written at the same time
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+/// Data format enumeration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    Binary,
+    Json,
+    Csv,
+    Parquet,
+    Xml,
+}
+
+/// Data transformation type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataTransformation {
+    Compression,
+    Encryption,
+    Conversion,
+    Aggregation,
+}
+
+/// Data object structure
+#[derive(Debug, Clone)]
+pub struct DataObject {
+    pub id: String,
+    pub name: String,
+    pub format: DataFormat,
+    pub size: u64,
+    /// Where the object lives: its path in host storage, or the id of the device a task
+    /// wrote it on. Copies staged onto other devices for tasks to read do not change it.
+    pub location: String,
+    pub metadata: HashMap<String, String>,
+}
+
+/// Data manager structure
+///
+/// Files are stored under `data_path`. A write goes to a temporary file in the same
+/// directory, which is synced and renamed over the target, so readers see the old
+/// or the new contents and never a torn file. Each file starts with a header holding
+/// the CRC-32 and length of the data after it, checked when the file is read back.
+/// A file without a header is refused, as a damaged header looks the same; files
+/// copied in by other means are taken on with `import_file`.
+/// Filenames are relative to `data_path` and may not leave it.
+///
+/// `save_data` passes data through the configured compression and encryption
+/// `pipeline`, and the header names the stages applied, so `load_data` can undo
+/// them whatever the configuration is by then. Data, checksum and stages are
+/// replaced together by the one rename. While encryption is enabled, data that no
+/// encryption stage authenticated is neither read nor written, so a file swapped
+/// for plain data or stripped of its header is refused rather than trusted.
 pub struct DataManager {
-    data_path: String,
+    data_path: PathBuf,
+    chunk_size: usize,
+    pipeline: Pipeline,
+    compression: Arc<dyn Transform>,
+    encryption: Option<Arc<dyn Transform>>,
+    require_encryption: bool,
+    keyring: Keyring,
+    data_objects: Arc<Mutex<HashMap<String, DataObject>>>,
+    next_id: AtomicU64,
 }
 
 impl DataManager {
+    /// Create a new data manager rooted at `data_path`, storing data untransformed
     pub fn new(data_path: &str) -> Self {
-        Self {
-            data_path: data_path.to_string(),
+        let config = DataConfig {
+            path: data_path.to_string(),
+            ..DataConfig::default()
+        };
+        Self::with_config(&config).expect("a configuration without encryption needs no key")
+    }
+
+    /// Create a data manager as the `data` section of application.yaml asks
+    ///
+    /// Fails if encryption is enabled without a key, or the key cannot be read.
+    pub fn with_config(config: &DataConfig) -> Result<Self, DataError> {
+        let encryption = config.encryption_stage()?;
+        if config.encryption_enabled && encryption.is_none() {
+            return Err(DataError::InvalidConfiguration(
+                "data.encryption needs a key or a key_file when encryption is enabled".to_string(),
+            ));
+        }
+        Ok(Self {
+            data_path: PathBuf::from(&config.path),
+            chunk_size: config.chunk_size().max(1),
+            pipeline: config.pipeline()?,
+            compression: config.compression_stage(),
+            encryption,
+            require_encryption: config.encryption_enabled,
+            keyring: config.keyring()?,
+            data_objects: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Read a whole file, checking it against its recorded checksum and undoing
+    /// the transforms it was saved with
+    pub fn load_data(&self, filename: &str) -> Result<Vec<u8>, DataError> {
+        let (stored, transforms) = self.load_stored(filename)?;
+        let pipeline = Pipeline::parse(&transforms, &self.keyring)?;
+        self.check_authenticated(filename, &pipeline)?;
+        pipeline.reverse(&stored)
+    }
+
+    /// Read a file chunk by chunk, for files too large to hold in memory
+    ///
+    /// The checksum is checked once the last chunk has been read; a mismatch is
+    /// the final item. Transformed files can only be undone whole, so they are
+    /// refused here and read with `load_data`; with encryption enabled, that is
+    /// every file.
+    pub fn read_chunks(&self, filename: &str) -> Result<DataChunks, DataError> {
+        let (chunks, transforms) = self.open(filename)?;
+        if transforms.is_empty() {
+            self.check_authenticated(filename, &Pipeline::new())?;
+        } else {
+            return Err(DataError::Transform {
+                stage: transforms,
+                reason: format!("{} cannot be read in chunks; use load_data", filename),
+            });
+        }
+        Ok(chunks)
+    }
+
+    /// Write a whole file atomically, through the configured pipeline
+    pub fn save_data(&self, filename: &str, data: &[u8]) -> Result<(), DataError> {
+        let stored = self.pipeline.apply(data)?;
+        self.store(filename, stored.as_slice(), &self.pipeline.describe()).map(|_| ())
+    }
+
+    /// Write everything `source` yields to a file atomically, a chunk at a time
+    ///
+    /// The data is stored as it is, without the pipeline, which needs whole files,
+    /// so this is refused while encryption is enabled. Returns the number of bytes
+    /// written.
+    pub fn save_stream(&self, filename: &str, source: impl Read) -> Result<u64, DataError> {
+        if self.require_encryption {
+            return Err(DataError::Transform {
+                stage: self.pipeline.describe(),
+                reason: format!("{} would be stored unencrypted; use save_data", filename),
+            });
+        }
+        self.store(filename, source, "")
+    }
+
+    /// With encryption enabled, refuse data that `pipeline` does not authenticate
+    fn check_authenticated(&self, filename: &str, pipeline: &Pipeline) -> Result<(), DataError> {
+        if !self.require_encryption || pipeline.authenticates() {
+            return Ok(());
+        }
+        Err(DataError::Transform {
+            stage: pipeline.describe(),
+            reason: format!("{} is not encrypted, and data.encryption_enabled requires it", filename),
+        })
+    }
+
+    /// Contents of a file as stored, and the transforms recorded for them
+    fn load_stored(&self, filename: &str) -> Result<(Vec<u8>, String), DataError> {
+        let (chunks, transforms) = self.open(filename)?;
+        let mut data = Vec::new();
+        for chunk in chunks {
+            data.extend_from_slice(&chunk?);
         }
+        Ok((data, transforms))
     }
 
-    pub fn load_data(&self, filename: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let full_path = format!("{}/{}", self.data_path, filename);
-        println!("Loading data from {}", full_path);
-        // In a real implementation, this would load actual data from disk
-        Ok(vec![])
+    /// Chunks of a file as stored, and the transforms recorded for them
+    fn open(&self, filename: &str) -> Result<(DataChunks, String), DataError> {
+        let path = self.resolve(filename)?;
+        self.check_contained(&path)?;
+        debug!("Loading data from {}", path.display());
+        let mut file = File::open(&path).map_err(|e| DataError::io(&path, e))?;
+        let header = Header::read(&mut file, &path)?.ok_or_else(|| DataError::ChecksumMismatch {
+            path: path.display().to_string(),
+            expected: "a header with a CRC-32, a length and the transforms applied".to_string(),
+            actual: "no header; files put there by other means are taken on with import_file".to_string(),
+        })?;
+        let chunks = DataChunks {
+            path,
+            file,
+            chunk_size: self.chunk_size,
+            hasher: crc32fast::Hasher::new(),
+            length: 0,
+            expected: header.checksum,
+            done: false,
+        };
+        Ok((chunks, header.transforms))
+    }
+
+    /// Take on a file put in the data directory by other means, so it can be read
+    ///
+    /// Reads refuse files without a header, so this is the explicit step that vouches
+    /// for the file's contents as they are now: it is rewritten with a header, its
+    /// data unchanged. A file that already has one is left alone. Like `save_stream`,
+    /// this is refused while encryption is enabled. Returns the data's length.
+    pub fn import_file(&self, filename: &str) -> Result<u64, DataError> {
+        let path = self.resolve(filename)?;
+        self.check_contained(&path)?;
+        let mut file = File::open(&path).map_err(|e| DataError::io(&path, e))?;
+        if let Some(header) = Header::read(&mut file, &path)? {
+            return Ok(header.checksum.length);
+        }
+        if self.require_encryption {
+            return Err(DataError::Transform {
+                stage: self.pipeline.describe(),
+                reason: format!("{} would be stored unencrypted; use save_data", filename),
+            });
+        }
+        debug!("Importing {}", path.display());
+        self.store(filename, io::BufReader::new(file), "")
+    }
+
+    /// Write `source` atomically, recording its checksum and the `transforms` it went through
+    fn store(&self, filename: &str, mut source: impl Read, transforms: &str) -> Result<u64, DataError> {
+        let path = self.resolve(filename)?;
+        let dir = path.parent().unwrap_or(&self.data_path).to_path_buf();
+        if transforms.len() > u16::MAX as usize {
+            return Err(DataError::Transform {
+                stage: transforms.to_string(),
+                reason: "too many stages to record".to_string(),
+            });
+        }
+        self.create_dirs(&dir)?;
+
+        // The header is written first to make room, and again once the checksum is known
+        let mut header = Header {
+            checksum: Checksum { crc: 0, length: 0 },
+            transforms: transforms.to_string(),
+        };
+        let mut hasher = crc32fast::Hasher::new();
+        let mut length = 0u64;
+        let mut buffer = vec![0; self.chunk_size];
+        let mut temp = TempFile::create(&path)?;
+        temp.write(&header.encode())?;
+        loop {
+            let read = match source.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(DataError::io(&path, e)),
+            };
+            hasher.update(&buffer[..read]);
+            temp.write(&buffer[..read])?;
+            length += read as u64;
+        }
+        header.checksum = Checksum { crc: hasher.finalize(), length };
+        temp.rewrite_start(&header.encode())?;
+        temp.commit()?;
+        sync_dir(&dir);
+        debug!("Saved {} bytes to {}", length, path.display());
+        Ok(length)
     }
 
-    pub fn save_data(&self, filename: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
-        let full_path = format!("{}/{}", self.data_path, filename);
-        println!("Saving data to {}", full_path);
-        // In a real implementation, this would save actual data to disk
+    /// Path of `filename` under the data directory, refusing names that would leave it
+    fn resolve(&self, filename: &str) -> Result<PathBuf, DataError> {
+        let invalid = |reason: &str| DataError::InvalidPath {
+            path: filename.to_string(),
+            reason: reason.to_string(),
+        };
+        let mut path = self.data_path.clone();
+        let mut named = false;
+        for component in Path::new(filename).components() {
+            match component {
+                Component::Normal(part) => {
+                    path.push(part);
+                    named = true;
+                }
+                Component::CurDir => {}
+                Component::ParentDir => return Err(invalid("must not contain '..'")),
+                Component::RootDir | Component::Prefix(_) => return Err(invalid("must be relative")),
+            }
+        }
+        if !named {
+            return Err(invalid("names no file"));
+        }
+        Ok(path)
+    }
+
+    /// Create `dir` and its missing parents, refusing to create anything past a
+    /// symbolic link that leads outside the data directory
+    fn create_dirs(&self, dir: &Path) -> Result<(), DataError> {
+        fs::create_dir_all(&self.data_path).map_err(|e| DataError::io(&self.data_path, e))?;
+        let mut path = self.data_path.clone();
+        for part in dir.strip_prefix(&self.data_path).unwrap_or(Path::new("")).components() {
+            path.push(part);
+            match fs::create_dir(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => self.check_contained(&path)?,
+                Err(e) => return Err(DataError::io(&path, e)),
+            }
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Refuse a path that symbolic links lead outside the data directory
+    fn check_contained(&self, path: &Path) -> Result<(), DataError> {
+        let real = path.canonicalize().map_err(|e| DataError::io(path, e))?;
+        let root = self.data_path.canonicalize().map_err(|e| DataError::io(&self.data_path, e))?;
+        if real.starts_with(&root) {
+            Ok(())
+        } else {
+            Err(DataError::InvalidPath {
+                path: path.display().to_string(),
+                reason: "leads outside the data directory".to_string(),
+            })
+        }
+    }
+
+    /// Create a new data object
+    pub fn create_data_object(
+        &self,
+        name: String,
+        format: DataFormat,
+        size: u64,
+        location: String,
+        metadata: HashMap<String, String>,
+    ) -> Result<String, DataError> {
+        debug!("Creating data object: {}", name);
+
+        let id = format!("data-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+
+        let data_object = DataObject {
+            id: id.clone(),
+            name,
+            format,
+            size,
+            location,
+            metadata,
+        };
+
+        self.data_objects.lock().unwrap().insert(id.clone(), data_object);
+
+        debug!("Created data object with ID: {}", id);
+        Ok(id)
+    }
+
+    /// Get a data object by ID
+    pub fn get_data_object(&self, id: &str) -> Option<DataObject> {
+        self.data_objects.lock().unwrap().get(id).cloned()
+    }
+
+    /// Remove a data object
+    pub fn remove_data_object(&self, id: &str) -> Result<(), DataError> {
+        debug!("Removing data object: {}", id);
+
+        if self.data_objects.lock().unwrap().remove(id).is_some() {
+            debug!("Removed data object: {}", id);
+            Ok(())
+        } else {
+            Err(DataError::UnknownObject(id.to_string()))
+        }
+    }
+
+    /// Compress or encrypt a data object held in host storage
+    ///
+    /// The object's `location` names its file under the data directory. The stage
+    /// is added to those the file already went through, and the object's size and
+    /// `transforms` metadata are updated to match.
+    pub fn transform_data(
+        &self,
+        data_id: &str,
+        transformation: DataTransformation,
+    ) -> Result<(), DataError> {
+        debug!("Transforming data {} with {:?}", data_id, transformation);
+
+        let stage = match transformation {
+            DataTransformation::Compression => self.compression.clone(),
+            DataTransformation::Encryption => self.encryption.clone().ok_or_else(|| {
+                DataError::InvalidConfiguration("data.encryption has no key to encrypt with".to_string())
+            })?,
+            DataTransformation::Conversion | DataTransformation::Aggregation => {
+                return Err(DataError::UnsupportedTransformation(transformation))
+            }
+        };
+        let location = self
+            .get_data_object(data_id)
+            .ok_or_else(|| DataError::UnknownObject(data_id.to_string()))?
+            .location;
+
+        let (stored, transforms) = self.load_stored(&location)?;
+        let transformed = stage.apply(&stored, &transforms)?;
+        let transforms = if transforms.is_empty() {
+            stage.name()
+        } else {
+            format!("{},{}", transforms, stage.name())
+        };
+        let size = self.store(&location, transformed.as_slice(), &transforms)?;
+
+        let mut data_objects = self.data_objects.lock().unwrap();
+        let data_obj = data_objects
+            .get_mut(data_id)
+            .ok_or_else(|| DataError::UnknownObject(data_id.to_string()))?;
+        data_obj.size = size;
+        data_obj.metadata.insert("transforms".to_string(), transforms);
+        debug!("Applied {:?} transformation to data {}", transformation, data_id);
+        Ok(())
+    }
+
+    /// Move data between storage locations
+    pub fn move_data(
+        &self,
+        data_id: &str,
+        new_location: String,
+    ) -> Result<(), DataError> {
+        debug!("Moving data {} to {}", data_id, new_location);
+
+        let mut data_objects = self.data_objects.lock().unwrap();
+        if let Some(data_obj) = data_objects.get_mut(data_id) {
+            data_obj.location = new_location;
+            debug!("Moved data {} to new location", data_id);
+            Ok(())
+        } else {
+            Err(DataError::UnknownObject(data_id.to_string()))
+        }
+    }
+
+    /// Get all data objects
+    pub fn get_all_data_objects(&self) -> Vec<DataObject> {
+        self.data_objects.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/// CRC-32 and length recorded for a stored file
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Checksum {
+    crc: u32,
+    length: u64,
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x} {}", self.crc, self.length)
+    }
+}
+
+/// Header at the start of a stored file: the CRC-32 and length of the bytes after
+/// it, then the transforms that produced them, if any
+#[derive(Debug, Clone, PartialEq)]
+struct Header {
+    checksum: Checksum,
+    transforms: String,
+}
+
+impl Header {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_MAGIC.len() + 14 + self.transforms.len());
+        bytes.extend_from_slice(&HEADER_MAGIC);
+        bytes.extend_from_slice(&self.checksum.crc.to_le_bytes());
+        bytes.extend_from_slice(&self.checksum.length.to_le_bytes());
+        bytes.extend_from_slice(&(self.transforms.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(self.transforms.as_bytes());
+        bytes
+    }
+
+    /// Read the header of `file`, leaving it at the data that follows
+    ///
+    /// `None` for a file without one, which is left at its start.
+    fn read(file: &mut File, path: &Path) -> Result<Option<Self>, DataError> {
+        let mut magic = Vec::with_capacity(HEADER_MAGIC.len());
+        Read::by_ref(file)
+            .take(HEADER_MAGIC.len() as u64)
+            .read_to_end(&mut magic)
+            .map_err(|e| DataError::io(path, e))?;
+        if magic != HEADER_MAGIC {
+            file.seek(SeekFrom::Start(0)).map_err(|e| DataError::io(path, e))?;
+            return Ok(None);
+        }
+
+        let malformed = |e: io::Error| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                DataError::ChecksumMismatch {
+                    path: path.display().to_string(),
+                    expected: "a header with a CRC-32, a length and the transforms applied".to_string(),
+                    actual: "a truncated header".to_string(),
+                }
+            } else {
+                DataError::io(path, e)
+            }
+        };
+        let mut fields = [0; 14];
+        file.read_exact(&mut fields).map_err(malformed)?;
+        let mut transforms = vec![0; u16::from_le_bytes([fields[12], fields[13]]) as usize];
+        file.read_exact(&mut transforms).map_err(malformed)?;
+        let transforms = String::from_utf8(transforms).map_err(|e| DataError::ChecksumMismatch {
+            path: path.display().to_string(),
+            expected: "transforms named in UTF-8".to_string(),
+            actual: format!("{:?}", e.as_bytes()),
+        })?;
+        Ok(Some(Self {
+            checksum: Checksum {
+                crc: u32::from_le_bytes(fields[0..4].try_into().unwrap()),
+                length: u64::from_le_bytes(fields[4..12].try_into().unwrap()),
+            },
+            transforms,
+        }))
+    }
+}
+
+/// A file written beside its target and renamed over it once complete
+///
+/// Dropped without `commit`, it is removed.
+struct TempFile {
+    file: Option<File>,
+    path: PathBuf,
+    target: PathBuf,
+}
+
+impl TempFile {
+    fn create(target: &Path) -> Result<Self, DataError> {
+        let mut name = std::ffi::OsString::from(".");
+        name.push(target.file_name().unwrap_or_default());
+        name.push(format!(".{}.{}.tmp", std::process::id(), NEXT_TEMP.fetch_add(1, Ordering::Relaxed)));
+        let path = target.with_file_name(name);
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| DataError::io(&path, e))?;
+        Ok(Self {
+            file: Some(file),
+            path,
+            target: target.to_path_buf(),
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), DataError> {
+        let file = self.file.as_mut().expect("temporary file already committed");
+        file.write_all(data).map_err(|e| DataError::io(&self.path, e))
+    }
+
+    /// Overwrite the start of what has been written with `data`
+    fn rewrite_start(&mut self, data: &[u8]) -> Result<(), DataError> {
+        let file = self.file.as_mut().expect("temporary file already committed");
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.write_all(data))
+            .and_then(|_| file.seek(SeekFrom::End(0)).map(|_| ()))
+            .map_err(|e| DataError::io(&self.path, e))
+    }
+
+    /// Flush the contents to disk and move them into place
+    fn commit(mut self) -> Result<(), DataError> {
+        let file = self.file.take().expect("temporary file already committed");
+        file.sync_all().map_err(|e| DataError::io(&self.path, e))?;
+        drop(file);
+        fs::rename(&self.path, &self.target).map_err(|e| DataError::io(&self.target, e))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("Cannot remove temporary file {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// Make renames in `dir` durable; best effort, as not every platform can sync a directory
+fn sync_dir(dir: &Path) {
+    if let Err(e) = File::open(dir).and_then(|dir| dir.sync_all()) {
+        debug!("Cannot sync directory {}: {}", dir.display(), e);
+    }
+}
+
+/// Chunks of a stored file, from `DataManager::read_chunks`
+pub struct DataChunks {
+    path: PathBuf,
+    file: File,
+    chunk_size: usize,
+    hasher: crc32fast::Hasher,
+    length: u64,
+    expected: Checksum,
+    done: bool,
+}
+
+impl DataChunks {
+    /// Compare what was read with the recorded checksum
+    fn verify(&self) -> Result<(), DataError> {
+        let expected = self.expected;
+        let actual = Checksum {
+            crc: self.hasher.clone().finalize(),
+            length: self.length,
+        };
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(DataError::ChecksumMismatch {
+                path: self.path.display().to_string(),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            })
+        }
+    }
+}
+
+impl Iterator for DataChunks {
+    type Item = Result<Vec<u8>, DataError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut chunk = vec![0; self.chunk_size];
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match self.file.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(DataError::io(&self.path, e)));
+                }
+            }
+        }
+        if filled == 0 {
+            self.done = true;
+            return self.verify().err().map(Err);
+        }
+        chunk.truncate(filled);
+        self.hasher.update(&chunk);
+        self.length += filled as u64;
+        Some(Ok(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_object_creation() {
+        let metadata = HashMap::new();
+        let data_object = DataObject {
+            id: "test-1".to_string(),
+            name: "test-data".to_string(),
+            format: DataFormat::Json,
+            size: 1024,
+            location: "/tmp/test.json".to_string(),
+            metadata,
+        };
+
+        assert_eq!(data_object.id, "test-1");
+        assert_eq!(data_object.name, "test-data");
+        assert_eq!(data_object.format, DataFormat::Json);
+        assert_eq!(data_object.size, 1024);
+    }
+
+    #[test]
+    fn test_data_manager_creation() {
+        let data_manager = DataManager::new("data");
+        assert!(data_manager.get_all_data_objects().is_empty());
+    }
+
+    /// A data manager over a fresh directory that reads and writes 4-byte chunks
+    fn scratch(name: &str) -> (DataManager, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("hca-data-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = DataConfig {
+            path: dir.display().to_string(),
+            buffer_size: 1,
+            ..DataConfig::default()
+        };
+        let mut manager = DataManager::with_config(&config).unwrap();
+        manager.chunk_size = 4;
+        (manager, dir)
+    }
+
+    #[test]
+    fn test_files_round_trip_in_chunks() {
+        let (manager, dir) = scratch("round-trip");
+        manager.save_data("models/a/config.json", b"{\"layers\": 2}").unwrap();
+        manager.save_data("models/a/config.json", b"0123456789").unwrap();
+
+        let chunks: Vec<Vec<u8>> = manager.read_chunks("models/a/config.json").unwrap().map(Result::unwrap).collect();
+        assert_eq!(chunks, vec![b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()]);
+        assert_eq!(manager.load_data("./models/a/config.json").unwrap(), b"0123456789");
+
+        let mut names: Vec<String> = fs::read_dir(dir.join("models/a"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["config.json"], "no temporary files are left behind");
+        assert!(matches!(manager.load_data("models/b/config.json"), Err(DataError::NotFound(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_files_are_detected() {
+        let (manager, dir) = scratch("corrupt");
+        manager.save_data("weights.bin", &[7; 10]).unwrap();
+        let mut stored = fs::read(dir.join("weights.bin")).unwrap();
+        let last = stored.len() - 1;
+        stored[last] = 0;
+        fs::write(dir.join("weights.bin"), &stored).unwrap();
+        assert!(matches!(manager.load_data("weights.bin"), Err(DataError::ChecksumMismatch { .. })));
+        let last = manager.read_chunks("weights.bin").unwrap().last().unwrap();
+        assert!(last.is_err(), "the mismatch ends the chunks");
+        fs::write(dir.join("weights.bin"), &stored[..12]).unwrap();
+        assert!(matches!(manager.load_data("weights.bin"), Err(DataError::ChecksumMismatch { .. })));
+
+        // A damaged header is refused like a missing one
+        stored[1] ^= 1;
+        fs::write(dir.join("weights.bin"), &stored).unwrap();
+        assert!(matches!(manager.load_data("weights.bin"), Err(DataError::ChecksumMismatch { .. })));
+        assert!(manager.read_chunks("weights.bin").is_err());
+
+        // Files put there by hand are read once imported
+        fs::write(dir.join("notes.txt"), "hand written").unwrap();
+        assert!(matches!(manager.load_data("notes.txt"), Err(DataError::ChecksumMismatch { .. })));
+        assert_eq!(manager.import_file("notes.txt").unwrap(), 12);
+        assert_eq!(manager.import_file("notes.txt").unwrap(), 12, "imported files are left alone");
+        assert_eq!(manager.load_data("notes.txt").unwrap(), b"hand written");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_transformed_files_record_their_pipeline() {
+        let (_, dir) = scratch("pipeline");
+        let config = DataConfig {
+            path: dir.display().to_string(),
+            compression_enabled: true,
+            encryption_enabled: true,
+            encryption: crate::data::data_config::EncryptionConfig {
+                key: Some("2a".repeat(32)),
+                ..Default::default()
+            },
+            ..DataConfig::default()
+        };
+        let keyless = DataConfig {
+            encryption: Default::default(),
+            ..config.clone()
+        };
+        assert!(matches!(DataManager::with_config(&keyless), Err(DataError::InvalidConfiguration(_))));
+        let manager = DataManager::with_config(&config).unwrap();
+        let data = b"activations ".repeat(100);
+        manager.save_data("batch.bin", &data).unwrap();
+        let mut file = File::open(dir.join("batch.bin")).unwrap();
+        let header = Header::read(&mut file, &dir).unwrap().unwrap();
+        assert_eq!(header.transforms, "zstd,aes-256-gcm:default");
+        assert_ne!(fs::read(dir.join("batch.bin")).unwrap(), data);
+        assert_eq!(manager.load_data("batch.bin").unwrap(), data);
+        assert!(matches!(manager.read_chunks("batch.bin"), Err(DataError::Transform { .. })));
+
+        // Editing the recorded stages, or stripping them, fails closed
+        let (stored, _) = manager.load_stored("batch.bin").unwrap();
+        manager.store("edited.bin", stored.as_slice(), "aes-256-gcm:default").unwrap();
+        assert!(matches!(manager.load_data("edited.bin"), Err(DataError::Transform { .. })));
+        fs::write(dir.join("stripped.bin"), &stored).unwrap();
+        assert!(matches!(manager.load_data("stripped.bin"), Err(DataError::ChecksumMismatch { .. })));
+        assert!(matches!(manager.import_file("stripped.bin"), Err(DataError::Transform { .. })));
+
+        // Without the key the file cannot be read back. Plain files are refused while
+        // encryption is enabled, and readable once they are encrypted.
+        let plain = DataManager::new(dir.to_str().unwrap());
+        assert!(matches!(plain.load_data("batch.bin"), Err(DataError::Transform { .. })));
+        plain.save_data("plain.bin", &data).unwrap();
+        assert_eq!(plain.load_data("plain.bin").unwrap(), data);
+        assert!(matches!(manager.load_data("plain.bin"), Err(DataError::Transform { .. })));
+        assert!(matches!(manager.read_chunks("plain.bin"), Err(DataError::Transform { .. })));
+        assert!(matches!(manager.save_stream("streamed.bin", &data[..]), Err(DataError::Transform { .. })));
+
+        let id = manager
+            .create_data_object("plain".to_string(), DataFormat::Binary, data.len() as u64, "plain.bin".to_string(), HashMap::new())
+            .unwrap();
+        manager.transform_data(&id, DataTransformation::Compression).unwrap();
+        manager.transform_data(&id, DataTransformation::Encryption).unwrap();
+        let object = manager.get_data_object(&id).unwrap();
+        assert_eq!(object.metadata["transforms"], "zstd,aes-256-gcm:default");
+        assert_eq!(object.size, manager.load_stored("plain.bin").unwrap().0.len() as u64);
+        assert_eq!(manager.load_data("plain.bin").unwrap(), data);
+        assert!(matches!(
+            manager.transform_data(&id, DataTransformation::Aggregation),
+            Err(DataError::UnsupportedTransformation(_))
+        ));
+        assert!(matches!(
+            plain.transform_data(&id, DataTransformation::Encryption),
+            Err(DataError::InvalidConfiguration(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_paths_cannot_leave_the_data_directory() {
+        let (manager, dir) = scratch("traversal");
+        for name in ["../escape", "a/../../escape", "/etc/passwd", "", "."] {
+            assert!(
+                matches!(manager.save_data(name, b"x"), Err(DataError::InvalidPath { .. })),
+                "{:?} was accepted",
+                name
+            );
+        }
+        assert!(matches!(manager.load_data("../escape"), Err(DataError::InvalidPath { .. })));
+
+        #[cfg(unix)]
+        {
+            let outside = std::env::temp_dir().join(format!("hca-data-outside-{}", std::process::id()));
+            fs::create_dir_all(&outside).unwrap();
+            fs::write(outside.join("secret"), "x").unwrap();
+            fs::create_dir_all(&dir).unwrap();
+            std::os::unix::fs::symlink(&outside, dir.join("link")).unwrap();
+            assert!(matches!(manager.load_data("link/secret"), Err(DataError::InvalidPath { .. })));
+            assert!(matches!(manager.save_data("link/secret", b"y"), Err(DataError::InvalidPath { .. })));
+            assert_eq!(fs::read(outside.join("secret")).unwrap(), b"x");
+            assert!(matches!(manager.save_data("link/new/file", b"y"), Err(DataError::InvalidPath { .. })));
+            assert!(!outside.join("new").exists(), "no directory is created outside");
+            fs::remove_dir_all(outside).unwrap();
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
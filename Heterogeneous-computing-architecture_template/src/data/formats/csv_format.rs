@@ -0,0 +1,68 @@
+// CSV with a header row
+
+use crate::data::columnar::{Table, Value};
+use crate::data::formats::{BatchSink, Batcher, ReadOptions, TableWriter};
+use crate::data::DataFormat;
+use crate::errors::DataError;
+use std::io::{Read, Write};
+
+fn malformed(error: impl std::fmt::Display) -> DataError {
+    DataError::Malformed {
+        format: DataFormat::Csv,
+        reason: error.to_string(),
+    }
+}
+
+/// Read CSV whose first row names the columns; empty cells are nulls
+pub fn read(input: impl Read, options: &ReadOptions, sink: &mut BatchSink<'_>) -> Result<usize, DataError> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input);
+    let headers: Vec<String> = reader.headers().map_err(malformed)?.iter().map(str::to_string).collect();
+    let mut batcher = Batcher::new(options, true, sink);
+    for record in reader.records() {
+        let record = record.map_err(malformed)?;
+        let values = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(name, cell)| {
+                let value = if cell.is_empty() { Value::Null } else { Value::Text(cell.to_string()) };
+                (name.clone(), value)
+            })
+            .collect();
+        batcher.push(values)?;
+    }
+    batcher.finish()
+}
+
+/// Writes CSV with a header row taken from the first batch
+pub struct CsvWriter<W: Write> {
+    writer: csv::Writer<W>,
+    header: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            writer: csv::Writer::from_writer(output),
+            header: false,
+        }
+    }
+}
+
+impl<W: Write> TableWriter for CsvWriter<W> {
+    fn write(&mut self, table: &Table) -> Result<(), DataError> {
+        if !self.header {
+            let names = table.schema().fields.iter().map(|field| field.name.as_str());
+            self.writer.write_record(names).map_err(malformed)?;
+            self.header = true;
+        }
+        for row in 0..table.num_rows() {
+            let cells = table.row(row).iter().map(Value::to_string).collect::<Vec<_>>();
+            self.writer.write_record(&cells).map_err(malformed)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), DataError> {
+        self.writer.flush().map_err(malformed)
+    }
+}
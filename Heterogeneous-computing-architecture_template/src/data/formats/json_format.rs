@@ -0,0 +1,141 @@
+// JSON records: an array of objects, or one object per line
+
+use crate::data::columnar::{Table, Value};
+use crate::data::formats::{BatchSink, Batcher, ReadOptions, TableWriter};
+use crate::data::DataFormat;
+use crate::errors::DataError;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde_json::{Map, Number};
+use std::fmt;
+use std::io::{BufRead, Write};
+
+fn malformed(error: impl fmt::Display) -> DataError {
+    DataError::Malformed {
+        format: DataFormat::Json,
+        reason: error.to_string(),
+    }
+}
+
+/// Read a JSON array of objects, or JSON Lines, one object at a time
+///
+/// Nested arrays and objects are kept as JSON text.
+pub fn read(mut input: impl BufRead, options: &ReadOptions, sink: &mut BatchSink<'_>) -> Result<usize, DataError> {
+    let mut batcher = Batcher::new(options, false, sink);
+    let array = loop {
+        let buffer = input.fill_buf().map_err(|e| DataError::io("JSON input", e))?;
+        match buffer.iter().position(|byte| !byte.is_ascii_whitespace()) {
+            Some(start) => break buffer[start] == b'[',
+            None if buffer.is_empty() => break false,
+            None => {
+                let length = buffer.len();
+                input.consume(length);
+            }
+        }
+    };
+
+    let mut deserializer = serde_json::Deserializer::from_reader(input);
+    if array {
+        let mut failed = None;
+        let records = Records {
+            batcher: &mut batcher,
+            failed: &mut failed,
+        };
+        let read = (&mut deserializer).deserialize_seq(records);
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        read.and_then(|_| deserializer.end()).map_err(malformed)?;
+    } else {
+        for object in deserializer.into_iter::<Map<String, serde_json::Value>>() {
+            batcher.push(to_record(object.map_err(malformed)?))?;
+        }
+    }
+    batcher.finish()
+}
+
+fn to_record(object: Map<String, serde_json::Value>) -> Vec<(String, Value)> {
+    object.into_iter().map(|(name, value)| (name, to_value(value))).collect()
+}
+
+fn to_value(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(value) => Value::Boolean(value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(value) => Value::Integer(value),
+            None => Value::Float(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(text) => Value::Text(text),
+        nested => Value::Text(nested.to_string()),
+    }
+}
+
+/// Batches the elements of a JSON array one by one instead of collecting them
+struct Records<'b, 'a> {
+    batcher: &'b mut Batcher<'a>,
+    /// Why the batcher refused a record
+    failed: &'b mut Option<DataError>,
+}
+
+impl<'de> Visitor<'de> for Records<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(object) = seq.next_element()? {
+            if let Err(e) = self.batcher.push(to_record(object)) {
+                *self.failed = Some(e);
+                return Err(de::Error::custom("record refused"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a JSON array with one object per row
+pub struct JsonWriter<W: Write> {
+    output: W,
+    rows: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(output: W) -> Self {
+        Self { output, rows: 0 }
+    }
+}
+
+impl<W: Write> TableWriter for JsonWriter<W> {
+    fn write(&mut self, table: &Table) -> Result<(), DataError> {
+        let names: Vec<&str> = table.schema().fields.iter().map(|field| field.name.as_str()).collect();
+        for row in 0..table.num_rows() {
+            let object: Map<String, serde_json::Value> = names
+                .iter()
+                .zip(table.row(row))
+                .map(|(name, value)| (name.to_string(), from_value(value)))
+                .collect();
+            let separator: &[u8] = if self.rows == 0 { b"[\n" } else { b",\n" };
+            self.output.write_all(separator).map_err(|e| DataError::io("JSON output", e))?;
+            serde_json::to_writer(&mut self.output, &object).map_err(malformed)?;
+            self.rows += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), DataError> {
+        let end: &[u8] = if self.rows == 0 { b"[]\n" } else { b"\n]\n" };
+        self.output.write_all(end).and_then(|_| self.output.flush()).map_err(|e| DataError::io("JSON output", e))
+    }
+}
+
+fn from_value(value: Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Boolean(value) => serde_json::Value::Bool(value),
+        Value::Integer(value) => serde_json::Value::Number(value.into()),
+        Value::Float(value) => Number::from_f64(value).map_or(serde_json::Value::Null, serde_json::Value::Number),
+        Value::Text(text) => serde_json::Value::String(text),
+    }
+}
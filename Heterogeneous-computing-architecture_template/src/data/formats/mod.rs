@@ -0,0 +1,142 @@
+// Readers and writers of the tabular formats
+
+pub mod csv_format;
+pub mod json_format;
+pub mod parquet_format;
+pub mod xml_format;
+
+use crate::data::columnar::{Schema, Table, Value};
+use crate::errors::DataError;
+
+/// Receives each batch a reader produces
+pub type BatchSink<'a> = dyn FnMut(Table) -> Result<(), DataError> + 'a;
+
+/// How readers turn records into batches
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// Schema to read into; inferred from the first batch when `None`
+    pub schema: Option<Schema>,
+
+    /// Rows per batch
+    pub batch_rows: usize,
+
+    /// Name of the XML elements holding one record each; the root's children when `None`
+    pub record_tag: Option<String>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            schema: None,
+            batch_rows: 8192,
+            record_tag: None,
+        }
+    }
+}
+
+/// Writes batches in one format
+pub trait TableWriter {
+    fn write(&mut self, table: &Table) -> Result<(), DataError>;
+
+    /// Write whatever closes the output, e.g. a footer
+    fn finish(self: Box<Self>) -> Result<(), DataError>;
+}
+
+/// Collects records into batches of `batch_rows` rows and hands them to a sink
+///
+/// Without an explicit schema, the first batch's records are held back until the
+/// batch is full and the schema is inferred from them. A later record whose values
+/// do not fit widens the schema for the rest of the input, so batches already handed
+/// over keep the narrower one; writers that need one schema for the whole output,
+/// such as Parquet's, refuse the change, so `DataTransformer` finds the widest first. Later records may not add fields. Readers
+/// given a schema through `ReadOptions` never change it.
+pub(crate) struct Batcher<'a> {
+    schema: Option<Schema>,
+    explicit: bool,
+    textual: bool,
+    batch_rows: usize,
+    held: Vec<Vec<(String, Value)>>,
+    table: Option<Table>,
+    rows: usize,
+    sink: &'a mut BatchSink<'a>,
+}
+
+impl<'a> Batcher<'a> {
+    /// `textual` marks formats whose values are all strings, e.g. CSV
+    pub fn new(options: &ReadOptions, textual: bool, sink: &'a mut BatchSink<'a>) -> Self {
+        Self {
+            schema: options.schema.clone(),
+            explicit: options.schema.is_some(),
+            textual,
+            batch_rows: options.batch_rows.max(1),
+            held: Vec::new(),
+            table: options.schema.clone().map(Table::new),
+            rows: 0,
+            sink,
+        }
+    }
+
+    /// Use the schema a file declares, e.g. a Parquet footer, unless one was given
+    pub fn declare(&mut self, schema: Schema) {
+        if self.schema.is_none() {
+            self.table = Some(Table::new(schema.clone()));
+            self.schema = Some(schema);
+        }
+    }
+
+    pub fn push(&mut self, record: Vec<(String, Value)>) -> Result<(), DataError> {
+        let Some(table) = self.table.as_mut() else {
+            self.held.push(record);
+            if self.held.len() >= self.batch_rows {
+                self.settle()?;
+            }
+            return Ok(());
+        };
+        if !self.explicit {
+            let schema = self.schema.as_mut().expect("a batch has a schema");
+            if schema.widen_to(&record, self.textual) {
+                *table = table.cast(schema.clone())?;
+            }
+        }
+        table.push_record(record, !self.explicit)?;
+        if table.num_rows() >= self.batch_rows {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Hand over the last batch, returning the number of rows read
+    ///
+    /// An input without records still yields one empty batch, so writers learn the schema.
+    pub fn finish(mut self) -> Result<usize, DataError> {
+        if self.table.is_none() {
+            self.settle()?;
+        }
+        if self.table.as_ref().is_some_and(|table| table.num_rows() > 0) || self.rows == 0 {
+            self.flush()?;
+        }
+        Ok(self.rows)
+    }
+
+    /// Infer the schema from the held records and move them into a batch
+    fn settle(&mut self) -> Result<(), DataError> {
+        let schema = Schema::infer(&self.held, self.textual);
+        let mut table = Table::new(schema.clone());
+        for record in self.held.drain(..) {
+            table.push_record(record, true)?;
+        }
+        self.schema = Some(schema);
+        self.table = Some(table);
+        if self.table.as_ref().unwrap().num_rows() >= self.batch_rows {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), DataError> {
+        let schema = self.schema.clone().unwrap_or_default();
+        let table = self.table.replace(Table::new(schema)).unwrap();
+        self.rows += table.num_rows();
+        (self.sink)(table)
+    }
+}
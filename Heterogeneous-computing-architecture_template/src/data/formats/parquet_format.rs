@@ -0,0 +1,198 @@
+// Parquet files of flat records
+
+use crate::data::columnar::{Column, ColumnType, Field, Schema, Table, Value};
+use crate::data::formats::{BatchSink, Batcher, ReadOptions, TableWriter};
+use crate::data::DataFormat;
+use crate::errors::DataError;
+use parquet::basic::{ConvertedType, LogicalType, Repetition, Type as PhysicalType};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{ChunkReader, FileReader, SerializedFileReader};
+use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use parquet::record::Field as ParquetField;
+use parquet::schema::types::Type;
+use std::io::Write;
+use std::sync::Arc;
+
+fn malformed(error: impl std::fmt::Display) -> DataError {
+    DataError::Malformed {
+        format: DataFormat::Parquet,
+        reason: error.to_string(),
+    }
+}
+
+/// Read a Parquet file one row group at a time
+///
+/// The file's schema is used unless one was given. Columns of types with no
+/// counterpart here, e.g. nested groups or decimals, are read as text.
+pub fn read<R: ChunkReader + 'static>(input: R, options: &ReadOptions, sink: &mut BatchSink<'_>) -> Result<usize, DataError> {
+    let reader = SerializedFileReader::new(input).map_err(malformed)?;
+    let mut batcher = Batcher::new(options, false, sink);
+    let root = reader.metadata().file_metadata().schema_descr().root_schema_ptr();
+    batcher.declare(Schema::new(root.get_fields().iter().map(|field| to_field(field)).collect()));
+
+    for index in 0..reader.num_row_groups() {
+        let group = reader.get_row_group(index).map_err(malformed)?;
+        for row in group.get_row_iter(None).map_err(malformed)? {
+            let row = row.map_err(malformed)?;
+            let record = row
+                .get_column_iter()
+                .map(|(name, field)| (name.clone(), to_value(field)))
+                .collect();
+            batcher.push(record)?;
+        }
+    }
+    batcher.finish()
+}
+
+fn to_field(field: &Type) -> Field {
+    let info = field.get_basic_info();
+    let column_type = if !field.is_primitive() {
+        ColumnType::Text
+    } else {
+        match field.get_physical_type() {
+            PhysicalType::BOOLEAN => ColumnType::Boolean,
+            PhysicalType::INT32 | PhysicalType::INT64
+                if !matches!(info.logical_type(), Some(LogicalType::Decimal { .. })) =>
+            {
+                ColumnType::Integer
+            }
+            PhysicalType::FLOAT | PhysicalType::DOUBLE => ColumnType::Float,
+            _ => ColumnType::Text,
+        }
+    };
+    Field {
+        name: info.name().to_string(),
+        column_type,
+        nullable: !info.has_repetition() || info.repetition() != Repetition::REQUIRED,
+    }
+}
+
+fn to_value(field: &ParquetField) -> Value {
+    match field {
+        ParquetField::Null => Value::Null,
+        ParquetField::Bool(value) => Value::Boolean(*value),
+        ParquetField::Byte(value) => Value::Integer(*value as i64),
+        ParquetField::Short(value) => Value::Integer(*value as i64),
+        ParquetField::Int(value) | ParquetField::Date(value) => Value::Integer(*value as i64),
+        ParquetField::Long(value) | ParquetField::TimestampMillis(value) | ParquetField::TimestampMicros(value) => {
+            Value::Integer(*value)
+        }
+        ParquetField::UByte(value) => Value::Integer(*value as i64),
+        ParquetField::UShort(value) => Value::Integer(*value as i64),
+        ParquetField::UInt(value) => Value::Integer(*value as i64),
+        ParquetField::ULong(value) => i64::try_from(*value).map_or(Value::Float(*value as f64), Value::Integer),
+        ParquetField::Float16(value) => Value::Float(f64::from(*value)),
+        ParquetField::Float(value) => Value::Float(*value as f64),
+        ParquetField::Double(value) => Value::Float(*value),
+        ParquetField::Str(value) => Value::Text(value.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+/// Writes each batch as a row group, uncompressed
+///
+/// The file's schema is the first batch's, and every later batch must have it.
+pub struct ParquetWriter<W: Write + Send> {
+    output: Option<W>,
+    writer: Option<SerializedFileWriter<W>>,
+    schema: Option<Schema>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            output: Some(output),
+            writer: None,
+            schema: None,
+        }
+    }
+}
+
+impl<W: Write + Send> TableWriter for ParquetWriter<W> {
+    fn write(&mut self, table: &Table) -> Result<(), DataError> {
+        if self.writer.is_none() {
+            let output = self.output.take().expect("output already handed to the writer");
+            let schema = Arc::new(to_parquet_schema(table.schema())?);
+            let properties = Arc::new(WriterProperties::builder().build());
+            self.writer = Some(SerializedFileWriter::new(output, schema, properties).map_err(malformed)?);
+            self.schema = Some(table.schema().clone());
+        }
+        if self.schema.as_ref() != Some(table.schema()) {
+            return Err(DataError::Schema(
+                "the inferred schema widened after the first batch, and a Parquet file has one schema; \
+                 give an explicit schema with DataTransformer::with_schema"
+                    .to_string(),
+            ));
+        }
+        if table.num_rows() == 0 {
+            return Ok(());
+        }
+        let writer = self.writer.as_mut().unwrap();
+        let mut group = writer.next_row_group().map_err(malformed)?;
+        for (field, column) in table.schema().fields.iter().zip(table.columns()) {
+            let mut writer = group
+                .next_column()
+                .map_err(malformed)?
+                .ok_or_else(|| malformed("row group has fewer columns than the schema"))?;
+            match column {
+                Column::Boolean(values) => write_column::<BoolType, _>(&mut writer, field, values, |value| *value)?,
+                Column::Integer(values) => write_column::<Int64Type, _>(&mut writer, field, values, |value| *value)?,
+                Column::Float(values) => write_column::<DoubleType, _>(&mut writer, field, values, |value| *value)?,
+                Column::Text(values) => {
+                    write_column::<ByteArrayType, _>(&mut writer, field, values, |value| ByteArray::from(value.as_str()))?
+                }
+            }
+            writer.close().map_err(malformed)?;
+        }
+        group.close().map_err(malformed)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), DataError> {
+        let mut this = *self;
+        if this.writer.is_none() {
+            this.write(&Table::new(Schema::default()))?;
+        }
+        this.writer.unwrap().close().map_err(malformed)?;
+        Ok(())
+    }
+}
+
+fn to_parquet_schema(schema: &Schema) -> Result<Type, DataError> {
+    let fields = schema
+        .fields
+        .iter()
+        .map(|field| {
+            let (physical, logical, converted) = match field.column_type {
+                ColumnType::Boolean => (PhysicalType::BOOLEAN, None, ConvertedType::NONE),
+                ColumnType::Integer => (PhysicalType::INT64, None, ConvertedType::NONE),
+                ColumnType::Float => (PhysicalType::DOUBLE, None, ConvertedType::NONE),
+                ColumnType::Text => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String), ConvertedType::UTF8),
+            };
+            let repetition = if field.nullable { Repetition::OPTIONAL } else { Repetition::REQUIRED };
+            Type::primitive_type_builder(&field.name, physical)
+                .with_repetition(repetition)
+                .with_logical_type(logical)
+                .with_converted_type(converted)
+                .build()
+                .map(Arc::new)
+                .map_err(malformed)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Type::group_type_builder("schema").with_fields(fields).build().map_err(malformed)
+}
+
+/// Write the non-null values of a column, with definition levels marking the nulls
+fn write_column<T: DataType, V>(
+    writer: &mut SerializedColumnWriter<'_>,
+    field: &Field,
+    values: &[Option<V>],
+    convert: impl Fn(&V) -> T::T,
+) -> Result<(), DataError> {
+    let present: Vec<T::T> = values.iter().flatten().map(&convert).collect();
+    let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+    let levels = field.nullable.then_some(levels.as_slice());
+    writer.typed::<T>().write_batch(&present, levels, None).map_err(malformed)?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+// Records extracted from XML
+
+use crate::data::columnar::Value;
+use crate::data::formats::{BatchSink, Batcher, ReadOptions};
+use crate::data::DataFormat;
+use crate::errors::DataError;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::BufRead;
+
+fn malformed(error: impl std::fmt::Display) -> DataError {
+    DataError::Malformed {
+        format: DataFormat::Xml,
+        reason: error.to_string(),
+    }
+}
+
+/// Record being read, and the field inside it whose text is being collected
+struct Open {
+    depth: usize,
+    fields: Vec<(String, Value)>,
+    field: Option<(String, String)>,
+}
+
+/// Extract records from XML
+///
+/// A record is an element named `options.record_tag`, or any child of the root
+/// element when no tag is given. Its attributes and the text of its child elements
+/// are its fields; deeper elements add their text to the child they are in.
+pub fn read(input: impl BufRead, options: &ReadOptions, sink: &mut BatchSink<'_>) -> Result<usize, DataError> {
+    let mut reader = Reader::from_reader(input);
+    reader.config_mut().trim_text(true);
+    let mut batcher = Batcher::new(options, true, sink);
+    let mut buffer = Vec::new();
+    let mut depth = 0;
+    let mut open: Option<Open> = None;
+    loop {
+        match reader.read_event_into(&mut buffer).map_err(malformed)? {
+            Event::Start(element) => {
+                open = enter(open, &element, depth, options)?;
+                depth += 1;
+            }
+            Event::Empty(element) => {
+                open = enter(open, &element, depth, options)?;
+                open = leave(open, depth, &mut batcher)?;
+            }
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                open = leave(open, depth, &mut batcher)?;
+            }
+            Event::Text(text) => {
+                if let Some((_, collected)) = open.as_mut().and_then(|record| record.field.as_mut()) {
+                    collected.push_str(&text.unescape().map_err(malformed)?);
+                }
+            }
+            Event::CData(text) => {
+                if let Some((_, collected)) = open.as_mut().and_then(|record| record.field.as_mut()) {
+                    collected.push_str(&text.decode().map_err(malformed)?);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+    batcher.finish()
+}
+
+fn name(element: &BytesStart<'_>) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+/// Handle an element opening at `depth`
+fn enter(open: Option<Open>, element: &BytesStart<'_>, depth: usize, options: &ReadOptions) -> Result<Option<Open>, DataError> {
+    let Some(mut record) = open else {
+        let is_record = match &options.record_tag {
+            Some(tag) => name(element) == *tag,
+            None => depth == 1,
+        };
+        if !is_record {
+            return Ok(None);
+        }
+        let mut fields = Vec::new();
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(malformed)?;
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+            fields.push((key, Value::Text(attribute.unescape_value().map_err(malformed)?.into_owned())));
+        }
+        return Ok(Some(Open { depth, fields, field: None }));
+    };
+    if depth == record.depth + 1 {
+        record.field = Some((name(element), String::new()));
+    }
+    Ok(Some(record))
+}
+
+/// Handle an element closing at `depth`, passing on a record once it is complete
+fn leave(open: Option<Open>, depth: usize, batcher: &mut Batcher<'_>) -> Result<Option<Open>, DataError> {
+    let Some(mut record) = open else {
+        return Ok(None);
+    };
+    if depth == record.depth + 1 {
+        if let Some((name, text)) = record.field.take() {
+            let value = if text.is_empty() { Value::Null } else { Value::Text(text) };
+            record.fields.push((name, value));
+        }
+    }
+    if depth == record.depth {
+        batcher.push(record.fields)?;
+        return Ok(None);
+    }
+    Ok(Some(record))
+}
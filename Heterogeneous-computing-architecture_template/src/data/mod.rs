@@ -1,8 +1,22 @@
 // Data module
 
+pub mod columnar;
+pub mod data_config;
 pub mod data_manager;
 pub mod data_transformer;
+pub mod formats;
+pub mod pipeline;
 
 // Re-export key types
-pub use data_manager::DataManager;
-pub use data_transformer::DataTransformer;
\ No newline at end of file
+pub use columnar::{Column, ColumnType, Field, Schema, Table, Value};
+pub use data_config::DataConfig;
+pub use data_manager::{DataChunks, DataManager, DataObject, DataFormat, DataTransformation};
+pub use data_transformer::DataTransformer;
+pub use formats::{ReadOptions, TableWriter};
+pub use pipeline::{Cipher, Codec, Key, Keyring, Pipeline, Transform};
+
+/// Initialize the data manager
+pub fn init_data_manager(config: &serde_yaml::Value) -> Result<DataManager, Box<dyn std::error::Error>> {
+    let data_config = DataConfig::from_yaml(config)?;
+    Ok(DataManager::with_config(&data_config)?)
+}
@@ -0,0 +1,405 @@
+//! Reversible transforms applied to stored data
+//!
+//! A `Pipeline` runs compression and encryption stages over data in order and undoes
+//! them in reverse. Each stage has a name that is enough to build it again, e.g. `zstd`
+//! or `aes-256-gcm:default`, so the names recorded beside stored data tell a later
+//! read how to get the original bytes back. Encryption stages name the key they used
+//! by id; the key itself is looked up in a `Keyring` and never recorded.
+//!
+//! Encryption is authenticated: each message gets a fresh random nonce, stored in
+//! front of the ciphertext, and data that was altered or encrypted under another key
+//! fails to decrypt rather than decrypting to garbage. The names of the stages up to
+//! and including the encryption are authenticated with it, as associated data, so a
+//! recorded pipeline that was edited to skip or swap a stage fails the same way.
+
+use crate::errors::DataError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Length of the nonce stored in front of each encrypted message
+const NONCE_LEN: usize = 12;
+
+/// One reversible step of a pipeline
+///
+/// `before` names the stages the data went through ahead of this one, as
+/// `Pipeline::describe` would, and must be the same when reversing as when applying.
+pub trait Transform: Send + Sync {
+    /// Name recorded with the data, from which `Pipeline::parse` rebuilds the stage
+    fn name(&self) -> String;
+
+    /// Whether the stage authenticates its output, so tampering with it is detected
+    fn authenticates(&self) -> bool {
+        false
+    }
+
+    fn apply(&self, data: &[u8], before: &str) -> Result<Vec<u8>, DataError>;
+
+    fn reverse(&self, data: &[u8], before: &str) -> Result<Vec<u8>, DataError>;
+}
+
+/// Compression codecs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    Zstd,
+    Lz4,
+}
+
+impl Codec {
+    /// The codec as a pipeline stage; `level` only applies to zstd
+    pub fn stage(self, level: i32) -> Arc<dyn Transform> {
+        match self {
+            Codec::Zstd => Arc::new(Zstd { level }),
+            Codec::Lz4 => Arc::new(Lz4),
+        }
+    }
+}
+
+/// Zstandard compression
+#[derive(Debug, Clone, Copy)]
+pub struct Zstd {
+    pub level: i32,
+}
+
+impl Transform for Zstd {
+    fn name(&self) -> String {
+        "zstd".to_string()
+    }
+
+    fn apply(&self, data: &[u8], _before: &str) -> Result<Vec<u8>, DataError> {
+        zstd::encode_all(data, self.level).map_err(|e| failed(self, e))
+    }
+
+    fn reverse(&self, data: &[u8], _before: &str) -> Result<Vec<u8>, DataError> {
+        zstd::decode_all(data).map_err(|e| failed(self, e))
+    }
+}
+
+/// LZ4 compression, faster than zstd but compressing less
+#[derive(Debug, Clone, Copy)]
+pub struct Lz4;
+
+impl Transform for Lz4 {
+    fn name(&self) -> String {
+        "lz4".to_string()
+    }
+
+    fn apply(&self, data: &[u8], _before: &str) -> Result<Vec<u8>, DataError> {
+        Ok(lz4_flex::compress_prepend_size(data))
+    }
+
+    fn reverse(&self, data: &[u8], _before: &str) -> Result<Vec<u8>, DataError> {
+        lz4_flex::decompress_size_prepended(data).map_err(|e| failed(self, e))
+    }
+}
+
+/// Authenticated ciphers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cipher {
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn name(self) -> &'static str {
+        match self {
+            Cipher::Aes256Gcm => "aes-256-gcm",
+            Cipher::ChaCha20Poly1305 => "chacha20-poly1305",
+        }
+    }
+}
+
+/// A 256-bit key
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parse 64 hexadecimal digits
+    pub fn from_hex(hex: &str) -> Result<Self, DataError> {
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(hex.trim(), &mut bytes)
+            .map_err(|e| DataError::InvalidConfiguration(format!("encryption key: {}", e)))?;
+        Ok(Self(bytes))
+    }
+
+    /// Read a key file holding either the 32 key bytes or 64 hexadecimal digits
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, DataError> {
+        let path = path.as_ref();
+        let content = std::fs::read(path).map_err(|e| DataError::io(path, e))?;
+        if let Ok(bytes) = <[u8; 32]>::try_from(content.as_slice()) {
+            return Ok(Self(bytes));
+        }
+        let hex = String::from_utf8(content).map_err(|_| {
+            DataError::InvalidConfiguration(format!("{} holds neither 32 bytes nor hexadecimal digits", path.display()))
+        })?;
+        Self::from_hex(&hex)
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// Keys by id
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: HashMap<String, Key>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, id: &str, key: Key) {
+        self.keys.insert(id.to_string(), key);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Key> {
+        self.keys.get(id)
+    }
+
+    /// Whether `id` can be recorded in a stage name
+    pub fn valid_id(id: &str) -> bool {
+        !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    }
+}
+
+/// Authenticated encryption under a named key
+#[derive(Debug, Clone)]
+pub struct Encrypt {
+    cipher: Cipher,
+    key_id: String,
+    key: Key,
+}
+
+impl Encrypt {
+    pub fn new(cipher: Cipher, key_id: &str, key: Key) -> Self {
+        Self {
+            cipher,
+            key_id: key_id.to_string(),
+            key,
+        }
+    }
+
+    /// Associated data for a message: the stages up to and including this one
+    fn associated_data(&self, before: &str) -> String {
+        if before.is_empty() {
+            self.name()
+        } else {
+            format!("{},{}", before, self.name())
+        }
+    }
+}
+
+impl Transform for Encrypt {
+    fn name(&self) -> String {
+        format!("{}:{}", self.cipher.name(), self.key_id)
+    }
+
+    fn authenticates(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, data: &[u8], before: &str) -> Result<Vec<u8>, DataError> {
+        let aad = self.associated_data(before);
+        let payload = Payload { msg: data, aad: aad.as_bytes() };
+        let (nonce, ciphertext) = match self.cipher {
+            Cipher::Aes256Gcm => {
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                (nonce, Aes256Gcm::new(&self.key.0.into()).encrypt(&nonce, payload))
+            }
+            Cipher::ChaCha20Poly1305 => {
+                let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+                (nonce, ChaCha20Poly1305::new(&self.key.0.into()).encrypt(&nonce, payload))
+            }
+        };
+        let ciphertext = ciphertext.map_err(|_| failed(self, "encryption failed"))?;
+        let mut message = nonce.to_vec();
+        message.extend_from_slice(&ciphertext);
+        Ok(message)
+    }
+
+    fn reverse(&self, data: &[u8], before: &str) -> Result<Vec<u8>, DataError> {
+        if data.len() < NONCE_LEN {
+            return Err(failed(self, "message is shorter than its nonce"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let aad = self.associated_data(before);
+        let payload = Payload { msg: ciphertext, aad: aad.as_bytes() };
+        let plaintext = match self.cipher {
+            Cipher::Aes256Gcm => Aes256Gcm::new(&self.key.0.into()).decrypt(nonce.into(), payload),
+            Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(&self.key.0.into()).decrypt(nonce.into(), payload),
+        };
+        plaintext.map_err(|_| {
+            failed(self, "authentication failed; the data or its recorded stages were altered, or the key is wrong")
+        })
+    }
+}
+
+/// Stages applied in order and reversed in the opposite order
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    stages: Vec<Arc<dyn Transform>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a stage
+    pub fn then(mut self, stage: Arc<dyn Transform>) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Whether a stage authenticates the data, so tampering with it is detected
+    pub fn authenticates(&self) -> bool {
+        self.stages.iter().any(|stage| stage.authenticates())
+    }
+
+    /// Stage names, comma separated; empty for an empty pipeline
+    pub fn describe(&self) -> String {
+        self.describe_first(self.stages.len())
+    }
+
+    /// Rebuild the pipeline `describe` returned, taking keys from `keyring`
+    pub fn parse(description: &str, keyring: &Keyring) -> Result<Self, DataError> {
+        let mut pipeline = Self::new();
+        for name in description.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let unknown = |reason: &str| DataError::Transform {
+                stage: name.to_string(),
+                reason: reason.to_string(),
+            };
+            let stage: Arc<dyn Transform> = match name.split_once(':') {
+                None if name == "zstd" => Codec::Zstd.stage(zstd::DEFAULT_COMPRESSION_LEVEL),
+                None if name == "lz4" => Codec::Lz4.stage(0),
+                Some((cipher, key_id)) => {
+                    let cipher = match cipher {
+                        "aes-256-gcm" => Cipher::Aes256Gcm,
+                        "chacha20-poly1305" => Cipher::ChaCha20Poly1305,
+                        _ => return Err(unknown("unknown cipher")),
+                    };
+                    let key = keyring.get(key_id).ok_or_else(|| unknown("no key with this id is configured"))?;
+                    Arc::new(Encrypt::new(cipher, key_id, key.clone()))
+                }
+                None => return Err(unknown("unknown transform")),
+            };
+            pipeline.stages.push(stage);
+        }
+        Ok(pipeline)
+    }
+
+    pub fn apply(&self, data: &[u8]) -> Result<Vec<u8>, DataError> {
+        let mut data = data.to_vec();
+        for (i, stage) in self.stages.iter().enumerate() {
+            data = stage.apply(&data, &self.describe_first(i))?;
+        }
+        Ok(data)
+    }
+
+    pub fn reverse(&self, data: &[u8]) -> Result<Vec<u8>, DataError> {
+        let mut data = data.to_vec();
+        for (i, stage) in self.stages.iter().enumerate().rev() {
+            data = stage.reverse(&data, &self.describe_first(i))?;
+        }
+        Ok(data)
+    }
+
+    /// Names of the first `count` stages, as `describe` joins them
+    fn describe_first(&self, count: usize) -> String {
+        self.stages[..count].iter().map(|stage| stage.name()).collect::<Vec<_>>().join(",")
+    }
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pipeline({:?})", self.describe())
+    }
+}
+
+fn failed(stage: &dyn Transform, reason: impl fmt::Display) -> DataError {
+    DataError::Transform {
+        stage: stage.name(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipelines_reverse_what_they_apply() {
+        let mut keyring = Keyring::new();
+        keyring.insert("primary", Key::new([7; 32]));
+        let data = b"the same few words, again and again. ".repeat(64);
+
+        for description in ["zstd", "lz4", "zstd,aes-256-gcm:primary", "lz4,chacha20-poly1305:primary"] {
+            let pipeline = Pipeline::parse(description, &keyring).unwrap();
+            assert_eq!(pipeline.describe(), description);
+            let stored = pipeline.apply(&data).unwrap();
+            assert!(stored.len() < data.len(), "{} did not compress", description);
+            assert_eq!(pipeline.reverse(&stored).unwrap(), data);
+        }
+
+        let encrypt = Pipeline::parse("aes-256-gcm:primary", &keyring).unwrap();
+        let once = encrypt.apply(&data).unwrap();
+        assert_ne!(once, encrypt.apply(&data).unwrap(), "every message has its own nonce");
+        let mut altered = once.clone();
+        altered[NONCE_LEN + 3] ^= 1;
+        assert!(matches!(encrypt.reverse(&altered), Err(DataError::Transform { .. })));
+
+        // The stages before the encryption are authenticated with it
+        let compressed = Pipeline::parse("zstd,aes-256-gcm:primary", &keyring).unwrap();
+        let stored = compressed.apply(&data).unwrap();
+        assert!(compressed.authenticates() && !Pipeline::parse("zstd", &keyring).unwrap().authenticates());
+        for edited in ["aes-256-gcm:primary", "lz4,aes-256-gcm:primary"] {
+            let edited = Pipeline::parse(edited, &keyring).unwrap();
+            assert!(edited.reverse(&stored).is_err(), "{:?} was accepted", edited);
+        }
+
+        keyring.insert("primary", Key::new([8; 32]));
+        let rotated = Pipeline::parse("aes-256-gcm:primary", &keyring).unwrap();
+        assert!(rotated.reverse(&once).is_err(), "the wrong key fails to authenticate");
+        assert!(Pipeline::parse("aes-256-gcm:missing", &keyring).is_err());
+        assert!(Pipeline::parse("gzip", &keyring).is_err());
+        assert!(Pipeline::parse("", &keyring).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_keys_load_from_hex_and_files() {
+        let hex = "00".repeat(31) + "ff";
+        assert_eq!(Key::from_hex(&hex).unwrap().0[31], 0xff);
+        assert!(Key::from_hex("abcd").is_err());
+        assert_eq!(format!("{:?}", Key::new([1; 32])), "Key(..)");
+
+        let dir = std::env::temp_dir().join(format!("hca-keys-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("raw.key"), [9; 32]).unwrap();
+        std::fs::write(dir.join("hex.key"), format!("{}\n", hex)).unwrap();
+        assert_eq!(Key::from_file(dir.join("raw.key")).unwrap(), Key::new([9; 32]));
+        assert_eq!(Key::from_file(dir.join("hex.key")).unwrap(), Key::from_hex(&hex).unwrap());
+        assert!(matches!(Key::from_file(dir.join("missing.key")), Err(DataError::NotFound(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
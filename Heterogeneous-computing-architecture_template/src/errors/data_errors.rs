@@ -0,0 +1,56 @@
+// Data errors
+
+use crate::data::{DataFormat, DataTransformation};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DataError {
+    #[error("I/O error on {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("File not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid data path {path}: {reason}")]
+    InvalidPath { path: String, reason: String },
+
+    #[error("Checksum mismatch in {path}: expected {expected}, found {actual}")]
+    ChecksumMismatch { path: String, expected: String, actual: String },
+
+    #[error("Data object not found: {0}")]
+    UnknownObject(String),
+
+    #[error("Invalid data configuration: {0}")]
+    InvalidConfiguration(String),
+
+    #[error("Schema error: {0}")]
+    Schema(String),
+
+    #[error("Cannot read {format:?} data: {reason}")]
+    Malformed { format: DataFormat, reason: String },
+
+    #[error("Cannot convert {from:?} data to {to:?}")]
+    UnsupportedConversion { from: DataFormat, to: DataFormat },
+
+    #[error("Transform {stage} failed: {reason}")]
+    Transform { stage: String, reason: String },
+
+    #[error("{0:?} cannot be applied to stored data")]
+    UnsupportedTransformation(DataTransformation),
+}
+
+impl DataError {
+    /// Wrap an I/O error on `path`, mapping a missing file to `NotFound`
+    pub fn io(path: impl AsRef<std::path::Path>, source: std::io::Error) -> Self {
+        let path = path.as_ref().display().to_string();
+        if source.kind() == std::io::ErrorKind::NotFound {
+            DataError::NotFound(path)
+        } else {
+            DataError::Io { path, source }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 // Hardware errors
 
+use crate::hardware::abstract_device::HealthState;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,12 +14,76 @@ pub enum HardwareError {
     #[error("Insufficient memory: required {required}, available {available}")]
     InsufficientMemory { required: u64, available: u64 },
     
+    #[error("Insufficient compute units: required {required}, available {available}")]
+    InsufficientComputeUnits { required: u64, available: u64 },
+
     #[error("Device unavailable: {0}")]
     DeviceUnavailable(String),
     
     #[error("Execution failed: {0}")]
     ExecutionError(String),
     
+    #[error("Execution cancelled: {0}")]
+    Cancelled(String),
+
     #[error("Unsupported operation: {0}")]
     UnsupportedOperation(String),
-}
\ No newline at end of file
+
+    #[error("Invalid bitstream: {0}")]
+    InvalidBitstream(String),
+
+    #[error("Invalid hardware configuration: {0}")]
+    InvalidConfiguration(String),
+
+    #[error("Invalid task: {0}")]
+    InvalidTask(String),
+
+    #[error("Device already registered: {0}")]
+    DuplicateDevice(String),
+
+    #[error("Device {device} still has {in_flight} tasks in flight")]
+    DrainTimeout { device: String, in_flight: usize },
+
+    #[error("Lease not found: {0}")]
+    LeaseNotFound(String),
+
+    #[error("Lease already released: {0}")]
+    DoubleRelease(String),
+
+    #[error("Lease expired before release: {0}")]
+    LeaseExpired(String),
+
+    #[error("Illegal health transition for {device}: {from} -> {to}")]
+    InvalidHealthTransition {
+        device: String,
+        from: HealthState,
+        to: HealthState,
+    },
+}
+
+impl HardwareError {
+    /// Turn the problems found while validating a config section into one error
+    pub fn from_config_problems(section: &str, problems: Vec<String>) -> Result<(), Self> {
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(HardwareError::InvalidConfiguration(format!("{}: {}", section, problems.join("; "))))
+        }
+    }
+
+    /// Whether the same task could succeed on a later attempt
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            HardwareError::UnsupportedOperation(_)
+                | HardwareError::InvalidBitstream(_)
+                | HardwareError::InvalidConfiguration(_)
+                | HardwareError::InvalidTask(_)
+        )
+    }
+
+    /// Whether the error points at the device rather than the task or the configuration
+    pub fn is_device_fault(&self) -> bool {
+        matches!(self, HardwareError::ExecutionError(_) | HardwareError::InitializationError(_))
+    }
+}
@@ -1,6 +1,10 @@
 // Errors module
 
+pub mod data_errors;
 pub mod hardware_errors;
+pub mod scheduling_errors;
 
 // Re-export key types
-pub use hardware_errors::HardwareError;
\ No newline at end of file
+pub use data_errors::DataError;
+pub use hardware_errors::HardwareError;
+pub use scheduling_errors::SchedulingError;
@@ -0,0 +1,27 @@
+// Scheduling errors
+
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SchedulingError {
+    #[error("Duplicate task id: {0}")]
+    DuplicateTask(String),
+
+    #[error("Task {task} depends on unknown task {dependency}")]
+    UnknownDependency { task: String, dependency: String },
+
+    #[error("Dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+
+    #[error("No device can run task {0}")]
+    NoEligibleDevice(String),
+
+    #[error("Run queue {queue} is full ({capacity} tasks)")]
+    QueueFull { queue: String, capacity: usize },
+
+    #[error("Unknown task: {0}")]
+    UnknownTask(String),
+
+    #[error("Invalid scheduler configuration: {0}")]
+    InvalidConfiguration(String),
+}
@@ -1,97 +1,502 @@
 //! Device manager for hardware abstraction
 
-use crate::hardware::abstract::{HardwareDevice, HardwareManager, HardwareType, TaskRequirements, HardwareMetrics};
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::{
+    Capacity, DeviceHealth, DeviceQuota, DeviceReservation, HardwareDevice, HardwareManager, HardwareMetrics, HardwareType,
+    HealthEvent, HealthPolicy, HealthState, Lease, LeaseLedger, Observation, TaskRequirements, place_gang,
+};
 use std::collections::HashMap;
-use std::sync::Mutex;
-use tracing::debug;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Registry slot of one device
+///
+/// The registry lock is only held to look entries up; everything else about a device
+/// lives behind its own locks and atomics so lookups never wait on a running task.
+struct DeviceEntry {
+    device: Arc<dyn HardwareDevice>,
+    health: Mutex<DeviceHealth>,
+    in_flight: AtomicUsize,
+    draining: AtomicBool,
+    idle: Notify,
+}
+
+impl DeviceEntry {
+    fn accepts_work(&self) -> bool {
+        !self.draining.load(Ordering::SeqCst) && self.health.lock().unwrap().state().is_schedulable()
+    }
+
+    /// Memory (MB) and compute units leases can reserve on this device
+    fn quota(&self) -> DeviceQuota {
+        let total = Capacity {
+            memory: self.device.total_memory() / (1024 * 1024),
+            compute_units: self.device.capabilities().compute_units,
+        };
+        DeviceQuota::new(total, &self.device.sharing())
+    }
+}
+
+/// Shared handle to a device with a task in flight
+///
+/// The device cannot finish draining until every handle taken on it is dropped.
+pub struct DeviceHandle {
+    entry: Arc<DeviceEntry>,
+}
+
+impl DeviceHandle {
+    /// The device itself
+    pub fn device(&self) -> &Arc<dyn HardwareDevice> {
+        &self.entry.device
+    }
+}
+
+impl Deref for DeviceHandle {
+    type Target = dyn HardwareDevice;
+
+    fn deref(&self) -> &Self::Target {
+        self.entry.device.as_ref()
+    }
+}
+
+impl Drop for DeviceHandle {
+    fn drop(&mut self) {
+        if self.entry.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.entry.idle.notify_waiters();
+        }
+    }
+}
 
 /// Concrete implementation of HardwareManager
 pub struct DeviceManager {
-    devices: Mutex<HashMap<String, Box<dyn HardwareDevice>>>,
+    devices: RwLock<HashMap<String, Arc<DeviceEntry>>>,
+    policy: HealthPolicy,
+    subscribers: Mutex<Vec<mpsc::UnboundedSender<HealthEvent>>>,
+    leases: Mutex<LeaseLedger>,
 }
 
 impl DeviceManager {
     /// Create a new DeviceManager
     pub fn new() -> Self {
+        Self::with_health_policy(HealthPolicy::default())
+    }
+
+    /// Create a new DeviceManager with explicit health settings
+    pub fn with_health_policy(policy: HealthPolicy) -> Self {
         Self {
-            devices: Mutex::new(HashMap::new()),
+            devices: RwLock::new(HashMap::new()),
+            policy,
+            subscribers: Mutex::new(Vec::new()),
+            leases: Mutex::new(LeaseLedger::new()),
         }
     }
-    
+
     /// Register a new hardware device
-    pub fn register_device(&self, device: Box<dyn HardwareDevice>) -> Result<(), Box<dyn std::error::Error>> {
-        let device_id = device.get_id();
-        debug!("Registering device: {} ({})", device.get_name(), device.get_type());
-        
-        self.devices.lock().unwrap().insert(device_id, device);
+    pub fn register_device(&self, device: Arc<dyn HardwareDevice>) -> Result<(), Box<dyn std::error::Error>> {
+        let device_id = device.id().to_string();
+        debug!("Registering device: {} ({})", device.name(), device.device_type());
+
+        let state = if device.is_available() { HealthState::Healthy } else { HealthState::Offline };
+        let entry = Arc::new(DeviceEntry {
+            health: Mutex::new(DeviceHealth::new(&device_id, state)),
+            device,
+            in_flight: AtomicUsize::new(0),
+            draining: AtomicBool::new(false),
+            idle: Notify::new(),
+        });
+
+        let mut devices = self.devices.write().unwrap();
+        if devices.contains_key(&device_id) {
+            return Err(HardwareError::DuplicateDevice(device_id).into());
+        }
+        devices.insert(device_id, entry);
         Ok(())
     }
-    
-    /// Unregister a hardware device
+
+    /// Unregister a hardware device, rejecting new work on it
+    ///
+    /// Tasks already running keep their handles and finish; use `drain_device` to wait for them.
     pub fn unregister_device(&self, device_id: &str) -> Result<(), Box<dyn std::error::Error>> {
         debug!("Unregistering device: {}", device_id);
-        
-        self.devices.lock().unwrap().remove(device_id);
+
+        if let Some(entry) = self.devices.write().unwrap().remove(device_id) {
+            entry.draining.store(true, Ordering::SeqCst);
+            let in_flight = entry.in_flight.load(Ordering::SeqCst);
+            if in_flight > 0 {
+                warn!("Device {} unregistered with {} tasks still running", device_id, in_flight);
+            }
+        }
         Ok(())
     }
-}
 
-impl HardwareManager for DeviceManager {
-    fn get_all_devices(&self) -> Vec<Box<dyn HardwareDevice>> {
-        self.devices.lock().unwrap().values().cloned().collect()
+    /// Stop sending work to a device, wait for its running tasks, then unregister it
+    ///
+    /// On timeout the device stays registered but keeps rejecting new work.
+    pub async fn drain_device(&self, device_id: &str, timeout: Duration) -> Result<(), HardwareError> {
+        let entry = self.entry(device_id)?;
+        entry.draining.store(true, Ordering::SeqCst);
+        debug!("Draining device {} ({} tasks in flight)", device_id, entry.in_flight.load(Ordering::SeqCst));
+
+        let drained = tokio::time::timeout(timeout, async {
+            loop {
+                // Register for the wakeup before checking so a final drop cannot be missed
+                let idle = entry.idle.notified();
+                if entry.in_flight.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                idle.await;
+            }
+        })
+        .await;
+        if drained.is_err() {
+            return Err(HardwareError::DrainTimeout {
+                device: device_id.to_string(),
+                in_flight: entry.in_flight.load(Ordering::SeqCst),
+            });
+        }
+
+        let mut devices = self.devices.write().unwrap();
+        if devices.get(device_id).is_some_and(|current| Arc::ptr_eq(current, &entry)) {
+            devices.remove(device_id);
+        }
+        Ok(())
     }
-    
-    fn get_devices_by_type(&self, device_type: HardwareType) -> Vec<Box<dyn HardwareDevice>> {
-        self.devices
+
+    /// Number of tasks currently holding a handle on a device
+    pub fn tasks_in_flight(&self, device_id: &str) -> usize {
+        self.entry(device_id).map_or(0, |entry| entry.in_flight.load(Ordering::SeqCst))
+    }
+
+    /// Active leases, oldest first
+    pub fn leases(&self) -> Vec<Lease> {
+        let mut ledger = self.leases.lock().unwrap();
+        ledger.expire(Instant::now());
+        ledger.leases()
+    }
+
+    /// Reclaim the capacity of leases whose task outlived its timeout
+    ///
+    /// Every allocation and release does this first, so expired capacity is never counted.
+    pub fn expire_leases(&self) -> Vec<Lease> {
+        self.leases.lock().unwrap().expire(Instant::now())
+    }
+
+    /// Health settings in use
+    pub fn health_policy(&self) -> &HealthPolicy {
+        &self.policy
+    }
+
+    /// Health record of a device
+    pub fn device_health(&self, device_id: &str) -> Option<DeviceHealth> {
+        self.entry(device_id).ok().map(|entry| entry.health.lock().unwrap().clone())
+    }
+
+    /// Receive every health state change from now on
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<HealthEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Move a device to `state` by hand, e.g. to quarantine it for maintenance
+    pub fn set_health(&self, device_id: &str, state: HealthState, reason: &str) -> Result<(), HardwareError> {
+        let entry = self.entry(device_id)?;
+        let mut health = entry.health.lock().unwrap();
+        let event = health.enter(state, reason.to_string())?;
+        self.publish(event);
+        Ok(())
+    }
+
+    /// Feed an observation into a device's health state machine
+    pub fn observe(&self, device_id: &str, observation: Observation) {
+        if let Ok(entry) = self.entry(device_id) {
+            let mut health = entry.health.lock().unwrap();
+            if let Some(event) = health.observe(&self.policy, observation) {
+                self.publish(event);
+            }
+        }
+    }
+
+    /// Probe every registered device once
+    pub async fn probe_devices(&self) {
+        for entry in self.entries() {
+            let device = &entry.device;
+            let observation = if !device.is_available() {
+                Observation::Unavailable
+            } else {
+                match device.health_check().await {
+                    Ok(()) => Observation::Success,
+                    Err(e) => Observation::Failure(e.to_string()),
+                }
+            };
+            self.observe(device.id(), observation);
+        }
+    }
+
+    /// Probe devices every `probe_interval` until the manager is dropped
+    pub fn spawn_health_monitor(self: &Arc<Self>) -> JoinHandle<()> {
+        let manager = Arc::downgrade(self);
+        let interval = self.policy.probe_interval().max(Duration::from_millis(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match manager.upgrade() {
+                    Some(manager) => manager.probe_devices().await,
+                    None => break,
+                }
+            }
+        })
+    }
+
+    /// Log a transition and hand it to subscribers, dropping those that went away
+    fn publish(&self, event: HealthEvent) {
+        match event.to {
+            HealthState::Quarantined | HealthState::Offline => {
+                warn!("Device {} is {} ({}): {}", event.device_id, event.to, event.from, event.reason)
+            }
+            _ => info!("Device {} is {} ({}): {}", event.device_id, event.to, event.from, event.reason),
+        }
+        self.subscribers
             .lock()
             .unwrap()
-            .values()
-            .filter(|device| device.get_type() == device_type)
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    fn entry(&self, device_id: &str) -> Result<Arc<DeviceEntry>, HardwareError> {
+        self.devices
+            .read()
+            .unwrap()
+            .get(device_id)
             .cloned()
+            .ok_or_else(|| HardwareError::DeviceNotFound(device_id.to_string()))
+    }
+
+    /// Snapshot of the registry, taken without holding its lock afterwards
+    fn entries(&self) -> Vec<Arc<DeviceEntry>> {
+        self.devices.read().unwrap().values().cloned().collect()
+    }
+
+    /// Devices in service that could run a task, best first
+    ///
+    /// Healthy devices come before degraded ones, then those ready to run the kernel.
+    /// Shared devices are packed, fullest first, so small tasks land together and leave
+    /// whole devices for large ones. Ties go to the least utilized, read once per device.
+    fn candidates(&self, requirements: &TaskRequirements, ledger: &LeaseLedger) -> Vec<Arc<DeviceEntry>> {
+        let mut candidates: Vec<_> = self
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.device.device_type() == requirements.hardware_type)
+            .filter(|entry| !entry.draining.load(Ordering::SeqCst) && entry.device.is_available())
+            .filter_map(|entry| {
+                let health = entry.health.lock().unwrap().state();
+                if !health.is_schedulable() {
+                    return None;
+                }
+                let setup_cost = match &requirements.kernel {
+                    Some(kernel) => entry.device.setup_cost(kernel)?,
+                    None => Duration::ZERO,
+                };
+                let quota = entry.quota();
+                let packed = (quota.per_task != quota.capacity || !quota.slices.is_empty())
+                    .then(|| quota.capacity.saturating_sub(ledger.reserved(entry.device.id())).memory);
+                let utilization = entry.device.get_metrics().utilization;
+                Some((health_rank(health), setup_cost, packed, utilization, entry))
+            })
+            .collect();
+
+        candidates.sort_by(|(a_rank, a_cost, a_free, a_used, _), (b_rank, b_cost, b_free, b_used, _)| {
+            a_rank
+                .cmp(b_rank)
+                .then_with(|| a_cost.cmp(b_cost))
+                .then_with(|| a_free.cmp(b_free))
+                .then_with(|| a_used.total_cmp(b_used))
+        });
+        candidates.into_iter().map(|(_, _, _, _, entry)| entry).collect()
+    }
+}
+
+impl Default for DeviceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HardwareManager for DeviceManager {
+    fn get_all_devices(&self) -> Vec<Arc<dyn HardwareDevice>> {
+        self.entries().into_iter().map(|entry| entry.device.clone()).collect()
+    }
+
+    fn get_devices_by_type(&self, device_type: HardwareType) -> Vec<Arc<dyn HardwareDevice>> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.device.device_type() == device_type)
+            .map(|entry| entry.device.clone())
             .collect()
     }
-    
-    fn get_device_by_id(&self, id: &str) -> Option<Box<dyn HardwareDevice>> {
-        self.devices.lock().unwrap().get(id).cloned()
+
+    fn get_device_by_id(&self, id: &str) -> Option<Arc<dyn HardwareDevice>> {
+        self.entry(id).ok().map(|entry| entry.device.clone())
     }
-    
-    fn allocate_resources(&self, task_requirements: &TaskRequirements) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+
+    fn acquire_device(&self, id: &str) -> Result<DeviceHandle, HardwareError> {
+        let entry = self.entry(id)?;
+        entry.in_flight.fetch_add(1, Ordering::SeqCst);
+        let handle = DeviceHandle { entry };
+
+        // Checked after counting the task so a concurrent drain either sees it or rejects it
+        if !handle.entry.accepts_work() || !handle.is_available() {
+            return Err(HardwareError::DeviceUnavailable(format!("device {} is not accepting work", id)));
+        }
+        Ok(handle)
+    }
+
+    fn allocate_resources(&self, task_id: &str, task_requirements: &TaskRequirements) -> Result<Vec<Lease>, HardwareError> {
         debug!(
-            "Allocating resources for task requiring {:?} with {} MB memory",
+            "Allocating resources for task {} requiring {:?} with {} MB memory",
+            task_id,
             task_requirements.hardware_type,
             task_requirements.memory_required
         );
-        
-        // Find available devices matching the requirements
-        let mut available_devices = self.get_devices_by_type(task_requirements.hardware_type);
-        
-        // Sort by availability and utilization
-        available_devices.sort_by_key(|device| {
-            device.get_metrics().utilization
-        });
-        
-        // Select one device for this task
-        if let Some(device) = available_devices.first() {
-            let device_id = device.get_id();
-            debug!("Allocated device {} for task", device_id);
-            Ok(vec![device_id])
-        } else {
-            Err("No suitable device found".into())
+
+        // Selection and reservation happen under the ledger lock so two tasks cannot both take the last capacity.
+        // A gang gets all of its devices in one go or none, so it never holds some devices while waiting for
+        // others and competing gangs cannot deadlock.
+        let mut ledger = self.leases.lock().unwrap();
+        ledger.expire(Instant::now());
+
+        // Every member may take any candidate with enough capacity left, but no two share one
+        let members = task_requirements.per_device();
+        let mut entries: HashMap<String, Arc<DeviceEntry>> = HashMap::new();
+        let mut options = Vec::with_capacity(members.len());
+        for member in &members {
+            let mut shortfall = None;
+            let fitting: Vec<String> = self
+                .candidates(member, &ledger)
+                .into_iter()
+                .filter(|entry| match ledger.check(entry.device.id(), &entry.quota(), member) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        shortfall.get_or_insert(e);
+                        false
+                    }
+                })
+                .map(|entry| {
+                    let id = entry.device.id().to_string();
+                    entries.insert(id.clone(), entry);
+                    id
+                })
+                .collect();
+            if fitting.is_empty() {
+                return Err(shortfall.unwrap_or_else(|| {
+                    HardwareError::DeviceUnavailable(format!("no suitable {} device found", member.hardware_type))
+                }));
+            }
+            options.push(fitting);
+        }
+        let placement = place_gang(&members, &options).ok_or_else(|| {
+            HardwareError::DeviceUnavailable(format!("not enough distinct devices for the {} members of task {}", members.len(), task_id))
+        })?;
+        let picked: Vec<(Arc<DeviceEntry>, TaskRequirements)> =
+            placement.iter().map(|id| Arc::clone(&entries[id])).zip(members).collect();
+
+        let now = Instant::now();
+        let mut leases = Vec::with_capacity(picked.len());
+        for (entry, member) in &picked {
+            match ledger.grant(task_id, entry.device.id(), &entry.quota(), member, now) {
+                Ok(lease) => {
+                    debug!("Allocated device {} for task {} ({})", lease.device_id, task_id, lease.id);
+                    leases.push(lease);
+                }
+                Err(e) => {
+                    for lease in &leases {
+                        let _ = ledger.release(&lease.id);
+                    }
+                    return Err(e);
+                }
+            }
         }
+        Ok(leases)
     }
-    
-    fn release_resources(&self, resource_ids: &[String]) -> Result<(), Box<dyn std::error::Error>> {
-        debug!("Releasing resources: {:?}", resource_ids);
-        // In a real implementation, this would actually free up the resources
-        Ok(())
+
+    fn reserve(&self, device_id: &str, task_id: &str, task_requirements: &TaskRequirements) -> Result<Lease, HardwareError> {
+        let entry = self.entry(device_id)?;
+        if !entry.accepts_work() || !entry.device.is_available() {
+            return Err(HardwareError::DeviceUnavailable(format!("device {} is not accepting work", device_id)));
+        }
+        let mut ledger = self.leases.lock().unwrap();
+        ledger.expire(Instant::now());
+        ledger.grant(task_id, device_id, &entry.quota(), task_requirements, Instant::now())
     }
-    
+
+    fn release_resources(&self, lease_ids: &[String]) -> Result<(), HardwareError> {
+        debug!("Releasing leases: {:?}", lease_ids);
+
+        let mut ledger = self.leases.lock().unwrap();
+        ledger.expire(Instant::now());
+
+        // Release every lease even if one of them is bad, then report the first problem
+        let mut first_error = None;
+        for id in lease_ids {
+            if let Err(e) = ledger.release(id) {
+                warn!("Releasing {}: {}", id, e);
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    fn reservations(&self) -> Vec<DeviceReservation> {
+        let mut ledger = self.leases.lock().unwrap();
+        ledger.expire(Instant::now());
+        self.entries()
+            .into_iter()
+            .map(|entry| DeviceReservation {
+                device_id: entry.device.id().to_string(),
+                quota: entry.quota(),
+                reserved: ledger.reserved(entry.device.id()),
+                leases: ledger.lease_count(entry.device.id()),
+            })
+            .collect()
+    }
+
     fn get_hardware_metrics(&self) -> Vec<HardwareMetrics> {
-        self.devices
-            .lock()
-            .unwrap()
-            .values()
-            .map(|device| device.get_metrics())
+        self.entries()
+            .into_iter()
+            .map(|entry| {
+                let mut metrics = entry.device.get_metrics();
+                if let Some(status) = entry.health.lock().unwrap().state().device_status() {
+                    metrics.status = status;
+                }
+                metrics
+            })
             .collect()
     }
-}
\ No newline at end of file
+
+    fn is_schedulable(&self, device_id: &str) -> bool {
+        self.entry(device_id).is_ok_and(|entry| entry.accepts_work())
+    }
+
+    fn report_task_outcome(&self, device_id: &str, outcome: Result<(), &HardwareError>) {
+        match outcome {
+            Ok(()) => self.observe(device_id, Observation::Success),
+            Err(e) if e.is_device_fault() => self.observe(device_id, Observation::Failure(e.to_string())),
+            // Bad payloads, unsupported operations and full memory are the task's fault
+            Err(_) => {}
+        }
+    }
+}
+
+/// Order of preference between schedulable health states
+fn health_rank(state: HealthState) -> u8 {
+    match state {
+        HealthState::Healthy => 0,
+        HealthState::Degraded => 1,
+        _ => 2,
+    }
+}
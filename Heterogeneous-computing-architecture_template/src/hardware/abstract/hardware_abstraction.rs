@@ -1,11 +1,18 @@
 //! Abstract hardware interface for heterogeneous computing
 
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::{Capacity, DeviceHandle, DeviceReservation, Lease, PowerModel, SharingPolicy};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
 
 /// Hardware type enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum HardwareType {
     Cpu,
     Gpu,
@@ -22,31 +29,173 @@ impl fmt::Display for HardwareType {
     }
 }
 
+/// Unit of work dispatched to a single device
+#[derive(Debug, Clone)]
+pub struct Task {
+    /// Task identifier
+    pub id: String,
+
+    /// Input payload
+    pub data: Vec<u8>,
+
+    /// Operation to run on the payload
+    pub operation: String,
+
+    /// Kernel or bitstream id the task needs loaded, when it differs from the operation
+    pub kernel: Option<String>,
+
+    /// Every device of a gang task, in member order; empty for a single-device task
+    pub devices: Vec<String>,
+
+    /// Set once the caller gives up on the task; devices stop their work when it is
+    pub cancel: CancelToken,
+
+    /// Most memory and compute units the task may use, from its lease; `None` for all of the device
+    pub limit: Option<Capacity>,
+}
+
+/// Shared flag telling a running task to stop
+///
+/// Clones observe the same flag, so a device can watch the token its caller keeps.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<(AtomicBool, Notify)>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every holder of the token to stop
+    pub fn cancel(&self) {
+        self.inner.0.store(true, Ordering::Release);
+        self.inner.1.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.0.load(Ordering::Acquire)
+    }
+
+    /// Resolve once the token is cancelled
+    pub async fn cancelled(&self) {
+        let notified = self.inner.1.notified();
+        tokio::pin!(notified);
+        // Register before checking the flag so a cancel in between is not missed
+        notified.as_mut().enable();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+/// Result of executing a task on a device
+#[derive(Debug, Clone)]
+pub struct TaskResult {
+    /// Identifier of the task that produced this result
+    pub task_id: String,
+
+    /// Output payload
+    pub data: Vec<u8>,
+
+    /// Execution time in milliseconds
+    pub execution_time: u64,
+
+    /// Estimated energy the device spent on the task, in joules
+    pub energy: f64,
+}
+
+/// Static capabilities advertised by a device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCapabilities {
+    /// Number of independent compute units (cores, SMs, PEs)
+    pub compute_units: u64,
+
+    /// Operations the device can execute
+    pub operations: Vec<String>,
+
+    /// Maximum clock speed in MHz
+    pub max_clock_speed: u64,
+}
+
+impl DeviceCapabilities {
+    /// Check whether the device supports an operation
+    pub fn supports(&self, operation: &str) -> bool {
+        self.operations.iter().any(|op| op == operation)
+    }
+}
+
 /// Base trait for all hardware devices
+#[async_trait]
 pub trait HardwareDevice: Send + Sync {
-    /// Get the hardware type
-    fn get_type(&self) -> HardwareType;
-    
     /// Get device identifier
-    fn get_id(&self) -> String;
-    
+    fn id(&self) -> &str;
+
     /// Get device name
-    fn get_name(&self) -> String;
-    
+    fn name(&self) -> &str;
+
+    /// Get the hardware type
+    fn device_type(&self) -> HardwareType;
+
     /// Get device properties
-    fn get_properties(&self) -> HashMap<String, String>;
-    
+    fn properties(&self) -> HashMap<String, String>;
+
+    /// Get device capabilities
+    fn capabilities(&self) -> DeviceCapabilities;
+
     /// Check if device is available
     fn is_available(&self) -> bool;
-    
+
     /// Initialize the device
-    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>>;
-    
+    async fn initialize(&self) -> Result<(), HardwareError>;
+
     /// Shutdown the device
-    fn shutdown(&mut self) -> Result<(), Box<dyn std::error::Error>>;
-    
+    async fn shutdown(&self) -> Result<(), HardwareError>;
+
     /// Get device metrics
     fn get_metrics(&self) -> HardwareMetrics;
+
+    /// Get the total memory in bytes
+    fn total_memory(&self) -> u64;
+
+    /// Get the available memory in bytes
+    fn available_memory(&self) -> u64;
+
+    /// Execute a task on the device
+    ///
+    /// A cancelled run keeps its lease until this returns, so implementations should
+    /// stop promptly once `task.cancel` is set.
+    async fn execute_task(&self, task: &Task) -> Result<TaskResult, HardwareError>;
+
+    /// Time needed before `kernel` can run, e.g. FPGA reconfiguration; `None` if it cannot run here
+    fn setup_cost(&self, _kernel: &str) -> Option<Duration> {
+        Some(Duration::ZERO)
+    }
+
+    /// Modelled time to run `task` here, excluding host transfers; `None` if there is no model
+    fn estimate_execution(&self, _task: &Task) -> Option<Duration> {
+        None
+    }
+
+    /// Modelled time to move `bytes` between host memory and the device
+    fn transfer_time(&self, _bytes: u64) -> Duration {
+        Duration::ZERO
+    }
+
+    /// Idle and active draw of the device; `None` if there is no model
+    fn power_model(&self) -> Option<PowerModel> {
+        None
+    }
+
+    /// How leases may divide the device between tasks
+    fn sharing(&self) -> SharingPolicy {
+        SharingPolicy::default()
+    }
+
+    /// Probe an available device for faults
+    async fn health_check(&self) -> Result<(), HardwareError> {
+        Ok(())
+    }
 }
 
 /// Hardware metrics structure
@@ -54,21 +203,25 @@ pub trait HardwareDevice: Send + Sync {
 pub struct HardwareMetrics {
     /// Current utilization percentage
     pub utilization: f64,
-    
+
     /// Temperature in Celsius
     pub temperature: f64,
-    
+
     /// Memory usage in MB
     pub memory_used: u64,
-    
+
     /// Total memory in MB
     pub memory_total: u64,
-    
+
     /// Clock speed in MHz
     pub clock_speed: u64,
-    
+
     /// Status of the device
     pub status: DeviceStatus,
+
+    /// Cores held by each running task, on devices that reserve cores
+    #[serde(default)]
+    pub core_reservations: HashMap<String, Vec<usize>>,
 }
 
 /// Device status enumeration
@@ -96,22 +249,41 @@ impl fmt::Display for DeviceStatus {
 /// Hardware manager trait
 pub trait HardwareManager: Send + Sync {
     /// Get all available hardware devices
-    fn get_all_devices(&self) -> Vec<Box<dyn HardwareDevice>>;
-    
+    fn get_all_devices(&self) -> Vec<Arc<dyn HardwareDevice>>;
+
     /// Get devices of a specific type
-    fn get_devices_by_type(&self, device_type: HardwareType) -> Vec<Box<dyn HardwareDevice>>;
-    
+    fn get_devices_by_type(&self, device_type: HardwareType) -> Vec<Arc<dyn HardwareDevice>>;
+
     /// Get a specific device by ID
-    fn get_device_by_id(&self, id: &str) -> Option<Box<dyn HardwareDevice>>;
-    
-    /// Allocate resources for a task
-    fn allocate_resources(&self, task_requirements: &TaskRequirements) -> Result<Vec<String>, Box<dyn std::error::Error>>;
-    
-    /// Release allocated resources
-    fn release_resources(&self, resource_ids: &[String]) -> Result<(), Box<dyn std::error::Error>>;
-    
+    fn get_device_by_id(&self, id: &str) -> Option<Arc<dyn HardwareDevice>>;
+
+    /// Take a handle for running a task on a device; fails if it is out of service or draining
+    fn acquire_device(&self, id: &str) -> Result<DeviceHandle, HardwareError>;
+
+    /// Pick devices for a task and reserve its requirements there
+    ///
+    /// A gang task gets one lease per device, in member order, or none at all.
+    fn allocate_resources(&self, task_id: &str, task_requirements: &TaskRequirements) -> Result<Vec<Lease>, HardwareError>;
+
+    /// Reserve a task's requirements on a specific device
+    fn reserve(&self, device_id: &str, task_id: &str, task_requirements: &TaskRequirements) -> Result<Lease, HardwareError>;
+
+    /// Release leases by id
+    fn release_resources(&self, lease_ids: &[String]) -> Result<(), HardwareError>;
+
+    /// Reserved and total capacity of every registered device
+    fn reservations(&self) -> Vec<DeviceReservation>;
+
     /// Get hardware metrics
     fn get_hardware_metrics(&self) -> Vec<HardwareMetrics>;
+
+    /// Check whether a device may receive new work
+    fn is_schedulable(&self, _device_id: &str) -> bool {
+        true
+    }
+
+    /// Report how a task dispatched to a device ended
+    fn report_task_outcome(&self, _device_id: &str, _outcome: Result<(), &HardwareError>) {}
 }
 
 /// Task requirements structure
@@ -119,16 +291,122 @@ pub trait HardwareManager: Send + Sync {
 pub struct TaskRequirements {
     /// Required hardware type
     pub hardware_type: HardwareType,
-    
+
     /// Required memory in MB
     pub memory_required: u64,
-    
+
     /// Required compute units
     pub compute_units: u64,
-    
+
     /// Priority level
     pub priority: u8,
-    
+
     /// Timeout in milliseconds
     pub timeout: u64,
-}
\ No newline at end of file
+
+    /// Kernel or bitstream id the task needs loaded
+    #[serde(default)]
+    pub kernel: Option<String>,
+
+    /// Devices the task needs all at once; empty for a task that runs on one device
+    ///
+    /// When set, it takes the place of `hardware_type`, `memory_required` and `compute_units`.
+    #[serde(default)]
+    pub gang: Vec<GangMember>,
+}
+
+impl TaskRequirements {
+    /// Whether the task runs on several devices at once
+    pub fn is_gang(&self) -> bool {
+        !self.gang.is_empty()
+    }
+
+    /// Requirements of each device the task runs on, in member order
+    ///
+    /// A gang member of count N gives N entries; a single-device task gives itself.
+    pub fn per_device(&self) -> Vec<TaskRequirements> {
+        if self.gang.is_empty() {
+            return vec![self.clone()];
+        }
+        self.gang
+            .iter()
+            .flat_map(|member| {
+                let requirements = TaskRequirements {
+                    hardware_type: member.hardware_type,
+                    memory_required: member.memory_required,
+                    compute_units: member.compute_units,
+                    gang: Vec::new(),
+                    ..self.clone()
+                };
+                std::iter::repeat_n(requirements, member.count)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl TaskRequirements {
+    /// A single-device task needing 16 MB and one compute unit, with a 1 s timeout
+    pub(crate) fn for_test(hardware_type: HardwareType) -> Self {
+        Self {
+            hardware_type,
+            memory_required: 16,
+            compute_units: 1,
+            priority: 50,
+            timeout: 1000,
+            kernel: None,
+            gang: Vec::new(),
+        }
+    }
+}
+
+/// Distinct devices of one type that a gang task needs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GangMember {
+    /// Required hardware type
+    pub hardware_type: HardwareType,
+
+    /// Number of devices of the type
+    pub count: usize,
+
+    /// Memory in MB required on each device
+    pub memory_required: u64,
+
+    /// Compute units required on each device
+    pub compute_units: u64,
+}
+
+/// Give each gang member a distinct device
+///
+/// `options[i]` lists the devices member `i` fits on, best first. Members are placed
+/// most demanding first, and one whose devices are all taken moves an earlier member
+/// to another device it fits on, so a small member never keeps the only device a large
+/// one fits on. Returns the device of each member, in member order.
+pub fn place_gang<T: Clone + PartialEq>(members: &[TaskRequirements], options: &[Vec<T>]) -> Option<Vec<T>> {
+    let mut order: Vec<usize> = (0..members.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((members[i].memory_required, members[i].compute_units)));
+
+    let mut placed = vec![None; members.len()];
+    for member in order {
+        if !place_member(member, options, &mut placed, &mut Vec::new()) {
+            return None;
+        }
+    }
+    placed.into_iter().collect()
+}
+
+/// Find `member` a device, moving the members in its way along (an augmenting path)
+fn place_member<T: Clone + PartialEq>(member: usize, options: &[Vec<T>], placed: &mut [Option<T>], seen: &mut Vec<T>) -> bool {
+    for device in &options[member] {
+        if seen.contains(device) {
+            continue;
+        }
+        seen.push(device.clone());
+        let holder = placed.iter().position(|taken| taken.as_ref() == Some(device));
+        if holder.is_none_or(|holder| place_member(holder, options, placed, seen)) {
+            placed[member] = Some(device.clone());
+            return true;
+        }
+    }
+    false
+}
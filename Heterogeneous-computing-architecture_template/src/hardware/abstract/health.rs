@@ -0,0 +1,357 @@
+//! Device health tracking
+//!
+//! Every registered device carries a `HealthState`. Task outcomes and periodic probes
+//! drive it through a small state machine: repeated failures quarantine a device, and
+//! once the quarantine period has passed a successful probe returns it to service on
+//! probation. Only `Healthy`, `Degraded` and `Probation` devices receive new work.
+
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::DeviceStatus;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Health of a registered device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HealthState {
+    /// Serving work normally
+    Healthy,
+    /// Serving work after recent failures
+    Degraded,
+    /// Taken out of service after repeated failures
+    Quarantined,
+    /// Back in service after a quarantine, one failure away from another
+    Probation,
+    /// Not available, e.g. shut down or not initialized
+    Offline,
+}
+
+impl HealthState {
+    /// Check whether the state machine allows moving to `next`
+    pub fn can_transition_to(self, next: HealthState) -> bool {
+        use HealthState::*;
+        matches!(
+            (self, next),
+            (Healthy, Degraded | Quarantined | Offline)
+                | (Degraded, Healthy | Quarantined | Offline)
+                | (Quarantined, Probation | Offline)
+                | (Probation, Healthy | Quarantined | Offline)
+                | (Offline, Probation | Quarantined)
+        )
+    }
+
+    /// Check whether a device in this state may receive new work
+    pub fn is_schedulable(self) -> bool {
+        matches!(self, HealthState::Healthy | HealthState::Degraded | HealthState::Probation)
+    }
+
+    /// Status reported in place of the device's own while in this state
+    pub fn device_status(self) -> Option<DeviceStatus> {
+        match self {
+            HealthState::Quarantined => Some(DeviceStatus::Error),
+            HealthState::Offline => Some(DeviceStatus::Offline),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HealthState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthState::Healthy => write!(f, "healthy"),
+            HealthState::Degraded => write!(f, "degraded"),
+            HealthState::Quarantined => write!(f, "quarantined"),
+            HealthState::Probation => write!(f, "probation"),
+            HealthState::Offline => write!(f, "offline"),
+        }
+    }
+}
+
+/// Health settings (`hardware.health` in application.yaml)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthPolicy {
+    /// Interval between health probes in milliseconds
+    pub probe_interval: u64,
+
+    /// Consecutive failures that quarantine a device
+    pub failure_threshold: u32,
+
+    /// Minimum time in quarantine in milliseconds
+    pub quarantine_period: u64,
+
+    /// Consecutive successes that end probation
+    pub probation_successes: u32,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            probe_interval: 5000,
+            failure_threshold: 3,
+            quarantine_period: 30000,
+            probation_successes: 5,
+        }
+    }
+}
+
+impl HealthPolicy {
+    /// Check for values that would disable the state machine
+    pub fn validate(&self) -> Result<(), HardwareError> {
+        let mut problems = Vec::new();
+        if self.probe_interval == 0 {
+            problems.push("probe_interval must be positive".to_string());
+        }
+        if self.failure_threshold == 0 {
+            problems.push("failure_threshold must be at least 1".to_string());
+        }
+        if self.probation_successes == 0 {
+            problems.push("probation_successes must be at least 1".to_string());
+        }
+        HardwareError::from_config_problems("hardware.health", problems)
+    }
+
+    /// Interval between health probes
+    pub fn probe_interval(&self) -> Duration {
+        Duration::from_millis(self.probe_interval)
+    }
+
+    /// Minimum time in quarantine
+    pub fn quarantine_period(&self) -> Duration {
+        Duration::from_millis(self.quarantine_period)
+    }
+}
+
+/// Something observed about a device
+#[derive(Debug, Clone, PartialEq)]
+pub enum Observation {
+    /// A task or probe succeeded
+    Success,
+    /// A task or probe failed because of the device
+    Failure(String),
+    /// The device reports itself unavailable
+    Unavailable,
+}
+
+/// Change of a device's health state, delivered to subscribers
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthEvent {
+    pub device_id: String,
+    pub from: HealthState,
+    pub to: HealthState,
+    pub reason: String,
+}
+
+/// Health record of one device
+#[derive(Debug, Clone)]
+pub struct DeviceHealth {
+    device_id: String,
+    state: HealthState,
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    quarantined_at: Option<Instant>,
+    quarantines: u32,
+    last_error: Option<String>,
+}
+
+impl DeviceHealth {
+    /// Start tracking a device in `state`
+    pub fn new(device_id: &str, state: HealthState) -> Self {
+        Self {
+            device_id: device_id.to_string(),
+            state,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            quarantined_at: None,
+            quarantines: 0,
+            last_error: None,
+        }
+    }
+
+    /// Current state
+    pub fn state(&self) -> HealthState {
+        self.state
+    }
+
+    /// Failures since the last success
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Number of times the device has been quarantined
+    pub fn quarantines(&self) -> u32 {
+        self.quarantines
+    }
+
+    /// Most recent failure reason
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Feed an observation into the state machine, returning the transition it caused
+    pub fn observe(&mut self, policy: &HealthPolicy, observation: Observation) -> Option<HealthEvent> {
+        use HealthState::*;
+        match observation {
+            Observation::Success => {
+                self.consecutive_failures = 0;
+                match self.state {
+                    Degraded => self.enter(Healthy, "recovered".to_string()).ok(),
+                    Probation => {
+                        self.consecutive_successes += 1;
+                        if self.consecutive_successes >= policy.probation_successes {
+                            let reason = format!("{} consecutive successes on probation", self.consecutive_successes);
+                            self.enter(Healthy, reason).ok()
+                        } else {
+                            None
+                        }
+                    }
+                    Quarantined => {
+                        let served = self.quarantined_at.is_none_or(|at| at.elapsed() >= policy.quarantine_period());
+                        if served {
+                            self.enter(Probation, "quarantine period elapsed".to_string()).ok()
+                        } else {
+                            None
+                        }
+                    }
+                    Offline => match self.quarantined_at {
+                        // A quarantine is served even while the device is offline
+                        Some(at) if at.elapsed() < policy.quarantine_period() => {
+                            self.enter(Quarantined, "available again before its quarantine ended".to_string()).ok()
+                        }
+                        _ => self.enter(Probation, "device is available again".to_string()).ok(),
+                    },
+                    Healthy => None,
+                }
+            }
+            Observation::Failure(reason) => {
+                self.consecutive_failures += 1;
+                self.consecutive_successes = 0;
+                self.last_error = Some(reason.clone());
+                match self.state {
+                    Healthy | Degraded if self.consecutive_failures >= policy.failure_threshold => {
+                        let reason = format!("{} consecutive failures, last: {}", self.consecutive_failures, reason);
+                        self.enter(Quarantined, reason).ok()
+                    }
+                    Healthy => self.enter(Degraded, reason).ok(),
+                    Probation => self.enter(Quarantined, format!("failed on probation: {}", reason)).ok(),
+                    Quarantined => {
+                        // Failing while quarantined restarts the quarantine period
+                        self.quarantined_at = Some(Instant::now());
+                        None
+                    }
+                    Degraded | Offline => None,
+                }
+            }
+            Observation::Unavailable => match self.state {
+                Offline => None,
+                _ => self.enter(Offline, "device reports itself unavailable".to_string()).ok(),
+            },
+        }
+    }
+
+    /// Move to `to` if the state machine allows it
+    pub fn enter(&mut self, to: HealthState, reason: String) -> Result<HealthEvent, HardwareError> {
+        let from = self.state;
+        if !from.can_transition_to(to) {
+            return Err(HardwareError::InvalidHealthTransition {
+                device: self.device_id.clone(),
+                from,
+                to,
+            });
+        }
+
+        self.state = to;
+        self.consecutive_successes = 0;
+        match to {
+            // Coming back from offline resumes the quarantine it left
+            HealthState::Quarantined if from == HealthState::Offline && self.quarantined_at.is_some() => {}
+            HealthState::Quarantined => {
+                self.quarantined_at = Some(Instant::now());
+                self.quarantines += 1;
+            }
+            HealthState::Healthy | HealthState::Probation => {
+                self.consecutive_failures = 0;
+                self.quarantined_at = None;
+            }
+            HealthState::Degraded | HealthState::Offline => {}
+        }
+
+        Ok(HealthEvent {
+            device_id: self.device_id.clone(),
+            from,
+            to,
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> HealthPolicy {
+        HealthPolicy {
+            probe_interval: 10,
+            failure_threshold: 3,
+            quarantine_period: 0,
+            probation_successes: 2,
+        }
+    }
+
+    fn failure() -> Observation {
+        Observation::Failure("kernel crashed".to_string())
+    }
+
+    #[test]
+    fn test_repeated_failures_quarantine_and_probation_recovers() {
+        let policy = policy();
+        let mut health = DeviceHealth::new("gpu-0", HealthState::Healthy);
+
+        assert_eq!(health.observe(&policy, failure()).unwrap().to, HealthState::Degraded);
+        assert!(health.observe(&policy, failure()).is_none());
+        let event = health.observe(&policy, failure()).unwrap();
+        assert_eq!((event.from, event.to), (HealthState::Degraded, HealthState::Quarantined));
+        assert!(event.reason.contains("3 consecutive failures"));
+        assert_eq!(health.quarantines(), 1);
+
+        assert_eq!(health.observe(&policy, Observation::Success).unwrap().to, HealthState::Probation);
+        assert!(health.observe(&policy, Observation::Success).is_none());
+        assert_eq!(health.observe(&policy, Observation::Success).unwrap().to, HealthState::Healthy);
+        assert_eq!(health.last_error(), Some("kernel crashed"));
+    }
+
+    #[test]
+    fn test_probation_failure_and_quarantine_period() {
+        let mut policy = policy();
+        let mut health = DeviceHealth::new("gpu-0", HealthState::Probation);
+        assert_eq!(health.observe(&policy, failure()).unwrap().to, HealthState::Quarantined);
+
+        policy.quarantine_period = 60_000;
+        assert!(health.observe(&policy, Observation::Success).is_none());
+        assert_eq!(health.state(), HealthState::Quarantined);
+
+        // Going offline does not cut the quarantine short
+        assert_eq!(health.observe(&policy, Observation::Unavailable).unwrap().to, HealthState::Offline);
+        assert_eq!(health.observe(&policy, Observation::Success).unwrap().to, HealthState::Quarantined);
+        assert_eq!(health.quarantines(), 1, "the same quarantine resumes");
+        assert!(health.observe(&policy, Observation::Success).is_none());
+
+        policy.quarantine_period = 0;
+        assert_eq!(health.observe(&policy, Observation::Unavailable).unwrap().to, HealthState::Offline);
+        assert_eq!(health.observe(&policy, Observation::Success).unwrap().to, HealthState::Probation);
+    }
+
+    #[test]
+    fn test_illegal_transitions_are_rejected() {
+        let mut health = DeviceHealth::new("fpga-0", HealthState::Quarantined);
+        let error = health.enter(HealthState::Healthy, "operator".to_string()).unwrap_err();
+        assert!(matches!(
+            error,
+            HardwareError::InvalidHealthTransition { from: HealthState::Quarantined, to: HealthState::Healthy, .. }
+        ));
+        assert_eq!(health.state(), HealthState::Quarantined);
+        assert!(HealthState::Offline.can_transition_to(HealthState::Probation));
+        assert!(!HealthState::Offline.can_transition_to(HealthState::Healthy));
+        assert!(!HealthState::Quarantined.is_schedulable());
+    }
+}
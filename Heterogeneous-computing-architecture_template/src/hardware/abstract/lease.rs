@@ -0,0 +1,516 @@
+//! Capacity reservations
+//!
+//! A `Lease` reserves memory and compute units on one device for one task. The
+//! `LeaseLedger` keeps the per-device totals in step with the active leases, refuses
+//! reservations that would over-commit a device and expires leases whose task has
+//! outlived its timeout.
+//!
+//! A device's `SharingPolicy` can hold memory back from leases, cap what one task
+//! may take, or split the device into fixed slices that hold one lease each.
+
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::TaskRequirements;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// How long a released or expired lease is remembered, to tell a late release apart
+/// from one of a lease that never existed
+const RETIRED_TTL: Duration = Duration::from_secs(600);
+
+/// Most released or expired leases remembered at once
+const RETIRED_CAP: usize = 4096;
+
+/// Memory and compute units of a device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capacity {
+    /// Memory in MB
+    pub memory: u64,
+
+    /// Compute units (cores, SMs, logic elements)
+    pub compute_units: u64,
+}
+
+impl Capacity {
+    /// Whether `demand` fits inside this capacity
+    pub fn holds(self, demand: Capacity) -> bool {
+        demand.memory <= self.memory && demand.compute_units <= self.compute_units
+    }
+
+    /// Capacity left after `used` is taken out
+    pub fn saturating_sub(self, used: Capacity) -> Capacity {
+        Capacity {
+            memory: self.memory.saturating_sub(used.memory),
+            compute_units: self.compute_units.saturating_sub(used.compute_units),
+        }
+    }
+}
+
+/// How leases divide a device between tasks, as fractions of the device
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharingPolicy {
+    /// Memory kept out of every lease, e.g. for the driver
+    pub memory_reservation: f64,
+
+    /// Most of the device's memory and compute units one task may reserve
+    pub max_share: f64,
+
+    /// Fixed slices, each held by at most one task; empty to share the whole device
+    pub partitions: Vec<f64>,
+}
+
+impl Default for SharingPolicy {
+    fn default() -> Self {
+        Self {
+            memory_reservation: 0.0,
+            max_share: 1.0,
+            partitions: Vec::new(),
+        }
+    }
+}
+
+/// What leases may reserve on one device
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceQuota {
+    /// Capacity all leases together may reserve
+    pub capacity: Capacity,
+
+    /// Most one lease may reserve
+    pub per_task: Capacity,
+
+    /// Fixed slices; empty when tasks share the whole capacity
+    pub slices: Vec<Capacity>,
+}
+
+impl DeviceQuota {
+    /// Quota of a device leased without restrictions
+    pub fn whole(capacity: Capacity) -> Self {
+        Self {
+            capacity,
+            per_task: capacity,
+            slices: Vec::new(),
+        }
+    }
+
+    /// Quota of a device with `total` capacity shared under `policy`
+    ///
+    /// Shares round down, so a share too small for one compute unit grants none.
+    pub fn new(total: Capacity, policy: &SharingPolicy) -> Self {
+        let capacity = Capacity {
+            memory: total.memory - fraction(total.memory, policy.memory_reservation),
+            compute_units: total.compute_units,
+        };
+        let share = |f: f64| Capacity {
+            memory: fraction(capacity.memory, f),
+            compute_units: fraction(capacity.compute_units, f),
+        };
+        Self {
+            capacity,
+            per_task: share(policy.max_share),
+            slices: policy.partitions.iter().map(|f| share(*f)).collect(),
+        }
+    }
+
+    /// Whether one task could ever be granted `demand`, with the device otherwise idle
+    pub fn admits(&self, demand: Capacity) -> bool {
+        let room = if self.slices.is_empty() {
+            self.capacity.holds(demand)
+        } else {
+            self.slices.iter().any(|slice| slice.holds(demand))
+        };
+        self.per_task.holds(demand) && room
+    }
+}
+
+/// `f` of `value`, rounded down and never more than `value`
+fn fraction(value: u64, f: f64) -> u64 {
+    ((value as f64 * f.clamp(0.0, 1.0)) as u64).min(value)
+}
+
+/// Reservation of device capacity held by a task
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lease {
+    /// Lease identifier, used to release it
+    pub id: String,
+
+    /// Task holding the lease
+    pub task_id: String,
+
+    /// Device the capacity is reserved on
+    pub device_id: String,
+
+    /// Reserved memory and compute units; the whole slice on a partitioned device
+    pub reserved: Capacity,
+
+    /// Slice of a partitioned device the lease holds
+    pub slice: Option<usize>,
+
+    /// Most the task may use while it runs: its slice, or the device's per-task share
+    pub limit: Capacity,
+
+    /// When the lease was granted
+    pub granted_at: Instant,
+
+    /// When the lease lapses on its own; `None` for tasks without a timeout
+    pub expires_at: Option<Instant>,
+}
+
+/// Reservation totals of one device
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceReservation {
+    pub device_id: String,
+    pub quota: DeviceQuota,
+    pub reserved: Capacity,
+    pub leases: usize,
+}
+
+impl DeviceReservation {
+    /// Capacity not covered by a lease
+    pub fn free(&self) -> Capacity {
+        self.quota.capacity.saturating_sub(self.reserved)
+    }
+}
+
+/// Active leases and the capacity they hold on each device
+#[derive(Debug, Default)]
+pub struct LeaseLedger {
+    next_id: u64,
+    active: HashMap<String, Lease>,
+    reserved: HashMap<String, Capacity>,
+    /// Leases that ended recently, and whether they expired without their task
+    /// having been told yet
+    retired: HashMap<String, bool>,
+    /// `retired` in the order leases ended, for forgetting the oldest
+    retired_order: VecDeque<(Instant, String)>,
+}
+
+impl LeaseLedger {
+    /// Create an empty ledger
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capacity currently reserved on a device
+    pub fn reserved(&self, device_id: &str) -> Capacity {
+        self.reserved.get(device_id).copied().unwrap_or_default()
+    }
+
+    /// Number of active leases on a device
+    pub fn lease_count(&self, device_id: &str) -> usize {
+        self.active.values().filter(|lease| lease.device_id == device_id).count()
+    }
+
+    /// Active leases, oldest first
+    pub fn leases(&self) -> Vec<Lease> {
+        let mut leases: Vec<Lease> = self.active.values().cloned().collect();
+        leases.sort_by_key(|lease| lease.granted_at);
+        leases
+    }
+
+    /// Check that `requirements` fit in what a device has left
+    ///
+    /// On a partitioned device this returns the smallest free slice they fit in.
+    pub fn check(&self, device_id: &str, quota: &DeviceQuota, requirements: &TaskRequirements) -> Result<Option<usize>, HardwareError> {
+        fits(demand(requirements), quota.per_task)?;
+        if quota.slices.is_empty() {
+            fits(demand(requirements), quota.capacity.saturating_sub(self.reserved(device_id)))?;
+            return Ok(None);
+        }
+
+        let taken: HashSet<usize> = self
+            .active
+            .values()
+            .filter(|lease| lease.device_id == device_id)
+            .filter_map(|lease| lease.slice)
+            .collect();
+        let free: Vec<(usize, Capacity)> = quota
+            .slices
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(index, _)| !taken.contains(index))
+            .collect();
+        let best = free
+            .iter()
+            .filter(|(_, slice)| fits(demand(requirements), *slice).is_ok())
+            .min_by_key(|(index, slice)| (slice.memory, slice.compute_units, *index));
+        match best {
+            Some((index, _)) => Ok(Some(*index)),
+            // Report against the largest free slice, or an empty one if all are taken
+            None => {
+                let largest = free.iter().map(|(_, slice)| *slice).max_by_key(|slice| (slice.memory, slice.compute_units));
+                fits(demand(requirements), largest.unwrap_or_default()).map(|()| None)
+            }
+        }
+    }
+
+    /// Reserve capacity on a device for a task, refusing to over-commit it
+    pub fn grant(
+        &mut self,
+        task_id: &str,
+        device_id: &str,
+        quota: &DeviceQuota,
+        requirements: &TaskRequirements,
+        now: Instant,
+    ) -> Result<Lease, HardwareError> {
+        let slice = self.check(device_id, quota, requirements)?;
+
+        self.next_id += 1;
+        let reserved = match slice {
+            Some(index) => quota.slices[index],
+            None => demand(requirements),
+        };
+        let lease = Lease {
+            id: format!("lease-{}", self.next_id),
+            task_id: task_id.to_string(),
+            device_id: device_id.to_string(),
+            reserved,
+            slice,
+            limit: if slice.is_some() { reserved } else { quota.per_task },
+            granted_at: now,
+            expires_at: (requirements.timeout > 0).then(|| now + Duration::from_millis(requirements.timeout)),
+        };
+        let total = self.reserved.entry(device_id.to_string()).or_default();
+        total.memory += reserved.memory;
+        total.compute_units += reserved.compute_units;
+        self.active.insert(lease.id.clone(), lease.clone());
+        Ok(lease)
+    }
+
+    /// Return a lease's capacity to its device
+    ///
+    /// Releasing a lease twice, or one that was never granted, is an error. So is
+    /// releasing a lease that already expired, the first time its task tries. Ended
+    /// leases are remembered for a while only; releasing one after that reports it
+    /// as not found.
+    pub fn release(&mut self, lease_id: &str) -> Result<Lease, HardwareError> {
+        if let Some(lease) = self.active.remove(lease_id) {
+            self.give_back(&lease);
+            self.retire(lease_id, false, Instant::now());
+            return Ok(lease);
+        }
+        match self.retired.get_mut(lease_id) {
+            Some(unreported) if *unreported => {
+                *unreported = false;
+                Err(HardwareError::LeaseExpired(lease_id.to_string()))
+            }
+            Some(_) => Err(HardwareError::DoubleRelease(lease_id.to_string())),
+            None => Err(HardwareError::LeaseNotFound(lease_id.to_string())),
+        }
+    }
+
+    /// Drop leases whose deadline has passed, returning them
+    pub fn expire(&mut self, now: Instant) -> Vec<Lease> {
+        let lapsed: Vec<String> = self
+            .active
+            .values()
+            .filter(|lease| lease.expires_at.is_some_and(|deadline| deadline <= now))
+            .map(|lease| lease.id.clone())
+            .collect();
+
+        let mut expired = Vec::with_capacity(lapsed.len());
+        for id in lapsed {
+            if let Some(lease) = self.active.remove(&id) {
+                warn!(
+                    "Lease {} of task {} on {} expired, reclaiming {} MB and {} compute units",
+                    lease.id, lease.task_id, lease.device_id, lease.reserved.memory, lease.reserved.compute_units
+                );
+                self.give_back(&lease);
+                self.retire(&id, true, now);
+                expired.push(lease);
+            }
+        }
+        expired
+    }
+
+    /// Remember an ended lease, forgetting those that ended long ago
+    fn retire(&mut self, lease_id: &str, expired: bool, now: Instant) {
+        self.retired.insert(lease_id.to_string(), expired);
+        self.retired_order.push_back((now, lease_id.to_string()));
+        while let Some((ended, id)) = self.retired_order.front() {
+            let stale = now.saturating_duration_since(*ended) >= RETIRED_TTL;
+            if !stale && self.retired_order.len() <= RETIRED_CAP {
+                break;
+            }
+            self.retired.remove(id);
+            self.retired_order.pop_front();
+        }
+    }
+
+    fn give_back(&mut self, lease: &Lease) {
+        let total = self.reserved.entry(lease.device_id.clone()).or_default();
+        debug_assert!(
+            total.memory >= lease.reserved.memory && total.compute_units >= lease.reserved.compute_units,
+            "ledger for {} holds less than lease {}",
+            lease.device_id,
+            lease.id
+        );
+        total.memory = total.memory.saturating_sub(lease.reserved.memory);
+        total.compute_units = total.compute_units.saturating_sub(lease.reserved.compute_units);
+    }
+}
+
+fn demand(requirements: &TaskRequirements) -> Capacity {
+    Capacity {
+        memory: requirements.memory_required,
+        compute_units: requirements.compute_units,
+    }
+}
+
+/// Check that `demand` fits in `room`
+fn fits(demand: Capacity, room: Capacity) -> Result<(), HardwareError> {
+    if demand.memory > room.memory {
+        return Err(HardwareError::InsufficientMemory {
+            required: demand.memory,
+            available: room.memory,
+        });
+    }
+    if demand.compute_units > room.compute_units {
+        return Err(HardwareError::InsufficientComputeUnits {
+            required: demand.compute_units,
+            available: room.compute_units,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::HardwareType;
+
+    fn requirements(memory: u64, units: u64, timeout: u64) -> TaskRequirements {
+        TaskRequirements {
+            memory_required: memory,
+            compute_units: units,
+            timeout,
+            ..TaskRequirements::for_test(HardwareType::Gpu)
+        }
+    }
+
+    const GPU: Capacity = Capacity { memory: 1024, compute_units: 8 };
+
+    #[test]
+    fn test_over_commit_and_double_release() {
+        let mut ledger = LeaseLedger::new();
+        let now = Instant::now();
+        let first = ledger.grant("task-1", "gpu-0", &DeviceQuota::whole(GPU), &requirements(768, 4, 0), now).unwrap();
+        assert_eq!(first.expires_at, None);
+
+        let error = ledger.grant("task-2", "gpu-0", &DeviceQuota::whole(GPU), &requirements(512, 1, 0), now).unwrap_err();
+        assert!(matches!(error, HardwareError::InsufficientMemory { required: 512, available: 256 }));
+        let error = ledger.grant("task-2", "gpu-0", &DeviceQuota::whole(GPU), &requirements(128, 5, 0), now).unwrap_err();
+        assert!(matches!(error, HardwareError::InsufficientComputeUnits { required: 5, available: 4 }));
+
+        let second = ledger.grant("task-2", "gpu-0", &DeviceQuota::whole(GPU), &requirements(256, 4, 0), now).unwrap();
+        assert_eq!(ledger.reserved("gpu-0"), GPU);
+        assert_eq!(ledger.lease_count("gpu-0"), 2);
+
+        assert_eq!(ledger.release(&first.id).unwrap().task_id, "task-1");
+        assert!(matches!(ledger.release(&first.id), Err(HardwareError::DoubleRelease(_))));
+        assert!(matches!(ledger.release("lease-99"), Err(HardwareError::LeaseNotFound(_))));
+        assert_eq!(ledger.reserved("gpu-0"), Capacity { memory: 256, compute_units: 4 });
+        ledger.release(&second.id).unwrap();
+        assert_eq!(ledger.reserved("gpu-0"), Capacity::default());
+    }
+
+    #[test]
+    fn test_leases_expire_after_timeout() {
+        let mut ledger = LeaseLedger::new();
+        let now = Instant::now();
+        let short = ledger.grant("task-1", "gpu-0", &DeviceQuota::whole(GPU), &requirements(512, 2, 100), now).unwrap();
+        let long = ledger.grant("task-2", "gpu-0", &DeviceQuota::whole(GPU), &requirements(256, 2, 10_000), now).unwrap();
+
+        assert!(ledger.expire(now + Duration::from_millis(99)).is_empty());
+        let expired = ledger.expire(now + Duration::from_millis(100));
+        assert_eq!(expired.iter().map(|l| l.id.as_str()).collect::<Vec<_>>(), vec![short.id.as_str()]);
+        assert_eq!(ledger.reserved("gpu-0"), Capacity { memory: 256, compute_units: 2 });
+
+        // The late release is reported once, then treated as a double release
+        assert!(matches!(ledger.release(&short.id), Err(HardwareError::LeaseExpired(_))));
+        assert!(matches!(ledger.release(&short.id), Err(HardwareError::DoubleRelease(_))));
+        assert_eq!(ledger.leases(), vec![long]);
+    }
+
+    #[test]
+    fn test_ended_leases_are_forgotten_eventually() {
+        let mut ledger = LeaseLedger::new();
+        let now = Instant::now();
+        let first = ledger.grant("task-0", "gpu-0", &DeviceQuota::whole(GPU), &requirements(1, 1, 0), now).unwrap();
+        ledger.release(&first.id).unwrap();
+        for n in 1..=RETIRED_CAP {
+            let lease = ledger.grant(&format!("task-{}", n), "gpu-0", &DeviceQuota::whole(GPU), &requirements(1, 1, 0), now).unwrap();
+            ledger.release(&lease.id).unwrap();
+        }
+        assert_eq!(ledger.retired.len(), RETIRED_CAP);
+        assert!(matches!(ledger.release(&first.id), Err(HardwareError::LeaseNotFound(_))));
+
+        // Expired leases whose task never returns are dropped once they are old enough
+        let lost = ledger.grant("lost", "gpu-0", &DeviceQuota::whole(GPU), &requirements(1, 1, 10), now).unwrap();
+        ledger.expire(now + Duration::from_millis(10));
+        let later = now + RETIRED_TTL + Duration::from_secs(1);
+        let other = ledger.grant("other", "gpu-0", &DeviceQuota::whole(GPU), &requirements(1, 1, 10), later).unwrap();
+        ledger.expire(later + Duration::from_millis(10));
+        assert!(matches!(ledger.release(&lost.id), Err(HardwareError::LeaseNotFound(_))));
+        assert!(matches!(ledger.release(&other.id), Err(HardwareError::LeaseExpired(_))));
+    }
+
+    #[test]
+    fn test_shares_and_slices_limit_each_task() {
+        let mut ledger = LeaseLedger::new();
+        let now = Instant::now();
+        let shared = DeviceQuota::new(
+            GPU,
+            &SharingPolicy {
+                memory_reservation: 0.25,
+                max_share: 0.5,
+                partitions: Vec::new(),
+            },
+        );
+        assert_eq!(shared.capacity, Capacity { memory: 768, compute_units: 8 });
+        let error = ledger.grant("big", "gpu-0", &shared, &requirements(400, 1, 0), now).unwrap_err();
+        assert!(matches!(error, HardwareError::InsufficientMemory { required: 400, available: 384 }));
+        assert!(!shared.admits(Capacity { memory: 400, compute_units: 1 }));
+        for task in ["a", "b", "c"] {
+            let lease = ledger.grant(task, "gpu-0", &shared, &requirements(256, 2, 0), now).unwrap();
+            assert_eq!(lease.limit, shared.per_task);
+        }
+        assert!(ledger.grant("d", "gpu-0", &shared, &requirements(1, 1, 0), now).is_err());
+
+        // Each task takes the smallest free slice it fits in, and holds all of it
+        let partitioned = DeviceQuota::new(
+            GPU,
+            &SharingPolicy {
+                partitions: vec![0.5, 0.25, 0.25],
+                ..SharingPolicy::default()
+            },
+        );
+        let small = ledger.grant("small", "gpu-1", &partitioned, &requirements(100, 1, 0), now).unwrap();
+        assert_eq!((small.slice, small.reserved), (Some(1), Capacity { memory: 256, compute_units: 2 }));
+        assert_eq!(small.limit, small.reserved);
+        assert!(partitioned.admits(Capacity { memory: 512, compute_units: 4 }));
+        assert!(!partitioned.admits(Capacity { memory: 600, compute_units: 1 }));
+        let large = ledger.grant("large", "gpu-1", &partitioned, &requirements(300, 1, 0), now).unwrap();
+        assert_eq!(large.slice, Some(0));
+        assert_eq!(ledger.grant("other", "gpu-1", &partitioned, &requirements(100, 1, 0), now).unwrap().slice, Some(2));
+        let error = ledger.grant("late", "gpu-1", &partitioned, &requirements(10, 1, 0), now).unwrap_err();
+        assert!(matches!(error, HardwareError::InsufficientMemory { required: 10, available: 0 }));
+
+        ledger.release(&small.id).unwrap();
+        let error = ledger.grant("late", "gpu-1", &partitioned, &requirements(10, 3, 0), now).unwrap_err();
+        assert!(matches!(error, HardwareError::InsufficientComputeUnits { required: 3, available: 2 }));
+        assert_eq!(ledger.grant("late", "gpu-1", &partitioned, &requirements(10, 2, 0), now).unwrap().slice, Some(1));
+
+        // A share smaller than one compute unit grants none
+        let thin = DeviceQuota::new(
+            GPU,
+            &SharingPolicy {
+                max_share: 0.1,
+                ..SharingPolicy::default()
+            },
+        );
+        assert_eq!(thin.per_task.compute_units, 0);
+        assert!(!thin.admits(Capacity { memory: 10, compute_units: 1 }));
+        assert!(ledger.grant("thin", "gpu-2", &thin, &requirements(10, 1, 0), now).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+//! Device memory arena
+//!
+//! A binary buddy allocator over a device's address space. Every allocation is
+//! owned by a task and returned as a typed `DeviceBuffer` that frees its block
+//! when dropped. Blocks are powers of two times `MIN_BLOCK_SIZE`; a capacity that
+//! is not a power of two is split into one top-level block per set bit.
+
+use crate::errors::HardwareError;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Smallest block handed out, matching the alignment of CUDA allocations
+pub const MIN_BLOCK_SIZE: u64 = 256;
+
+/// Live allocation as reported by leak detection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationInfo {
+    /// Device address (offset into the arena)
+    pub offset: u64,
+
+    /// Bytes requested by the owner
+    pub size: u64,
+
+    /// Bytes reserved, including rounding up to the block size
+    pub block_size: u64,
+
+    /// Task that made the allocation
+    pub owner: String,
+}
+
+/// Usage and fragmentation statistics
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryStats {
+    /// Managed bytes
+    pub capacity: u64,
+
+    /// Bytes reserved by live blocks
+    pub allocated: u64,
+
+    /// Bytes requested by live allocations
+    pub requested: u64,
+
+    /// Bytes not reserved by any block
+    pub free: u64,
+
+    /// Largest allocation that can currently succeed
+    pub largest_free_block: u64,
+
+    /// Number of live allocations
+    pub allocations: usize,
+
+    /// Highest `allocated` value seen
+    pub peak_allocated: u64,
+}
+
+impl MemoryStats {
+    /// Share of reserved bytes lost to rounding up to block sizes
+    pub fn internal_fragmentation(&self) -> f64 {
+        if self.allocated == 0 {
+            return 0.0;
+        }
+        1.0 - self.requested as f64 / self.allocated as f64
+    }
+
+    /// Share of free bytes that cannot be handed out as a single block
+    pub fn external_fragmentation(&self) -> f64 {
+        if self.free == 0 {
+            return 0.0;
+        }
+        1.0 - self.largest_free_block as f64 / self.free as f64
+    }
+}
+
+struct Allocation {
+    owner: String,
+    order: usize,
+    size: u64,
+}
+
+struct ArenaState {
+    /// Free block offsets per order
+    free: Vec<BTreeSet<u64>>,
+    allocations: HashMap<u64, Allocation>,
+
+    /// Requested bytes per owning task
+    owners: HashMap<String, u64>,
+    allocated: u64,
+    requested: u64,
+    peak_allocated: u64,
+
+    /// Bumped on reset so buffers from before the reset do not free into the new arena
+    generation: u64,
+}
+
+struct ArenaShared {
+    name: String,
+    capacity: u64,
+    max_order: usize,
+    max_allocation: Option<u64>,
+    state: Mutex<ArenaState>,
+}
+
+impl ArenaShared {
+    fn block_size(order: usize) -> u64 {
+        MIN_BLOCK_SIZE << order
+    }
+
+    fn free_block(&self, state: &mut ArenaState, mut offset: u64, mut order: usize) {
+        while order < self.max_order {
+            let buddy = offset ^ Self::block_size(order);
+            if !state.free[order].remove(&buddy) {
+                break;
+            }
+            offset = offset.min(buddy);
+            order += 1;
+        }
+        state.free[order].insert(offset);
+    }
+
+    fn release(&self, offset: u64, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+        let Some(allocation) = state.allocations.remove(&offset) else {
+            return;
+        };
+
+        state.allocated -= Self::block_size(allocation.order);
+        state.requested -= allocation.size;
+        if let Some(used) = state.owners.get_mut(&allocation.owner) {
+            *used -= allocation.size;
+            if *used == 0 {
+                state.owners.remove(&allocation.owner);
+            }
+        }
+        self.free_block(&mut state, offset, allocation.order);
+    }
+
+    fn initial_state(capacity: u64, max_order: usize, generation: u64) -> ArenaState {
+        let mut free = vec![BTreeSet::new(); max_order + 1];
+        let mut offset = 0;
+        for order in (0..=max_order).rev() {
+            if capacity - offset >= Self::block_size(order) {
+                free[order].insert(offset);
+                offset += Self::block_size(order);
+            }
+        }
+        ArenaState {
+            free,
+            allocations: HashMap::new(),
+            owners: HashMap::new(),
+            allocated: 0,
+            requested: 0,
+            peak_allocated: 0,
+            generation,
+        }
+    }
+}
+
+/// Buddy allocator for one device's memory
+#[derive(Clone)]
+pub struct MemoryArena {
+    shared: Arc<ArenaShared>,
+}
+
+impl MemoryArena {
+    /// Manage `capacity` bytes, optionally limiting what a single task may hold
+    pub fn new(name: &str, capacity: u64, max_allocation: Option<u64>) -> Self {
+        let capacity = capacity - capacity % MIN_BLOCK_SIZE;
+        let max_order = (capacity / MIN_BLOCK_SIZE).checked_ilog2().unwrap_or(0) as usize;
+        Self {
+            shared: Arc::new(ArenaShared {
+                name: name.to_string(),
+                capacity,
+                max_order,
+                max_allocation,
+                state: Mutex::new(ArenaShared::initial_state(capacity, max_order, 0)),
+            }),
+        }
+    }
+
+    /// Managed bytes
+    pub fn capacity(&self) -> u64 {
+        self.shared.capacity
+    }
+
+    /// Per-task allocation limit in bytes
+    pub fn max_allocation(&self) -> Option<u64> {
+        self.shared.max_allocation
+    }
+
+    /// Allocate room for `len` values of `T` on behalf of `owner`
+    pub fn allocate<T>(&self, owner: &str, len: usize) -> Result<DeviceBuffer<T>, HardwareError> {
+        let size = (len as u64).saturating_mul(std::mem::size_of::<T>() as u64);
+        let mut state = self.shared.state.lock().unwrap();
+
+        let owner_used = state.owners.get(owner).copied().unwrap_or(0);
+        if let Some(limit) = self.shared.max_allocation {
+            if owner_used + size > limit {
+                return Err(HardwareError::InsufficientMemory {
+                    required: size,
+                    available: limit.saturating_sub(owner_used),
+                });
+            }
+        }
+
+        let insufficient = |state: &ArenaState| HardwareError::InsufficientMemory {
+            required: size,
+            available: largest_free_block(state),
+        };
+        let wanted = (0..=self.shared.max_order)
+            .find(|order| ArenaShared::block_size(*order) >= size)
+            .ok_or_else(|| insufficient(&state))?;
+        let mut order = (wanted..=self.shared.max_order)
+            .find(|order| !state.free[*order].is_empty())
+            .ok_or_else(|| insufficient(&state))?;
+
+        let offset = state.free[order].pop_first().expect("free list checked above");
+        while order > wanted {
+            order -= 1;
+            state.free[order].insert(offset + ArenaShared::block_size(order));
+        }
+
+        state.allocations.insert(
+            offset,
+            Allocation {
+                owner: owner.to_string(),
+                order,
+                size,
+            },
+        );
+        *state.owners.entry(owner.to_string()).or_insert(0) += size;
+        state.allocated += ArenaShared::block_size(order);
+        state.requested += size;
+        state.peak_allocated = state.peak_allocated.max(state.allocated);
+
+        Ok(DeviceBuffer {
+            arena: Arc::clone(&self.shared),
+            offset,
+            len,
+            generation: state.generation,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Current usage and fragmentation
+    pub fn stats(&self) -> MemoryStats {
+        let state = self.shared.state.lock().unwrap();
+        MemoryStats {
+            capacity: self.shared.capacity,
+            allocated: state.allocated,
+            requested: state.requested,
+            free: self.shared.capacity - state.allocated,
+            largest_free_block: largest_free_block(&state),
+            allocations: state.allocations.len(),
+            peak_allocated: state.peak_allocated,
+        }
+    }
+
+    /// Bytes currently held by `owner`
+    pub fn owner_usage(&self, owner: &str) -> u64 {
+        self.shared.state.lock().unwrap().owners.get(owner).copied().unwrap_or(0)
+    }
+
+    /// Allocations that are still live, ordered by address
+    pub fn live_allocations(&self) -> Vec<AllocationInfo> {
+        let state = self.shared.state.lock().unwrap();
+        let mut live: Vec<AllocationInfo> = state
+            .allocations
+            .iter()
+            .map(|(offset, allocation)| AllocationInfo {
+                offset: *offset,
+                size: allocation.size,
+                block_size: ArenaShared::block_size(allocation.order),
+                owner: allocation.owner.clone(),
+            })
+            .collect();
+        live.sort_by_key(|info| info.offset);
+        live
+    }
+
+    /// Report live allocations as leaks and return the arena to its empty state
+    ///
+    /// Buffers that outlive the reset are detached and no longer free anything.
+    pub fn reset(&self) -> Vec<AllocationInfo> {
+        let leaks = self.live_allocations();
+        for leak in &leaks {
+            warn!(
+                "Leaked {} bytes at {:#x} on {} (owner {})",
+                leak.size, leak.offset, self.shared.name, leak.owner
+            );
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        let generation = state.generation + 1;
+        *state = ArenaShared::initial_state(self.shared.capacity, self.shared.max_order, generation);
+        leaks
+    }
+}
+
+impl fmt::Debug for MemoryArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryArena")
+            .field("name", &self.shared.name)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+fn largest_free_block(state: &ArenaState) -> u64 {
+    state
+        .free
+        .iter()
+        .rposition(|blocks| !blocks.is_empty())
+        .map_or(0, ArenaShared::block_size)
+}
+
+/// Typed handle to device memory, freed when dropped
+pub struct DeviceBuffer<T> {
+    arena: Arc<ArenaShared>,
+    offset: u64,
+    len: usize,
+    generation: u64,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> DeviceBuffer<T> {
+    /// Device address of the buffer
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Number of `T` values the buffer holds
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the buffer holds no values
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size in bytes
+    pub fn size_bytes(&self) -> u64 {
+        (self.len * std::mem::size_of::<T>()) as u64
+    }
+}
+
+impl<T> fmt::Debug for DeviceBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceBuffer")
+            .field("arena", &self.arena.name)
+            .field("offset", &self.offset)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<T> Drop for DeviceBuffer<T> {
+    fn drop(&mut self) {
+        self.arena.release(self.offset, self.generation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_coalesce() {
+        let arena = MemoryArena::new("test", 16 * MIN_BLOCK_SIZE, None);
+        let a = arena.allocate::<u8>("task-a", 100).unwrap();
+        let b = arena.allocate::<f32>("task-b", 100).unwrap();
+        assert_ne!(a.offset(), b.offset());
+        assert_eq!(b.size_bytes(), 400);
+
+        let stats = arena.stats();
+        assert_eq!(stats.allocated, MIN_BLOCK_SIZE + 2 * MIN_BLOCK_SIZE);
+        assert_eq!(stats.requested, 500);
+        assert!(stats.internal_fragmentation() > 0.0);
+        assert!(stats.external_fragmentation() > 0.0);
+
+        drop(a);
+        drop(b);
+        let stats = arena.stats();
+        assert_eq!(stats.allocated, 0);
+        assert_eq!(stats.largest_free_block, 16 * MIN_BLOCK_SIZE);
+        assert_eq!(stats.external_fragmentation(), 0.0);
+        assert_eq!(stats.peak_allocated, 3 * MIN_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn test_insufficient_memory_reports_real_numbers() {
+        // 3 blocks: one pair and one single, so the largest block is 2 * MIN_BLOCK_SIZE
+        let arena = MemoryArena::new("test", 3 * MIN_BLOCK_SIZE, None);
+        assert_eq!(arena.stats().largest_free_block, 2 * MIN_BLOCK_SIZE);
+
+        let result = arena.allocate::<u8>("task", 3 * MIN_BLOCK_SIZE as usize);
+        assert!(matches!(
+            result,
+            Err(HardwareError::InsufficientMemory { required, available })
+                if required == 3 * MIN_BLOCK_SIZE && available == 2 * MIN_BLOCK_SIZE
+        ));
+
+        let _held = arena.allocate::<u8>("task", 2 * MIN_BLOCK_SIZE as usize).unwrap();
+        let _last = arena.allocate::<u8>("task", 1).unwrap();
+        assert!(arena.allocate::<u8>("task", 1).is_err());
+    }
+
+    #[test]
+    fn test_per_task_limit() {
+        let arena = MemoryArena::new("test", 64 * MIN_BLOCK_SIZE, Some(1000));
+        let _first = arena.allocate::<u8>("task-a", 600).unwrap();
+        let result = arena.allocate::<u8>("task-a", 600);
+        assert!(matches!(
+            result,
+            Err(HardwareError::InsufficientMemory { required: 600, available: 400 })
+        ));
+        assert!(arena.allocate::<u8>("task-b", 600).is_ok());
+        assert_eq!(arena.owner_usage("task-a"), 600);
+    }
+
+    #[test]
+    fn test_reset_reports_leaks() {
+        let arena = MemoryArena::new("test", 8 * MIN_BLOCK_SIZE, None);
+        let leaked = arena.allocate::<u8>("leaky-task", 10).unwrap();
+        drop(arena.allocate::<u8>("tidy-task", 10).unwrap());
+
+        let leaks = arena.reset();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].owner, "leaky-task");
+        assert_eq!(leaks[0].size, 10);
+
+        // A buffer from before the reset must not free blocks of the new arena
+        let fresh = arena.allocate::<u8>("task", 10).unwrap();
+        drop(leaked);
+        assert_eq!(arena.stats().allocations, 1);
+        assert_eq!(fresh.offset(), 0);
+    }
+}
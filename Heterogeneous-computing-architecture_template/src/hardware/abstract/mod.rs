@@ -2,6 +2,16 @@
 
 pub mod hardware_abstraction;
 pub mod device_manager;
+pub mod health;
+pub mod lease;
+pub mod memory;
+pub mod operations;
+pub mod power;
 
 pub use hardware_abstraction::*;
-pub use device_manager::*;
\ No newline at end of file
+pub use device_manager::*;
+pub use health::*;
+pub use lease::*;
+pub use memory::*;
+pub use operations::*;
+pub use power::*;
\ No newline at end of file
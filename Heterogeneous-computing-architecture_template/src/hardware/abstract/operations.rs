@@ -0,0 +1,439 @@
+//! Device-independent task operations and their reference kernels
+//!
+//! Task payloads are a list of `f32` tensors encoded as a little-endian frame:
+//! `count: u32`, then for every tensor `rank: u32`, `rank` dimensions as `u32`
+//! and the row-major values as `f32`.
+
+use crate::errors::HardwareError;
+use std::fmt;
+use std::ops::Range;
+
+/// Operation executed by a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Opaque compute step that passes its payload through unchanged
+    Compute,
+    /// Elementwise addition of two tensors of the same shape
+    Add,
+    /// Sum of all elements
+    ReduceSum,
+    /// Maximum of all elements
+    ReduceMax,
+    /// Matrix multiplication of `[m, k]` by `[k, n]`
+    Gemm,
+    /// Valid 2D cross-correlation with stride 1 (`[c, h, w]` input, `[o, c, kh, kw]` filters)
+    Conv2d,
+}
+
+impl Operation {
+    /// All operations known to the runtime
+    pub const ALL: [Operation; 6] = [
+        Operation::Compute,
+        Operation::Add,
+        Operation::ReduceSum,
+        Operation::ReduceMax,
+        Operation::Gemm,
+        Operation::Conv2d,
+    ];
+
+    /// Parse an operation from its task name
+    pub fn parse(name: &str) -> Result<Self, HardwareError> {
+        match name {
+            "compute" => Ok(Operation::Compute),
+            "add" => Ok(Operation::Add),
+            "reduce_sum" => Ok(Operation::ReduceSum),
+            "reduce_max" => Ok(Operation::ReduceMax),
+            "gemm" => Ok(Operation::Gemm),
+            "conv2d" => Ok(Operation::Conv2d),
+            other => Err(HardwareError::UnsupportedOperation(other.to_string())),
+        }
+    }
+
+    /// Task name of the operation
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Compute => "compute",
+            Operation::Add => "add",
+            Operation::ReduceSum => "reduce_sum",
+            Operation::ReduceMax => "reduce_max",
+            Operation::Gemm => "gemm",
+            Operation::Conv2d => "conv2d",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Dense row-major `f32` tensor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+impl Tensor {
+    /// Create a tensor, checking that the data matches the shape
+    pub fn new(shape: Vec<usize>, data: Vec<f32>) -> Result<Self, HardwareError> {
+        let expected: usize = shape.iter().product();
+        if expected != data.len() {
+            return Err(HardwareError::InvalidTask(format!(
+                "tensor of shape {:?} needs {} values, got {}",
+                shape,
+                expected,
+                data.len()
+            )));
+        }
+        Ok(Self { shape, data })
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Check whether the tensor has no elements
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Encode tensors into a task payload
+    pub fn encode_all(tensors: &[Tensor]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(tensors.len() as u32).to_le_bytes());
+        for tensor in tensors {
+            bytes.extend_from_slice(&(tensor.shape.len() as u32).to_le_bytes());
+            for dim in &tensor.shape {
+                bytes.extend_from_slice(&(*dim as u32).to_le_bytes());
+            }
+            for value in &tensor.data {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decode tensors from a task payload
+    pub fn decode_all(bytes: &[u8]) -> Result<Vec<Tensor>, HardwareError> {
+        let mut reader = PayloadReader { bytes, offset: 0 };
+        let count = reader.read_u32()? as usize;
+        let mut tensors = Vec::with_capacity(count.min(reader.remaining() / 4));
+        for _ in 0..count {
+            let rank = reader.read_u32()? as usize;
+            let mut shape = Vec::with_capacity(rank.min(reader.remaining() / 4));
+            for _ in 0..rank {
+                shape.push(reader.read_u32()? as usize);
+            }
+            let len = shape
+                .iter()
+                .try_fold(1usize, |acc, dim| acc.checked_mul(*dim))
+                .filter(|len| len.saturating_mul(4) <= reader.remaining())
+                .ok_or_else(|| HardwareError::InvalidTask(format!("truncated tensor of shape {:?}", shape)))?;
+            let mut data = Vec::with_capacity(len);
+            for _ in 0..len {
+                data.push(f32::from_le_bytes(reader.read_array()?));
+            }
+            tensors.push(Tensor { shape, data });
+        }
+        if reader.offset != bytes.len() {
+            return Err(HardwareError::InvalidTask(format!(
+                "{} trailing bytes in task payload",
+                bytes.len() - reader.offset
+            )));
+        }
+        Ok(tensors)
+    }
+}
+
+struct PayloadReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl PayloadReader<'_> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    fn read_array(&mut self) -> Result<[u8; 4], HardwareError> {
+        let end = self.offset + 4;
+        let slice = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or_else(|| HardwareError::InvalidTask("truncated task payload".to_string()))?;
+        self.offset = end;
+        Ok([slice[0], slice[1], slice[2], slice[3]])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, HardwareError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+}
+
+/// Validated operation with its inputs, split into independently computable work items
+///
+/// Map-style operations (add, gemm, conv2d) have one work item per output element and
+/// return the computed slice of the output. Reductions have one work item per input
+/// element and return a single partial value. `finish` joins the per-range results in order.
+#[derive(Debug, Clone)]
+pub struct Kernel {
+    operation: Operation,
+    inputs: Vec<Tensor>,
+    output_shape: Vec<usize>,
+}
+
+impl Kernel {
+    /// Validate inputs for an operation
+    pub fn new(operation: Operation, inputs: Vec<Tensor>) -> Result<Self, HardwareError> {
+        let invalid = |msg: String| HardwareError::InvalidTask(format!("{}: {}", operation, msg));
+        let expect_inputs = |count: usize| {
+            if inputs.len() == count {
+                Ok(())
+            } else {
+                Err(invalid(format!("expected {} input tensors, got {}", count, inputs.len())))
+            }
+        };
+        if let Some(tensor) = inputs.iter().find(|t| t.shape.iter().product::<usize>() != t.len()) {
+            return Err(invalid(format!("tensor of shape {:?} has {} values", tensor.shape, tensor.len())));
+        }
+
+        let output_shape = match operation {
+            Operation::Compute => {
+                return Err(HardwareError::UnsupportedOperation(
+                    "compute has no kernel; its payload is passed through".to_string(),
+                ))
+            }
+            Operation::Add => {
+                expect_inputs(2)?;
+                if inputs[0].shape != inputs[1].shape {
+                    return Err(invalid(format!(
+                        "shape mismatch {:?} vs {:?}",
+                        inputs[0].shape, inputs[1].shape
+                    )));
+                }
+                inputs[0].shape.clone()
+            }
+            Operation::ReduceSum | Operation::ReduceMax => {
+                expect_inputs(1)?;
+                if operation == Operation::ReduceMax && inputs[0].is_empty() {
+                    return Err(invalid("cannot take the maximum of an empty tensor".to_string()));
+                }
+                vec![1]
+            }
+            Operation::Gemm => {
+                expect_inputs(2)?;
+                match (inputs[0].shape.as_slice(), inputs[1].shape.as_slice()) {
+                    ([m, k], [k2, n]) if k == k2 => vec![*m, *n],
+                    (a, b) => return Err(invalid(format!("incompatible matrices {:?} x {:?}", a, b))),
+                }
+            }
+            Operation::Conv2d => {
+                expect_inputs(2)?;
+                let (c, h, w) = match inputs[0].shape.as_slice() {
+                    [h, w] => (1, *h, *w),
+                    [c, h, w] => (*c, *h, *w),
+                    other => return Err(invalid(format!("input must be [h, w] or [c, h, w], got {:?}", other))),
+                };
+                let (o, kc, kh, kw) = match inputs[1].shape.as_slice() {
+                    [kh, kw] => (1, 1, *kh, *kw),
+                    [o, kc, kh, kw] => (*o, *kc, *kh, *kw),
+                    other => return Err(invalid(format!("filter must be [kh, kw] or [o, c, kh, kw], got {:?}", other))),
+                };
+                if c != kc {
+                    return Err(invalid(format!("input has {} channels, filter expects {}", c, kc)));
+                }
+                if kh == 0 || kw == 0 || kh > h || kw > w {
+                    return Err(invalid(format!("filter {}x{} does not fit input {}x{}", kh, kw, h, w)));
+                }
+                if inputs[0].shape.len() == 2 && inputs[1].shape.len() == 2 {
+                    vec![h - kh + 1, w - kw + 1]
+                } else {
+                    vec![o, h - kh + 1, w - kw + 1]
+                }
+            }
+        };
+
+        Ok(Self {
+            operation,
+            inputs,
+            output_shape,
+        })
+    }
+
+    /// Operation executed by the kernel
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Input tensors
+    pub fn inputs(&self) -> &[Tensor] {
+        &self.inputs
+    }
+
+    /// Shape of the output tensor
+    pub fn output_shape(&self) -> &[usize] {
+        &self.output_shape
+    }
+
+    /// Number of independently computable work items
+    pub fn work_items(&self) -> usize {
+        match self.operation {
+            Operation::ReduceSum | Operation::ReduceMax => self.inputs[0].len(),
+            _ => self.output_shape.iter().product(),
+        }
+    }
+
+    /// Floating point operations needed to compute the whole output
+    pub fn flops(&self) -> u64 {
+        let output: usize = self.output_shape.iter().product();
+        let flops = match self.operation {
+            Operation::Compute => 0,
+            Operation::Add | Operation::ReduceSum | Operation::ReduceMax => self.work_items(),
+            // One multiply and one add per term of every dot product
+            Operation::Gemm => 2 * output * self.inputs[0].shape[1],
+            Operation::Conv2d => {
+                let taps = match self.inputs[1].shape.as_slice() {
+                    [kh, kw] => kh * kw,
+                    [_, c, kh, kw] => c * kh * kw,
+                    _ => 0,
+                };
+                2 * output * taps
+            }
+        };
+        flops as u64
+    }
+
+    /// Bytes read from and written to memory assuming every tensor is touched once
+    pub fn bytes_moved(&self) -> u64 {
+        let values: usize = self.inputs.iter().map(Tensor::len).sum::<usize>()
+            + self.output_shape.iter().product::<usize>();
+        (values * std::mem::size_of::<f32>()) as u64
+    }
+
+    /// Compute the work items in `range`
+    pub fn run_range(&self, range: Range<usize>) -> Vec<f32> {
+        match self.operation {
+            Operation::Compute => Vec::new(),
+            Operation::Add => {
+                let (a, b) = (&self.inputs[0].data, &self.inputs[1].data);
+                range.map(|i| a[i] + b[i]).collect()
+            }
+            Operation::ReduceSum => vec![self.inputs[0].data[range].iter().sum()],
+            Operation::ReduceMax => {
+                vec![self.inputs[0].data[range].iter().copied().fold(f32::NEG_INFINITY, f32::max)]
+            }
+            Operation::Gemm => {
+                let (a, b) = (&self.inputs[0], &self.inputs[1]);
+                let k = a.shape[1];
+                let n = b.shape[1];
+                range
+                    .map(|i| {
+                        let (row, col) = (i / n, i % n);
+                        (0..k).map(|p| a.data[row * k + p] * b.data[p * n + col]).sum()
+                    })
+                    .collect()
+            }
+            Operation::Conv2d => {
+                let (input, filter) = (&self.inputs[0], &self.inputs[1]);
+                let (c, h, w) = match input.shape.as_slice() {
+                    [h, w] => (1, *h, *w),
+                    shape => (shape[0], shape[1], shape[2]),
+                };
+                let (kh, kw) = {
+                    let rank = filter.shape.len();
+                    (filter.shape[rank - 2], filter.shape[rank - 1])
+                };
+                let (oh, ow) = (h - kh + 1, w - kw + 1);
+                range
+                    .map(|i| {
+                        let (o, y, x) = (i / (oh * ow), (i / ow) % oh, i % ow);
+                        let mut acc = 0.0;
+                        for ch in 0..c {
+                            for dy in 0..kh {
+                                for dx in 0..kw {
+                                    let value = input.data[(ch * h + y + dy) * w + x + dx];
+                                    let weight = filter.data[((o * c + ch) * kh + dy) * kw + dx];
+                                    acc += value * weight;
+                                }
+                            }
+                        }
+                        acc
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Join per-range results, given in range order, into the output tensor
+    pub fn finish(&self, partials: Vec<Vec<f32>>) -> Tensor {
+        let data = match self.operation {
+            Operation::ReduceSum => vec![partials.iter().flatten().sum()],
+            Operation::ReduceMax => vec![partials.iter().flatten().copied().fold(f32::NEG_INFINITY, f32::max)],
+            _ => partials.into_iter().flatten().collect(),
+        };
+        Tensor {
+            shape: self.output_shape.clone(),
+            data,
+        }
+    }
+
+    /// Run the whole kernel on the calling thread
+    pub fn run(&self) -> Tensor {
+        let partial = self.run_range(0..self.work_items());
+        self.finish(vec![partial])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(shape: &[usize], data: &[f32]) -> Tensor {
+        Tensor::new(shape.to_vec(), data.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_payload_roundtrip() {
+        let tensors = vec![tensor(&[2, 2], &[1.0, 2.0, 3.0, 4.0]), tensor(&[1], &[-0.5])];
+        let decoded = Tensor::decode_all(&Tensor::encode_all(&tensors)).unwrap();
+        assert_eq!(decoded, tensors);
+
+        let mut truncated = Tensor::encode_all(&tensors);
+        truncated.pop();
+        assert!(Tensor::decode_all(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_gemm_kernel() {
+        let a = tensor(&[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let b = tensor(&[3, 2], &[7.0, 8.0, 9.0, 10.0, 11.0, 12.0]);
+        let kernel = Kernel::new(Operation::Gemm, vec![a, b]).unwrap();
+        assert_eq!(kernel.run(), tensor(&[2, 2], &[58.0, 64.0, 139.0, 154.0]));
+        assert_eq!(kernel.flops(), 2 * 2 * 2 * 3);
+
+        let mismatched = vec![tensor(&[2, 3], &[0.0; 6]), tensor(&[2, 2], &[0.0; 4])];
+        assert!(Kernel::new(Operation::Gemm, mismatched).is_err());
+    }
+
+    #[test]
+    fn test_conv2d_kernel() {
+        let input = tensor(&[3, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        let filter = tensor(&[2, 2], &[1.0, 0.0, 0.0, 1.0]);
+        let kernel = Kernel::new(Operation::Conv2d, vec![input, filter]).unwrap();
+        assert_eq!(kernel.run(), tensor(&[2, 2], &[6.0, 8.0, 12.0, 14.0]));
+        assert_eq!(kernel.flops(), 2 * 4 * 4);
+        assert_eq!(kernel.bytes_moved(), (9 + 4 + 4) * 4);
+    }
+
+    #[test]
+    fn test_split_reduction_matches_single_pass() {
+        let input = tensor(&[6], &[3.0, -1.0, 4.0, 1.0, -5.0, 9.0]);
+        let kernel = Kernel::new(Operation::ReduceMax, vec![input]).unwrap();
+        let partials = vec![kernel.run_range(0..2), kernel.run_range(2..6)];
+        assert_eq!(kernel.finish(partials), kernel.run());
+        assert_eq!(kernel.run().data, vec![9.0]);
+    }
+}
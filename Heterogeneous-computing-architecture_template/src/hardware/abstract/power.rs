@@ -0,0 +1,117 @@
+//! Device power models
+//!
+//! A `PowerModel` gives the draw of a device when idle and when running work. A
+//! device that scales voltage and frequency can list its DVFS points instead of a
+//! single active figure; the draw at other frequencies is interpolated between them.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Draw of a device at one clock frequency
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DvfsPoint {
+    /// Clock frequency in MHz
+    pub frequency: u64,
+
+    /// Draw in watts at that frequency
+    pub watts: f64,
+}
+
+/// Idle and active draw of a device, in watts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerModel {
+    /// Draw with no work running
+    pub idle: f64,
+
+    /// Draw while running work at the highest frequency
+    pub active: f64,
+
+    /// Draw at each supported frequency, in any order
+    pub dvfs: Vec<DvfsPoint>,
+}
+
+impl PowerModel {
+    /// A device that draws `idle` watts with nothing to do and `active` watts when busy
+    pub fn new(idle: f64, active: f64) -> Self {
+        Self {
+            idle,
+            active,
+            dvfs: Vec::new(),
+        }
+    }
+
+    /// Draw while running work at `frequency` MHz
+    ///
+    /// Frequencies outside the DVFS points use the nearest point. Without DVFS points
+    /// this is always `active`.
+    pub fn watts_at(&self, frequency: u64) -> f64 {
+        let mut points = self.dvfs.clone();
+        points.sort_by_key(|point| point.frequency);
+        let (Some(lowest), Some(highest)) = (points.first(), points.last()) else {
+            return self.active;
+        };
+        if frequency <= lowest.frequency {
+            return lowest.watts;
+        }
+        if frequency >= highest.frequency {
+            return highest.watts;
+        }
+        let above = points.iter().position(|point| point.frequency >= frequency).unwrap();
+        let (low, high) = (points[above - 1], points[above]);
+        let share = (frequency - low.frequency) as f64 / (high.frequency - low.frequency) as f64;
+        low.watts + share * (high.watts - low.watts)
+    }
+
+    /// Energy in joules of running work for `duration` at the active draw
+    pub fn energy(&self, duration: Duration) -> f64 {
+        self.active * duration.as_secs_f64()
+    }
+
+    /// Extra draw of a busy device over an idle one
+    pub fn headroom(&self) -> f64 {
+        (self.active - self.idle).max(0.0)
+    }
+
+    /// Problems with the figures, prefixed with `section`
+    pub fn problems(&self, section: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(self.idle >= 0.0 && self.active >= self.idle) {
+            problems.push(format!(
+                "{}: idle draw ({} W) must be non-negative and at most the active draw ({} W)",
+                section, self.idle, self.active
+            ));
+        }
+        if self.dvfs.iter().any(|point| point.frequency == 0 || point.watts.is_nan() || point.watts < 0.0) {
+            problems.push(format!("{}: every DVFS point needs a positive frequency and non-negative watts", section));
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_follows_dvfs_points() {
+        let flat = PowerModel::new(10.0, 100.0);
+        assert_eq!(flat.watts_at(1000), 100.0);
+        assert_eq!(flat.energy(Duration::from_millis(500)), 50.0);
+        assert_eq!(flat.headroom(), 90.0);
+
+        let scaled = PowerModel {
+            dvfs: vec![
+                DvfsPoint { frequency: 3000, watts: 90.0 },
+                DvfsPoint { frequency: 1000, watts: 30.0 },
+            ],
+            ..flat
+        };
+        assert_eq!(scaled.watts_at(500), 30.0);
+        assert_eq!(scaled.watts_at(2000), 60.0);
+        assert_eq!(scaled.watts_at(4000), 90.0);
+
+        assert!(scaled.problems("cpu.power").is_empty());
+        assert_eq!(PowerModel::new(50.0, 20.0).problems("gpu.power").len(), 1);
+    }
+}
@@ -0,0 +1,304 @@
+//! CPU core binding
+//!
+//! `CoreLayout` splits the cores a process may use into worker cores and cores
+//! isolated for system or latency-critical work. Worker threads are pinned with
+//! `sched_setaffinity`, and a running task can reserve worker cores of its own
+//! through `CoreReservations`. Outside Linux, pinning is a no-op.
+
+use crate::errors::HardwareError;
+use crate::hardware::cpu::cpu_config::{BindingStrategy, CpuAllocationConfig, CpuConfig};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use tracing::warn;
+
+/// Parse a hexadecimal core mask such as "0xFF" into core indices
+pub fn parse_mask(mask: &str) -> Result<BTreeSet<usize>, HardwareError> {
+    let digits = mask.trim().trim_start_matches("0x").trim_start_matches("0X");
+    if digits.is_empty() {
+        return Err(HardwareError::InvalidConfiguration(format!("empty CPU mask {:?}", mask)));
+    }
+    let mut cores = BTreeSet::new();
+    for (position, digit) in digits.chars().rev().enumerate() {
+        let nibble = digit
+            .to_digit(16)
+            .ok_or_else(|| HardwareError::InvalidConfiguration(format!("CPU mask {:?} is not hexadecimal", mask)))?;
+        for bit in 0..4 {
+            if nibble & (1 << bit) != 0 {
+                cores.insert(position * 4 + bit);
+            }
+        }
+    }
+    Ok(cores)
+}
+
+/// Which cores worker threads and isolated work run on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreLayout {
+    /// Cores task workers run on
+    pub workers: Vec<usize>,
+
+    /// Cores kept for system or latency-critical work
+    pub isolated: Vec<usize>,
+
+    pub strategy: BindingStrategy,
+}
+
+impl CoreLayout {
+    /// Lay out the `allowed` cores as cpu.yaml and `allocation.cpu` ask
+    ///
+    /// Returns `None` when neither binding nor isolation is enabled. The highest cores
+    /// of the mask are the ones isolated, but at least one core is left to workers.
+    pub fn new(
+        config: &CpuConfig,
+        allocation: &CpuAllocationConfig,
+        allowed: &BTreeSet<usize>,
+    ) -> Result<Option<Self>, HardwareError> {
+        let isolate = if allocation.isolation.enabled { allocation.isolation.cores } else { 0 };
+        if !config.affinity.enabled && !allocation.affinity.enabled && isolate == 0 {
+            return Ok(None);
+        }
+
+        let usable: Vec<usize> = if config.affinity.enabled {
+            let mask = parse_mask(&config.affinity.mask)?;
+            let missing: Vec<&usize> = mask.difference(allowed).collect();
+            if !missing.is_empty() {
+                warn!("CPU mask {} names cores {:?} this process cannot use", config.affinity.mask, missing);
+            }
+            mask.intersection(allowed).copied().collect()
+        } else {
+            allowed.iter().copied().collect()
+        };
+        if usable.is_empty() {
+            return Err(HardwareError::InvalidConfiguration(format!(
+                "CPU mask {} leaves no usable cores",
+                config.affinity.mask
+            )));
+        }
+        if usable.len() <= isolate {
+            warn!("Cannot isolate {} of {} usable cores, keeping one for workers", isolate, usable.len());
+        }
+        let isolate = isolate.min(usable.len() - 1);
+
+        let (workers, isolated) = usable.split_at(usable.len() - isolate);
+        Ok(Some(Self {
+            workers: workers.to_vec(),
+            isolated: isolated.to_vec(),
+            strategy: if allocation.affinity.enabled { allocation.affinity.strategy } else { BindingStrategy::ThreadBinding },
+        }))
+    }
+
+    /// Cores the `index`th worker of a pool over `cores` is pinned to
+    pub fn home(strategy: BindingStrategy, cores: &[usize], index: usize) -> Vec<usize> {
+        match strategy {
+            BindingStrategy::CoreBinding if !cores.is_empty() => vec![cores[index % cores.len()]],
+            _ => cores.to_vec(),
+        }
+    }
+}
+
+/// Cores taken by one run of a task
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreReservation {
+    /// Id to release the cores by; unique even between runs of the same task
+    pub id: u64,
+    pub cores: Vec<usize>,
+}
+
+/// Worker cores held by running tasks
+///
+/// Each reservation has its own id, so a retried attempt or a second gang member
+/// of the same task holds and releases its cores independently.
+#[derive(Debug)]
+pub struct CoreReservations {
+    free: BTreeSet<usize>,
+    held: HashMap<u64, (String, Vec<usize>)>,
+    next_id: u64,
+}
+
+impl CoreReservations {
+    pub fn new(cores: &[usize]) -> Self {
+        Self {
+            free: cores.iter().copied().collect(),
+            held: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Take up to `wanted` free cores for a task, lowest first
+    pub fn reserve(&mut self, task_id: &str, wanted: usize) -> CoreReservation {
+        let id = self.next_id;
+        self.next_id += 1;
+        let cores: Vec<usize> = self.free.iter().take(wanted).copied().collect();
+        if !cores.is_empty() {
+            for core in &cores {
+                self.free.remove(core);
+            }
+            self.held.insert(id, (task_id.to_string(), cores.clone()));
+        }
+        CoreReservation { id, cores }
+    }
+
+    /// Give the cores of one reservation back
+    pub fn release(&mut self, id: u64) {
+        if let Some((_, cores)) = self.held.remove(&id) {
+            self.free.extend(cores);
+        }
+    }
+
+    /// Cores held by each running task
+    pub fn held(&self) -> HashMap<String, Vec<usize>> {
+        let mut held: HashMap<String, Vec<usize>> = HashMap::new();
+        for (task_id, cores) in self.held.values() {
+            held.entry(task_id.clone()).or_default().extend(cores);
+        }
+        for cores in held.values_mut() {
+            cores.sort_unstable();
+        }
+        held
+    }
+}
+
+thread_local! {
+    /// Cores the current thread returns to after running on reserved cores
+    static HOME: RefCell<Option<Vec<usize>>> = const { RefCell::new(None) };
+}
+
+/// Pin the current thread to `cores` and remember them as its home
+pub fn pin_home(cores: &[usize]) -> Result<(), HardwareError> {
+    pin_current_thread(cores)?;
+    HOME.with(|home| *home.borrow_mut() = Some(cores.to_vec()));
+    Ok(())
+}
+
+/// Run `f` on `cores`, then move the thread back to its home cores
+pub fn run_on<T>(cores: &[usize], f: impl FnOnce() -> T) -> T {
+    if let Err(e) = pin_current_thread(cores) {
+        warn!("Cannot move thread to cores {:?}: {}", cores, e);
+    }
+    let result = f();
+    if let Some(home) = HOME.with(|home| home.borrow().clone()) {
+        if let Err(e) = pin_current_thread(&home) {
+            warn!("Cannot move thread back to cores {:?}: {}", home, e);
+        }
+    }
+    result
+}
+
+/// Cores the current thread may run on
+#[cfg(target_os = "linux")]
+pub fn current_affinity() -> Result<BTreeSet<usize>, HardwareError> {
+    // SAFETY: cpu_set_t is plain data, and the size passed matches the set handed in
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(HardwareError::ExecutionError(format!(
+                "sched_getaffinity failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok((0..libc::CPU_SETSIZE as usize).filter(|core| libc::CPU_ISSET(*core, &set)).collect())
+    }
+}
+
+/// Cores the current thread may run on
+#[cfg(not(target_os = "linux"))]
+pub fn current_affinity() -> Result<BTreeSet<usize>, HardwareError> {
+    Ok((0..num_cpus::get()).collect())
+}
+
+/// Restrict the current thread to `cores`
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cores: &[usize]) -> Result<(), HardwareError> {
+    // SAFETY: cpu_set_t is plain data, and the size passed matches the set handed in
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for core in cores.iter().filter(|core| **core < libc::CPU_SETSIZE as usize) {
+            libc::CPU_SET(*core, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(HardwareError::ExecutionError(format!(
+                "cannot pin thread to cores {:?}: {}",
+                cores,
+                std::io::Error::last_os_error()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Restrict the current thread to `cores`
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cores: &[usize]) -> Result<(), HardwareError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::cpu::cpu_config::IsolationConfig;
+
+    #[test]
+    fn test_mask_and_isolation_split_cores() {
+        assert_eq!(parse_mask("0xFF").unwrap(), (0..8).collect());
+        assert_eq!(parse_mask("0x105").unwrap(), [0, 2, 8].into_iter().collect());
+        assert!(parse_mask("0xZZ").is_err());
+
+        let mut config = CpuConfig::default();
+        config.affinity.enabled = true;
+        config.affinity.mask = "0xFF".to_string();
+        let allocation = CpuAllocationConfig {
+            isolation: IsolationConfig { enabled: true, cores: 2 },
+            ..CpuAllocationConfig::default()
+        };
+        // Only cores 0-11 exist here, and the mask leaves out 8-11
+        let allowed: BTreeSet<usize> = (0..12).collect();
+        let layout = CoreLayout::new(&config, &allocation, &allowed).unwrap().unwrap();
+        assert_eq!(layout.workers, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(layout.isolated, vec![6, 7]);
+        assert_eq!(CoreLayout::home(BindingStrategy::CoreBinding, &layout.workers, 7), vec![1]);
+        assert_eq!(CoreLayout::home(BindingStrategy::ThreadBinding, &layout.workers, 7), layout.workers);
+
+        let narrow: BTreeSet<usize> = (0..2).collect();
+        let layout = CoreLayout::new(&config, &allocation, &narrow).unwrap().unwrap();
+        assert_eq!((layout.workers, layout.isolated), (vec![0], vec![1]));
+        let outside: BTreeSet<usize> = (8..12).collect();
+        assert!(CoreLayout::new(&config, &allocation, &outside).is_err());
+        assert_eq!(CoreLayout::new(&CpuConfig::default(), &CpuAllocationConfig::default(), &allowed).unwrap(), None);
+    }
+
+    #[test]
+    fn test_reservations_hand_out_free_cores() {
+        let mut reservations = CoreReservations::new(&[0, 1, 2, 3]);
+        let a = reservations.reserve("a", 3);
+        assert_eq!(a.cores, vec![0, 1, 2]);
+        assert_eq!(reservations.reserve("b", 3).cores, vec![3]);
+        assert!(reservations.reserve("c", 1).cores.is_empty());
+        assert_eq!(reservations.held().len(), 2);
+
+        reservations.release(a.id);
+        let first = reservations.reserve("c", 2);
+        assert_eq!(first.cores, vec![0, 1]);
+        assert_eq!(reservations.held()["c"], vec![0, 1]);
+
+        // A second run of the same task keeps its own cores when the first ends
+        let second = reservations.reserve("c", 1);
+        assert_eq!(reservations.held()["c"], vec![0, 1, 2]);
+        reservations.release(first.id);
+        assert_eq!(reservations.held()["c"], second.cores);
+    }
+
+    #[test]
+    fn test_pinning_moves_the_thread() {
+        let allowed = current_affinity().unwrap();
+        let core = *allowed.iter().next().unwrap();
+        std::thread::spawn(move || {
+            pin_home(&[core]).unwrap();
+            assert_eq!(current_affinity().unwrap(), [core].into_iter().collect());
+            let inside = run_on(&allowed.iter().copied().collect::<Vec<_>>(), || current_affinity().unwrap());
+            assert_eq!(inside, allowed);
+            assert_eq!(current_affinity().unwrap(), [core].into_iter().collect());
+        })
+        .join()
+        .unwrap();
+    }
+}
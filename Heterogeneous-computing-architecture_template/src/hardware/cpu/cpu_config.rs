@@ -0,0 +1,254 @@
+//! CPU device configuration (`config/hardware/cpu.yaml`)
+
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::PowerModel;
+use crate::hardware::cpu::affinity::parse_mask;
+use crate::hardware::cpu::thread_pool::ThreadPoolConfig;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Frequency range in MHz
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FrequencyConfig {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl Default for FrequencyConfig {
+    fn default() -> Self {
+        Self { min: 1000, max: 3500 }
+    }
+}
+
+/// Host memory budget
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CpuMemoryConfig {
+    /// Maximum memory usage in MB
+    pub max_usage: u64,
+}
+
+impl Default for CpuMemoryConfig {
+    fn default() -> Self {
+        Self { max_usage: 8192 }
+    }
+}
+
+/// Thread affinity settings (`cpu.affinity` in cpu.yaml)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AffinityConfig {
+    /// Restrict worker threads to the cores in `mask`
+    pub enabled: bool,
+
+    /// Hexadecimal mask of the cores to use
+    pub mask: String,
+}
+
+impl Default for AffinityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mask: "0xFF".to_string(),
+        }
+    }
+}
+
+/// CPU device configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CpuConfig {
+    /// Number of logical cores to use
+    pub logical_cores: usize,
+    pub frequency: FrequencyConfig,
+    pub memory: CpuMemoryConfig,
+    pub affinity: AffinityConfig,
+    pub thread_pool: ThreadPoolConfig,
+
+    /// Package draw; DVFS points cover the frequency range
+    pub power: PowerModel,
+}
+
+impl Default for CpuConfig {
+    fn default() -> Self {
+        Self {
+            logical_cores: num_cpus::get(),
+            frequency: FrequencyConfig::default(),
+            memory: CpuMemoryConfig::default(),
+            affinity: AffinityConfig::default(),
+            thread_pool: ThreadPoolConfig::default(),
+            power: PowerModel::new(10.0, 65.0),
+        }
+    }
+}
+
+impl CpuConfig {
+    /// Parse the `cpu` section of a cpu.yaml document
+    pub fn from_yaml(document: &serde_yaml::Value) -> Result<Self, HardwareError> {
+        match document.get("cpu") {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| HardwareError::InitializationError(format!("invalid cpu config: {}", e))),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load a cpu.yaml file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HardwareError> {
+        Self::from_yaml(&read_yaml(path.as_ref())?)
+    }
+
+    /// Check for values the CPU backend cannot work with
+    pub fn validate(&self) -> Result<(), HardwareError> {
+        let mut problems = Vec::new();
+        if self.logical_cores == 0 {
+            problems.push("logical_cores must be at least 1".to_string());
+        }
+        if self.frequency.max == 0 || self.frequency.min > self.frequency.max {
+            problems.push(format!(
+                "frequency range {}-{} MHz is empty",
+                self.frequency.min, self.frequency.max
+            ));
+        }
+        if self.memory.max_usage == 0 {
+            problems.push("memory.max_usage must be positive".to_string());
+        }
+        if self.affinity.enabled {
+            if let Err(e) = parse_mask(&self.affinity.mask) {
+                problems.push(e.to_string());
+            }
+        }
+        if self.thread_pool.max_threads == 0 || self.thread_pool.min_threads > self.thread_pool.max_threads {
+            problems.push(format!(
+                "thread_pool.min_threads ({}) must not exceed a positive thread_pool.max_threads ({})",
+                self.thread_pool.min_threads, self.thread_pool.max_threads
+            ));
+        }
+        problems.extend(self.power.problems("power"));
+        HardwareError::from_config_problems("cpu", problems)
+    }
+
+    /// Worker pool settings, capped by the number of logical cores in use
+    pub fn effective_thread_pool(&self) -> ThreadPoolConfig {
+        let max_threads = self.thread_pool.max_threads.min(self.logical_cores.max(1));
+        ThreadPoolConfig {
+            min_threads: self.thread_pool.min_threads.min(max_threads),
+            max_threads,
+            idle_timeout: self.thread_pool.idle_timeout,
+        }
+    }
+}
+
+/// How worker threads are bound to cores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingStrategy {
+    /// Each worker runs on one core of its own, round robin
+    CoreBinding,
+    /// Workers may run on any worker core
+    ThreadBinding,
+}
+
+/// Worker binding settings (`allocation.cpu.affinity`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CpuBindingConfig {
+    pub enabled: bool,
+    pub strategy: BindingStrategy,
+}
+
+impl Default for CpuBindingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strategy: BindingStrategy::CoreBinding,
+        }
+    }
+}
+
+/// Core isolation settings (`allocation.cpu.isolation`)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct IsolationConfig {
+    pub enabled: bool,
+
+    /// Cores kept away from task workers for system or latency-critical work
+    pub cores: usize,
+}
+
+/// CPU allocation settings (`allocation.cpu` in resource_allocation.yaml)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CpuAllocationConfig {
+    pub affinity: CpuBindingConfig,
+    pub isolation: IsolationConfig,
+}
+
+impl CpuAllocationConfig {
+    /// Parse the `allocation.cpu` section of a resource_allocation.yaml document
+    pub fn from_yaml(document: &serde_yaml::Value) -> Result<Self, HardwareError> {
+        match document.get("allocation").and_then(|a| a.get("cpu")) {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| HardwareError::InitializationError(format!("invalid cpu allocation config: {}", e))),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load a resource_allocation.yaml file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HardwareError> {
+        Self::from_yaml(&read_yaml(path.as_ref())?)
+    }
+}
+
+fn read_yaml(path: &Path) -> Result<serde_yaml::Value, HardwareError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| HardwareError::InitializationError(format!("cannot read {}: {}", path.display(), e)))?;
+    serde_yaml::from_str(&content)
+        .map_err(|e| HardwareError::InitializationError(format!("cannot parse {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_repository_cpu_config() {
+        let config = CpuConfig::load(concat!(env!("CARGO_MANIFEST_DIR"), "/config/hardware/cpu.yaml")).unwrap();
+        assert_eq!(config.logical_cores, 8);
+        assert_eq!(config.thread_pool.min_threads, 2);
+        assert_eq!(config.thread_pool.max_threads, 16);
+        assert_eq!(config.thread_pool.idle_timeout, 60);
+        assert!(config.affinity.enabled);
+        assert_eq!(config.affinity.mask, "0xFF");
+        assert_eq!((config.power.idle, config.power.active), (10.0, 65.0));
+        assert_eq!(config.power.watts_at(config.frequency.min), 25.0);
+
+        let pool = config.effective_thread_pool();
+        assert_eq!(pool.max_threads, 8);
+        assert_eq!(pool.min_threads, 2);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut config = CpuConfig::default();
+        config.frequency.min = 4000;
+        config.thread_pool.min_threads = 32;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("frequency range 4000-3500 MHz"));
+        assert!(message.contains("thread_pool.min_threads (32)"));
+    }
+
+    #[test]
+    fn test_load_repository_cpu_allocation() {
+        let allocation = CpuAllocationConfig::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/config/scheduling/resource_allocation.yaml"
+        ))
+        .unwrap();
+        assert!(allocation.affinity.enabled);
+        assert_eq!(allocation.affinity.strategy, BindingStrategy::CoreBinding);
+        assert!(allocation.isolation.enabled);
+        assert_eq!(allocation.isolation.cores, 2);
+    }
+}
@@ -1,108 +1,556 @@
 //! CPU device implementation
 
-use crate::hardware::abstract::{HardwareDevice, HardwareType, HardwareMetrics, DeviceStatus};
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::{
+    CancelToken, DeviceCapabilities, DeviceStatus, HardwareDevice, HardwareMetrics, HardwareType, Kernel, Operation, PowerModel,
+    Task, TaskResult, Tensor,
+};
+use crate::hardware::cpu::affinity::{current_affinity, run_on, CoreLayout, CoreReservation, CoreReservations};
+use crate::hardware::cpu::cpu_config::{BindingStrategy, CpuAllocationConfig, CpuConfig};
+use crate::hardware::cpu::telemetry::HostTelemetry;
+use crate::hardware::cpu::thread_pool::{ThreadPoolConfig, WorkerPool};
+use async_trait::async_trait;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tracing::debug;
 
+/// Smallest number of work items worth handing to a separate worker
+const MIN_ITEMS_PER_JOB: usize = 4096;
+
+/// FLOPs per core and cycle assumed by the cost model (one scalar fused multiply-add)
+const FLOPS_PER_CYCLE: u64 = 2;
+
 /// CPU device structure
 pub struct CpuDevice {
     id: String,
     name: String,
-    properties: HashMap<String, String>,
-    is_initialized: AtomicU64,
-    cpu_count: u64,
-    max_threads: u64,
-    current_utilization: AtomicU64,
-    temperature: AtomicU64,
-    memory_used: AtomicU64,
+    config: CpuConfig,
+    allocation: CpuAllocationConfig,
+    properties: Mutex<HashMap<String, String>>,
+    pool: Mutex<Option<Arc<WorkerPool>>>,
+    layout: Mutex<Option<CoreLayout>>,
+    isolated_pool: Mutex<Option<WorkerPool>>,
+    reservations: Mutex<CoreReservations>,
+    telemetry: Arc<HostTelemetry>,
+    monitoring_interval: Duration,
+    sampler: Mutex<Option<JoinHandle<()>>>,
     memory_total: AtomicU64,
 }
 
 impl CpuDevice {
-    /// Create a new CPU device
+    /// Create a new CPU device with default settings
     pub fn new(id: String, name: String) -> Self {
+        Self::with_config(id, name, CpuConfig::default())
+    }
+
+    /// Create a new CPU device from cpu.yaml settings
+    pub fn with_config(id: String, name: String, config: CpuConfig) -> Self {
         Self {
             id,
             name,
-            properties: HashMap::new(),
-            is_initialized: AtomicU64::new(0),
-            cpu_count: num_cpus::get() as u64,
-            max_threads: num_cpus::get() as u64,
-            current_utilization: AtomicU64::new(0),
-            temperature: AtomicU64::new(0),
-            memory_used: AtomicU64::new(0),
+            config,
+            allocation: CpuAllocationConfig::default(),
+            properties: Mutex::new(HashMap::new()),
+            pool: Mutex::new(None),
+            layout: Mutex::new(None),
+            isolated_pool: Mutex::new(None),
+            reservations: Mutex::new(CoreReservations::new(&[])),
+            telemetry: Arc::new(HostTelemetry::new()),
+            monitoring_interval: Duration::from_millis(5000),
+            sampler: Mutex::new(None),
             memory_total: AtomicU64::new(0),
         }
     }
-    
+
+    /// Use a specific telemetry collector, sampled every `interval`
+    pub fn with_telemetry(mut self, telemetry: Arc<HostTelemetry>, interval: Duration) -> Self {
+        self.telemetry = telemetry;
+        self.monitoring_interval = interval;
+        self
+    }
+
+    /// Bind workers and isolate cores as `allocation.cpu` asks
+    pub fn with_allocation(mut self, allocation: CpuAllocationConfig) -> Self {
+        self.allocation = allocation;
+        self
+    }
+
+    /// How cores are split between workers and isolated work, once initialized
+    pub fn core_layout(&self) -> Option<CoreLayout> {
+        self.layout.lock().unwrap().clone()
+    }
+
+    /// Run a job on the isolated cores, away from task workers
+    pub fn execute_isolated<F>(&self, job: F) -> Result<(), HardwareError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match self.isolated_pool.lock().unwrap().as_ref() {
+            Some(pool) => pool.execute(job),
+            None => Err(HardwareError::DeviceUnavailable(format!("CPU device {} has no isolated cores", self.id))),
+        }
+    }
+
+    /// Telemetry collector feeding this device's metrics
+    pub fn telemetry(&self) -> &Arc<HostTelemetry> {
+        &self.telemetry
+    }
+
     /// Initialize CPU device
-    pub fn initialize_cpu(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn initialize_cpu(&self) -> Result<(), HardwareError> {
         debug!("Initializing CPU device: {}", self.name);
-        
-        // Simulate CPU initialization
-        self.is_initialized.store(1, Ordering::Relaxed);
-        
+
+        let pool_config = self.config.effective_thread_pool();
+        let layout = CoreLayout::new(&self.config, &self.allocation, &current_affinity()?)?;
+        let pool = match &layout {
+            Some(layout) => WorkerPool::with_affinity(&self.id, pool_config.clone(), layout.workers.clone(), layout.strategy)?,
+            None => WorkerPool::new(&self.id, pool_config.clone())?,
+        };
+        let isolated_pool = match &layout {
+            Some(layout) if !layout.isolated.is_empty() => Some(WorkerPool::with_affinity(
+                &format!("{}-isolated", self.id),
+                ThreadPoolConfig {
+                    min_threads: 0,
+                    max_threads: layout.isolated.len(),
+                    idle_timeout: pool_config.idle_timeout,
+                },
+                layout.isolated.clone(),
+                BindingStrategy::ThreadBinding,
+            )?),
+            _ => None,
+        };
+        *self.pool.lock().unwrap() = Some(Arc::new(pool));
+        if let Some(previous) = std::mem::replace(&mut *self.isolated_pool.lock().unwrap(), isolated_pool) {
+            previous.shutdown();
+        }
+        let workers = layout.as_ref().map(|layout| layout.workers.clone()).unwrap_or_default();
+        *self.reservations.lock().unwrap() = CoreReservations::new(&workers);
+
         // Set up properties
-        self.properties.insert("vendor".to_string(), "Intel".to_string());
-        self.properties.insert("model".to_string(), "Xeon".to_string());
-        self.properties.insert("cores".to_string(), self.cpu_count.to_string());
-        self.properties.insert("threads".to_string(), self.max_threads.to_string());
-        self.properties.insert("arch".to_string(), "x86_64".to_string());
-        
+        let (vendor, model) = read_cpu_identity();
+        let mut properties = self.properties.lock().unwrap();
+        properties.insert("vendor".to_string(), vendor);
+        properties.insert("model".to_string(), model);
+        properties.insert("cores".to_string(), self.config.logical_cores.to_string());
+        properties.insert("threads".to_string(), pool_config.max_threads.to_string());
+        properties.insert("arch".to_string(), std::env::consts::ARCH.to_string());
+        properties.insert("frequency_min".to_string(), self.config.frequency.min.to_string());
+        properties.insert("frequency_max".to_string(), self.config.frequency.max.to_string());
+        properties.insert("memory".to_string(), format!("{}MB", self.config.memory.max_usage));
+        if let Some(layout) = &layout {
+            properties.insert("worker_cores".to_string(), format!("{:?}", layout.workers));
+            properties.insert("isolated_cores".to_string(), format!("{:?}", layout.isolated));
+        }
+        drop(properties);
+        *self.layout.lock().unwrap() = layout;
+
         // Set memory values
-        self.memory_total.store(8192, Ordering::Relaxed); // 8GB
-        
+        self.memory_total.store(self.config.memory.max_usage, Ordering::Relaxed);
+
+        // Take a first sample now and keep sampling if we are running on a tokio runtime
+        self.telemetry.sample();
+        if tokio::runtime::Handle::try_current().is_ok() {
+            let sampler = self.telemetry.spawn_sampler(self.monitoring_interval);
+            if let Some(previous) = self.sampler.lock().unwrap().replace(sampler) {
+                previous.abort();
+            }
+        }
+
         Ok(())
     }
-    
+
     /// Monitor CPU metrics
     fn monitor_cpu_metrics(&self) -> HardwareMetrics {
-        // In a real implementation, this would gather actual metrics
+        let sample = self.telemetry.latest();
+        let memory_total = if sample.memory_total > 0 {
+            sample.memory_total
+        } else {
+            self.memory_total.load(Ordering::Relaxed)
+        };
+        let busy = self.pool.lock().unwrap().as_ref().is_some_and(|pool| pool.busy_count() > 0);
         HardwareMetrics {
-            utilization: self.current_utilization.load(Ordering::Relaxed) as f64,
-            temperature: self.temperature.load(Ordering::Relaxed) as f64,
-            memory_used: self.memory_used.load(Ordering::Relaxed),
-            memory_total: self.memory_total.load(Ordering::Relaxed),
-            clock_speed: 3000, // 3GHz
-            status: DeviceStatus::Active,
+            utilization: sample.utilization,
+            temperature: sample.temperature,
+            memory_used: sample.memory_used,
+            memory_total,
+            clock_speed: if sample.clock_speed > 0 { sample.clock_speed } else { self.config.frequency.max },
+            status: if busy { DeviceStatus::Busy } else { DeviceStatus::Idle },
+            core_reservations: self.reservations.lock().unwrap().held(),
+        }
+    }
+
+    /// Split a kernel into row ranges and run them on the worker pool
+    ///
+    /// With a core layout, the task reserves a free worker core per job for as long as
+    /// it runs; jobs that find no free core run wherever their worker is pinned.
+    /// A task with a lease limit runs at most one job per leased core. Jobs check the
+    /// task's cancel token between slices of their range, and the cores are only
+    /// released once every job has stopped.
+    async fn run_kernel(&self, task: &Task, pool: Arc<WorkerPool>, kernel: Kernel) -> Result<Tensor, HardwareError> {
+        let (task_id, cancel) = (task.id.as_str(), &task.cancel);
+        let kernel = Arc::new(kernel);
+        let items = kernel.work_items();
+        let max_jobs = task.limit.map_or(pool.max_threads(), |limit| limit.compute_units as usize);
+        let jobs = items.div_ceil(MIN_ITEMS_PER_JOB).clamp(1, max_jobs.clamp(1, pool.max_threads()));
+        let chunk = items.div_ceil(jobs).max(1);
+        let reserved = ReservedCores {
+            reservation: self.reservations.lock().unwrap().reserve(task_id, jobs),
+            reservations: &self.reservations,
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for job in 0..jobs {
+            let range = (job * chunk).min(items)..((job + 1) * chunk).min(items);
+            let (kernel, tx, cancel) = (Arc::clone(&kernel), tx.clone(), cancel.clone());
+            let core = reserved.reservation.cores.get(job).copied();
+            pool.execute(move || {
+                let run = || run_slices(&kernel, range, &cancel);
+                let partial = match core {
+                    Some(core) => run_on(&[core], run),
+                    None => run(),
+                };
+                let _ = tx.send((job, partial));
+            })?;
+        }
+        drop(tx);
+
+        // Wait for every job, even a cancelled one, so none outlives the reservation
+        let mut partials = vec![None; jobs];
+        while let Some((job, partial)) = rx.recv().await {
+            partials[job] = partial;
+        }
+        if cancel.is_cancelled() {
+            return Err(HardwareError::Cancelled(format!("task {} on {}", task_id, self.id)));
+        }
+        let partials = partials
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| HardwareError::ExecutionError(format!("a worker on {} failed", self.id)))?;
+
+        Ok(kernel.finish(partials))
+    }
+}
+
+/// Run `range` of a kernel a slice at a time, giving up once `cancel` is set
+fn run_slices(kernel: &Kernel, range: Range<usize>, cancel: &CancelToken) -> Option<Vec<f32>> {
+    let mut partial = Vec::new();
+    for start in range.clone().step_by(MIN_ITEMS_PER_JOB) {
+        if cancel.is_cancelled() {
+            return None;
+        }
+        partial.extend(kernel.run_range(start..(start + MIN_ITEMS_PER_JOB).min(range.end)));
+    }
+    Some(partial)
+}
+
+/// Worker cores reserved for a running task, released when dropped
+struct ReservedCores<'a> {
+    reservation: CoreReservation,
+    reservations: &'a Mutex<CoreReservations>,
+}
+
+impl Drop for ReservedCores<'_> {
+    fn drop(&mut self) {
+        if !self.reservation.cores.is_empty() {
+            self.reservations.lock().unwrap().release(self.reservation.id);
         }
     }
 }
 
+/// Read the CPU vendor and model name from /proc/cpuinfo
+fn read_cpu_identity() -> (String, String) {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let field = |key: &str| {
+        cpuinfo
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim() == key)
+            .map(|(_, value)| value.trim().to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+    (field("vendor_id"), field("model name"))
+}
+
+#[async_trait]
 impl HardwareDevice for CpuDevice {
-    fn get_type(&self) -> HardwareType {
-        HardwareType::Cpu
+    fn id(&self) -> &str {
+        &self.id
     }
-    
-    fn get_id(&self) -> String {
-        self.id.clone()
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn device_type(&self) -> HardwareType {
+        HardwareType::Cpu
     }
-    
-    fn get_name(&self) -> String {
-        self.name.clone()
+
+    fn properties(&self) -> HashMap<String, String> {
+        self.properties.lock().unwrap().clone()
     }
-    
-    fn get_properties(&self) -> HashMap<String, String> {
-        self.properties.clone()
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            compute_units: match self.layout.lock().unwrap().as_ref() {
+                Some(layout) => layout.workers.len() as u64,
+                None => self.config.logical_cores as u64,
+            },
+            operations: Operation::ALL.iter().map(|op| op.name().to_string()).collect(),
+            max_clock_speed: self.config.frequency.max,
+        }
     }
-    
+
     fn is_available(&self) -> bool {
-        self.is_initialized.load(Ordering::Relaxed) == 1
+        self.pool.lock().unwrap().is_some()
     }
-    
-    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+
+    async fn initialize(&self) -> Result<(), HardwareError> {
         self.initialize_cpu()
     }
-    
-    fn shutdown(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+
+    async fn shutdown(&self) -> Result<(), HardwareError> {
         debug!("Shutting down CPU device: {}", self.name);
-        self.is_initialized.store(0, Ordering::Relaxed);
+        if let Some(pool) = self.pool.lock().unwrap().take() {
+            pool.shutdown();
+        }
+        if let Some(pool) = self.isolated_pool.lock().unwrap().take() {
+            pool.shutdown();
+        }
+        if let Some(sampler) = self.sampler.lock().unwrap().take() {
+            sampler.abort();
+        }
         Ok(())
     }
-    
+
     fn get_metrics(&self) -> HardwareMetrics {
         self.monitor_cpu_metrics()
     }
-}
\ No newline at end of file
+
+    fn total_memory(&self) -> u64 {
+        self.memory_total.load(Ordering::Relaxed) * 1024 * 1024
+    }
+
+    fn available_memory(&self) -> u64 {
+        // Never promise more than the configured budget or what the host has free
+        let sample = self.telemetry.latest();
+        if sample.memory_total > 0 {
+            self.total_memory().min(sample.memory_available * 1024 * 1024)
+        } else {
+            self.total_memory()
+        }
+    }
+
+    fn power_model(&self) -> Option<PowerModel> {
+        Some(self.config.power.clone())
+    }
+
+    fn estimate_execution(&self, task: &Task) -> Option<Duration> {
+        let kernel = match Operation::parse(&task.operation).ok()? {
+            Operation::Compute => return Some(Duration::ZERO),
+            operation => Kernel::new(operation, Tensor::decode_all(&task.data).ok()?).ok()?,
+        };
+        let cores = (self.config.logical_cores as u64).min(kernel.work_items() as u64).max(1);
+        let flops_per_second = (cores * FLOPS_PER_CYCLE * self.config.frequency.max.max(1)) as f64 * 1e6;
+        Some(Duration::from_secs_f64(kernel.flops() as f64 / flops_per_second))
+    }
+
+    async fn execute_task(&self, task: &Task) -> Result<TaskResult, HardwareError> {
+        let pool = self
+            .pool
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| HardwareError::DeviceUnavailable(format!("CPU device {} is not initialized", self.id)))?;
+
+        debug!("Executing task {} ({}) on CPU {}", task.id, task.operation, self.id);
+        let start = Instant::now();
+
+        let data = match Operation::parse(&task.operation)? {
+            Operation::Compute => task.data.clone(),
+            operation => {
+                let kernel = Kernel::new(operation, Tensor::decode_all(&task.data)?)?;
+                let output = self.run_kernel(task, pool, kernel).await?;
+                Tensor::encode_all(&[output])
+            }
+        };
+
+        // Draw depends on the clock the cores ran at
+        let elapsed = start.elapsed();
+        let clock_speed = match self.telemetry.latest().clock_speed {
+            0 => self.config.frequency.max,
+            clock_speed => clock_speed,
+        };
+        Ok(TaskResult {
+            task_id: task.id.clone(),
+            data,
+            execution_time: elapsed.as_millis() as u64,
+            energy: self.config.power.watts_at(clock_speed) * elapsed.as_secs_f64(),
+        })
+    }
+}
+
+impl Drop for CpuDevice {
+    fn drop(&mut self) {
+        if let Some(sampler) = self.sampler.lock().unwrap().take() {
+            sampler.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::cpu::thread_pool::ThreadPoolConfig;
+
+    fn test_device() -> CpuDevice {
+        let config = CpuConfig {
+            logical_cores: 4,
+            thread_pool: ThreadPoolConfig {
+                min_threads: 1,
+                max_threads: 4,
+                idle_timeout: 1,
+            },
+            ..CpuConfig::default()
+        };
+        CpuDevice::with_config("cpu-test".to_string(), "Test CPU".to_string(), config)
+    }
+
+    fn task(operation: &str, inputs: &[Tensor]) -> Task {
+        Task {
+            id: format!("{}-task", operation),
+            data: Tensor::encode_all(inputs),
+            operation: operation.to_string(),
+            kernel: None,
+            devices: Vec::new(),
+            cancel: CancelToken::new(),
+            limit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_gemm_matches_reference_kernel() {
+        let device = test_device();
+        device.initialize().await.unwrap();
+
+        let (m, k, n) = (96, 64, 80);
+        let a = Tensor::new(vec![m, k], (0..m * k).map(|i| (i % 7) as f32).collect()).unwrap();
+        let b = Tensor::new(vec![k, n], (0..k * n).map(|i| (i % 5) as f32 - 2.0).collect()).unwrap();
+        let expected = Kernel::new(Operation::Gemm, vec![a.clone(), b.clone()]).unwrap().run();
+
+        let result = device.execute_task(&task("gemm", &[a, b])).await.unwrap();
+        assert_eq!(result.task_id, "gemm-task");
+        assert_eq!(Tensor::decode_all(&result.data).unwrap(), vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_reduce() {
+        let device = test_device();
+        device.initialize().await.unwrap();
+
+        let len = 10_000;
+        let a = Tensor::new(vec![len], vec![1.0; len]).unwrap();
+        let b = Tensor::new(vec![len], vec![2.0; len]).unwrap();
+        let sum = device.execute_task(&task("add", &[a.clone(), b])).await.unwrap();
+        assert_eq!(Tensor::decode_all(&sum.data).unwrap()[0].data, vec![3.0; len]);
+
+        let reduced = device.execute_task(&task("reduce_sum", &[a])).await.unwrap();
+        assert_eq!(Tensor::decode_all(&reduced.data).unwrap()[0].data, vec![len as f32]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_the_pool_jobs() {
+        let device = test_device();
+        device.initialize().await.unwrap();
+
+        let n = 1024;
+        let a = Tensor::new(vec![n, n], vec![1.0; n * n]).unwrap();
+        let task = task("gemm", &[a.clone(), a]);
+        let (result, ()) = tokio::join!(device.execute_task(&task), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            task.cancel.cancel();
+        });
+        assert!(matches!(result, Err(HardwareError::Cancelled(_))));
+        assert!(device.get_metrics().core_reservations.is_empty());
+
+        // A task cancelled before it starts runs nothing
+        let result = device.execute_task(&task).await;
+        assert!(matches!(result, Err(HardwareError::Cancelled(_))));
+    }
+
+    #[tokio::test]
+    async fn test_execute_requires_initialization_and_known_operation() {
+        let device = test_device();
+        let result = device.execute_task(&task("add", &[])).await;
+        assert!(matches!(result, Err(HardwareError::DeviceUnavailable(_))));
+
+        device.initialize().await.unwrap();
+        let result = device.execute_task(&task("fft", &[])).await;
+        assert!(matches!(result, Err(HardwareError::UnsupportedOperation(_))));
+
+        device.shutdown().await.unwrap();
+        assert!(!device.is_available());
+    }
+
+    #[tokio::test]
+    async fn test_workers_stay_on_the_mask_and_release_reserved_cores() {
+        use crate::hardware::cpu::cpu_config::IsolationConfig;
+        use crate::hardware::cpu::current_affinity;
+
+        let allowed = current_affinity().unwrap();
+        let mut device = test_device();
+        device.config.affinity.enabled = true;
+        device.config.affinity.mask = format!("{:#x}", allowed.iter().map(|core| 1u128 << core).sum::<u128>());
+        let device = device.with_allocation(CpuAllocationConfig {
+            isolation: IsolationConfig { enabled: true, cores: 1 },
+            ..CpuAllocationConfig::default()
+        });
+        device.initialize().await.unwrap();
+
+        let layout = device.core_layout().unwrap();
+        assert!(!layout.workers.is_empty());
+        assert_eq!(layout.isolated.len(), usize::from(allowed.len() > 1));
+        assert_eq!(device.capabilities().compute_units, layout.workers.len() as u64);
+
+        let len = 20_000;
+        let a = Tensor::new(vec![len], vec![1.0; len]).unwrap();
+        let reduced = device.execute_task(&task("reduce_sum", &[a])).await.unwrap();
+        assert_eq!(Tensor::decode_all(&reduced.data).unwrap()[0].data, vec![len as f32]);
+        assert!(device.get_metrics().core_reservations.is_empty());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let isolated = device.execute_isolated(move || tx.send(current_affinity().unwrap()).unwrap());
+        if layout.isolated.is_empty() {
+            assert!(matches!(isolated, Err(HardwareError::DeviceUnavailable(_))));
+        } else {
+            isolated.unwrap();
+            let cores = rx.recv().unwrap();
+            assert_eq!(cores, layout.isolated.iter().copied().collect());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_come_from_telemetry() {
+        let root = std::env::temp_dir().join(format!("hetero-cpu-metrics-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("proc")).unwrap();
+        std::fs::write(root.join("proc/stat"), "cpu  250 0 250 500 0 0 0 0\n").unwrap();
+        std::fs::write(root.join("proc/meminfo"), "MemTotal: 4096000 kB\nMemAvailable: 1024000 kB\n").unwrap();
+
+        let telemetry = Arc::new(HostTelemetry::with_root(&root));
+        let device = test_device().with_telemetry(telemetry, Duration::from_secs(3600));
+        device.initialize().await.unwrap();
+
+        let metrics = device.get_metrics();
+        assert_eq!(metrics.utilization, 50.0);
+        assert_eq!(metrics.memory_total, 4000);
+        assert_eq!(metrics.memory_used, 3000);
+        assert_eq!(device.available_memory(), 1000 * 1024 * 1024);
+
+        device.shutdown().await.unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
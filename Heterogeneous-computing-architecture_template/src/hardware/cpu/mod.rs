@@ -1,5 +1,13 @@
 //! CPU hardware module
 
+pub mod affinity;
+pub mod cpu_config;
 pub mod cpu_device;
+pub mod telemetry;
+pub mod thread_pool;
 
-pub use cpu_device::*;
\ No newline at end of file
+pub use affinity::*;
+pub use cpu_config::*;
+pub use cpu_device::*;
+pub use telemetry::*;
+pub use thread_pool::*;
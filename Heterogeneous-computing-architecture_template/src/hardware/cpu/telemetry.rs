@@ -0,0 +1,302 @@
+//! Host telemetry collected from procfs and sysfs
+//!
+//! Reads `/proc/stat`, `/proc/meminfo`, `/sys/devices/system/cpu/*/cpufreq` and
+//! `/sys/class/thermal` relative to a configurable root so tests can use a fake tree.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Thermal zone types that report CPU package or core temperatures
+const CPU_THERMAL_ZONES: [&str; 5] = ["x86_pkg_temp", "coretemp", "k10temp", "cpu", "soc"];
+
+/// One telemetry reading
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TelemetrySample {
+    /// CPU utilization percentage since the previous sample
+    pub utilization: f64,
+
+    /// Hottest CPU thermal zone in Celsius
+    pub temperature: f64,
+
+    /// Memory in use in MB
+    pub memory_used: u64,
+
+    /// Total memory in MB
+    pub memory_total: u64,
+
+    /// Memory available for new allocations in MB
+    pub memory_available: u64,
+
+    /// Average current core frequency in MHz
+    pub clock_speed: u64,
+}
+
+/// Aggregate jiffy counters from the `cpu` line of /proc/stat
+#[derive(Debug, Clone, Copy)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+/// Collector for live host metrics
+pub struct HostTelemetry {
+    root: PathBuf,
+    previous: Mutex<Option<CpuTimes>>,
+    latest: Mutex<TelemetrySample>,
+}
+
+impl HostTelemetry {
+    /// Collector reading the real host filesystem
+    pub fn new() -> Self {
+        Self::with_root("/")
+    }
+
+    /// Collector reading procfs/sysfs below `root`
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            previous: Mutex::new(None),
+            latest: Mutex::new(TelemetrySample::default()),
+        }
+    }
+
+    /// Most recent sample
+    pub fn latest(&self) -> TelemetrySample {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Take a new sample and make it the latest one
+    pub fn sample(&self) -> TelemetrySample {
+        let (memory_total, memory_available) = self.read_meminfo().unwrap_or((0, 0));
+        let sample = TelemetrySample {
+            utilization: self.read_utilization().unwrap_or(0.0),
+            temperature: self.read_temperature().unwrap_or(0.0),
+            memory_used: memory_total.saturating_sub(memory_available),
+            memory_total,
+            memory_available,
+            clock_speed: self.read_clock_speed().unwrap_or(0),
+        };
+
+        *self.latest.lock().unwrap() = sample.clone();
+        sample
+    }
+
+    /// Sample periodically on the current tokio runtime
+    pub fn spawn_sampler(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let telemetry = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let sample = telemetry.sample();
+                debug!("Host telemetry sample: {:?}", sample);
+            }
+        })
+    }
+
+    fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    fn read_utilization(&self) -> Option<f64> {
+        let stat = fs::read_to_string(self.path("proc/stat")).ok()?;
+        let fields: Vec<u64> = stat
+            .lines()
+            .find(|line| line.starts_with("cpu "))?
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        if fields.len() < 4 {
+            return None;
+        }
+
+        // user nice system idle iowait irq softirq steal; guest time is already in user/nice
+        let total: u64 = fields.iter().take(8).sum();
+        let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+        let current = CpuTimes {
+            busy: total - idle,
+            total,
+        };
+
+        let previous = self.previous.lock().unwrap().replace(current);
+        let (busy, total) = match previous {
+            Some(prev) if current.total > prev.total => {
+                (current.busy.saturating_sub(prev.busy), current.total - prev.total)
+            }
+            _ => (current.busy, current.total),
+        };
+        if total == 0 {
+            return None;
+        }
+        Some(busy as f64 / total as f64 * 100.0)
+    }
+
+    /// Returns (total, available) in MB
+    fn read_meminfo(&self) -> Option<(u64, u64)> {
+        let meminfo = fs::read_to_string(self.path("proc/meminfo")).ok()?;
+        let field_kb = |key: &str| {
+            meminfo
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| *name == key)
+                .and_then(|(_, value)| value.split_whitespace().next()?.parse::<u64>().ok())
+        };
+        let total = field_kb("MemTotal")?;
+        let available = field_kb("MemAvailable").or_else(|| field_kb("MemFree"))?;
+        Some((total / 1024, available / 1024))
+    }
+
+    fn read_clock_speed(&self) -> Option<u64> {
+        let frequencies: Vec<u64> = read_dir_sorted(&self.path("sys/devices/system/cpu"))
+            .into_iter()
+            .filter(|dir| {
+                dir.file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix("cpu"))
+                    .is_some_and(|index| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()))
+            })
+            .filter_map(|dir| read_u64(&dir.join("cpufreq/scaling_cur_freq")))
+            .collect();
+
+        if !frequencies.is_empty() {
+            // cpufreq reports kHz
+            return Some(frequencies.iter().sum::<u64>() / frequencies.len() as u64 / 1000);
+        }
+
+        let cpuinfo = fs::read_to_string(self.path("proc/cpuinfo")).ok()?;
+        let clocks: Vec<f64> = cpuinfo
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.trim() == "cpu MHz")
+            .filter_map(|(_, value)| value.trim().parse().ok())
+            .collect();
+        if clocks.is_empty() {
+            return None;
+        }
+        Some((clocks.iter().sum::<f64>() / clocks.len() as f64) as u64)
+    }
+
+    fn read_temperature(&self) -> Option<f64> {
+        let zones: Vec<(String, f64)> = read_dir_sorted(&self.path("sys/class/thermal"))
+            .into_iter()
+            .filter(|dir| {
+                dir.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("thermal_zone"))
+            })
+            .filter_map(|dir| {
+                let kind = fs::read_to_string(dir.join("type")).unwrap_or_default();
+                // Thermal zones report millidegrees Celsius
+                let temp = fs::read_to_string(dir.join("temp")).ok()?.trim().parse::<i64>().ok()? as f64 / 1000.0;
+                Some((kind.trim().to_string(), temp))
+            })
+            .collect();
+
+        let cpu_zones: Vec<f64> = zones
+            .iter()
+            .filter(|(kind, _)| CPU_THERMAL_ZONES.iter().any(|prefix| kind.starts_with(prefix)))
+            .map(|(_, temp)| *temp)
+            .collect();
+        let candidates = if cpu_zones.is_empty() {
+            zones.iter().map(|(_, temp)| *temp).collect()
+        } else {
+            cpu_zones
+        };
+        candidates.into_iter().reduce(f64::max)
+    }
+}
+
+impl Default for HostTelemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_dir_sorted(path: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)
+        .map(|dir| dir.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fake procfs/sysfs tree under the system temp directory
+    struct FakeSysfs {
+        root: PathBuf,
+    }
+
+    impl FakeSysfs {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!("hetero-sysfs-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            Self { root }
+        }
+
+        fn write(&self, relative: &str, content: &str) {
+            let path = self.root.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn test_sample_from_fake_sysfs() {
+        let fake = FakeSysfs::new("sample");
+        fake.write("proc/stat", "cpu  100 0 100 800 0 0 0 0 0 0\ncpu0 50 0 50 400 0 0 0 0 0 0\n");
+        fake.write("proc/meminfo", "MemTotal:       16384000 kB\nMemFree:         1024000 kB\nMemAvailable:    8192000 kB\n");
+        fake.write("sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq", "2400000\n");
+        fake.write("sys/devices/system/cpu/cpu1/cpufreq/scaling_cur_freq", "3600000\n");
+        fake.write("sys/devices/system/cpu/cpufreq/policy0/scaling_cur_freq", "9999999\n");
+        fake.write("sys/class/thermal/thermal_zone0/type", "acpitz\n");
+        fake.write("sys/class/thermal/thermal_zone0/temp", "90000\n");
+        fake.write("sys/class/thermal/thermal_zone1/type", "x86_pkg_temp\n");
+        fake.write("sys/class/thermal/thermal_zone1/temp", "55500\n");
+
+        let telemetry = HostTelemetry::with_root(&fake.root);
+        let first = telemetry.sample();
+        assert_eq!(first.utilization, 20.0);
+        assert_eq!(first.memory_total, 16000);
+        assert_eq!(first.memory_available, 8000);
+        assert_eq!(first.memory_used, 8000);
+        assert_eq!(first.clock_speed, 3000);
+        assert_eq!(first.temperature, 55.5);
+
+        // 300 busy out of 400 new jiffies
+        fake.write("proc/stat", "cpu  300 0 200 900 0 0 0 0 0 0\n");
+        let second = telemetry.sample();
+        assert_eq!(second.utilization, 75.0);
+        assert_eq!(telemetry.latest(), second);
+    }
+
+    #[test]
+    fn test_missing_sources_fall_back() {
+        let fake = FakeSysfs::new("fallback");
+        fake.write("proc/cpuinfo", "processor\t: 0\ncpu MHz\t\t: 2000.000\nprocessor\t: 1\ncpu MHz\t\t: 3000.000\n");
+
+        let sample = HostTelemetry::with_root(&fake.root).sample();
+        assert_eq!(sample.clock_speed, 2500);
+        assert_eq!(sample.utilization, 0.0);
+        assert_eq!(sample.memory_total, 0);
+        assert_eq!(sample.temperature, 0.0);
+    }
+}
@@ -0,0 +1,284 @@
+//! Elastic worker thread pool backing CPU task execution
+
+use crate::errors::HardwareError;
+use crate::hardware::cpu::affinity::{pin_home, CoreLayout};
+use crate::hardware::cpu::cpu_config::BindingStrategy;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Thread pool settings (`cpu.thread_pool` in cpu.yaml)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThreadPoolConfig {
+    /// Workers kept alive even when idle
+    pub min_threads: usize,
+
+    /// Upper bound on concurrently running workers
+    pub max_threads: usize,
+
+    /// Seconds a surplus worker may stay idle before it exits
+    pub idle_timeout: u64,
+}
+
+impl Default for ThreadPoolConfig {
+    fn default() -> Self {
+        Self {
+            min_threads: 2,
+            max_threads: 16,
+            idle_timeout: 60,
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    workers: usize,
+    idle: usize,
+    shutdown: bool,
+}
+
+struct PoolShared {
+    name: String,
+    config: ThreadPoolConfig,
+    /// Cores workers are pinned to, and how they are spread over them
+    affinity: Option<(Vec<usize>, BindingStrategy)>,
+    state: Mutex<PoolState>,
+    job_available: Condvar,
+}
+
+/// Worker pool that grows on demand up to `max_threads` and shrinks back to `min_threads`
+pub struct WorkerPool {
+    shared: Arc<PoolShared>,
+}
+
+impl WorkerPool {
+    /// Create a pool and start its minimum number of workers
+    pub fn new(name: &str, config: ThreadPoolConfig) -> Result<Self, HardwareError> {
+        Self::build(name, config, None)
+    }
+
+    /// Create a pool whose workers are pinned to `cores`
+    pub fn with_affinity(
+        name: &str,
+        config: ThreadPoolConfig,
+        cores: Vec<usize>,
+        strategy: BindingStrategy,
+    ) -> Result<Self, HardwareError> {
+        Self::build(name, config, Some((cores, strategy)))
+    }
+
+    fn build(
+        name: &str,
+        config: ThreadPoolConfig,
+        affinity: Option<(Vec<usize>, BindingStrategy)>,
+    ) -> Result<Self, HardwareError> {
+        if config.max_threads == 0 || config.min_threads > config.max_threads {
+            return Err(HardwareError::InitializationError(format!(
+                "invalid thread pool bounds: min_threads {} / max_threads {}",
+                config.min_threads, config.max_threads
+            )));
+        }
+
+        let pool = Self {
+            shared: Arc::new(PoolShared {
+                name: name.to_string(),
+                state: Mutex::new(PoolState {
+                    jobs: VecDeque::new(),
+                    workers: 0,
+                    idle: 0,
+                    shutdown: false,
+                }),
+                job_available: Condvar::new(),
+                config,
+                affinity,
+            }),
+        };
+
+        let mut state = pool.shared.state.lock().unwrap();
+        for _ in 0..pool.shared.config.min_threads {
+            Self::spawn_worker(&pool.shared, &mut state)?;
+        }
+        drop(state);
+
+        Ok(pool)
+    }
+
+    /// Queue a job, starting another worker if none is idle and the pool is below its limit
+    pub fn execute<F>(&self, job: F) -> Result<(), HardwareError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.shutdown {
+            return Err(HardwareError::DeviceUnavailable(format!("worker pool {} is shut down", self.shared.name)));
+        }
+
+        state.jobs.push_back(Box::new(job));
+        if state.idle < state.jobs.len() && state.workers < self.shared.config.max_threads {
+            Self::spawn_worker(&self.shared, &mut state)?;
+        }
+        drop(state);
+
+        self.shared.job_available.notify_one();
+        Ok(())
+    }
+
+    /// Maximum number of workers
+    pub fn max_threads(&self) -> usize {
+        self.shared.config.max_threads
+    }
+
+    /// Number of live workers
+    pub fn worker_count(&self) -> usize {
+        self.shared.state.lock().unwrap().workers
+    }
+
+    /// Number of workers currently running a job
+    pub fn busy_count(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.workers - state.idle
+    }
+
+    /// Stop accepting jobs; workers finish queued jobs and exit
+    pub fn shutdown(&self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.job_available.notify_all();
+    }
+
+    fn spawn_worker(shared: &Arc<PoolShared>, state: &mut PoolState) -> Result<(), HardwareError> {
+        let worker_shared = Arc::clone(shared);
+        let home = shared
+            .affinity
+            .as_ref()
+            .map(|(cores, strategy)| CoreLayout::home(*strategy, cores, state.workers));
+        thread::Builder::new()
+            .name(format!("{}-worker-{}", shared.name, state.workers))
+            .spawn(move || {
+                if let Some(cores) = home {
+                    if let Err(e) = pin_home(&cores) {
+                        warn!("Worker on pool {} runs unpinned: {}", worker_shared.name, e);
+                    }
+                }
+                Self::worker_loop(worker_shared)
+            })
+            .map_err(|e| HardwareError::InitializationError(format!("failed to spawn worker: {}", e)))?;
+        state.workers += 1;
+        Ok(())
+    }
+
+    fn worker_loop(shared: Arc<PoolShared>) {
+        let idle_timeout = Duration::from_secs(shared.config.idle_timeout);
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    warn!("Job panicked on worker pool {}", shared.name);
+                }
+                state = shared.state.lock().unwrap();
+                continue;
+            }
+
+            if state.shutdown {
+                break;
+            }
+
+            // Only surplus workers time out; the core workers wait indefinitely
+            state.idle += 1;
+            let timed_out = if state.workers > shared.config.min_threads {
+                let (guard, wait) = shared.job_available.wait_timeout(state, idle_timeout).unwrap();
+                state = guard;
+                wait.timed_out()
+            } else {
+                state = shared.job_available.wait(state).unwrap();
+                false
+            };
+            state.idle -= 1;
+
+            if timed_out && state.jobs.is_empty() && state.workers > shared.config.min_threads {
+                debug!("Retiring idle worker on pool {}", shared.name);
+                break;
+            }
+        }
+        state.workers -= 1;
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_pool_grows_to_max_and_runs_jobs() {
+        let config = ThreadPoolConfig {
+            min_threads: 1,
+            max_threads: 3,
+            idle_timeout: 60,
+        };
+        let pool = WorkerPool::new("test", config).unwrap();
+        assert_eq!(pool.worker_count(), 1);
+
+        let (tx, rx) = mpsc::channel();
+        for i in 0..8 {
+            let tx = tx.clone();
+            pool.execute(move || tx.send(i).unwrap()).unwrap();
+        }
+        drop(tx);
+
+        let mut results: Vec<i32> = rx.iter().collect();
+        results.sort();
+        assert_eq!(results, (0..8).collect::<Vec<_>>());
+        assert!(pool.worker_count() <= 3);
+    }
+
+    #[test]
+    fn test_idle_workers_shrink_to_min() {
+        let config = ThreadPoolConfig {
+            min_threads: 1,
+            max_threads: 4,
+            idle_timeout: 0,
+        };
+        let pool = WorkerPool::new("test", config).unwrap();
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..4 {
+            let tx = tx.clone();
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(20));
+                tx.send(()).unwrap();
+            })
+            .unwrap();
+        }
+        drop(tx);
+        assert_eq!(rx.iter().count(), 4);
+
+        for _ in 0..100 {
+            if pool.worker_count() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(pool.worker_count(), 1);
+    }
+
+    #[test]
+    fn test_rejects_jobs_after_shutdown() {
+        let pool = WorkerPool::new("test", ThreadPoolConfig::default()).unwrap();
+        pool.shutdown();
+        assert!(pool.execute(|| {}).is_err());
+        assert!(WorkerPool::new("bad", ThreadPoolConfig { min_threads: 4, max_threads: 2, idle_timeout: 1 }).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! Config-driven device discovery
+//!
+//! `application.yaml` decides which device types are enabled and how many of each
+//! may be used; the per-type files under `config/hardware/` describe the devices.
+
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::{HardwareDevice, HealthPolicy};
+use crate::hardware::cpu::{CpuAllocationConfig, CpuConfig, CpuDevice, HostTelemetry};
+use crate::hardware::fpga::{BitstreamCacheConfig, FpgaConfig, FpgaDevice};
+use crate::hardware::gpu::{GpuConfig, GpuDevice, GpuSharingConfig};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+/// Default telemetry cadence when `scheduling.resource_monitoring_interval` is unset
+const DEFAULT_MONITORING_INTERVAL_MS: u64 = 5000;
+
+/// `hardware.cpu` in application.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CpuSettings {
+    pub enabled: bool,
+
+    /// Upper bound on worker threads, applied on top of cpu.yaml
+    pub max_threads: Option<usize>,
+    pub affinity: bool,
+}
+
+impl Default for CpuSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_threads: None,
+            affinity: false,
+        }
+    }
+}
+
+/// `hardware.gpu` and `hardware.fpga` in application.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AcceleratorSettings {
+    pub enabled: bool,
+
+    /// Most devices of this type to register
+    pub max_devices: usize,
+
+    /// Only meaningful for GPUs; the simulator is used when no CUDA device is present
+    pub cuda_enabled: bool,
+}
+
+impl Default for AcceleratorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_devices: 1,
+            cuda_enabled: false,
+        }
+    }
+}
+
+/// `hardware` section of application.yaml
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HardwareSettings {
+    pub cpu: CpuSettings,
+    pub gpu: AcceleratorSettings,
+    pub fpga: AcceleratorSettings,
+    pub health: HealthPolicy,
+}
+
+impl HardwareSettings {
+    /// Parse the `hardware` section of an application config
+    pub fn from_yaml(config: &serde_yaml::Value) -> Result<Self, HardwareError> {
+        let settings: Self = match config.get("hardware") {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| HardwareError::InvalidConfiguration(format!("hardware: {}", e)))?,
+            None => Self::default(),
+        };
+        settings.health.validate()?;
+        Ok(settings)
+    }
+}
+
+/// Builds the devices described by the configuration
+pub struct DeviceDiscovery {
+    settings: HardwareSettings,
+    config_dir: PathBuf,
+    monitoring_interval: Duration,
+}
+
+impl DeviceDiscovery {
+    /// Read settings from an application config whose per-type files live in `config_dir`
+    pub fn new(config: &serde_yaml::Value, config_dir: impl Into<PathBuf>) -> Result<Self, HardwareError> {
+        let monitoring_interval = config["scheduling"]["resource_monitoring_interval"]
+            .as_u64()
+            .unwrap_or(DEFAULT_MONITORING_INTERVAL_MS);
+        Ok(Self {
+            settings: HardwareSettings::from_yaml(config)?,
+            config_dir: config_dir.into(),
+            monitoring_interval: Duration::from_millis(monitoring_interval.max(1)),
+        })
+    }
+
+    /// Parsed `hardware` settings
+    pub fn settings(&self) -> &HardwareSettings {
+        &self.settings
+    }
+
+    /// Validate every enabled device type, then create and initialize the devices
+    ///
+    /// Nothing is initialized unless the whole configuration is valid.
+    pub fn discover(&self) -> Result<Vec<Arc<dyn HardwareDevice>>, HardwareError> {
+        let cpu = self.settings.cpu.enabled.then(|| self.cpu_config()).transpose()?;
+        let gpu = self.settings.gpu.enabled.then(|| self.gpu_config()).transpose()?;
+        let fpga = self.settings.fpga.enabled.then(|| self.fpga_config()).transpose()?;
+
+        let mut devices: Vec<Arc<dyn HardwareDevice>> = Vec::new();
+        if let Some((config, allocation)) = cpu {
+            let device = CpuDevice::with_config("cpu-0".to_string(), "Host CPU".to_string(), config)
+                .with_allocation(allocation)
+                .with_telemetry(Arc::new(HostTelemetry::new()), self.monitoring_interval);
+            device.initialize_cpu()?;
+            devices.push(Arc::new(device));
+        }
+        if let Some((config, sharing)) = gpu {
+            if self.settings.gpu.cuda_enabled {
+                info!("No CUDA runtime available, using the simulated GPU backend");
+            }
+            for index in 0..config.count {
+                let device = GpuDevice::with_config(
+                    format!("gpu-{}", index),
+                    format!("Simulated GPU {}", index),
+                    config.clone(),
+                )
+                .with_sharing(sharing.clone());
+                device.initialize_gpu()?;
+                devices.push(Arc::new(device));
+            }
+        }
+        if let Some((config, cache)) = fpga {
+            for index in 0..config.count {
+                let device = FpgaDevice::with_config(format!("fpga-{}", index), config.clone(), cache.clone());
+                device.initialize_fpga()?;
+                devices.push(Arc::new(device));
+            }
+        }
+
+        info!("Discovered {} hardware devices", devices.len());
+        Ok(devices)
+    }
+
+    fn path(&self, relative: &str) -> PathBuf {
+        self.config_dir.join(relative)
+    }
+
+    fn cpu_config(&self) -> Result<(CpuConfig, CpuAllocationConfig), HardwareError> {
+        let mut config = load_or_default(&self.path("hardware/cpu.yaml"), |path| CpuConfig::load(path))?;
+        config.validate()?;
+
+        if let Some(max_threads) = self.settings.cpu.max_threads {
+            if max_threads == 0 {
+                return Err(HardwareError::InvalidConfiguration(
+                    "hardware.cpu.max_threads must be at least 1".to_string(),
+                ));
+            }
+            config.thread_pool.max_threads = config.thread_pool.max_threads.min(max_threads);
+            config.thread_pool.min_threads = config.thread_pool.min_threads.min(max_threads);
+        }
+
+        let allocation = load_or_default(
+            &self.path("scheduling/resource_allocation.yaml"),
+            |path| CpuAllocationConfig::load(path),
+        )?;
+        Ok((config, allocation))
+    }
+
+    fn gpu_config(&self) -> Result<(GpuConfig, GpuSharingConfig), HardwareError> {
+        let path = self.path("hardware/gpu.yaml");
+        let config = load_or_default(&path, |path| GpuConfig::load(path))?;
+        config.validate()?;
+        check_declared(&path, "gpu", &self.settings.gpu, config.enabled, config.count)?;
+
+        let sharing = load_or_default(
+            &self.path("scheduling/resource_allocation.yaml"),
+            |path| GpuSharingConfig::load(path),
+        )?;
+        sharing.validate()?;
+        Ok((config, sharing))
+    }
+
+    fn fpga_config(&self) -> Result<(FpgaConfig, BitstreamCacheConfig), HardwareError> {
+        let path = self.path("hardware/fpga.yaml");
+        let config = load_or_default(&path, |path| FpgaConfig::load(path))?;
+        config.validate()?;
+        // A missing fpga.yaml defaults to disabled, so only an explicit file can contradict
+        let declared_enabled = config.enabled || !path.exists();
+        check_declared(&path, "fpga", &self.settings.fpga, declared_enabled, config.count)?;
+
+        let cache = load_or_default(
+            &self.path("scheduling/resource_allocation.yaml"),
+            |path| BitstreamCacheConfig::load(path),
+        )?;
+        Ok((config, cache))
+    }
+}
+
+fn load_or_default<T: Default>(
+    path: &Path,
+    load: impl FnOnce(&Path) -> Result<T, HardwareError>,
+) -> Result<T, HardwareError> {
+    if path.exists() {
+        load(path)
+    } else {
+        Ok(T::default())
+    }
+}
+
+/// Check a per-type file against the matching `hardware.<kind>` settings
+fn check_declared(
+    path: &Path,
+    kind: &str,
+    settings: &AcceleratorSettings,
+    declared_enabled: bool,
+    declared_count: usize,
+) -> Result<(), HardwareError> {
+    let mut problems = Vec::new();
+    if !declared_enabled {
+        problems.push(format!(
+            "hardware.{}.enabled is true but {} disables it",
+            kind,
+            path.display()
+        ));
+    }
+    if settings.max_devices == 0 {
+        problems.push(format!("hardware.{}.max_devices must be at least 1", kind));
+    }
+    if declared_count > settings.max_devices {
+        problems.push(format!(
+            "{} declares {} devices but hardware.{}.max_devices is {}",
+            path.display(),
+            declared_count,
+            kind,
+            settings.max_devices
+        ));
+    }
+    HardwareError::from_config_problems(kind, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Config directory with the given per-type files
+    fn config_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hetero-discovery-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (relative, content) in files {
+            let path = dir.join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn app(yaml: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_repository_config() {
+        let application = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config/application.yaml"));
+        let discovery = DeviceDiscovery::new(&app(&application.unwrap()), concat!(env!("CARGO_MANIFEST_DIR"), "/config"))
+            .unwrap();
+        let devices = discovery.discover().unwrap();
+        let ids: Vec<&str> = devices.iter().map(|device| device.id()).collect();
+        assert_eq!(ids, vec!["cpu-0", "gpu-0", "gpu-1"]);
+        assert_eq!(devices[1].total_memory(), 16384 * 1024 * 1024);
+        assert_eq!(devices[1].get_metrics().clock_speed, 1500);
+        assert_eq!(devices[0].properties()["threads"], "8");
+    }
+
+    #[test]
+    fn test_enabled_types_and_declared_hardware() {
+        let dir = config_dir(
+            "enabled",
+            &[
+                ("hardware/gpu.yaml", "gpu:\n  count: 1\n  memory:\n    total: 8192\n    max_allocation: 1024\n"),
+                ("hardware/fpga.yaml", "fpga:\n  enabled: true\n  count: 2\n  memory:\n    total: 4096\n"),
+            ],
+        );
+        let config = app("hardware:\n  cpu:\n    enabled: false\n  gpu:\n    enabled: true\n  fpga:\n    enabled: true\n    max_devices: 2\n");
+        let devices = DeviceDiscovery::new(&config, &dir).unwrap().discover().unwrap();
+        let ids: Vec<&str> = devices.iter().map(|device| device.id()).collect();
+        assert_eq!(ids, vec!["gpu-0", "fpga-0", "fpga-1"]);
+        assert_eq!(devices[0].total_memory(), 8192 * 1024 * 1024);
+        assert_eq!(devices[2].total_memory(), 4096 * 1024 * 1024);
+        assert!(devices.iter().all(|device| device.is_available()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_contradictions_are_reported() {
+        let dir = config_dir(
+            "contradictions",
+            &[("hardware/gpu.yaml", "gpu:\n  enabled: false\n  count: 3\n")],
+        );
+        let config = app("hardware:\n  cpu:\n    enabled: false\n  gpu:\n    enabled: true\n    max_devices: 2\n");
+        let error = DeviceDiscovery::new(&config, &dir).unwrap().discover().err().unwrap();
+        let message = error.to_string();
+        assert!(matches!(error, HardwareError::InvalidConfiguration(_)));
+        assert!(message.contains("hardware.gpu.enabled is true"));
+        assert!(message.contains("declares 3 devices but hardware.gpu.max_devices is 2"));
+
+        let typo = app("hardware:\n  gpu:\n    enabeld: true\n");
+        assert!(matches!(DeviceDiscovery::new(&typo, &dir), Err(HardwareError::InvalidConfiguration(_))));
+
+        let bad_type = app("hardware:\n  gpu:\n    max_devices: two\n");
+        assert!(DeviceDiscovery::new(&bad_type, &dir).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,372 @@
+// FPGA bitstream format, registry and cache
+//
+// Bitstream files (`*.bit`) start with a little-endian header:
+// magic `HCBS`, version `u16`, kernel name length `u16`, kernel name (UTF-8),
+// logic elements required `u32`, payload length `u64`, CRC-32 of the payload `u32`.
+// The configuration payload follows the header.
+
+use crate::errors::HardwareError;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// File magic of a bitstream
+pub const BITSTREAM_MAGIC: [u8; 4] = *b"HCBS";
+
+/// Supported header version
+pub const BITSTREAM_VERSION: u16 = 1;
+
+/// File extension of bitstreams picked up by `BitstreamRegistry::scan`
+pub const BITSTREAM_EXTENSION: &str = "bit";
+
+/// Size of the header before the kernel name
+const PREFIX_LEN: usize = 8;
+
+/// Size of the header after the kernel name
+const SUFFIX_LEN: usize = 16;
+
+/// Parsed bitstream header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitstreamHeader {
+    pub version: u16,
+
+    /// Operation implemented by the bitstream
+    pub kernel: String,
+
+    /// Logic elements the design occupies
+    pub logic_elements: u32,
+    pub payload_len: u64,
+    pub checksum: u32,
+}
+
+impl BitstreamHeader {
+    /// Encoded size of the header
+    pub fn encoded_len(&self) -> usize {
+        PREFIX_LEN + self.kernel.len() + SUFFIX_LEN
+    }
+
+    /// Parse and validate a header from the start of `bytes`
+    pub fn parse(bytes: &[u8]) -> Result<Self, HardwareError> {
+        let invalid = |msg: &str| HardwareError::InvalidBitstream(msg.to_string());
+        if bytes.len() < PREFIX_LEN {
+            return Err(invalid("truncated header"));
+        }
+        if bytes[0..4] != BITSTREAM_MAGIC {
+            return Err(invalid("bad magic"));
+        }
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != BITSTREAM_VERSION {
+            return Err(HardwareError::InvalidBitstream(format!("unsupported version {}", version)));
+        }
+
+        let kernel_len = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        let suffix = bytes
+            .get(PREFIX_LEN + kernel_len..PREFIX_LEN + kernel_len + SUFFIX_LEN)
+            .ok_or_else(|| invalid("truncated header"))?;
+        let kernel = std::str::from_utf8(&bytes[PREFIX_LEN..PREFIX_LEN + kernel_len])
+            .map_err(|_| invalid("kernel name is not UTF-8"))?;
+        if kernel.is_empty() {
+            return Err(invalid("empty kernel name"));
+        }
+
+        Ok(Self {
+            version,
+            kernel: kernel.to_string(),
+            logic_elements: u32::from_le_bytes(suffix[0..4].try_into().unwrap()),
+            payload_len: u64::from_le_bytes(suffix[4..12].try_into().unwrap()),
+            checksum: u32::from_le_bytes(suffix[12..16].try_into().unwrap()),
+        })
+    }
+
+    /// Read and validate the header of a bitstream file without reading its payload
+    pub fn read(path: &Path) -> Result<Self, HardwareError> {
+        let io_error = |e: std::io::Error| HardwareError::InvalidBitstream(format!("{}: {}", path.display(), e));
+        let mut file = File::open(path).map_err(io_error)?;
+        let mut header = vec![0; PREFIX_LEN];
+        file.read_exact(&mut header).map_err(io_error)?;
+        let kernel_len = u16::from_le_bytes([header[6], header[7]]) as usize;
+        header.resize(PREFIX_LEN + kernel_len + SUFFIX_LEN, 0);
+        file.read_exact(&mut header[PREFIX_LEN..]).map_err(io_error)?;
+        Self::parse(&header)
+    }
+}
+
+/// Validated bitstream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitstream {
+    pub id: String,
+    pub header: BitstreamHeader,
+    pub payload: Vec<u8>,
+}
+
+impl Bitstream {
+    /// Encode a bitstream file for `kernel`
+    pub fn encode(kernel: &str, logic_elements: u32, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PREFIX_LEN + kernel.len() + SUFFIX_LEN + payload.len());
+        bytes.extend_from_slice(&BITSTREAM_MAGIC);
+        bytes.extend_from_slice(&BITSTREAM_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(kernel.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(kernel.as_bytes());
+        bytes.extend_from_slice(&logic_elements.to_le_bytes());
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// Parse a whole bitstream, checking its length and checksum
+    pub fn decode(id: &str, bytes: &[u8]) -> Result<Self, HardwareError> {
+        let header = BitstreamHeader::parse(bytes)?;
+        let payload = &bytes[header.encoded_len()..];
+        if payload.len() as u64 != header.payload_len {
+            return Err(HardwareError::InvalidBitstream(format!(
+                "{}: payload is {} bytes, header says {}",
+                id,
+                payload.len(),
+                header.payload_len
+            )));
+        }
+        let checksum = crc32fast::hash(payload);
+        if checksum != header.checksum {
+            return Err(HardwareError::InvalidBitstream(format!(
+                "{}: checksum mismatch (expected {:08x}, got {:08x})",
+                id, header.checksum, checksum
+            )));
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            payload: payload.to_vec(),
+            header,
+        })
+    }
+
+    /// Size of the configuration payload in bytes
+    pub fn size(&self) -> u64 {
+        self.payload.len() as u64
+    }
+}
+
+/// Registered bitstream file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitstreamInfo {
+    /// Registry id (the file stem)
+    pub id: String,
+    pub path: PathBuf,
+    pub kernel: String,
+    pub logic_elements: u32,
+
+    /// Payload size in bytes
+    pub size: u64,
+}
+
+/// Bitstream cache counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub cached: usize,
+}
+
+/// Known bitstreams plus an LRU cache of loaded ones
+pub struct BitstreamRegistry {
+    entries: BTreeMap<String, BitstreamInfo>,
+
+    /// Least recently used first
+    cache: VecDeque<Arc<Bitstream>>,
+    cache_size: usize,
+    stats: CacheStats,
+}
+
+impl BitstreamRegistry {
+    /// Create an empty registry caching up to `cache_size` bitstreams
+    pub fn new(cache_size: usize) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            cache: VecDeque::new(),
+            cache_size,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Register every `*.bit` file in `dir`, skipping invalid ones; returns the number registered
+    pub fn scan(&mut self, dir: &Path) -> usize {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            debug!("Bitstream directory {} is not readable", dir.display());
+            return 0;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == BITSTREAM_EXTENSION))
+            .collect();
+        paths.sort();
+
+        paths
+            .iter()
+            .filter(|path| match self.register(path) {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("Skipping bitstream {}: {}", path.display(), e);
+                    false
+                }
+            })
+            .count()
+    }
+
+    /// Register one bitstream file after validating its header
+    pub fn register(&mut self, path: &Path) -> Result<BitstreamInfo, HardwareError> {
+        let id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| HardwareError::InvalidBitstream(format!("{}: invalid file name", path.display())))?
+            .to_string();
+        let header = BitstreamHeader::read(path)?;
+        let info = BitstreamInfo {
+            id: id.clone(),
+            path: path.to_path_buf(),
+            kernel: header.kernel,
+            logic_elements: header.logic_elements,
+            size: header.payload_len,
+        };
+
+        // Re-registering replaces any stale cached copy
+        self.cache.retain(|bitstream| bitstream.id != id);
+        self.entries.insert(id, info.clone());
+        Ok(info)
+    }
+
+    /// Look up a bitstream by id
+    pub fn get(&self, id: &str) -> Option<&BitstreamInfo> {
+        self.entries.get(id)
+    }
+
+    /// Resolve a task's declared kernel: a bitstream id, or else the first bitstream implementing that kernel
+    pub fn resolve(&self, kernel: &str) -> Option<&BitstreamInfo> {
+        self.entries
+            .get(kernel)
+            .or_else(|| self.entries.values().find(|info| info.kernel == kernel))
+    }
+
+    /// All registered bitstreams ordered by id
+    pub fn entries(&self) -> impl Iterator<Item = &BitstreamInfo> {
+        self.entries.values()
+    }
+
+    /// Return a bitstream from the cache, reading and validating it on a miss
+    pub fn load(&mut self, id: &str) -> Result<Arc<Bitstream>, HardwareError> {
+        if let Some(position) = self.cache.iter().position(|bitstream| bitstream.id == id) {
+            self.stats.hits += 1;
+            let bitstream = self.cache.remove(position).expect("position is in range");
+            self.cache.push_back(Arc::clone(&bitstream));
+            return Ok(bitstream);
+        }
+
+        self.stats.misses += 1;
+        let info = self
+            .entries
+            .get(id)
+            .ok_or_else(|| HardwareError::InvalidBitstream(format!("unknown bitstream {}", id)))?;
+        let bytes = std::fs::read(&info.path)
+            .map_err(|e| HardwareError::InvalidBitstream(format!("{}: {}", info.path.display(), e)))?;
+        let bitstream = Arc::new(Bitstream::decode(id, &bytes)?);
+        if bitstream.header.kernel != info.kernel {
+            return Err(HardwareError::InvalidBitstream(format!("{} changed on disk since registration", id)));
+        }
+
+        if self.cache_size > 0 {
+            if self.cache.len() >= self.cache_size {
+                if let Some(evicted) = self.cache.pop_front() {
+                    debug!("Evicting bitstream {} from cache", evicted.id);
+                    self.stats.evictions += 1;
+                }
+            }
+            self.cache.push_back(Arc::clone(&bitstream));
+        }
+        Ok(bitstream)
+    }
+
+    /// Cache counters
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            cached: self.cache.len(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hetero-bitstreams-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_header_and_checksum_validation() {
+        let bytes = Bitstream::encode("gemm", 256, &[7; 64]);
+        let bitstream = Bitstream::decode("gemm_v1", &bytes).unwrap();
+        assert_eq!(bitstream.header.kernel, "gemm");
+        assert_eq!(bitstream.header.logic_elements, 256);
+        assert_eq!(bitstream.size(), 64);
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(matches!(Bitstream::decode("x", &corrupted), Err(HardwareError::InvalidBitstream(_))));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(Bitstream::decode("x", &bad_magic).is_err());
+        assert!(Bitstream::decode("x", &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_scan_skips_invalid_files() {
+        let dir = temp_dir("scan");
+        std::fs::write(dir.join("gemm_v1.bit"), Bitstream::encode("gemm", 256, &[1; 32])).unwrap();
+        std::fs::write(dir.join("conv.bit"), Bitstream::encode("conv2d", 512, &[2; 32])).unwrap();
+        std::fs::write(dir.join("junk.bit"), b"not a bitstream").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+
+        let mut registry = BitstreamRegistry::new(4);
+        assert_eq!(registry.scan(&dir), 2);
+        assert_eq!(registry.resolve("gemm").unwrap().id, "gemm_v1");
+        assert_eq!(registry.resolve("conv").unwrap().kernel, "conv2d");
+        assert!(registry.resolve("fft").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_lru_cache() {
+        let dir = temp_dir("lru");
+        let mut registry = BitstreamRegistry::new(2);
+        for name in ["a", "b", "c"] {
+            let path = dir.join(format!("{}.bit", name));
+            std::fs::write(&path, Bitstream::encode("add", 1, name.as_bytes())).unwrap();
+            registry.register(&path).unwrap();
+        }
+
+        registry.load("a").unwrap();
+        registry.load("b").unwrap();
+        registry.load("a").unwrap();
+        // "b" is least recently used and gets evicted
+        registry.load("c").unwrap();
+        registry.load("a").unwrap();
+        registry.load("b").unwrap();
+
+        let stats = registry.cache_stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 4);
+        assert_eq!(stats.evictions, 2);
+        assert_eq!(stats.cached, 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
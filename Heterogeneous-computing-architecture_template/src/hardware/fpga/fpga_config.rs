@@ -0,0 +1,275 @@
+// FPGA device configuration (`config/hardware/fpga.yaml`)
+
+use crate::errors::HardwareError;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Board description
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FpgaBoardConfig {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub architecture: String,
+}
+
+impl Default for FpgaBoardConfig {
+    fn default() -> Self {
+        Self {
+            kind: "Xilinx_Versal".to_string(),
+            name: "VCK5000".to_string(),
+            architecture: "Versal".to_string(),
+        }
+    }
+}
+
+/// On-board memory settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FpgaMemoryConfig {
+    /// Total memory in MB
+    pub total: u64,
+    pub ddr_type: String,
+
+    /// Memory bandwidth in GB/s
+    pub bandwidth: u64,
+}
+
+impl Default for FpgaMemoryConfig {
+    fn default() -> Self {
+        Self {
+            total: 8192,
+            ddr_type: "DDR4".to_string(),
+            bandwidth: 64,
+        }
+    }
+}
+
+/// Processing element fabric
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FpgaPeConfig {
+    /// Number of processing elements
+    pub count: u64,
+
+    /// PE width in bits
+    pub width: u64,
+
+    /// Clock frequency in MHz
+    pub clock_frequency: u64,
+}
+
+impl Default for FpgaPeConfig {
+    fn default() -> Self {
+        Self {
+            count: 1024,
+            width: 32,
+            clock_frequency: 300,
+        }
+    }
+}
+
+/// Bitstream programming settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FpgaProgrammingConfig {
+    pub interface: String,
+
+    /// Longest acceptable reconfiguration in seconds
+    pub time_limit: u64,
+
+    /// Directory scanned for bitstreams
+    pub bitstream_path: String,
+
+    /// Number of partial reconfiguration regions
+    pub regions: usize,
+
+    /// Configuration port bandwidth in MB/s
+    pub configuration_bandwidth: u64,
+}
+
+impl Default for FpgaProgrammingConfig {
+    fn default() -> Self {
+        Self {
+            interface: "JTAG".to_string(),
+            time_limit: 300,
+            bitstream_path: "/opt/fpga/bitstreams".to_string(),
+            regions: 4,
+            configuration_bandwidth: 400,
+        }
+    }
+}
+
+/// Power settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FpgaPowerConfig {
+    /// Draw in watts while running work
+    pub consumption: u64,
+
+    /// Draw in watts with no work running
+    pub idle: u64,
+    pub mode: String,
+}
+
+impl Default for FpgaPowerConfig {
+    fn default() -> Self {
+        Self {
+            consumption: 150,
+            idle: 20,
+            mode: "dynamic".to_string(),
+        }
+    }
+}
+
+/// FPGA device configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FpgaConfig {
+    pub enabled: bool,
+
+    /// Number of FPGAs to use
+    pub count: usize,
+    pub devices: FpgaBoardConfig,
+    pub memory: FpgaMemoryConfig,
+    pub pe: FpgaPeConfig,
+    pub programming: FpgaProgrammingConfig,
+    pub power: FpgaPowerConfig,
+}
+
+impl Default for FpgaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            count: 1,
+            devices: FpgaBoardConfig::default(),
+            memory: FpgaMemoryConfig::default(),
+            pe: FpgaPeConfig::default(),
+            programming: FpgaProgrammingConfig::default(),
+            power: FpgaPowerConfig::default(),
+        }
+    }
+}
+
+impl FpgaConfig {
+    /// Parse the `fpga` section of an fpga.yaml document
+    pub fn from_yaml(document: &serde_yaml::Value) -> Result<Self, HardwareError> {
+        match document.get("fpga") {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| HardwareError::InitializationError(format!("invalid fpga config: {}", e))),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load an fpga.yaml file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HardwareError> {
+        Self::from_yaml(&read_yaml(path.as_ref())?)
+    }
+
+    /// Check for missing or contradictory values
+    pub fn validate(&self) -> Result<(), HardwareError> {
+        let mut problems = Vec::new();
+        if self.count == 0 {
+            problems.push("count must be at least 1".to_string());
+        }
+        if self.memory.total == 0 || self.memory.bandwidth == 0 {
+            problems.push("memory.total and memory.bandwidth must be positive".to_string());
+        }
+        if self.pe.count == 0 || self.pe.clock_frequency == 0 {
+            problems.push("pe.count and pe.clock_frequency must be positive".to_string());
+        }
+        if self.programming.regions == 0 || self.programming.regions as u64 > self.pe.count {
+            problems.push(format!(
+                "programming.regions ({}) must be between 1 and pe.count ({})",
+                self.programming.regions, self.pe.count
+            ));
+        }
+        if self.programming.configuration_bandwidth == 0 || self.programming.time_limit == 0 {
+            problems.push("programming.configuration_bandwidth and programming.time_limit must be positive".to_string());
+        }
+        if self.power.idle > self.power.consumption {
+            problems.push(format!(
+                "power.idle ({} W) exceeds power.consumption ({} W)",
+                self.power.idle, self.power.consumption
+            ));
+        }
+        HardwareError::from_config_problems("fpga", problems)
+    }
+}
+
+/// When bitstreams are read into the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BitstreamPolicy {
+    /// Load every registered bitstream when the device initializes
+    Preloaded,
+    /// Load bitstreams the first time a task needs them
+    Dynamic,
+}
+
+/// Bitstream cache settings (`allocation.fpga.bitstream` in resource_allocation.yaml)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BitstreamCacheConfig {
+    pub policy: BitstreamPolicy,
+
+    /// Number of bitstreams kept in memory
+    pub cache_size: usize,
+}
+
+impl Default for BitstreamCacheConfig {
+    fn default() -> Self {
+        Self {
+            policy: BitstreamPolicy::Dynamic,
+            cache_size: 100,
+        }
+    }
+}
+
+impl BitstreamCacheConfig {
+    /// Parse the `allocation.fpga.bitstream` section of a resource_allocation.yaml document
+    pub fn from_yaml(document: &serde_yaml::Value) -> Result<Self, HardwareError> {
+        match document.get("allocation").and_then(|a| a.get("fpga")).and_then(|f| f.get("bitstream")) {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| HardwareError::InitializationError(format!("invalid bitstream config: {}", e))),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load a resource_allocation.yaml file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HardwareError> {
+        Self::from_yaml(&read_yaml(path.as_ref())?)
+    }
+}
+
+fn read_yaml(path: &Path) -> Result<serde_yaml::Value, HardwareError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| HardwareError::InitializationError(format!("cannot read {}: {}", path.display(), e)))?;
+    serde_yaml::from_str(&content)
+        .map_err(|e| HardwareError::InitializationError(format!("cannot parse {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_repository_fpga_configs() {
+        let config = FpgaConfig::load(concat!(env!("CARGO_MANIFEST_DIR"), "/config/hardware/fpga.yaml")).unwrap();
+        assert_eq!(config.devices.name, "VCK5000");
+        assert_eq!(config.pe.count, 1024);
+        assert_eq!(config.programming.time_limit, 300);
+        assert_eq!(config.programming.regions, 4);
+        assert_eq!((config.power.consumption, config.power.idle), (150, 20));
+        assert!(config.validate().is_ok());
+
+        let cache = BitstreamCacheConfig::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/config/scheduling/resource_allocation.yaml"
+        ))
+        .unwrap();
+        assert_eq!(cache.policy, BitstreamPolicy::Preloaded);
+        assert_eq!(cache.cache_size, 100);
+    }
+}
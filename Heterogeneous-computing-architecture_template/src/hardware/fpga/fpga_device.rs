@@ -1,24 +1,257 @@
 // FPGA device implementation
+//
+// The fabric is split into partial reconfiguration regions. A task runs in a
+// region programmed with the bitstream for its kernel; programming a region is
+// charged at `programming.configuration_bandwidth`. Results are computed on the
+// host and kernel time is modelled from the PE array and memory bandwidth.
 
 use async_trait::async_trait;
 use crate::errors::HardwareError;
-use crate::hardware::abstract::{HardwareDevice, Task, TaskResult};
+use crate::hardware::abstract_device::{
+    DeviceCapabilities, DeviceStatus, HardwareDevice, HardwareMetrics, HardwareType, Kernel, Operation, PowerModel,
+    Task, TaskResult, Tensor,
+};
+use crate::hardware::fpga::bitstream::{BitstreamInfo, BitstreamRegistry, CacheStats};
+use crate::hardware::fpga::fpga_config::{BitstreamCacheConfig, BitstreamPolicy, FpgaConfig};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::debug;
+
+/// State of one reconfigurable region
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionInfo {
+    pub index: usize,
+
+    /// Logic elements available to a bitstream in this region
+    pub logic_elements: u64,
+
+    /// Bitstream currently programmed
+    pub bitstream: Option<String>,
+
+    /// Whether a task is running in the region
+    pub busy: bool,
+}
+
+#[derive(Default)]
+struct RegionState {
+    bitstream: Option<String>,
+    busy: bool,
+    last_used: u64,
+}
 
 pub struct FpgaDevice {
     id: String,
+    name: String,
+    config: FpgaConfig,
+    cache_config: BitstreamCacheConfig,
     memory: u64, // in bytes
     logic_elements: u64,
-    available: bool,
+    available: AtomicBool,
+    registry: Mutex<BitstreamRegistry>,
+    regions: Mutex<Vec<RegionState>>,
+    clock: AtomicU64,
+    reconfigurations: AtomicU64,
 }
 
 impl FpgaDevice {
     pub fn new(id: String, memory: u64, logic_elements: u64) -> Self {
+        let mut device = Self::with_config(id, FpgaConfig::default(), BitstreamCacheConfig::default());
+        device.memory = memory;
+        device.logic_elements = logic_elements;
+        device
+    }
+
+    /// Create an FPGA device from fpga.yaml and the bitstream cache settings
+    pub fn with_config(id: String, config: FpgaConfig, cache_config: BitstreamCacheConfig) -> Self {
+        let regions = (0..config.programming.regions.max(1)).map(|_| RegionState::default()).collect();
         Self {
+            name: format!("FPGA {}", id),
             id,
-            memory,
-            logic_elements,
-            available: true,
+            memory: config.memory.total * 1024 * 1024,
+            logic_elements: config.pe.count,
+            available: AtomicBool::new(false),
+            registry: Mutex::new(BitstreamRegistry::new(cache_config.cache_size)),
+            regions: Mutex::new(regions),
+            clock: AtomicU64::new(0),
+            reconfigurations: AtomicU64::new(0),
+            config,
+            cache_config,
+        }
+    }
+
+    /// Initialize FPGA device: register bitstreams and apply the cache policy
+    pub fn initialize_fpga(&self) -> Result<(), HardwareError> {
+        debug!("Initializing FPGA device: {}", self.id);
+        let found = self
+            .registry
+            .lock()
+            .unwrap()
+            .scan(Path::new(&self.config.programming.bitstream_path));
+        debug!("Registered {} bitstreams for {}", found, self.id);
+
+        if self.cache_config.policy == BitstreamPolicy::Preloaded {
+            self.preload()?;
+        }
+        self.available.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Register a bitstream file outside the configured bitstream directory
+    pub fn register_bitstream(&self, path: &Path) -> Result<BitstreamInfo, HardwareError> {
+        self.registry.lock().unwrap().register(path)
+    }
+
+    /// Registered bitstreams
+    pub fn bitstreams(&self) -> Vec<BitstreamInfo> {
+        self.registry.lock().unwrap().entries().cloned().collect()
+    }
+
+    /// Bitstream cache counters
+    pub fn cache_stats(&self) -> CacheStats {
+        self.registry.lock().unwrap().cache_stats()
+    }
+
+    /// Number of region reconfigurations performed
+    pub fn reconfigurations(&self) -> u64 {
+        self.reconfigurations.load(Ordering::Relaxed)
+    }
+
+    /// Current state of every reconfigurable region
+    pub fn regions(&self) -> Vec<RegionInfo> {
+        let capacity = self.region_capacity();
+        self.regions
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, region)| RegionInfo {
+                index,
+                logic_elements: capacity,
+                bitstream: region.bitstream.clone(),
+                busy: region.busy,
+            })
+            .collect()
+    }
+
+    /// Modelled time to program a region with a bitstream
+    pub fn reconfiguration_time(&self, bitstream: &BitstreamInfo) -> Duration {
+        let bandwidth = self.config.programming.configuration_bandwidth.max(1) * 1024 * 1024;
+        Duration::from_secs_f64(bitstream.size as f64 / bandwidth as f64)
+    }
+
+    fn region_capacity(&self) -> u64 {
+        self.logic_elements / self.config.programming.regions.max(1) as u64
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Program the first regions with registered bitstreams and warm the cache
+    fn preload(&self) -> Result<(), HardwareError> {
+        let fitting: Vec<String> = {
+            let mut registry = self.registry.lock().unwrap();
+            let entries: Vec<BitstreamInfo> = registry.entries().cloned().collect();
+            for info in &entries {
+                registry.load(&info.id)?;
+            }
+            entries
+                .into_iter()
+                .filter(|info| info.logic_elements as u64 <= self.region_capacity())
+                .map(|info| info.id)
+                .collect()
+        };
+
+        let mut regions = self.regions.lock().unwrap();
+        for (region, id) in regions.iter_mut().zip(fitting) {
+            region.bitstream = Some(id);
         }
+        Ok(())
+    }
+
+    /// Reserve a region holding `bitstream`, reprogramming an idle one if needed
+    ///
+    /// Returns the region index and the reconfiguration time charged to the task.
+    fn acquire_region(&self, bitstream: &BitstreamInfo) -> Result<(usize, Duration), HardwareError> {
+        if bitstream.logic_elements as u64 > self.region_capacity() {
+            return Err(HardwareError::InvalidBitstream(format!(
+                "{} needs {} logic elements, regions have {}",
+                bitstream.id,
+                bitstream.logic_elements,
+                self.region_capacity()
+            )));
+        }
+
+        let mut regions = self.regions.lock().unwrap();
+        let loaded = regions
+            .iter()
+            .position(|region| !region.busy && region.bitstream.as_deref() == Some(bitstream.id.as_str()));
+        if let Some(index) = loaded {
+            regions[index].busy = true;
+            regions[index].last_used = self.tick();
+            return Ok((index, Duration::ZERO));
+        }
+
+        // Prefer an empty region, otherwise evict the least recently used idle one
+        let index = regions
+            .iter()
+            .enumerate()
+            .filter(|(_, region)| !region.busy)
+            .min_by_key(|(_, region)| (region.bitstream.is_some(), region.last_used))
+            .map(|(index, _)| index)
+            .ok_or_else(|| {
+                HardwareError::DeviceUnavailable(format!("all reconfigurable regions of {} are busy", self.id))
+            })?;
+
+        let time = self.reconfiguration_time(bitstream);
+        if time > Duration::from_secs(self.config.programming.time_limit) {
+            return Err(HardwareError::ExecutionError(format!(
+                "programming {} would take {:?}, over the {}s limit",
+                bitstream.id, time, self.config.programming.time_limit
+            )));
+        }
+
+        // Validates the checksum before the region is touched
+        self.registry.lock().unwrap().load(&bitstream.id)?;
+
+        debug!("Reconfiguring region {} of {} with {}", index, self.id, bitstream.id);
+        regions[index] = RegionState {
+            bitstream: Some(bitstream.id.clone()),
+            busy: true,
+            last_used: self.tick(),
+        };
+        self.reconfigurations.fetch_add(1, Ordering::Relaxed);
+        Ok((index, time))
+    }
+
+    /// Modelled kernel time on the PE array of one region
+    fn kernel_time(&self, kernel: &Kernel, bitstream: &BitstreamInfo) -> Duration {
+        // Every PE completes one multiply-accumulate per cycle
+        let pes = (bitstream.logic_elements as u64).clamp(1, self.region_capacity().max(1));
+        let flops_per_second = (pes * 2 * self.config.pe.clock_frequency) as f64 * 1e6;
+        let bytes_per_second = (self.config.memory.bandwidth.max(1) * 1_000_000_000) as f64;
+        let compute = kernel.flops() as f64 / flops_per_second;
+        let memory = kernel.bytes_moved() as f64 / bytes_per_second;
+        Duration::from_secs_f64(compute.max(memory))
+    }
+
+    fn power(&self) -> PowerModel {
+        PowerModel::new(self.config.power.idle as f64, self.config.power.consumption as f64)
+    }
+}
+
+/// Marks a region idle again when the task leaves it
+struct RegionGuard<'a> {
+    device: &'a FpgaDevice,
+    index: usize,
+}
+
+impl Drop for RegionGuard<'_> {
+    fn drop(&mut self) {
+        self.device.regions.lock().unwrap()[self.index].busy = false;
     }
 }
 
@@ -28,8 +261,59 @@ impl HardwareDevice for FpgaDevice {
         &self.id
     }
 
-    fn device_type(&self) -> &str {
-        "FPGA"
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn device_type(&self) -> HardwareType {
+        HardwareType::Fpga
+    }
+
+    fn properties(&self) -> HashMap<String, String> {
+        let mut properties = HashMap::new();
+        properties.insert("logic_elements".to_string(), self.logic_elements.to_string());
+        properties.insert("memory".to_string(), self.memory.to_string());
+        properties.insert("board".to_string(), self.config.devices.name.clone());
+        properties.insert("regions".to_string(), self.config.programming.regions.max(1).to_string());
+        properties.insert("bitstreams".to_string(), self.registry.lock().unwrap().entries().count().to_string());
+        properties
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let kernels: BTreeSet<String> = self.registry.lock().unwrap().entries().map(|info| info.kernel.clone()).collect();
+        DeviceCapabilities {
+            compute_units: self.logic_elements,
+            operations: std::iter::once("compute".to_string()).chain(kernels).collect(),
+            max_clock_speed: self.config.pe.clock_frequency,
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        self.available.load(Ordering::Relaxed)
+    }
+
+    async fn initialize(&self) -> Result<(), HardwareError> {
+        self.initialize_fpga()
+    }
+
+    async fn shutdown(&self) -> Result<(), HardwareError> {
+        debug!("Shutting down FPGA device: {}", self.id);
+        self.available.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn get_metrics(&self) -> HardwareMetrics {
+        let regions = self.regions.lock().unwrap();
+        let busy = regions.iter().filter(|region| region.busy).count();
+        HardwareMetrics {
+            utilization: busy as f64 / regions.len() as f64 * 100.0,
+            temperature: 0.0,
+            memory_used: 0,
+            memory_total: self.memory / (1024 * 1024),
+            clock_speed: self.config.pe.clock_frequency,
+            status: if busy > 0 { DeviceStatus::Busy } else { DeviceStatus::Idle },
+            core_reservations: HashMap::new(),
+        }
     }
 
     fn available_memory(&self) -> u64 {
@@ -40,21 +324,172 @@ impl HardwareDevice for FpgaDevice {
         self.memory
     }
 
+    fn power_model(&self) -> Option<PowerModel> {
+        Some(self.power())
+    }
+
+    fn setup_cost(&self, kernel: &str) -> Option<Duration> {
+        if kernel == Operation::Compute.name() {
+            return Some(Duration::ZERO);
+        }
+        let bitstream = self.registry.lock().unwrap().resolve(kernel).cloned()?;
+        let loaded = self
+            .regions
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|region| region.bitstream.as_deref() == Some(bitstream.id.as_str()));
+        Some(if loaded { Duration::ZERO } else { self.reconfiguration_time(&bitstream) })
+    }
+
+    fn estimate_execution(&self, task: &Task) -> Option<Duration> {
+        let operation = Operation::parse(&task.operation).ok()?;
+        if operation == Operation::Compute {
+            return Some(Duration::ZERO);
+        }
+        let wanted = task.kernel.as_deref().unwrap_or(operation.name());
+        let bitstream = self.registry.lock().unwrap().resolve(wanted).cloned()?;
+        let kernel = Kernel::new(operation, Tensor::decode_all(&task.data).ok()?).ok()?;
+        Some(self.setup_cost(wanted)? + self.kernel_time(&kernel, &bitstream))
+    }
+
     async fn execute_task(&self, task: &Task) -> Result<TaskResult, HardwareError> {
-        // Simulate FPGA computation
-        println!("Executing task {} on FPGA", task.id);
-        
-        // In a real implementation, this would perform actual FPGA computation
-        let result = TaskResult {
+        if !self.is_available() {
+            return Err(HardwareError::DeviceUnavailable(format!("FPGA device {} is not initialized", self.id)));
+        }
+
+        debug!("Executing task {} ({}) on FPGA {}", task.id, task.operation, self.id);
+
+        let operation = Operation::parse(&task.operation)?;
+        if operation == Operation::Compute {
+            return Ok(TaskResult {
+                task_id: task.id.clone(),
+                data: task.data.clone(),
+                execution_time: 0,
+                energy: 0.0,
+            });
+        }
+
+        let wanted = task.kernel.as_deref().unwrap_or(operation.name());
+        let bitstream = self
+            .registry
+            .lock()
+            .unwrap()
+            .resolve(wanted)
+            .cloned()
+            .ok_or_else(|| HardwareError::UnsupportedOperation(format!("no bitstream for {} on {}", wanted, self.id)))?;
+        if bitstream.kernel != operation.name() {
+            return Err(HardwareError::ExecutionError(format!(
+                "bitstream {} implements {}, task needs {}",
+                bitstream.id, bitstream.kernel, operation
+            )));
+        }
+
+        let kernel = Kernel::new(operation, Tensor::decode_all(&task.data)?)?;
+        let (index, reconfiguration) = self.acquire_region(&bitstream)?;
+        let _region = RegionGuard { device: self, index };
+
+        let output = kernel.run();
+        let total = reconfiguration + self.kernel_time(&kernel, &bitstream);
+        Ok(TaskResult {
             task_id: task.id.clone(),
-            data: task.data.clone(),
-            execution_time: 75, // Simulated execution time in ms
-        };
-        
-        Ok(result)
+            data: Tensor::encode_all(&[output]),
+            execution_time: total.as_micros().div_ceil(1000) as u64,
+            energy: self.power().energy(total),
+        })
     }
+}
 
-    fn is_available(&self) -> bool {
-        self.available
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::CancelToken;
+    use crate::hardware::fpga::bitstream::Bitstream;
+    use std::path::PathBuf;
+
+    fn bitstream_dir(name: &str, bitstreams: &[(&str, &str, u32, usize)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hetero-fpga-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (id, kernel, logic_elements, size) in bitstreams {
+            let bytes = Bitstream::encode(kernel, *logic_elements, &vec![0xA5; *size]);
+            std::fs::write(dir.join(format!("{}.bit", id)), bytes).unwrap();
+        }
+        dir
     }
-}
\ No newline at end of file
+
+    fn device(dir: &Path, regions: usize, policy: BitstreamPolicy) -> FpgaDevice {
+        let mut config = FpgaConfig::default();
+        config.programming.bitstream_path = dir.to_string_lossy().into_owned();
+        config.programming.regions = regions;
+        config.programming.configuration_bandwidth = 1;
+        let cache = BitstreamCacheConfig { policy, cache_size: 8 };
+        FpgaDevice::with_config("fpga-test".to_string(), config, cache)
+    }
+
+    fn add_task(id: &str, kernel: Option<&str>) -> Task {
+        let a = Tensor::new(vec![4], vec![1.0, 2.0, 3.0, 4.0]).unwrap();
+        Task {
+            id: id.to_string(),
+            data: Tensor::encode_all(&[a.clone(), a]),
+            operation: "add".to_string(),
+            kernel: kernel.map(str::to_string),
+            devices: Vec::new(),
+            cancel: CancelToken::new(),
+            limit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconfiguration_is_charged_once() {
+        let dir = bitstream_dir("reconfigure", &[("add_v1", "add", 64, 512 * 1024)]);
+        let fpga = device(&dir, 2, BitstreamPolicy::Dynamic);
+        fpga.initialize().await.unwrap();
+        assert!(fpga.capabilities().supports("add"));
+        assert_eq!(fpga.setup_cost("add"), Some(Duration::from_millis(500)));
+        assert_eq!(fpga.setup_cost("fft"), None);
+
+        let first = fpga.execute_task(&add_task("t1", None)).await.unwrap();
+        assert_eq!(Tensor::decode_all(&first.data).unwrap()[0].data, vec![2.0, 4.0, 6.0, 8.0]);
+        assert!(first.execution_time >= 500);
+
+        let second = fpga.execute_task(&add_task("t2", Some("add_v1"))).await.unwrap();
+        assert!(second.execution_time < 500);
+        assert_eq!(fpga.reconfigurations(), 1);
+        assert_eq!(fpga.setup_cost("add"), Some(Duration::ZERO));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lru_region_is_reprogrammed() {
+        let dir = bitstream_dir(
+            "regions",
+            &[("add_a", "add", 64, 1024), ("add_b", "add", 64, 1024), ("add_c", "add", 64, 1024)],
+        );
+        let fpga = device(&dir, 2, BitstreamPolicy::Preloaded);
+        fpga.initialize().await.unwrap();
+        let loaded: Vec<_> = fpga.regions().into_iter().map(|region| region.bitstream).collect();
+        assert_eq!(loaded, vec![Some("add_a".to_string()), Some("add_b".to_string())]);
+        assert_eq!(fpga.cache_stats().cached, 3);
+
+        fpga.execute_task(&add_task("t1", Some("add_b"))).await.unwrap();
+        fpga.execute_task(&add_task("t2", Some("add_c"))).await.unwrap();
+        let loaded: Vec<_> = fpga.regions().into_iter().map(|region| region.bitstream).collect();
+        assert_eq!(loaded, vec![Some("add_c".to_string()), Some("add_b".to_string())]);
+        assert_eq!(fpga.reconfigurations(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejects_mismatched_or_oversized_bitstreams() {
+        let dir = bitstream_dir("reject", &[("gemm_v1", "gemm", 64, 16), ("huge_add", "add", 100_000, 16)]);
+        let fpga = device(&dir, 4, BitstreamPolicy::Dynamic);
+        fpga.initialize().await.unwrap();
+
+        let result = fpga.execute_task(&add_task("t1", Some("gemm_v1"))).await;
+        assert!(matches!(result, Err(HardwareError::ExecutionError(_))));
+        let result = fpga.execute_task(&add_task("t2", None)).await;
+        assert!(matches!(result, Err(HardwareError::InvalidBitstream(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,10 @@
 // FPGA module
 
+pub mod bitstream;
+pub mod fpga_config;
 pub mod fpga_device;
 
 // Re-export key types
-pub use fpga_device::FpgaDevice;
\ No newline at end of file
+pub use bitstream::{Bitstream, BitstreamHeader, BitstreamInfo, BitstreamRegistry, CacheStats};
+pub use fpga_config::{BitstreamCacheConfig, BitstreamPolicy, FpgaConfig};
+pub use fpga_device::{FpgaDevice, RegionInfo};
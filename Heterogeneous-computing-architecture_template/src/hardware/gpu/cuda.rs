@@ -27,6 +27,11 @@ impl CudaContext {
         Ok(())
     }
     
+    /// Get the raw context handle
+    pub fn as_raw(&self) -> *mut std::ffi::c_void {
+        self.context_ptr
+    }
+    
     /// Destroy the CUDA context
     pub fn destroy(&mut self) {
         debug!("Destroying CUDA context");
@@ -42,24 +47,6 @@ impl Drop for CudaContext {
     }
 }
 
-/// CUDA memory management utilities
-pub struct CudaMemoryManager {}
-
-impl CudaMemoryManager {
-    /// Allocate GPU memory
-    pub fn allocate_memory(size: usize) -> Result<*mut std::ffi::c_void, Box<dyn std::error::Error>> {
-        debug!("Allocating {} bytes of GPU memory", size);
-        // In a real implementation, this would call CUDA malloc
-        Ok(std::ptr::null_mut())
-    }
-    
-    /// Free GPU memory
-    pub fn free_memory(ptr: *mut std::ffi::c_void) {
-        debug!("Freeing GPU memory at pointer {:?}", ptr);
-        // In a real implementation, this would call CUDA free
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,464 @@
+//! GPU device configuration (`config/hardware/gpu.yaml`)
+
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::SharingPolicy;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Device memory settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuMemoryConfig {
+    /// Total device memory in MB
+    pub total: u64,
+
+    /// Maximum memory allocation per task in MB
+    pub max_allocation: u64,
+
+    /// Memory management strategy
+    pub management: String,
+}
+
+impl Default for GpuMemoryConfig {
+    fn default() -> Self {
+        Self {
+            total: 16384,
+            max_allocation: 4096,
+            management: "dynamic".to_string(),
+        }
+    }
+}
+
+/// Compute capability settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuComputeConfig {
+    pub min_capability: String,
+    pub target_capability: String,
+}
+
+impl Default for GpuComputeConfig {
+    fn default() -> Self {
+        Self {
+            min_capability: "5.0".to_string(),
+            target_capability: "7.5".to_string(),
+        }
+    }
+}
+
+/// Clock settings in MHz
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuClockConfig {
+    /// Graphics (SM) clock
+    pub graphics: u64,
+
+    /// Effective memory clock (transfers per second in millions)
+    pub memory: u64,
+}
+
+impl Default for GpuClockConfig {
+    fn default() -> Self {
+        Self {
+            graphics: 1500,
+            memory: 6000,
+        }
+    }
+}
+
+/// Power management settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuPowerConfig {
+    /// Max power limit in watts, drawn while running work
+    pub limit: u64,
+
+    /// Draw in watts with no work running
+    pub idle: u64,
+    pub mode: String,
+}
+
+impl Default for GpuPowerConfig {
+    fn default() -> Self {
+        Self {
+            limit: 250,
+            idle: 30,
+            mode: "auto".to_string(),
+        }
+    }
+}
+
+/// Performance settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GpuPerformanceConfig {
+    pub clock: GpuClockConfig,
+    pub power: GpuPowerConfig,
+}
+
+/// Driver settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuDriverConfig {
+    pub version: String,
+    pub path: String,
+}
+
+impl Default for GpuDriverConfig {
+    fn default() -> Self {
+        Self {
+            version: "525.60.11".to_string(),
+            path: "/usr/lib/x86_64-linux-gnu/nvidia".to_string(),
+        }
+    }
+}
+
+/// Hardware model used by the simulated backend
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuSimulationConfig {
+    /// Number of streaming multiprocessors
+    pub sm_count: u64,
+
+    /// FP32 cores per streaming multiprocessor
+    pub cores_per_sm: u64,
+
+    /// Memory bus width in bits
+    pub memory_bus_width: u64,
+
+    /// Host to device link bandwidth in MB/s
+    pub host_bandwidth: u64,
+
+    /// Fixed cost of a kernel launch in microseconds
+    pub launch_overhead: u64,
+
+    /// Sleep for the estimated latency so wall-clock timing matches the model
+    pub realtime: bool,
+}
+
+impl Default for GpuSimulationConfig {
+    fn default() -> Self {
+        Self {
+            sm_count: 40,
+            cores_per_sm: 64,
+            memory_bus_width: 256,
+            host_bandwidth: 16000,
+            launch_overhead: 5,
+            realtime: false,
+        }
+    }
+}
+
+/// GPU device configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuConfig {
+    pub enabled: bool,
+
+    /// Number of GPUs to use
+    pub count: usize,
+    pub memory: GpuMemoryConfig,
+    pub compute: GpuComputeConfig,
+    pub performance: GpuPerformanceConfig,
+    pub driver: GpuDriverConfig,
+    pub simulation: GpuSimulationConfig,
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            count: 1,
+            memory: GpuMemoryConfig::default(),
+            compute: GpuComputeConfig::default(),
+            performance: GpuPerformanceConfig::default(),
+            driver: GpuDriverConfig::default(),
+            simulation: GpuSimulationConfig::default(),
+        }
+    }
+}
+
+impl GpuConfig {
+    /// Parse the `gpu` section of a gpu.yaml document
+    pub fn from_yaml(document: &serde_yaml::Value) -> Result<Self, HardwareError> {
+        match document.get("gpu") {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| HardwareError::InitializationError(format!("invalid gpu config: {}", e))),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load a gpu.yaml file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HardwareError> {
+        Self::from_yaml(&read_yaml(path.as_ref())?)
+    }
+
+    /// Check for missing or contradictory values
+    pub fn validate(&self) -> Result<(), HardwareError> {
+        let mut problems = Vec::new();
+        if self.count == 0 {
+            problems.push("count must be at least 1".to_string());
+        }
+        if self.memory.total == 0 || self.memory.max_allocation == 0 {
+            problems.push("memory.total and memory.max_allocation must be positive".to_string());
+        }
+        if self.memory.max_allocation > self.memory.total {
+            problems.push(format!(
+                "memory.max_allocation ({} MB) exceeds memory.total ({} MB)",
+                self.memory.max_allocation, self.memory.total
+            ));
+        }
+        if self.performance.clock.graphics == 0 || self.performance.clock.memory == 0 {
+            problems.push("performance.clock.graphics and performance.clock.memory must be positive".to_string());
+        }
+        if self.performance.power.idle > self.performance.power.limit {
+            problems.push(format!(
+                "performance.power.idle ({} W) exceeds performance.power.limit ({} W)",
+                self.performance.power.idle, self.performance.power.limit
+            ));
+        }
+        let sim = &self.simulation;
+        if sim.sm_count == 0 || sim.cores_per_sm == 0 || sim.memory_bus_width == 0 || sim.host_bandwidth == 0 {
+            problems.push(
+                "simulation.sm_count, cores_per_sm, memory_bus_width and host_bandwidth must be positive".to_string(),
+            );
+        }
+        match (
+            parse_capability(&self.compute.min_capability),
+            parse_capability(&self.compute.target_capability),
+        ) {
+            (Some(min), Some(target)) if target < min => problems.push(format!(
+                "compute.target_capability {} is below compute.min_capability {}",
+                self.compute.target_capability, self.compute.min_capability
+            )),
+            (None, _) | (_, None) => problems.push(format!(
+                "compute capabilities must look like \"7.5\", got {:?} and {:?}",
+                self.compute.min_capability, self.compute.target_capability
+            )),
+            _ => {}
+        }
+        HardwareError::from_config_problems("gpu", problems)
+    }
+}
+
+/// How GPUs are divided between tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpuAllocationAlgorithm {
+    /// Tasks run on a GPU side by side, each capped at its `compute.max_units` share
+    ///
+    /// There is no time slicing; a task's kernels only ever get its share of the SMs.
+    /// `fair_share` is accepted as the old name.
+    #[serde(alias = "fair_share")]
+    Shared,
+    /// Fixed MIG-like slices taken from `partitions`, one task per slice
+    Partitioned,
+    /// One task per GPU
+    Dedicated,
+}
+
+/// How `compute.max_units` is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComputeUnitMode {
+    /// A fraction of the GPU's streaming multiprocessors
+    Percentage,
+    /// A number of streaming multiprocessors
+    Fixed,
+}
+
+/// GPU memory sharing settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuMemoryShareConfig {
+    pub policy: String,
+
+    /// Fraction of GPU memory no task can reserve
+    pub reservation: f64,
+}
+
+impl Default for GpuMemoryShareConfig {
+    fn default() -> Self {
+        Self {
+            policy: "dynamic".to_string(),
+            reservation: 0.0,
+        }
+    }
+}
+
+/// GPU compute sharing settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuComputeShareConfig {
+    pub units: ComputeUnitMode,
+
+    /// Most compute one task may take, read according to `units`
+    pub max_units: f64,
+}
+
+impl Default for GpuComputeShareConfig {
+    fn default() -> Self {
+        Self {
+            units: ComputeUnitMode::Percentage,
+            max_units: 1.0,
+        }
+    }
+}
+
+/// GPU sharing settings (`allocation.gpu` in resource_allocation.yaml)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpuSharingConfig {
+    pub algorithm: GpuAllocationAlgorithm,
+    pub memory: GpuMemoryShareConfig,
+    pub compute: GpuComputeShareConfig,
+
+    /// Slice sizes as fractions of the GPU, for the `partitioned` algorithm
+    pub partitions: Vec<f64>,
+}
+
+impl Default for GpuSharingConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: GpuAllocationAlgorithm::Shared,
+            memory: GpuMemoryShareConfig::default(),
+            compute: GpuComputeShareConfig::default(),
+            partitions: Vec::new(),
+        }
+    }
+}
+
+impl GpuSharingConfig {
+    /// Parse the `allocation.gpu` section of a resource_allocation.yaml document
+    pub fn from_yaml(document: &serde_yaml::Value) -> Result<Self, HardwareError> {
+        match document.get("allocation").and_then(|a| a.get("gpu")) {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .map_err(|e| HardwareError::InitializationError(format!("invalid gpu allocation config: {}", e))),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load a resource_allocation.yaml file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HardwareError> {
+        Self::from_yaml(&read_yaml(path.as_ref())?)
+    }
+
+    /// Check for missing or contradictory values
+    pub fn validate(&self) -> Result<(), HardwareError> {
+        let mut problems = Vec::new();
+        if !(0.0..1.0).contains(&self.memory.reservation) {
+            problems.push(format!("memory.reservation must be in [0, 1), got {}", self.memory.reservation));
+        }
+        match self.compute.units {
+            ComputeUnitMode::Percentage if !(self.compute.max_units > 0.0 && self.compute.max_units <= 1.0) => {
+                problems.push(format!("compute.max_units must be in (0, 1] as a percentage, got {}", self.compute.max_units))
+            }
+            ComputeUnitMode::Fixed if self.compute.max_units < 1.0 => {
+                problems.push(format!("compute.max_units must be at least 1 when fixed, got {}", self.compute.max_units))
+            }
+            _ => {}
+        }
+        if self.algorithm == GpuAllocationAlgorithm::Partitioned && self.partitions.is_empty() {
+            problems.push("the partitioned algorithm needs at least one partition".to_string());
+        }
+        if self.partitions.iter().any(|f| !(*f > 0.0 && *f <= 1.0)) {
+            problems.push(format!("partitions must be fractions in (0, 1], got {:?}", self.partitions));
+        }
+        let total: f64 = self.partitions.iter().sum();
+        if total > 1.0 + 1e-9 {
+            problems.push(format!("partitions add up to {} of the GPU", total));
+        }
+        HardwareError::from_config_problems("allocation.gpu", problems)
+    }
+
+    /// Sharing policy of a GPU with `compute_units` streaming multiprocessors
+    pub fn policy(&self, compute_units: u64) -> SharingPolicy {
+        let max_share = match self.compute.units {
+            ComputeUnitMode::Percentage => self.compute.max_units,
+            ComputeUnitMode::Fixed => self.compute.max_units / compute_units.max(1) as f64,
+        };
+        let (max_share, partitions) = match self.algorithm {
+            GpuAllocationAlgorithm::Shared => (max_share.min(1.0), Vec::new()),
+            GpuAllocationAlgorithm::Partitioned => (1.0, self.partitions.clone()),
+            GpuAllocationAlgorithm::Dedicated => (1.0, vec![1.0]),
+        };
+        SharingPolicy {
+            memory_reservation: self.memory.reservation,
+            max_share,
+            partitions,
+        }
+    }
+}
+
+fn read_yaml(path: &Path) -> Result<serde_yaml::Value, HardwareError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| HardwareError::InitializationError(format!("cannot read {}: {}", path.display(), e)))?;
+    serde_yaml::from_str(&content)
+        .map_err(|e| HardwareError::InitializationError(format!("cannot parse {}: {}", path.display(), e)))
+}
+
+/// Parse a "major.minor" compute capability
+fn parse_capability(value: &str) -> Option<(u32, u32)> {
+    let (major, minor) = value.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_repository_gpu_config() {
+        let config = GpuConfig::load(concat!(env!("CARGO_MANIFEST_DIR"), "/config/hardware/gpu.yaml")).unwrap();
+        assert_eq!(config.count, 2);
+        assert_eq!(config.memory.total, 16384);
+        assert_eq!(config.memory.max_allocation, 4096);
+        assert_eq!(config.performance.clock.graphics, 1500);
+        assert_eq!(config.performance.clock.memory, 6000);
+        assert_eq!(config.performance.power.limit, 250);
+        assert_eq!(config.performance.power.idle, 30);
+        assert_eq!(config.simulation.sm_count, 40);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_contradictions() {
+        let mut config = GpuConfig::default();
+        config.memory.max_allocation = 20000;
+        config.compute.target_capability = "3.0".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("memory.max_allocation (20000 MB) exceeds memory.total (16384 MB)"));
+        assert!(message.contains("target_capability 3.0 is below"));
+    }
+
+    #[test]
+    fn test_repository_sharing_config() {
+        let sharing = GpuSharingConfig::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/config/scheduling/resource_allocation.yaml"
+        ))
+        .unwrap();
+        assert_eq!(sharing.algorithm, GpuAllocationAlgorithm::Shared);
+        assert_eq!(serde_yaml::from_str::<GpuAllocationAlgorithm>("fair_share").unwrap(), GpuAllocationAlgorithm::Shared);
+        assert!(sharing.validate().is_ok());
+        let policy = sharing.policy(40);
+        assert_eq!((policy.memory_reservation, policy.max_share), (0.1, 0.5));
+
+        let fixed = GpuSharingConfig {
+            compute: GpuComputeShareConfig {
+                units: ComputeUnitMode::Fixed,
+                max_units: 10.0,
+            },
+            ..sharing.clone()
+        };
+        assert_eq!(fixed.policy(40).max_share, 0.25);
+
+        let partitioned = GpuSharingConfig {
+            algorithm: GpuAllocationAlgorithm::Partitioned,
+            partitions: vec![0.5, 0.5, 0.25],
+            ..sharing
+        };
+        let message = partitioned.validate().unwrap_err().to_string();
+        assert!(message.contains("partitions add up to 1.25"));
+    }
+}
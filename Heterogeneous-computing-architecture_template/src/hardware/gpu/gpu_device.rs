@@ -1,106 +1,446 @@
 //! GPU device implementation
+//!
+//! Without a CUDA runtime the device is backed by the simulator: results are
+//! computed on the host and latencies come from the roofline model.
 
-use crate::hardware::abstract::{HardwareDevice, HardwareType, HardwareMetrics, DeviceStatus};
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::{
+    DeviceCapabilities, DeviceStatus, HardwareDevice, HardwareMetrics, HardwareType, Kernel, MemoryArena, Operation,
+    PowerModel, SharingPolicy, Task, TaskResult, Tensor,
+};
+use crate::hardware::gpu::gpu_config::{GpuConfig, GpuSharingConfig};
+use crate::hardware::gpu::simulator::{GpuModel, KernelEstimate};
+use async_trait::async_trait;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
-use tracing::debug;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Idle temperature of the simulated device in Celsius
+const IDLE_TEMPERATURE: f64 = 35.0;
+
+/// Temperature added at full utilization in Celsius
+const LOAD_TEMPERATURE_RISE: f64 = 45.0;
 
 /// GPU device structure
 pub struct GpuDevice {
     id: String,
     name: String,
-    properties: HashMap<String, String>,
-    is_initialized: AtomicU64,
-    memory_used: AtomicU64,
-    memory_total: AtomicU64,
-    current_utilization: AtomicU64,
-    temperature: AtomicU64,
-    compute_capability: String,
+    config: GpuConfig,
+    sharing: GpuSharingConfig,
+    model: GpuModel,
+    properties: Mutex<HashMap<String, String>>,
+    is_initialized: AtomicBool,
+    memory: MemoryArena,
+
+    /// Streaming multiprocessors occupied by running kernels
+    busy_sms: AtomicU64,
 }
 
 impl GpuDevice {
-    /// Create a new GPU device
+    /// Create a new GPU device with default settings
     pub fn new(id: String, name: String) -> Self {
+        Self::with_config(id, name, GpuConfig::default())
+    }
+
+    /// Create a new GPU device from gpu.yaml settings
+    pub fn with_config(id: String, name: String, config: GpuConfig) -> Self {
+        let memory = MemoryArena::new(
+            &id,
+            config.memory.total * 1024 * 1024,
+            Some(config.memory.max_allocation * 1024 * 1024),
+        );
         Self {
             id,
             name,
-            properties: HashMap::new(),
-            is_initialized: AtomicU64::new(0),
-            memory_used: AtomicU64::new(0),
-            memory_total: AtomicU64::new(0),
-            current_utilization: AtomicU64::new(0),
-            temperature: AtomicU64::new(0),
-            compute_capability: "7.5".to_string(),
+            model: GpuModel::from_config(&config),
+            config,
+            sharing: GpuSharingConfig::default(),
+            properties: Mutex::new(HashMap::new()),
+            is_initialized: AtomicBool::new(false),
+            memory,
+            busy_sms: AtomicU64::new(0),
         }
     }
-    
+
+    /// Share the GPU between tasks as `allocation.gpu` in resource_allocation.yaml describes
+    pub fn with_sharing(mut self, sharing: GpuSharingConfig) -> Self {
+        self.sharing = sharing;
+        self
+    }
+
+    /// Device memory arena
+    pub fn memory(&self) -> &MemoryArena {
+        &self.memory
+    }
+
+    /// Performance model of the device
+    pub fn model(&self) -> &GpuModel {
+        &self.model
+    }
+
     /// Initialize GPU device
-    pub fn initialize_gpu(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        debug!("Initializing GPU device: {}", self.name);
-        
-        // Simulate GPU initialization
-        self.is_initialized.store(1, Ordering::Relaxed);
-        
-        // Set up properties
-        self.properties.insert("vendor".to_string(), "NVIDIA".to_string());
-        self.properties.insert("model".to_string(), "RTX 3090".to_string());
-        self.properties.insert("memory".to_string(), "24GB".to_string());
-        self.properties.insert("compute_capability".to_string(), self.compute_capability.clone());
-        self.properties.insert("arch".to_string(), "CUDA".to_string());
-        self.properties.insert("driver".to_string(), "525.60.11".to_string());
-        
-        // Set memory values (24GB)
-        self.memory_total.store(24576, Ordering::Relaxed);
-        
+    pub fn initialize_gpu(&self) -> Result<(), HardwareError> {
+        debug!("Initializing simulated GPU device: {}", self.name);
+
+        let mut properties = self.properties.lock().unwrap();
+        properties.insert("vendor".to_string(), "Simulated".to_string());
+        properties.insert("model".to_string(), self.name.clone());
+        properties.insert("memory".to_string(), format!("{}MB", self.config.memory.total));
+        properties.insert("compute_capability".to_string(), self.config.compute.target_capability.clone());
+        properties.insert("arch".to_string(), "simulated".to_string());
+        properties.insert("driver".to_string(), self.config.driver.version.clone());
+        properties.insert("sm_count".to_string(), self.config.simulation.sm_count.to_string());
+        properties.insert("graphics_clock".to_string(), self.config.performance.clock.graphics.to_string());
+        properties.insert("memory_clock".to_string(), self.config.performance.clock.memory.to_string());
+        properties.insert("power_limit".to_string(), self.config.performance.power.limit.to_string());
+        properties.insert("peak_gflops".to_string(), format!("{:.1}", self.model.peak_flops / 1e9));
+        properties.insert("memory_bandwidth_gbps".to_string(), format!("{:.1}", self.model.memory_bandwidth / 1e9));
+
+        self.is_initialized.store(true, Ordering::Relaxed);
         Ok(())
     }
-    
+
+    /// Estimate the latency of a task without running it
+    pub fn estimate(&self, task: &Task) -> Result<KernelEstimate, HardwareError> {
+        match Operation::parse(&task.operation)? {
+            Operation::Compute => Ok(self.model.estimate_copy(2 * task.data.len() as u64)),
+            operation => {
+                let kernel = Kernel::new(operation, Tensor::decode_all(&task.data)?)?;
+                Ok(self.estimate_kernel(&kernel, task.data.len() as u64))
+            }
+        }
+    }
+
+    fn estimate_kernel(&self, kernel: &Kernel, payload_bytes: u64) -> KernelEstimate {
+        let output_bytes = kernel.output_shape().iter().product::<usize>() as u64 * 4;
+        self.model.estimate(kernel, payload_bytes + output_bytes)
+    }
+
+    /// Idle draw, and the power limit as the draw under load
+    fn power(&self) -> PowerModel {
+        let power = &self.config.performance.power;
+        PowerModel::new(power.idle as f64, power.limit as f64)
+    }
+
     /// Monitor GPU metrics
     fn monitor_gpu_metrics(&self) -> HardwareMetrics {
-        // In a real implementation, this would gather actual metrics
+        let sm_count = self.config.simulation.sm_count.max(1);
+        let utilization = self.busy_sms.load(Ordering::Relaxed).min(sm_count) as f64 / sm_count as f64 * 100.0;
         HardwareMetrics {
-            utilization: self.current_utilization.load(Ordering::Relaxed) as f64,
-            temperature: self.temperature.load(Ordering::Relaxed) as f64,
-            memory_used: self.memory_used.load(Ordering::Relaxed),
-            memory_total: self.memory_total.load(Ordering::Relaxed),
-            clock_speed: 1500, // 1500MHz
-            status: DeviceStatus::Active,
+            utilization,
+            temperature: IDLE_TEMPERATURE + LOAD_TEMPERATURE_RISE * utilization / 100.0,
+            memory_used: self.memory.stats().allocated / (1024 * 1024),
+            memory_total: self.config.memory.total,
+            clock_speed: self.config.performance.clock.graphics,
+            status: if utilization > 0.0 { DeviceStatus::Busy } else { DeviceStatus::Idle },
+            core_reservations: HashMap::new(),
         }
     }
 }
 
+/// Releases the SMs held by a running kernel
+struct OccupancyGuard<'a> {
+    device: &'a GpuDevice,
+    sms: u64,
+}
+
+impl Drop for OccupancyGuard<'_> {
+    fn drop(&mut self) {
+        self.device.busy_sms.fetch_sub(self.sms, Ordering::AcqRel);
+    }
+}
+
+#[async_trait]
 impl HardwareDevice for GpuDevice {
-    fn get_type(&self) -> HardwareType {
-        HardwareType::Gpu
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
     }
-    
-    fn get_id(&self) -> String {
-        self.id.clone()
+
+    fn device_type(&self) -> HardwareType {
+        HardwareType::Gpu
     }
-    
-    fn get_name(&self) -> String {
-        self.name.clone()
+
+    fn properties(&self) -> HashMap<String, String> {
+        self.properties.lock().unwrap().clone()
     }
-    
-    fn get_properties(&self) -> HashMap<String, String> {
-        self.properties.clone()
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            compute_units: self.config.simulation.sm_count,
+            operations: Operation::ALL.iter().map(|op| op.name().to_string()).collect(),
+            max_clock_speed: self.config.performance.clock.graphics,
+        }
     }
-    
+
     fn is_available(&self) -> bool {
-        self.is_initialized.load(Ordering::Relaxed) == 1
+        self.is_initialized.load(Ordering::Relaxed)
     }
-    
-    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+
+    async fn initialize(&self) -> Result<(), HardwareError> {
         self.initialize_gpu()
     }
-    
-    fn shutdown(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+
+    async fn shutdown(&self) -> Result<(), HardwareError> {
         debug!("Shutting down GPU device: {}", self.name);
-        self.is_initialized.store(0, Ordering::Relaxed);
+        self.is_initialized.store(false, Ordering::Relaxed);
+        let leaks = self.memory.reset();
+        if !leaks.is_empty() {
+            warn!("GPU device {} shut down with {} live allocations", self.id, leaks.len());
+        }
         Ok(())
     }
-    
+
     fn get_metrics(&self) -> HardwareMetrics {
         self.monitor_gpu_metrics()
     }
-}
\ No newline at end of file
+
+    fn total_memory(&self) -> u64 {
+        self.config.memory.total * 1024 * 1024
+    }
+
+    fn available_memory(&self) -> u64 {
+        self.memory.stats().free
+    }
+
+    fn estimate_execution(&self, task: &Task) -> Option<Duration> {
+        self.estimate(task).ok().map(|estimate| estimate.kernel_time())
+    }
+
+    fn transfer_time(&self, bytes: u64) -> Duration {
+        self.model.transfer_time(bytes)
+    }
+
+    fn power_model(&self) -> Option<PowerModel> {
+        Some(self.power())
+    }
+
+    fn sharing(&self) -> SharingPolicy {
+        self.sharing.policy(self.config.simulation.sm_count)
+    }
+
+    async fn execute_task(&self, task: &Task) -> Result<TaskResult, HardwareError> {
+        if !self.is_available() {
+            return Err(HardwareError::DeviceUnavailable(format!("GPU device {} is not initialized", self.id)));
+        }
+
+        debug!("Executing task {} ({}) on simulated GPU {}", task.id, task.operation, self.id);
+
+        let operation = Operation::parse(&task.operation)?;
+        let kernel = match operation {
+            Operation::Compute => None,
+            operation => Some(Kernel::new(operation, Tensor::decode_all(&task.data)?)?),
+        };
+        let sm_count = self.config.simulation.sm_count;
+        let estimate = match &kernel {
+            Some(kernel) => self.estimate_kernel(kernel, task.data.len() as u64),
+            None => self.model.estimate_copy(2 * task.data.len() as u64),
+        };
+        // A task runs on the streaming multiprocessors of its slice or share only
+        let estimate = match task.limit {
+            Some(limit) => estimate.limited_to(limit.compute_units.min(sm_count) as f64 / sm_count.max(1) as f64),
+            None => estimate,
+        };
+
+        // Inputs and output are resident on the device while the kernel runs. A task
+        // over its limit is refused before it takes memory co-located tasks may need.
+        let lens: Vec<usize> = match &kernel {
+            Some(kernel) => {
+                let mut lens: Vec<usize> = kernel.inputs().iter().map(|input| input.len()).collect();
+                lens.push(kernel.output_shape().iter().product());
+                lens
+            }
+            None => vec![task.data.len().div_ceil(4)],
+        };
+        if let Some(limit) = task.limit {
+            let required = lens.iter().map(|len| *len as u64 * 4).sum::<u64>();
+            let available = limit.memory * 1024 * 1024;
+            if required > available {
+                return Err(HardwareError::InsufficientMemory { required, available });
+            }
+        }
+        let _buffers = lens
+            .into_iter()
+            .map(|len| self.memory.allocate::<f32>(&task.id, len))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sms = (estimate.occupancy * sm_count as f64).ceil() as u64;
+        self.busy_sms.fetch_add(sms, Ordering::AcqRel);
+        let _occupancy = OccupancyGuard { device: self, sms };
+
+        let cancelled = || HardwareError::Cancelled(format!("task {} on {}", task.id, self.id));
+        let data = match kernel {
+            _ if task.cancel.is_cancelled() => return Err(cancelled()),
+            // The arithmetic is done on the host; keep it off the async runtime's threads
+            Some(kernel) => {
+                let output = tokio::task::spawn_blocking(move || kernel.run())
+                    .await
+                    .map_err(|e| HardwareError::ExecutionError(format!("kernel on {} failed: {}", self.id, e)))?;
+                Tensor::encode_all(&[output])
+            }
+            None => task.data.clone(),
+        };
+        if self.config.simulation.realtime {
+            tokio::select! {
+                _ = tokio::time::sleep(estimate.total_time()) => {}
+                _ = task.cancel.cancelled() => return Err(cancelled()),
+            }
+        }
+
+        Ok(TaskResult {
+            task_id: task.id.clone(),
+            data,
+            execution_time: estimate.total_millis(),
+            energy: self.power().energy(estimate.total_time()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{CancelToken, Capacity};
+
+    fn task(operation: &str, inputs: &[Tensor]) -> Task {
+        Task {
+            id: format!("{}-task", operation),
+            data: Tensor::encode_all(inputs),
+            operation: operation.to_string(),
+            kernel: None,
+            devices: Vec::new(),
+            cancel: CancelToken::new(),
+            limit: None,
+        }
+    }
+
+    fn square(n: usize, value: f32) -> Tensor {
+        Tensor::new(vec![n, n], vec![value; n * n]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_results_computed_on_host() {
+        let device = GpuDevice::new("gpu-test".to_string(), "Test GPU".to_string());
+        device.initialize().await.unwrap();
+
+        let (a, b) = (square(64, 1.0), square(64, 2.0));
+        let expected = Kernel::new(Operation::Gemm, vec![a.clone(), b.clone()]).unwrap().run();
+        let gemm = task("gemm", &[a, b]);
+        let result = device.execute_task(&gemm).await.unwrap();
+        assert_eq!(Tensor::decode_all(&result.data).unwrap(), vec![expected]);
+
+        // Reported latency is the model's, so repeated runs agree
+        let again = device.execute_task(&gemm).await.unwrap();
+        assert_eq!(result.execution_time, again.execution_time);
+        assert_eq!(result.execution_time, device.estimate(&gemm).unwrap().total_millis());
+        assert_eq!(result.energy, 250.0 * device.estimate(&gemm).unwrap().total_time().as_secs_f64());
+        assert_eq!(device.available_memory(), device.total_memory());
+    }
+
+    #[tokio::test]
+    async fn test_kernels_run_off_the_runtime() {
+        let device = GpuDevice::new("gpu-test".to_string(), "Test GPU".to_string());
+        device.initialize().await.unwrap();
+        let gemm = task("gemm", &[square(128, 1.0), square(128, 1.0)]);
+        let run = device.execute_task(&gemm);
+        tokio::pin!(run);
+
+        // Run inline, the kernel would finish within its first poll
+        let mut polls = 0;
+        loop {
+            tokio::select! {
+                biased;
+                result = &mut run => {
+                    result.unwrap();
+                    break;
+                }
+                _ = tokio::task::yield_now() => polls += 1,
+            }
+        }
+        assert!(polls > 0);
+    }
+
+    #[tokio::test]
+    async fn test_enforces_max_allocation() {
+        let mut config = GpuConfig::default();
+        config.memory.max_allocation = 1;
+        let device = GpuDevice::with_config("gpu-test".to_string(), "Test GPU".to_string(), config);
+        device.initialize().await.unwrap();
+
+        let result = device.execute_task(&task("add", &[square(512, 1.0), square(512, 1.0)])).await;
+        assert!(matches!(
+            result,
+            Err(HardwareError::InsufficientMemory { required, available })
+                if required == 512 * 512 * 4 && available == 1024 * 1024 - 512 * 512 * 4
+        ));
+        assert!(device.memory().live_allocations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_runs_stay_inside_their_lease_limit() {
+        // Two slow single-core multiprocessors, so a small gemm takes milliseconds
+        let mut config = GpuConfig::default();
+        config.simulation.sm_count = 2;
+        config.simulation.cores_per_sm = 1;
+        config.performance.clock.graphics = 10;
+        let device = GpuDevice::with_config("gpu-test".to_string(), "Test GPU".to_string(), config);
+        device.initialize().await.unwrap();
+
+        // On one of the two multiprocessors the gemm takes twice as long
+        let gemm = task("gemm", &[square(64, 1.0), square(64, 1.0)]);
+        let full = device.execute_task(&gemm).await.unwrap();
+        let one = Task {
+            limit: Some(Capacity { memory: 16, compute_units: 1 }),
+            ..gemm.clone()
+        };
+        let one = device.execute_task(&one).await.unwrap();
+        assert!(one.execution_time > full.execution_time);
+        assert!(one.energy > full.energy);
+
+        // 512x512 inputs and output take 3 MB
+        let add = Task {
+            limit: Some(Capacity { memory: 2, compute_units: 2 }),
+            ..task("add", &[square(512, 1.0), square(512, 1.0)])
+        };
+        let peak = device.memory().stats().peak_allocated;
+        let result = device.execute_task(&add).await;
+        assert!(matches!(
+            result,
+            Err(HardwareError::InsufficientMemory { required, available })
+                if required == 3 * 512 * 512 * 4 && available == 2 * 1024 * 1024
+        ));
+        assert_eq!(device.memory().stats().peak_allocated, peak, "refused before allocating");
+    }
+
+    #[tokio::test]
+    async fn test_memory_used_tracks_arena() {
+        let device = GpuDevice::new("gpu-test".to_string(), "Test GPU".to_string());
+        device.initialize().await.unwrap();
+
+        let buffer = device.memory().allocate::<f32>("task", 64 * 1024 * 1024).unwrap();
+        assert_eq!(device.get_metrics().memory_used, 256);
+        assert_eq!(device.available_memory(), device.total_memory() - 256 * 1024 * 1024);
+
+        // Leaked buffers are reported and reclaimed on shutdown
+        device.shutdown().await.unwrap();
+        assert_eq!(device.get_metrics().memory_used, 0);
+        drop(buffer);
+        assert_eq!(device.available_memory(), device.total_memory());
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_follow_config() {
+        let device = GpuDevice::new("gpu-test".to_string(), "Test GPU".to_string());
+        assert!(!device.is_available());
+        device.initialize().await.unwrap();
+
+        let capabilities = device.capabilities();
+        assert_eq!(capabilities.compute_units, 40);
+        assert!(capabilities.supports("conv2d"));
+        assert_eq!(device.get_metrics().memory_total, 16384);
+        assert_eq!(device.get_metrics().status, DeviceStatus::Idle);
+    }
+}
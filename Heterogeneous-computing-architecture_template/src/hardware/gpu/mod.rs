@@ -1,7 +1,11 @@
 //! GPU hardware module
 
+pub mod gpu_config;
 pub mod gpu_device;
 pub mod cuda;
+pub mod simulator;
 
+pub use gpu_config::*;
 pub use gpu_device::*;
-pub use cuda::*;
\ No newline at end of file
+pub use cuda::*;
+pub use simulator::*;
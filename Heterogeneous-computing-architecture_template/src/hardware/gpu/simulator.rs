@@ -0,0 +1,209 @@
+//! Deterministic GPU performance model for hosts without CUDA
+//!
+//! Latency follows a roofline: a kernel takes as long as the slower of its
+//! arithmetic at peak FLOP/s and its memory traffic at peak bandwidth, plus
+//! host transfers and a fixed launch overhead. Small kernels that cannot fill
+//! every core only get a proportional share of the peak.
+
+use crate::hardware::abstract_device::Kernel;
+use crate::hardware::gpu::gpu_config::GpuConfig;
+use std::time::Duration;
+
+/// Resource limiting a kernel's device time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RooflineBound {
+    Compute,
+    Memory,
+}
+
+/// Modelled cost of one task on the simulated device
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelEstimate {
+    pub flops: u64,
+
+    /// Device memory traffic in bytes
+    pub bytes_moved: u64,
+
+    /// Bytes copied between host and device
+    pub transfer_bytes: u64,
+
+    /// Fraction of the device's cores the kernel can occupy
+    pub occupancy: f64,
+    pub compute_time: Duration,
+    pub memory_time: Duration,
+    pub transfer_time: Duration,
+    pub launch_time: Duration,
+    pub bound: RooflineBound,
+}
+
+impl KernelEstimate {
+    /// Time spent on the device itself
+    pub fn kernel_time(&self) -> Duration {
+        self.launch_time + self.compute_time.max(self.memory_time)
+    }
+
+    /// End-to-end latency including host transfers
+    pub fn total_time(&self) -> Duration {
+        self.kernel_time() + self.transfer_time
+    }
+
+    /// End-to-end latency in whole milliseconds, rounded up
+    pub fn total_millis(&self) -> u64 {
+        self.total_time().as_micros().div_ceil(1000) as u64
+    }
+
+    /// The same kernel held to at most `occupancy` of the device, computing more slowly
+    pub fn limited_to(mut self, occupancy: f64) -> Self {
+        if occupancy > 0.0 && self.occupancy > occupancy {
+            self.compute_time = self.compute_time.mul_f64(self.occupancy / occupancy);
+            self.occupancy = occupancy;
+            if self.compute_time > self.memory_time {
+                self.bound = RooflineBound::Compute;
+            }
+        }
+        self
+    }
+}
+
+/// Peak throughput figures derived from gpu.yaml
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuModel {
+    /// Total FP32 cores
+    pub cores: u64,
+
+    /// Peak FP32 FLOP/s, counting a fused multiply-add as two operations
+    pub peak_flops: f64,
+
+    /// Peak device memory bandwidth in bytes/s
+    pub memory_bandwidth: f64,
+
+    /// Host link bandwidth in bytes/s
+    pub host_bandwidth: f64,
+    pub launch_overhead: Duration,
+}
+
+impl GpuModel {
+    /// Build the model from a GPU configuration
+    pub fn from_config(config: &GpuConfig) -> Self {
+        let sim = &config.simulation;
+        let clocks = &config.performance.clock;
+        let cores = (sim.sm_count * sim.cores_per_sm).max(1);
+        Self {
+            cores,
+            peak_flops: (cores * 2 * clocks.graphics) as f64 * 1e6,
+            memory_bandwidth: (clocks.memory * sim.memory_bus_width / 8) as f64 * 1e6,
+            host_bandwidth: (sim.host_bandwidth * 1024 * 1024) as f64,
+            launch_overhead: Duration::from_micros(sim.launch_overhead),
+        }
+    }
+
+    /// FLOPs per byte above which kernels are compute bound
+    pub fn ridge_point(&self) -> f64 {
+        self.peak_flops / self.memory_bandwidth
+    }
+
+    /// Estimate a kernel whose inputs and output cross the host link
+    pub fn estimate(&self, kernel: &Kernel, transfer_bytes: u64) -> KernelEstimate {
+        let flops = kernel.flops();
+        let bytes_moved = kernel.bytes_moved();
+        let occupancy = (kernel.work_items() as f64 / self.cores as f64).clamp(f64::MIN_POSITIVE, 1.0);
+
+        let compute_time = seconds(flops as f64 / (self.peak_flops * occupancy));
+        let memory_time = seconds(bytes_moved as f64 / self.memory_bandwidth);
+        KernelEstimate {
+            flops,
+            bytes_moved,
+            transfer_bytes,
+            occupancy,
+            bound: if compute_time > memory_time {
+                RooflineBound::Compute
+            } else {
+                RooflineBound::Memory
+            },
+            compute_time,
+            memory_time,
+            transfer_time: self.transfer_time(transfer_bytes),
+            launch_time: self.launch_overhead,
+        }
+    }
+
+    /// Estimate a task that only copies its payload through the device
+    pub fn estimate_copy(&self, transfer_bytes: u64) -> KernelEstimate {
+        KernelEstimate {
+            flops: 0,
+            bytes_moved: 0,
+            transfer_bytes,
+            occupancy: 0.0,
+            compute_time: Duration::ZERO,
+            memory_time: Duration::ZERO,
+            transfer_time: self.transfer_time(transfer_bytes),
+            launch_time: Duration::ZERO,
+            bound: RooflineBound::Memory,
+        }
+    }
+
+    /// Time to copy `bytes` over the host link
+    pub fn transfer_time(&self, bytes: u64) -> Duration {
+        seconds(bytes as f64 / self.host_bandwidth)
+    }
+}
+
+fn seconds(value: f64) -> Duration {
+    if value.is_finite() && value > 0.0 {
+        Duration::from_secs_f64(value)
+    } else {
+        Duration::ZERO
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{Operation, Tensor};
+
+    fn model() -> GpuModel {
+        GpuModel::from_config(&GpuConfig::default())
+    }
+
+    fn square(n: usize) -> Tensor {
+        Tensor::new(vec![n, n], vec![1.0; n * n]).unwrap()
+    }
+
+    #[test]
+    fn test_peak_figures_from_config() {
+        let model = model();
+        assert_eq!(model.cores, 40 * 64);
+        assert_eq!(model.peak_flops, 40.0 * 64.0 * 2.0 * 1500e6);
+        assert_eq!(model.memory_bandwidth, 6000e6 * 32.0);
+    }
+
+    #[test]
+    fn test_roofline_bounds() {
+        let model = model();
+        let gemm = Kernel::new(Operation::Gemm, vec![square(256), square(256)]).unwrap();
+        let estimate = model.estimate(&gemm, 0);
+        assert_eq!(estimate.bound, RooflineBound::Compute);
+        assert_eq!(estimate.occupancy, 1.0);
+
+        let add = Kernel::new(Operation::Add, vec![square(256), square(256)]).unwrap();
+        let estimate = model.estimate(&add, 0);
+        assert_eq!(estimate.bound, RooflineBound::Memory);
+        assert_eq!(estimate.kernel_time(), estimate.launch_time + estimate.memory_time);
+
+        // Half the device computes the gemm half as fast
+        let full = model.estimate(&gemm, 0);
+        let half = full.clone().limited_to(0.5);
+        assert_eq!(half.occupancy, 0.5);
+        assert_eq!(half.compute_time, full.compute_time * 2);
+    }
+
+    #[test]
+    fn test_estimates_are_deterministic_and_scale() {
+        let model = model();
+        let small = Kernel::new(Operation::Gemm, vec![square(128), square(128)]).unwrap();
+        let large = Kernel::new(Operation::Gemm, vec![square(512), square(512)]).unwrap();
+        assert_eq!(model.estimate(&small, 1024), model.estimate(&small, 1024));
+        assert!(model.estimate(&large, 0).total_time() > model.estimate(&small, 0).total_time());
+        assert!(model.estimate(&small, 1 << 30).total_millis() >= 60);
+    }
+}
@@ -1,6 +1,30 @@
 // Hardware module
 
+#[path = "abstract/mod.rs"]
 pub mod abstract_device;
 pub mod cpu;
+pub mod discovery;
 pub mod gpu;
-pub mod fpga;
\ No newline at end of file
+pub mod fpga;
+
+use crate::hardware::abstract_device::{DeviceManager, HardwareManager};
+use crate::hardware::discovery::DeviceDiscovery;
+use std::sync::Arc;
+
+/// Directory holding the per-type `hardware/*.yaml` files
+const CONFIG_DIR: &str = "config";
+
+/// Initialize the hardware manager with the devices described by the configuration
+pub fn init_hardware_manager(config: &serde_yaml::Value) -> Result<Arc<dyn HardwareManager>, Box<dyn std::error::Error>> {
+    let discovery = DeviceDiscovery::new(config, CONFIG_DIR)?;
+    let manager = Arc::new(DeviceManager::with_health_policy(discovery.settings().health.clone()));
+    for device in discovery.discover()? {
+        manager.register_device(device)?;
+    }
+
+    // Probe device health in the background when running on a tokio runtime
+    if tokio::runtime::Handle::try_current().is_ok() {
+        manager.spawn_health_monitor();
+    }
+    Ok(manager)
+}
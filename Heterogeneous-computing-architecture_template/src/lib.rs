@@ -0,0 +1,9 @@
+//! Heterogeneous computing architecture library
+
+pub mod hardware;
+pub mod scheduling;
+pub mod computation;
+pub mod data;
+pub mod models;
+pub mod utils;
+pub mod errors;
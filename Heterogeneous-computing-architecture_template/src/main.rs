@@ -5,13 +5,7 @@ use tokio::signal;
 use tracing::{info, error};
 use clap::Parser;
 
-mod hardware;
-mod scheduling;
-mod computation;
-mod data;
-mod models;
-mod utils;
-mod errors;
+use heterogeneous_computing_architecture::{computation, data, hardware, models, scheduling};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Heterogeneous Computing Architecture", version = "0.1.0")]
@@ -42,22 +36,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let hardware_manager = hardware::init_hardware_manager(&config)?;
     
     // Initialize scheduling components
-    let scheduler = scheduling::init_scheduler(&config)?;
+    let _scheduler = scheduling::init_scheduler(&config, hardware_manager.clone())?;
     
     // Initialize computation engine
-    let computation_engine = computation::init_computation_engine(&config)?;
+    let _computation_engine = computation::init_computation_engine(&config, hardware_manager.clone())?;
     
     // Initialize data manager
-    let data_manager = data::init_data_manager(&config)?;
+    let _data_manager = data::init_data_manager(&config)?;
     
     // Initialize model manager
-    let model_manager = models::init_model_manager(&config)?;
+    let _model_manager = models::init_model_manager(&config)?;
     
     // Create shared state
-    let shared_state = Arc::new(hardware_manager);
+    let _shared_state = Arc::clone(&hardware_manager);
     
     // Start services
-    let services = tokio::spawn(async move {
+    let _services = tokio::spawn(async move {
         // Start monitoring services
         // TODO: Implement actual monitoring services
         loop {
@@ -5,4 +5,10 @@ pub mod model_optimizer;
 
 // Re-export key types
 pub use model_loader::{ModelLoader, Model, ModelConfig};
-pub use model_optimizer::ModelOptimizer;
\ No newline at end of file
+pub use model_optimizer::ModelOptimizer;
+
+/// Initialize the model manager
+pub fn init_model_manager(config: &serde_yaml::Value) -> Result<ModelLoader, Box<dyn std::error::Error>> {
+    let model_path = config["models"]["path"].as_str().unwrap_or("models");
+    Ok(ModelLoader::new(crate::data::DataManager::new(model_path)))
+}
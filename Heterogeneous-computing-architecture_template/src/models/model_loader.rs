@@ -1,6 +1,7 @@
 // Model loader implementation
 
 use crate::data::DataManager;
+use crate::errors::DataError;
 
 pub struct ModelLoader {
     data_manager: DataManager,
@@ -22,7 +23,10 @@ impl ModelLoader {
         let weights_data = self.data_manager.load_data(&format!("{}/model.bin", model_path))?;
         
         // Load tokenizer (for language models)
-        let tokenizer_data = self.data_manager.load_data(&format!("{}/tokenizer.json", model_path)).unwrap_or_default();
+        let tokenizer_data = match self.data_manager.load_data(&format!("{}/tokenizer.json", model_path)) {
+            Err(DataError::NotFound(_)) => Vec::new(),
+            loaded => loaded?,
+        };
         
         let model = Model {
             config,
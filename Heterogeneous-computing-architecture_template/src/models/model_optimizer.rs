@@ -1,9 +1,11 @@
 // Model optimizer implementation
 
+use crate::models::Model;
+
 pub struct ModelOptimizer;
 
 impl ModelOptimizer {
-    pub fn optimize_for_hardware(&self, model: &mut Model, hardware_type: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn optimize_for_hardware(&self, _model: &mut Model, hardware_type: &str) -> Result<(), Box<dyn std::error::Error>> {
         println!("Optimizing model for {}", hardware_type);
         
         // In a real implementation, this would perform hardware-specific optimizations
@@ -30,7 +32,7 @@ impl ModelOptimizer {
         Ok(())
     }
 
-    pub fn quantize_model(&self, model: &mut Model, bits: u8) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn quantize_model(&self, _model: &mut Model, bits: u8) -> Result<(), Box<dyn std::error::Error>> {
         println!("Quantizing model to {} bits", bits);
         
         // In a real implementation, this would perform model quantization
@@ -0,0 +1,863 @@
+//! Per-device run queues
+//!
+//! Every submitted task joins the run queue of one device. That is the device it is
+//! pinned to, or else the least loaded device of its hardware type that supports its
+//! operation and is large enough for it. Queues are bounded. A device whose queue
+//! runs dry steals from the longest queue of a compatible device, but never takes a
+//! pinned task. Tasks that no device can run wait in a shared queue. They are still
+//! dispatched, so the executor reports why they failed.
+//!
+//! Gang tasks wait in the shared queue too. One is dispatched only once every member
+//! has a distinct device with room for it, and it then holds capacity on all of them.
+//!
+//! Under the `energy` objective a task joins the queue of the device modelled to
+//! spend the least energy on it instead. With a power cap, work is only started on an
+//! idle device while the node's draw, counting busy devices at their active draw,
+//! stays under the cap. An idle node always takes one task so that nothing starves.
+//!
+//! Hot devices are placed on last and run one task at a time. A critical device
+//! starts nothing, and its unpinned queued tasks move to the queues of other devices.
+//!
+//! Among otherwise equal devices, a task that reads data objects joins the one its
+//! inputs are cheapest to copy to (see `locality`).
+
+use crate::hardware::abstract_device::{place_gang, Capacity, DeviceQuota, HardwareDevice, PowerModel, TaskRequirements};
+use crate::scheduling::locality::{Locality, Transfer};
+use crate::scheduling::scheduler_config::{EnergyConfig, OverflowPolicy, PlacementObjective};
+use crate::scheduling::task_scheduler::Task;
+use crate::scheduling::thermal::ThermalState;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Name of the queue holding tasks no registered device can run
+pub const UNPLACED_QUEUE: &str = "unplaced";
+
+/// Dispatch order of a queued task; the smallest key goes first
+pub(crate) type OrderKey = (u64, u64);
+
+/// Devices a dispatched task holds capacity on, and how much on each
+type Held = Vec<(String, Capacity)>;
+
+/// A task waiting in a run queue, with its submission sequence number
+struct Queued {
+    seq: u64,
+    task: Task,
+}
+
+/// A device the run queues can dispatch to
+pub(crate) struct DeviceSlot {
+    pub device: Arc<dyn HardwareDevice>,
+    /// What leases may take on the device, in total and per task
+    pub quota: DeviceQuota,
+    /// Whether the device may receive new work
+    pub schedulable: bool,
+    pub thermal: ThermalState,
+}
+
+impl DeviceSlot {
+    /// Check whether the device could ever run a task that is not pinned to it
+    fn accepts(&self, task: &Task) -> bool {
+        self.runs(&task.requirements, &task.operation, self.quota.capacity)
+    }
+
+    /// Check whether the device runs `operation` with `requirements` inside `room`
+    ///
+    /// The task must also fit the device's per-task share, or one of its slices.
+    fn runs(&self, requirements: &TaskRequirements, operation: &str, room: Capacity) -> bool {
+        let demand = Capacity {
+            memory: requirements.memory_required,
+            compute_units: requirements.compute_units,
+        };
+        self.device.device_type() == requirements.hardware_type
+            && self.device.capabilities().supports(operation)
+            && room.holds(demand)
+            && self.quota.admits(demand)
+    }
+
+    fn power(&self) -> PowerModel {
+        self.device.power_model().unwrap_or_default()
+    }
+
+    /// Modelled energy in joules of running a task here, host transfers included
+    fn energy(&self, task: &Task) -> Option<f64> {
+        let power = self.device.power_model()?;
+        let execution = self.device.estimate_execution(&task.to_device_task())?;
+        Some(power.energy(execution + self.device.transfer_time(task.data.len() as u64)))
+    }
+}
+
+struct DeviceQueue {
+    slot: DeviceSlot,
+    tasks: VecDeque<Queued>,
+    /// Capacity held by dispatched tasks that have not finished
+    dispatched: Capacity,
+}
+
+impl DeviceQueue {
+    /// Check whether a task fits next to the tasks already dispatched to the device
+    fn fits(&self, task: &Task) -> bool {
+        let free = self.slot.quota.capacity.saturating_sub(self.dispatched);
+        free.holds(demand(task)) && self.slot.quota.admits(demand(task))
+    }
+}
+
+fn demand(task: &Task) -> Capacity {
+    Capacity {
+        memory: task.requirements.memory_required,
+        compute_units: task.requirements.compute_units,
+    }
+}
+
+/// Whether a gang task's members can be given devices
+enum GangFit {
+    /// With the capacity free now, on these devices
+    Now(Held),
+    /// Once dispatched tasks give capacity back
+    Later,
+    /// Not with the registered devices
+    Never,
+}
+
+/// Result of offering a task to the run queues
+pub(crate) enum Admission {
+    Queued,
+    /// Queued after evicting an older task
+    Evicted(Task),
+    /// The queue the task belongs in is full
+    Full { task: Task, queue: String },
+}
+
+/// Run queues of every device, plus the shared queue and the backlog
+pub(crate) struct RunQueues {
+    max_size: usize,
+    devices: BTreeMap<String, DeviceQueue>,
+    unplaced: VecDeque<Queued>,
+    /// Already admitted tasks, e.g. graph tasks released while their queue was full
+    backlog: VecDeque<Task>,
+    /// Dispatched tasks and the device capacity each holds
+    running: HashMap<String, Held>,
+    energy: EnergyConfig,
+    locality: Locality,
+    next_seq: u64,
+}
+
+impl RunQueues {
+    /// Create queues holding at most `max_size` tasks each
+    pub(crate) fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            devices: BTreeMap::new(),
+            unplaced: VecDeque::new(),
+            backlog: VecDeque::new(),
+            running: HashMap::new(),
+            energy: EnergyConfig::default(),
+            locality: Locality::default(),
+            next_seq: 0,
+        }
+    }
+
+    /// Place tasks and cap the node's draw as `energy` asks
+    pub(crate) fn with_energy(mut self, energy: EnergyConfig) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    /// Use `locality` for where data objects are held from now on
+    pub(crate) fn set_locality(&mut self, locality: Locality) {
+        self.locality = locality;
+    }
+
+    /// Copies of its inputs a dispatched task needs on its device
+    pub(crate) fn transfers(&self, task: &Task) -> Vec<Transfer> {
+        match task.device_id.as_ref().and_then(|id| self.devices.get(id)) {
+            Some(queue) if !task.requirements.is_gang() => self.locality.transfers(task, queue.slot.device.as_ref()),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Bring the device set up to date, moving the work of removed devices to the backlog
+    pub(crate) fn sync(&mut self, slots: Vec<DeviceSlot>) {
+        let mut slots: BTreeMap<String, DeviceSlot> =
+            slots.into_iter().map(|slot| (slot.device.id().to_string(), slot)).collect();
+
+        let removed: Vec<String> = self.devices.keys().filter(|id| !slots.contains_key(*id)).cloned().collect();
+        for id in removed {
+            let queue = self.devices.remove(&id).unwrap();
+            debug!("Device {} left, requeueing {} tasks", id, queue.tasks.len());
+            self.backlog.extend(queue.tasks.into_iter().map(|queued| queued.task));
+        }
+
+        for (id, queue) in self.devices.iter_mut() {
+            queue.slot = slots.remove(id).unwrap();
+        }
+        self.migrate();
+        for (id, slot) in slots {
+            self.devices.insert(
+                id,
+                DeviceQueue {
+                    slot,
+                    tasks: VecDeque::new(),
+                    dispatched: Capacity::default(),
+                },
+            );
+        }
+        self.promote();
+    }
+
+    /// Tasks waiting in any queue or the backlog
+    pub(crate) fn len(&self) -> usize {
+        self.devices.values().map(|queue| queue.tasks.len()).sum::<usize>() + self.unplaced.len() + self.backlog.len()
+    }
+
+    /// Tasks dispatched and not yet finished
+    pub(crate) fn in_flight(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Length of each device queue and of the shared queue
+    pub(crate) fn lengths(&self) -> Vec<(String, usize)> {
+        self.devices
+            .iter()
+            .map(|(id, queue)| (id.clone(), queue.tasks.len()))
+            .chain(std::iter::once((UNPLACED_QUEUE.to_string(), self.unplaced.len())))
+            .collect()
+    }
+
+    /// Queue a task, applying `policy` if its queue is full
+    pub(crate) fn admit(&mut self, task: Task, policy: OverflowPolicy) -> Admission {
+        let target = self.target(&task);
+        if self.queue_mut(target.as_deref()).len() < self.max_size {
+            self.push(target.as_deref(), task);
+            return Admission::Queued;
+        }
+
+        if policy == OverflowPolicy::Drop {
+            // A queue with no room at all has nothing to evict
+            if let Some(evicted) = self.queue_mut(target.as_deref()).pop_front() {
+                self.push(target.as_deref(), task);
+                return Admission::Evicted(evicted.task);
+            }
+        }
+        Admission::Full {
+            task,
+            queue: target.unwrap_or_else(|| UNPLACED_QUEUE.to_string()),
+        }
+    }
+
+    /// Queue an already admitted task, holding it in the backlog if its queue is full
+    pub(crate) fn admit_or_hold(&mut self, task: Task) {
+        let target = self.target(&task);
+        if self.queue_mut(target.as_deref()).len() < self.max_size {
+            self.push(target.as_deref(), task);
+        } else {
+            self.backlog.push_back(task);
+        }
+    }
+
+    /// Draw of every device in watts, busy devices at their active draw
+    pub(crate) fn power_draw(&self) -> f64 {
+        let busy = self.busy();
+        self.devices
+            .iter()
+            .map(|(id, queue)| {
+                let power = queue.slot.power();
+                if busy.contains(id.as_str()) {
+                    power.active
+                } else {
+                    power.idle
+                }
+            })
+            .sum()
+    }
+
+    /// Take the next task to run and mark it dispatched
+    ///
+    /// Each queue offers its first task by `key`, and the smallest of those that fits
+    /// its device and the power cap is taken. The shared queue offers its first task
+    /// that is not a gang waiting for capacity. When none does, a device with an empty
+    /// queue steals.
+    pub(crate) fn next(&mut self, key: impl Fn(&Task, u64) -> OrderKey, max_in_flight: usize) -> Option<Task> {
+        if self.running.len() >= max_in_flight {
+            return None;
+        }
+
+        let spare = self.spare_power();
+        let mut best: Option<(OrderKey, Option<String>, usize)> = None;
+        for (id, queue) in &self.devices {
+            if !self.may_start(id, spare) {
+                continue;
+            }
+            if let Some((index, order)) = first(&queue.tasks, &key) {
+                if queue.fits(&queue.tasks[index].task) && best.as_ref().is_none_or(|(b, ..)| order < *b) {
+                    best = Some((order, Some(id.clone()), index));
+                }
+            }
+        }
+        let mut gang = Vec::new();
+        if let Some((index, order, devices)) = self.first_unplaced(&key, spare) {
+            if best.as_ref().is_none_or(|(b, ..)| order < *b) {
+                best = Some((order, None, index));
+                gang = devices;
+            }
+        }
+
+        let task = match best {
+            Some((_, Some(device), index)) => {
+                let task = self.devices.get_mut(&device).unwrap().tasks.remove(index).unwrap().task;
+                let held = vec![(device, demand(&task))];
+                self.start(task, held)
+            }
+            Some((_, None, index)) => {
+                let task = self.unplaced.remove(index).unwrap().task;
+                self.start(task, gang)
+            }
+            None => self.steal(&key, spare)?,
+        };
+        self.promote();
+        Some(task)
+    }
+
+    /// Record that a dispatched task finished, returning its capacity to the device
+    pub(crate) fn finish(&mut self, task_id: &str) {
+        for (device, capacity) in self.running.remove(task_id).unwrap_or_default() {
+            if let Some(queue) = self.devices.get_mut(&device) {
+                queue.dispatched = queue.dispatched.saturating_sub(capacity);
+            }
+        }
+    }
+
+    /// Take a task out of whichever queue holds it
+    pub(crate) fn remove(&mut self, task_id: &str) -> Option<Task> {
+        let queues = self
+            .devices
+            .values_mut()
+            .map(|queue| &mut queue.tasks)
+            .chain(std::iter::once(&mut self.unplaced));
+        let mut removed = None;
+        for queue in queues {
+            if let Some(index) = queue.iter().position(|queued| queued.task.id == task_id) {
+                removed = queue.remove(index).map(|queued| queued.task);
+                break;
+            }
+        }
+        if removed.is_some() {
+            self.promote();
+            return removed;
+        }
+        let index = self.backlog.iter().position(|task| task.id == task_id)?;
+        self.backlog.remove(index)
+    }
+
+    /// Device queue a task belongs in; `None` for the shared queue
+    fn target(&self, task: &Task) -> Option<String> {
+        if task.requirements.is_gang() {
+            return None;
+        }
+        if let Some(pinned) = &task.device_id {
+            return self.devices.contains_key(pinned).then(|| pinned.clone());
+        }
+        // Prefer devices that are in service, then cooler ones, then the least energy if
+        // asked, then the cheapest inputs, then the shortest queue
+        self.devices
+            .iter()
+            .filter(|(_, queue)| queue.slot.accepts(task))
+            .map(|(id, queue)| {
+                let energy = match self.energy.objective {
+                    PlacementObjective::Energy => queue.slot.energy(task).unwrap_or(f64::INFINITY),
+                    PlacementObjective::Performance => 0.0,
+                };
+                let inputs = self.locality.cost(task, queue.slot.device.as_ref());
+                (id, (!queue.slot.schedulable, queue.slot.thermal), energy, (inputs, queue.tasks.len()))
+            })
+            .min_by(|a, b| a.1.cmp(&b.1).then(a.2.total_cmp(&b.2)).then(a.3.cmp(&b.3)))
+            .map(|(id, ..)| id.clone())
+    }
+
+    fn queue_mut(&mut self, device: Option<&str>) -> &mut VecDeque<Queued> {
+        match device.and_then(|id| self.devices.get_mut(id)) {
+            Some(queue) => &mut queue.tasks,
+            None => &mut self.unplaced,
+        }
+    }
+
+    fn push(&mut self, device: Option<&str>, task: Task) {
+        self.next_seq += 1;
+        let seq = self.next_seq;
+        self.queue_mut(device).push_back(Queued { seq, task });
+    }
+
+    /// Move the unpinned queued tasks of critical devices to devices that can take them
+    fn migrate(&mut self) {
+        let critical: Vec<String> = self
+            .devices
+            .iter()
+            .filter(|(_, queue)| queue.slot.thermal == ThermalState::Critical)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in critical {
+            let tasks = std::mem::take(&mut self.devices.get_mut(&id).unwrap().tasks);
+            let mut moved = 0;
+            for queued in tasks {
+                let target = match queued.task.device_id {
+                    None => self.target(&queued.task).filter(|target| {
+                        *target != id && self.devices[target].tasks.len() < self.max_size
+                    }),
+                    Some(_) => None,
+                };
+                match target {
+                    Some(target) => {
+                        self.devices.get_mut(&target).unwrap().tasks.push_back(queued);
+                        moved += 1;
+                    }
+                    None => self.devices.get_mut(&id).unwrap().tasks.push_back(queued),
+                }
+            }
+            if moved > 0 {
+                warn!("Moved {} queued tasks off critical device {}", moved, id);
+            }
+        }
+    }
+
+    /// Move backlog tasks into their queues while there is room
+    fn promote(&mut self) {
+        for _ in 0..self.backlog.len() {
+            let task = self.backlog.pop_front().unwrap();
+            self.admit_or_hold(task);
+        }
+    }
+
+    /// Let a device with an empty queue take work from the longest compatible queue
+    fn steal(&mut self, key: &impl Fn(&Task, u64) -> OrderKey, spare: Option<f64>) -> Option<Task> {
+        let thieves: Vec<String> = self
+            .devices
+            .iter()
+            .filter(|(id, queue)| queue.tasks.is_empty() && self.may_start(id, spare))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for thief in thieves {
+            let mut victims: Vec<(usize, String)> = self
+                .devices
+                .iter()
+                .filter(|(id, queue)| **id != thief && !queue.tasks.is_empty())
+                .map(|(id, queue)| (queue.tasks.len(), id.clone()))
+                .collect();
+            victims.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+            for (_, victim) in victims {
+                let thief_queue = &self.devices[&thief];
+                // Take the task the victim would get to last
+                let index = self.devices[&victim]
+                    .tasks
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, queued)| {
+                        queued.task.device_id.is_none()
+                            && thief_queue.slot.accepts(&queued.task)
+                            && thief_queue.fits(&queued.task)
+                    })
+                    .max_by_key(|(_, queued)| key(&queued.task, queued.seq))
+                    .map(|(index, _)| index);
+                if let Some(index) = index {
+                    let task = self.devices.get_mut(&victim).unwrap().tasks.remove(index).unwrap().task;
+                    debug!("Device {} stole task {} from {}", thief, task.id, victim);
+                    let held = vec![(thief, demand(&task))];
+                    return Some(self.start(task, held));
+                }
+            }
+        }
+        None
+    }
+
+    /// First task of the shared queue that can be dispatched, with the devices a gang takes
+    fn first_unplaced(&self, key: &impl Fn(&Task, u64) -> OrderKey, spare: Option<f64>) -> Option<(usize, OrderKey, Held)> {
+        self.unplaced
+            .iter()
+            .enumerate()
+            .filter_map(|(index, queued)| {
+                if !queued.task.requirements.is_gang() {
+                    return Some((index, key(&queued.task, queued.seq), Vec::new()));
+                }
+                let devices = match self.gang_fit(&queued.task) {
+                    GangFit::Now(devices) if self.affordable(spare, devices.iter().map(|(id, _)| id.as_str())) => devices,
+                    GangFit::Now(_) => return None,
+                    GangFit::Later => return None,
+                    // Dispatched anyway so the executor reports why it cannot run
+                    GangFit::Never => Vec::new(),
+                };
+                Some((index, key(&queued.task, queued.seq), devices))
+            })
+            .min_by_key(|(_, order, _)| *order)
+    }
+
+    fn gang_fit(&self, task: &Task) -> GangFit {
+        if let Some(devices) = self.match_gang(task, true) {
+            GangFit::Now(devices)
+        } else if self.match_gang(task, false).is_some() {
+            GangFit::Later
+        } else {
+            GangFit::Never
+        }
+    }
+
+    /// Give each gang member its own device, preferring those with the shortest queue
+    ///
+    /// With `free_only`, a device must be in service and have room next to the tasks
+    /// already dispatched to it; otherwise its total capacity is enough.
+    fn match_gang(&self, task: &Task, free_only: bool) -> Option<Held> {
+        let members = task.requirements.per_device();
+        let options: Vec<Vec<&String>> = members
+            .iter()
+            .map(|member| {
+                let mut fitting: Vec<(&String, &DeviceQueue)> = self
+                    .devices
+                    .iter()
+                    .filter(|(id, queue)| {
+                        let room = if !free_only {
+                            queue.slot.quota.capacity
+                        } else if queue.slot.schedulable && self.cool_enough(id) {
+                            queue.slot.quota.capacity.saturating_sub(queue.dispatched)
+                        } else {
+                            return false;
+                        };
+                        queue.slot.runs(member, &task.operation, room)
+                    })
+                    .collect();
+                fitting.sort_by_key(|(_, queue)| queue.tasks.len());
+                fitting.into_iter().map(|(id, _)| id).collect()
+            })
+            .collect();
+        let placement = place_gang(&members, &options)?;
+        let held = placement
+            .into_iter()
+            .zip(&members)
+            .map(|(id, member)| {
+                let demand = Capacity {
+                    memory: member.memory_required,
+                    compute_units: member.compute_units,
+                };
+                (id.clone(), demand)
+            })
+            .collect();
+        Some(held)
+    }
+
+    /// Devices holding capacity for a dispatched task
+    fn busy(&self) -> HashSet<&str> {
+        self.running.values().flatten().map(|(id, _)| id.as_str()).collect()
+    }
+
+    /// Whether a device's temperature lets it start another task
+    fn cool_enough(&self, id: &str) -> bool {
+        match self.devices[id].slot.thermal {
+            ThermalState::Normal => true,
+            ThermalState::Hot => !self.busy().contains(id),
+            ThermalState::Critical => false,
+        }
+    }
+
+    /// Whether a device may start another task now
+    fn may_start(&self, id: &str, spare: Option<f64>) -> bool {
+        self.devices[id].slot.schedulable && self.cool_enough(id) && self.affordable(spare, [id])
+    }
+
+    /// Watts that starting more work may add; `None` without a cap or on an idle node
+    fn spare_power(&self) -> Option<f64> {
+        if self.energy.power_cap <= 0.0 || self.running.is_empty() {
+            return None;
+        }
+        Some(self.energy.power_cap - self.power_draw())
+    }
+
+    /// Whether starting work on `devices` keeps the added draw within `spare`
+    fn affordable<'a>(&self, spare: Option<f64>, devices: impl IntoIterator<Item = &'a str>) -> bool {
+        let Some(spare) = spare else {
+            return true;
+        };
+        let busy = self.busy();
+        let mut woken = HashSet::new();
+        let added: f64 = devices
+            .into_iter()
+            .filter(|id| !busy.contains(id) && woken.insert(*id))
+            .filter_map(|id| self.devices.get(id))
+            .map(|queue| queue.slot.power().headroom())
+            .sum();
+        added <= spare
+    }
+
+    /// Mark a task dispatched, holding `held` capacity on each device
+    fn start(&mut self, mut task: Task, held: Held) -> Task {
+        for (id, capacity) in &held {
+            let queue = self.devices.get_mut(id).unwrap();
+            queue.dispatched.memory += capacity.memory;
+            queue.dispatched.compute_units += capacity.compute_units;
+        }
+        if let [(id, _)] = held.as_slice() {
+            if !task.requirements.is_gang() {
+                task.device_id = Some(id.clone());
+            }
+        }
+        self.running.insert(task.id.clone(), held);
+        task
+    }
+}
+
+/// Position and key of the task a queue would dispatch first
+fn first(tasks: &VecDeque<Queued>, key: &impl Fn(&Task, u64) -> OrderKey) -> Option<(usize, OrderKey)> {
+    tasks
+        .iter()
+        .enumerate()
+        .map(|(index, queued)| (index, key(&queued.task, queued.seq)))
+        .min_by_key(|(_, order)| *order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{GangMember, HardwareType, TaskRequirements, Tensor};
+    use crate::hardware::gpu::{GpuConfig, GpuDevice};
+    use crate::scheduling::TaskPriority;
+
+    fn slot(id: &str) -> DeviceSlot {
+        DeviceSlot {
+            device: Arc::new(GpuDevice::new(id.to_string(), id.to_string())),
+            quota: DeviceQuota::whole(Capacity { memory: 1024, compute_units: 4 }),
+            schedulable: true,
+            thermal: ThermalState::Normal,
+        }
+    }
+
+    fn task(id: &str, units: u64) -> Task {
+        let requirements = TaskRequirements {
+            compute_units: units,
+            timeout: 0,
+            ..TaskRequirements::for_test(HardwareType::Gpu)
+        };
+        Task::new(id.to_string(), id.to_string(), requirements, TaskPriority::Normal)
+    }
+
+    fn fifo(_: &Task, seq: u64) -> OrderKey {
+        (0, seq)
+    }
+
+    #[test]
+    fn test_bounded_queues_and_overflow() {
+        let mut queues = RunQueues::new(2);
+        queues.sync(vec![slot("gpu-0")]);
+        assert!(matches!(queues.admit(task("a", 1), OverflowPolicy::Reject), Admission::Queued));
+        assert!(matches!(queues.admit(task("b", 1), OverflowPolicy::Reject), Admission::Queued));
+        assert!(matches!(
+            queues.admit(task("c", 1), OverflowPolicy::Block),
+            Admission::Full { ref queue, .. } if queue == "gpu-0"
+        ));
+        match queues.admit(task("c", 1), OverflowPolicy::Drop) {
+            Admission::Evicted(evicted) => assert_eq!(evicted.id, "a"),
+            _ => panic!("expected the oldest task to be evicted"),
+        }
+
+        // Held tasks join once a dispatch makes room
+        queues.admit_or_hold(task("d", 1));
+        assert_eq!(queues.len(), 3);
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "b");
+        assert_eq!(queues.lengths()[0], ("gpu-0".to_string(), 2));
+        assert_eq!(queues.in_flight(), 1);
+
+        let mut empty = RunQueues::new(0);
+        empty.sync(vec![slot("gpu-0")]);
+        assert!(matches!(empty.admit(task("e", 1), OverflowPolicy::Drop), Admission::Full { .. }));
+    }
+
+    #[test]
+    fn test_idle_device_steals_unpinned_work() {
+        let mut queues = RunQueues::new(10);
+        queues.sync(vec![slot("gpu-0")]);
+        queues.admit(task("big", 4), OverflowPolicy::Reject);
+        queues.admit(task("small", 1), OverflowPolicy::Reject);
+        let mut pinned = task("pinned", 1);
+        pinned.device_id = Some("gpu-0".to_string());
+        queues.admit(pinned, OverflowPolicy::Reject);
+
+        // gpu-1 joins with an empty queue
+        queues.sync(vec![slot("gpu-0"), slot("gpu-1")]);
+        let first = queues.next(fifo, 10).unwrap();
+        assert_eq!((first.id.as_str(), first.device_id.as_deref()), ("big", Some("gpu-0")));
+
+        // gpu-0 is full, so gpu-1 steals the last unpinned task it can run
+        let stolen = queues.next(fifo, 10).unwrap();
+        assert_eq!((stolen.id.as_str(), stolen.device_id.as_deref()), ("small", Some("gpu-1")));
+        assert!(queues.next(fifo, 10).is_none());
+
+        queues.finish("big");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "pinned");
+        assert!(queues.next(fifo, 2).is_none(), "limited by tasks in flight");
+    }
+
+    #[test]
+    fn test_tasks_over_the_per_task_share_are_not_placed() {
+        use crate::hardware::abstract_device::SharingPolicy;
+
+        let capped = DeviceSlot {
+            quota: DeviceQuota::new(
+                Capacity { memory: 1024, compute_units: 4 },
+                &SharingPolicy {
+                    max_share: 0.5,
+                    ..SharingPolicy::default()
+                },
+            ),
+            ..slot("gpu-0")
+        };
+        let mut queues = RunQueues::new(10);
+        queues.sync(vec![capped]);
+        queues.admit(task("half", 2), OverflowPolicy::Reject);
+        queues.admit(task("whole", 4), OverflowPolicy::Reject);
+        assert_eq!(queues.lengths(), vec![("gpu-0".to_string(), 1), (UNPLACED_QUEUE.to_string(), 1)]);
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "half");
+
+        // Handed out unpinned, so the executor reports why no lease covers it
+        let whole = queues.next(fifo, 10).unwrap();
+        assert_eq!((whole.id.as_str(), whole.device_id), ("whole", None));
+    }
+
+    #[test]
+    fn test_gang_waits_for_every_member() {
+        let mut queues = RunQueues::new(10);
+        queues.sync(vec![slot("gpu-0"), slot("gpu-1")]);
+        let mut gang = task("gang", 0);
+        gang.requirements.gang = vec![GangMember {
+            hardware_type: HardwareType::Gpu,
+            count: 2,
+            memory_required: 512,
+            compute_units: 2,
+        }];
+        let mut too_big = gang.clone();
+        too_big.id = "too-big".to_string();
+        too_big.requirements.gang[0].count = 3;
+        queues.admit(task("busy", 3), OverflowPolicy::Reject);
+        queues.admit(gang, OverflowPolicy::Reject);
+        queues.admit(too_big, OverflowPolicy::Reject);
+        assert_eq!(queues.lengths()[2], (UNPLACED_QUEUE.to_string(), 2));
+
+        // One device is short of room, so only the gang that can never run goes out
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "busy");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "too-big");
+        assert!(queues.next(fifo, 10).is_none());
+
+        queues.finish("busy");
+        let gang = queues.next(fifo, 10).unwrap();
+        assert_eq!((gang.id.as_str(), gang.device_id.as_deref()), ("gang", None));
+        assert!(queues.next(fifo, 10).is_none());
+        queues.admit(task("after", 3), OverflowPolicy::Reject);
+        assert!(queues.next(fifo, 10).is_none(), "both devices are held by the gang");
+        queues.finish("gang");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "after");
+    }
+
+    #[test]
+    fn test_small_gang_members_leave_the_large_device_to_large_ones() {
+        let small = DeviceSlot {
+            quota: DeviceQuota::whole(Capacity { memory: 256, compute_units: 4 }),
+            ..slot("gpu-1")
+        };
+        let mut queues = RunQueues::new(10);
+        queues.sync(vec![slot("gpu-0"), small]);
+        let member = |memory_required| GangMember {
+            hardware_type: HardwareType::Gpu,
+            count: 1,
+            memory_required,
+            compute_units: 1,
+        };
+        let mut gang = task("gang", 0);
+        gang.requirements.gang = vec![member(128), member(512)];
+
+        // Taken in member order, the small member would claim gpu-0 first
+        let held = queues.match_gang(&gang, true).unwrap();
+        assert_eq!(
+            held,
+            vec![
+                ("gpu-1".to_string(), Capacity { memory: 128, compute_units: 1 }),
+                ("gpu-0".to_string(), Capacity { memory: 512, compute_units: 1 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_energy_placement_under_a_power_cap() {
+        let gpu = |id: &str, limit| {
+            let mut config = GpuConfig::default();
+            config.performance.power.limit = limit;
+            config.performance.power.idle = 50;
+            DeviceSlot {
+                device: Arc::new(GpuDevice::with_config(id.to_string(), id.to_string(), config)),
+                ..slot(id)
+            }
+        };
+        let add = |id: &str| {
+            let input = Tensor::new(vec![4096], vec![1.0; 4096]).unwrap();
+            task(id, 1).with_operation("add", Tensor::encode_all(&[input.clone(), input]))
+        };
+        let pinned = |id: &str, device: &str| {
+            let mut task = add(id);
+            task.device_id = Some(device.to_string());
+            task
+        };
+        let energy = EnergyConfig {
+            objective: PlacementObjective::Energy,
+            power_cap: 500.0,
+        };
+        let mut queues = RunQueues::new(10).with_energy(energy);
+        queues.sync(vec![gpu("gpu-0", 300), gpu("gpu-1", 150), gpu("gpu-2", 300)]);
+        assert_eq!(queues.power_draw(), 150.0);
+
+        // The frugal GPU gets both tasks even though its queue is longer
+        queues.admit(add("a"), OverflowPolicy::Reject);
+        queues.admit(add("b"), OverflowPolicy::Reject);
+        assert_eq!(queues.lengths()[1], ("gpu-1".to_string(), 2));
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "a");
+        assert_eq!(queues.power_draw(), 250.0);
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "b");
+
+        // Waking gpu-0 reaches the cap, so gpu-2 has to wait for it to go idle
+        queues.admit(pinned("c", "gpu-0"), OverflowPolicy::Reject);
+        queues.admit(pinned("d", "gpu-2"), OverflowPolicy::Reject);
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "c");
+        assert_eq!(queues.power_draw(), 500.0);
+        assert!(queues.next(fifo, 10).is_none());
+        queues.finish("c");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "d");
+    }
+
+    #[test]
+    fn test_hot_devices_throttle_and_critical_ones_hand_work_on() {
+        let with = |id: &str, thermal| DeviceSlot { thermal, ..slot(id) };
+        let pinned = |id: &str, device: &str| {
+            let mut task = task(id, 1);
+            task.device_id = Some(device.to_string());
+            task
+        };
+        let mut queues = RunQueues::new(10);
+        queues.sync(vec![with("gpu-0", ThermalState::Hot), with("gpu-1", ThermalState::Normal)]);
+        queues.admit(pinned("p1", "gpu-0"), OverflowPolicy::Reject);
+        queues.admit(pinned("p2", "gpu-0"), OverflowPolicy::Reject);
+        queues.admit(task("a", 1), OverflowPolicy::Reject);
+        queues.admit(task("b", 1), OverflowPolicy::Reject);
+        assert_eq!(queues.lengths()[1], ("gpu-1".to_string(), 2), "new work avoids the hot device");
+
+        // The hot device runs one task at a time
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "p1");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "a");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "b");
+        assert!(queues.next(fifo, 10).is_none());
+        queues.finish("p1");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "p2");
+
+        // Crossing the critical threshold moves unpinned work off gpu-1
+        queues.admit(task("c", 1), OverflowPolicy::Reject);
+        queues.admit(pinned("d", "gpu-1"), OverflowPolicy::Reject);
+        queues.sync(vec![with("gpu-0", ThermalState::Hot), with("gpu-1", ThermalState::Critical)]);
+        assert_eq!(queues.lengths()[..2], [("gpu-0".to_string(), 1), ("gpu-1".to_string(), 1)]);
+        assert!(queues.next(fifo, 10).is_none());
+        queues.finish("p2");
+        assert_eq!(queues.next(fifo, 10).unwrap().id, "c");
+    }
+}
@@ -0,0 +1,165 @@
+//! Weighted fair queuing across priority levels
+//!
+//! Each queued task gets a virtual start and finish time, as in start-time fair
+//! queuing. A level's tasks are spaced `TURN / weight` apart in virtual time, so with
+//! every level busy, each level is dispatched in proportion to its weight. The task
+//! with the earliest finish time goes first. A level that was idle starts again from
+//! the current virtual time instead of having built up credit, so a background task
+//! waits behind at most one turn's worth of work from each other level.
+//!
+//! Aging moves a waiting task ahead by one turn of its level for each
+//! `aging.interval` it has waited.
+
+use crate::scheduling::scheduler_config::PrioritiesConfig;
+use crate::scheduling::task_scheduler::{Task, TaskPriority};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Virtual time one task takes at weight 1
+const TURN: u64 = 1_000_000;
+
+struct Tag {
+    start: u64,
+    finish: u64,
+    /// Virtual time between two tasks of the level
+    turn: u64,
+    queued_at: Instant,
+}
+
+/// Virtual-time bookkeeping for the tasks waiting in the run queues
+pub(crate) struct FairShare {
+    weights: HashMap<TaskPriority, u64>,
+    aging_interval: Option<Duration>,
+    /// Start time of the last dispatched task
+    virtual_time: u64,
+    /// Finish time of the last task queued at each level
+    last_finish: HashMap<TaskPriority, u64>,
+    tags: HashMap<String, Tag>,
+}
+
+impl FairShare {
+    pub fn new(config: &PrioritiesConfig) -> Self {
+        Self {
+            weights: TaskPriority::ALL
+                .iter()
+                .map(|priority| (*priority, config.weight(*priority).max(1)))
+                .collect(),
+            aging_interval: (config.aging.interval > 0).then(|| Duration::from_millis(config.aging.interval)),
+            virtual_time: 0,
+            last_finish: HashMap::new(),
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Give a task joining the queues its virtual start and finish times
+    pub fn enqueue(&mut self, task: &Task, now: Instant) {
+        if self.tags.contains_key(&task.id) {
+            return;
+        }
+        let turn = TURN / self.weights[&task.priority];
+        let last_finish = self.last_finish.entry(task.priority).or_default();
+        let start = self.virtual_time.max(*last_finish);
+        let finish = start + turn;
+        *last_finish = finish;
+        self.tags.insert(
+            task.id.clone(),
+            Tag {
+                start,
+                finish,
+                turn,
+                queued_at: now,
+            },
+        );
+    }
+
+    /// Dispatch order of a queued task; the smallest goes first
+    pub fn key(&self, task: &Task, now: Instant) -> u64 {
+        let Some(tag) = self.tags.get(&task.id) else {
+            return u64::MAX;
+        };
+        let turns_waited = match self.aging_interval {
+            Some(interval) => (now.saturating_duration_since(tag.queued_at).as_millis() / interval.as_millis()) as u64,
+            None => 0,
+        };
+        tag.finish.saturating_sub(turns_waited.saturating_mul(tag.turn))
+    }
+
+    /// Advance virtual time past a dispatched task
+    pub fn dispatched(&mut self, task_id: &str) {
+        if let Some(tag) = self.tags.remove(task_id) {
+            self.virtual_time = self.virtual_time.max(tag.start);
+        }
+    }
+
+    /// Forget a task that left the queues without being dispatched
+    pub fn remove(&mut self, task_id: &str) {
+        self.tags.remove(task_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{HardwareType, TaskRequirements};
+    use crate::scheduling::scheduler_config::AgingConfig;
+
+    fn task(id: &str, priority: TaskPriority) -> Task {
+        let requirements = TaskRequirements {
+            priority: priority as u8,
+            ..TaskRequirements::for_test(HardwareType::Cpu)
+        };
+        Task::new(id.to_string(), id.to_string(), requirements, priority)
+    }
+
+    /// Dispatch everything queued, returning the order
+    fn drain(fair: &mut FairShare, queued: &mut Vec<Task>, now: Instant) -> Vec<String> {
+        let mut order = Vec::new();
+        while !queued.is_empty() {
+            let next = (0..queued.len()).min_by_key(|&i| (fair.key(&queued[i], now), i)).unwrap();
+            let task = queued.remove(next);
+            fair.dispatched(&task.id);
+            order.push(task.id);
+        }
+        order
+    }
+
+    #[test]
+    fn test_levels_share_dispatches_by_weight() {
+        let mut fair = FairShare::new(&PrioritiesConfig::default());
+        let now = Instant::now();
+        let mut queued = Vec::new();
+        for i in 0..20 {
+            queued.push(task(&format!("critical-{}", i), TaskPriority::Critical));
+        }
+        queued.push(task("background", TaskPriority::Background));
+        for t in &queued {
+            fair.enqueue(t, now);
+        }
+
+        // Background gets one turn per ten critical ones
+        let order = drain(&mut fair, &mut queued, now);
+        assert_eq!(order.iter().position(|id| id == "background"), Some(10));
+    }
+
+    #[test]
+    fn test_aging_moves_waiting_tasks_ahead() {
+        let config = PrioritiesConfig {
+            aging: AgingConfig { interval: 100 },
+            ..PrioritiesConfig::default()
+        };
+        let mut fair = FairShare::new(&config);
+        let start = Instant::now();
+        let mut queued = vec![task("background", TaskPriority::Background)];
+        fair.enqueue(&queued[0], start);
+
+        // Critical work queued later still loses to a task that waited long enough
+        let later = start + Duration::from_millis(200);
+        for i in 0..20 {
+            let critical = task(&format!("critical-{}", i), TaskPriority::Critical);
+            fair.enqueue(&critical, later);
+            queued.push(critical);
+        }
+        let order = drain(&mut fair, &mut queued, later);
+        assert_eq!(order[0], "background");
+    }
+}
@@ -0,0 +1,146 @@
+//! Retries and dead letters
+//!
+//! A task that fails with a retryable error waits here until its backoff delay has
+//! passed and is then queued again. A task that runs out of attempts is moved to the
+//! dead-letter queue, where it stays until someone inspects or takes it.
+
+use crate::scheduling::task_scheduler::Task;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+/// What the scheduler did with a failed task
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailureAction {
+    /// The task runs again once the delay has passed
+    Retried { delay: Duration },
+    /// The task used up its attempts and was moved to the dead-letter queue
+    DeadLettered,
+    /// The error is permanent, so the task failed without another attempt
+    Failed,
+    /// The task was cancelled while it ran
+    Cancelled,
+}
+
+/// A task that failed on every attempt
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub task: Task,
+    pub attempts: u32,
+    /// Error of the last attempt
+    pub error: String,
+    pub failed_at: SystemTime,
+}
+
+/// Tasks waiting for a retry, and the dead-letter queue
+pub(crate) struct RetryQueue {
+    delayed: Vec<(Instant, Task)>,
+    dead: VecDeque<DeadLetter>,
+    /// Most dead letters kept; the oldest is discarded beyond this
+    dead_capacity: usize,
+}
+
+impl RetryQueue {
+    pub fn new(dead_capacity: usize) -> Self {
+        Self {
+            delayed: Vec::new(),
+            dead: VecDeque::new(),
+            dead_capacity,
+        }
+    }
+
+    /// Hold a task until `due`
+    pub fn delay(&mut self, task: Task, due: Instant) {
+        self.delayed.push((due, task));
+    }
+
+    /// Take every task whose delay has passed, earliest first
+    pub fn due(&mut self, now: Instant) -> Vec<Task> {
+        let mut due: Vec<(Instant, Task)> = Vec::new();
+        let mut index = 0;
+        while index < self.delayed.len() {
+            if self.delayed[index].0 <= now {
+                due.push(self.delayed.swap_remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        due.sort_by_key(|(at, _)| *at);
+        due.into_iter().map(|(_, task)| task).collect()
+    }
+
+    /// When the next delayed task becomes due
+    pub fn next_due(&self) -> Option<Instant> {
+        self.delayed.iter().map(|(due, _)| *due).min()
+    }
+
+    /// Number of tasks waiting for a retry
+    pub fn len(&self) -> usize {
+        self.delayed.len()
+    }
+
+    /// Drop a task waiting for a retry
+    pub fn remove(&mut self, task_id: &str) -> Option<Task> {
+        let index = self.delayed.iter().position(|(_, task)| task.id == task_id)?;
+        Some(self.delayed.swap_remove(index).1)
+    }
+
+    /// Move a task to the dead-letter queue
+    pub fn bury(&mut self, letter: DeadLetter) {
+        if self.dead.len() >= self.dead_capacity {
+            if let Some(discarded) = self.dead.pop_front() {
+                warn!("Dead-letter queue full, discarded task {}", discarded.task.id);
+            }
+        }
+        self.dead.push_back(letter);
+    }
+
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead.iter().cloned().collect()
+    }
+
+    pub fn take_dead_letters(&mut self) -> Vec<DeadLetter> {
+        self.dead.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{HardwareType, TaskRequirements};
+    use crate::scheduling::TaskPriority;
+
+    fn task(id: &str) -> Task {
+        let requirements = TaskRequirements::for_test(HardwareType::Cpu);
+        Task::new(id.to_string(), id.to_string(), requirements, TaskPriority::Normal)
+    }
+
+    #[test]
+    fn test_delayed_tasks_come_back_in_due_order() {
+        let mut queue = RetryQueue::new(1);
+        let now = Instant::now();
+        queue.delay(task("late"), now + Duration::from_millis(20));
+        queue.delay(task("later"), now + Duration::from_secs(60));
+        queue.delay(task("early"), now + Duration::from_millis(10));
+        assert_eq!(queue.next_due(), Some(now + Duration::from_millis(10)));
+
+        let due: Vec<String> = queue.due(now + Duration::from_millis(30)).into_iter().map(|t| t.id).collect();
+        assert_eq!(due, vec!["early", "late"]);
+        assert_eq!(queue.len(), 1);
+        assert!(queue.remove("later").is_some());
+        assert_eq!(queue.next_due(), None);
+
+        // Only the newest dead letter fits
+        for id in ["a", "b"] {
+            queue.bury(DeadLetter {
+                task: task(id),
+                attempts: 3,
+                error: "boom".to_string(),
+                failed_at: SystemTime::now(),
+            });
+        }
+        assert_eq!(queue.dead_letters()[0].task.id, "b");
+        assert_eq!(queue.take_dead_letters().len(), 1);
+        assert!(queue.dead_letters().is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+// Cost-model-driven list scheduling (HEFT and PEFT)
+
+use crate::errors::SchedulingError;
+use crate::hardware::abstract_device::{HardwareDevice, HardwareType, Kernel, Operation, Tensor};
+use crate::scheduling::task_graph::TaskGraph;
+use crate::scheduling::task_scheduler::Task;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How a list scheduler ranks tasks and picks devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListHeuristic {
+    /// Heterogeneous Earliest Finish Time: rank by upward rank, place on the earliest finish
+    Heft,
+    /// Predict Earliest Finish Time: rank and place using the optimistic cost table
+    Peft,
+}
+
+/// Per-operation, per-device execution estimates and transfer costs
+///
+/// Execution time comes from, in order: an estimate set with `with_estimate` for the
+/// operation and device type, the device's own model, then the task's
+/// `estimated_duration`. Copying the task's own payload to the device is added on top.
+/// Moving a result between two devices is charged as a copy to the host and a copy
+/// from it.
+pub struct CostModel {
+    devices: Vec<Arc<dyn HardwareDevice>>,
+    estimates: HashMap<(String, HardwareType), Duration>,
+}
+
+impl CostModel {
+    /// Build a cost model over the devices a plan may use
+    pub fn new(devices: Vec<Arc<dyn HardwareDevice>>) -> Self {
+        Self {
+            devices,
+            estimates: HashMap::new(),
+        }
+    }
+
+    /// Use a fixed execution time for an operation on a device type
+    pub fn with_estimate(mut self, operation: &str, device_type: HardwareType, estimate: Duration) -> Self {
+        self.estimates.insert((operation.to_string(), device_type), estimate);
+        self
+    }
+
+    /// Use fixed execution times for several operations
+    pub fn with_estimates(mut self, estimates: HashMap<(String, HardwareType), Duration>) -> Self {
+        self.estimates.extend(estimates);
+        self
+    }
+
+    /// Devices the model covers
+    pub fn devices(&self) -> &[Arc<dyn HardwareDevice>] {
+        &self.devices
+    }
+
+    /// Execution time of a task on a device, or `None` if the device cannot run it
+    ///
+    /// Only devices of the task's required hardware type can run it.
+    pub fn execution_time(&self, task: &Task, device: &dyn HardwareDevice) -> Option<Duration> {
+        let fits = task.requirements.memory_required <= device.total_memory() / (1024 * 1024);
+        if device.device_type() != task.requirements.hardware_type || !fits || !device.capabilities().supports(&task.operation) {
+            return None;
+        }
+        // Devices that cannot load the kernel, e.g. FPGAs without its bitstream, are out
+        device.setup_cost(task.requirements.kernel.as_deref().unwrap_or(&task.operation))?;
+
+        let estimate = self
+            .estimates
+            .get(&(task.operation.clone(), device.device_type()))
+            .copied()
+            .or_else(|| device.estimate_execution(&task.to_device_task()))
+            .unwrap_or(Duration::from_millis(task.estimated_duration));
+        Some(estimate + device.transfer_time(task.data.len() as u64))
+    }
+
+    /// Time to move `bytes` from one device to another
+    pub fn transfer_time(&self, bytes: u64, from: &dyn HardwareDevice, to: &dyn HardwareDevice) -> Duration {
+        if from.id() == to.id() {
+            return Duration::ZERO;
+        }
+        from.transfer_time(bytes) + to.transfer_time(bytes)
+    }
+}
+
+/// Placement of one task in a schedule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub task_id: String,
+    pub device_id: String,
+
+    /// Predicted start, relative to the start of the schedule
+    pub start: Duration,
+
+    /// Predicted finish, relative to the start of the schedule
+    pub finish: Duration,
+}
+
+/// Predicted placement and timing of every task in a graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    /// Assignments ordered by predicted start
+    pub assignments: Vec<Assignment>,
+
+    /// Predicted finish of the last task
+    pub makespan: Duration,
+}
+
+impl Schedule {
+    /// Placement of one task
+    pub fn assignment(&self, task_id: &str) -> Option<&Assignment> {
+        self.assignments.iter().find(|assignment| assignment.task_id == task_id)
+    }
+}
+
+/// Estimated size of a task's output, used to price the edges leaving it
+fn output_bytes(task: &Task) -> u64 {
+    let output = Operation::parse(&task.operation)
+        .ok()
+        .filter(|operation| *operation != Operation::Compute)
+        .and_then(|operation| Kernel::new(operation, Tensor::decode_all(&task.data).ok()?).ok())
+        .map(|kernel| kernel.output_shape().iter().product::<usize>() * 4);
+    output.unwrap_or(task.data.len()) as u64
+}
+
+/// Graph flattened to indices, with every cost the heuristics need
+struct Problem<'a> {
+    tasks: Vec<&'a Task>,
+    /// Predecessors of each task and whether the edge carries data
+    preds: Vec<Vec<(usize, bool)>>,
+    succs: Vec<Vec<(usize, bool)>>,
+    /// Execution time of each task on each device, in seconds
+    cost: Vec<Vec<Option<f64>>>,
+    /// Output size of each task in bytes
+    output: Vec<u64>,
+    order: Vec<usize>,
+}
+
+impl<'a> Problem<'a> {
+    fn new(graph: &'a TaskGraph, model: &CostModel) -> Result<Self, SchedulingError> {
+        let order_ids = graph.topological_order()?;
+        let ids: Vec<&str> = graph.task_ids().collect();
+        let index: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let tasks: Vec<&Task> = ids.iter().map(|id| graph.task(id).unwrap()).collect();
+
+        let mut preds = vec![Vec::new(); tasks.len()];
+        let mut succs = vec![Vec::new(); tasks.len()];
+        for (i, id) in ids.iter().enumerate() {
+            for dependency in graph.dependencies(id).unwrap_or_default() {
+                let pred = index[dependency.task_id.as_str()];
+                preds[i].push((pred, dependency.passes_data));
+                succs[pred].push((i, dependency.passes_data));
+            }
+        }
+
+        let mut cost = Vec::with_capacity(tasks.len());
+        for task in &tasks {
+            let row: Vec<Option<f64>> = model
+                .devices()
+                .iter()
+                .map(|device| model.execution_time(task, device.as_ref()).map(|time| time.as_secs_f64()))
+                .collect();
+            if row.iter().all(Option::is_none) {
+                return Err(SchedulingError::NoEligibleDevice(task.id.clone()));
+            }
+            cost.push(row);
+        }
+
+        Ok(Self {
+            output: tasks.iter().map(|task| output_bytes(task)).collect(),
+            order: order_ids.iter().map(|id| index[id.as_str()]).collect(),
+            tasks,
+            preds,
+            succs,
+            cost,
+        })
+    }
+
+    /// Transfer time in seconds along an edge between two devices
+    fn comm(&self, model: &CostModel, from_task: usize, passes_data: bool, from: usize, to: usize) -> f64 {
+        if !passes_data {
+            return 0.0;
+        }
+        let devices = model.devices();
+        model
+            .transfer_time(self.output[from_task], devices[from].as_ref(), devices[to].as_ref())
+            .as_secs_f64()
+    }
+
+    /// Mean transfer time along an edge over every pair of distinct devices
+    fn mean_comm(&self, model: &CostModel, from_task: usize, passes_data: bool) -> f64 {
+        let n = model.devices().len();
+        if !passes_data || n < 2 {
+            return 0.0;
+        }
+        let mut total = 0.0;
+        for from in 0..n {
+            for to in (0..n).filter(|&to| to != from) {
+                total += self.comm(model, from_task, true, from, to);
+            }
+        }
+        total / (n * (n - 1)) as f64
+    }
+
+    fn mean_cost(&self, task: usize) -> f64 {
+        let eligible: Vec<f64> = self.cost[task].iter().flatten().copied().collect();
+        eligible.iter().sum::<f64>() / eligible.len() as f64
+    }
+
+    /// HEFT upward rank: mean cost plus the longest mean path to an exit task
+    fn upward_ranks(&self, model: &CostModel) -> Vec<f64> {
+        let mut rank = vec![0.0; self.tasks.len()];
+        for &task in self.order.iter().rev() {
+            let tail = self.succs[task]
+                .iter()
+                .map(|&(succ, data)| self.mean_comm(model, task, data) + rank[succ])
+                .fold(0.0, f64::max);
+            rank[task] = self.mean_cost(task) + tail;
+        }
+        rank
+    }
+
+    /// PEFT optimistic cost table: shortest remaining path if the task runs on each device
+    fn optimistic_costs(&self, model: &CostModel) -> Vec<Vec<f64>> {
+        let devices = model.devices().len();
+        let mut oct = vec![vec![0.0; devices]; self.tasks.len()];
+        for &task in self.order.iter().rev() {
+            for device in 0..devices {
+                oct[task][device] = self.succs[task]
+                    .iter()
+                    .map(|&(succ, data)| {
+                        (0..devices)
+                            .filter_map(|other| {
+                                let run = self.cost[succ][other]?;
+                                let comm = if other == device { 0.0 } else { self.mean_comm(model, task, data) };
+                                Some(oct[succ][other] + run + comm)
+                            })
+                            .fold(f64::INFINITY, f64::min)
+                    })
+                    .fold(0.0, f64::max);
+            }
+        }
+        oct
+    }
+}
+
+/// Plan a graph over the devices of a cost model
+///
+/// Tasks become ready once their predecessors are placed, and the ready task with
+/// the highest rank is placed next. Each device runs one task at a time in the
+/// model, and a task may fill an idle gap left earlier on a device.
+pub fn plan(graph: &TaskGraph, model: &CostModel, heuristic: ListHeuristic) -> Result<Schedule, SchedulingError> {
+    let problem = Problem::new(graph, model)?;
+    let devices = model.devices();
+    let n = problem.tasks.len();
+
+    let oct = problem.optimistic_costs(model);
+    let rank: Vec<f64> = match heuristic {
+        ListHeuristic::Heft => problem.upward_ranks(model),
+        ListHeuristic::Peft => (0..n)
+            .map(|task| {
+                let eligible: Vec<f64> = (0..devices.len())
+                    .filter(|&device| problem.cost[task][device].is_some())
+                    .map(|device| oct[task][device])
+                    .collect();
+                eligible.iter().sum::<f64>() / eligible.len() as f64
+            })
+            .collect(),
+    };
+
+    // Busy intervals of each device, kept sorted by start
+    let mut busy: Vec<Vec<(f64, f64)>> = vec![Vec::new(); devices.len()];
+    let mut placed: Vec<Option<(usize, f64, f64)>> = vec![None; n];
+    let mut waiting: Vec<usize> = problem.preds.iter().map(Vec::len).collect();
+    let mut ready: Vec<usize> = (0..n).filter(|&task| waiting[task] == 0).collect();
+
+    while !ready.is_empty() {
+        // Highest rank first, graph insertion order on ties
+        let position = (0..ready.len())
+            .max_by(|&a, &b| rank[ready[a]].total_cmp(&rank[ready[b]]).then(ready[b].cmp(&ready[a])))
+            .unwrap();
+        let task = ready.swap_remove(position);
+
+        let mut best: Option<(f64, usize, f64, f64)> = None;
+        for device in 0..devices.len() {
+            let Some(run) = problem.cost[task][device] else {
+                continue;
+            };
+            let data_ready = problem.preds[task]
+                .iter()
+                .map(|&(pred, data)| {
+                    let (pred_device, _, finish) = placed[pred].unwrap();
+                    finish + problem.comm(model, pred, data, pred_device, device)
+                })
+                .fold(0.0, f64::max);
+            let start = earliest_gap(&busy[device], data_ready, run);
+            let finish = start + run;
+            let score = match heuristic {
+                ListHeuristic::Heft => finish,
+                ListHeuristic::Peft => finish + oct[task][device],
+            };
+            if best.is_none_or(|(best_score, ..)| score < best_score) {
+                best = Some((score, device, start, finish));
+            }
+        }
+
+        let (_, device, start, finish) = best.expect("every task has an eligible device");
+        let slot = busy[device].partition_point(|&(begin, _)| begin <= start);
+        busy[device].insert(slot, (start, finish));
+        placed[task] = Some((device, start, finish));
+
+        for &(succ, _) in &problem.succs[task] {
+            waiting[succ] -= 1;
+            if waiting[succ] == 0 {
+                ready.push(succ);
+            }
+        }
+    }
+
+    let mut assignments: Vec<Assignment> = placed
+        .iter()
+        .enumerate()
+        .map(|(task, placement)| {
+            let (device, start, finish) = placement.unwrap();
+            Assignment {
+                task_id: problem.tasks[task].id.clone(),
+                device_id: devices[device].id().to_string(),
+                start: Duration::from_secs_f64(start),
+                finish: Duration::from_secs_f64(finish),
+            }
+        })
+        .collect();
+    assignments.sort_by_key(|assignment| (assignment.start, assignment.finish));
+    let makespan = assignments.iter().map(|assignment| assignment.finish).max().unwrap_or_default();
+
+    Ok(Schedule { assignments, makespan })
+}
+
+/// Earliest start at or after `ready` where a task of length `run` fits on a device
+fn earliest_gap(busy: &[(f64, f64)], ready: f64, run: f64) -> f64 {
+    let mut start = ready;
+    for &(begin, end) in busy {
+        if start + run <= begin {
+            break;
+        }
+        start = start.max(end);
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::TaskRequirements;
+    use crate::hardware::cpu::CpuDevice;
+    use crate::hardware::gpu::GpuDevice;
+    use crate::scheduling::TaskPriority;
+
+    fn task(id: &str, operation: &str, hardware_type: HardwareType) -> Task {
+        let requirements = TaskRequirements::for_test(hardware_type);
+        Task::new(id.to_string(), id.to_string(), requirements, TaskPriority::Normal).with_operation(operation, vec![])
+    }
+
+    fn model() -> CostModel {
+        let cpu = CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string());
+        cpu.initialize_cpu().unwrap();
+        let gpu = |id: &str| Arc::new(GpuDevice::new(id.to_string(), "Test GPU".to_string()));
+        let ms = Duration::from_millis;
+        CostModel::new(vec![Arc::new(cpu), gpu("gpu-0"), gpu("gpu-1")])
+            .with_estimate("add", HardwareType::Cpu, ms(2))
+            .with_estimate("add", HardwareType::Gpu, ms(3))
+            .with_estimate("gemm", HardwareType::Cpu, ms(40))
+            .with_estimate("gemm", HardwareType::Gpu, ms(5))
+    }
+
+    #[test]
+    fn test_earliest_gap_fills_idle_time() {
+        let busy = [(0.0, 1.0), (3.0, 4.0)];
+        assert_eq!(earliest_gap(&busy, 0.5, 1.0), 1.0);
+        assert_eq!(earliest_gap(&busy, 0.5, 2.5), 4.0);
+        assert_eq!(earliest_gap(&[], 2.0, 1.0), 2.0);
+    }
+
+    #[test]
+    fn test_plans_place_each_task_on_its_fastest_device() {
+        // Two independent pre-processing steps on the GPUs fan into a GEMM
+        let mut graph = TaskGraph::new();
+        graph.add_task(task("left", "add", HardwareType::Gpu)).unwrap();
+        graph.add_task(task("right", "add", HardwareType::Gpu)).unwrap();
+        graph.add_task(task("gemm", "gemm", HardwareType::Gpu)).unwrap();
+        graph.add_dependency("gemm", "left").unwrap();
+        graph.add_dependency("gemm", "right").unwrap();
+        // The CPU is slower at a GEMM, but this one asks for a CPU
+        graph.add_task(task("host", "gemm", HardwareType::Cpu)).unwrap();
+
+        for heuristic in [ListHeuristic::Heft, ListHeuristic::Peft] {
+            let schedule = plan(&graph, &model(), heuristic).unwrap();
+            // One add runs on each GPU rather than queueing both on one
+            let devices: Vec<&str> = ["left", "right"]
+                .iter()
+                .map(|id| schedule.assignment(id).unwrap().device_id.as_str())
+                .collect();
+            assert!(devices.contains(&"gpu-0") && devices.contains(&"gpu-1"), "{:?}", heuristic);
+            let gemm = schedule.assignment("gemm").unwrap();
+            assert!(gemm.device_id.starts_with("gpu-"));
+            assert_eq!(gemm.finish, Duration::from_millis(8));
+            assert_eq!(schedule.assignment("host").unwrap().device_id, "cpu-0");
+            assert_eq!(schedule.makespan, Duration::from_millis(40));
+        }
+
+        let mut unknown = TaskGraph::new();
+        unknown.add_task(task("fft", "fft", HardwareType::Cpu)).unwrap();
+        assert_eq!(
+            plan(&unknown, &model(), ListHeuristic::Heft),
+            Err(SchedulingError::NoEligibleDevice("fft".to_string()))
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Data locality
+//!
+//! Tasks name the data objects they read and write. An object whose `location` is the
+//! id of a registered device is resident on that device; any other location is host
+//! storage. Inputs copied to a device for a task that then ran there stay on it as
+//! replicas, which the scheduler tracks next to the location without changing it.
+//! Running a task on a device means first copying every input held elsewhere:
+//! one copy over the device's host link from host storage, or a copy to the host and
+//! on from there between two devices. The run queues prefer the device whose inputs
+//! are cheapest to gather, and a dispatched task carries the copies its device needs.
+
+use crate::data::DataObject;
+use crate::hardware::abstract_device::HardwareDevice;
+use crate::scheduling::task_scheduler::Task;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Location of data held in host storage rather than on a device
+pub const HOST: &str = "host";
+
+/// One copy of a data object made before a task runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub data_id: String,
+    pub bytes: u64,
+
+    /// Device the object is copied from, or `HOST`
+    pub from: String,
+
+    /// Device the task runs on
+    pub to: String,
+
+    /// Modelled time the copy takes
+    pub estimate: Duration,
+}
+
+/// A data object's size, the device holding it if any, and the devices with a replica
+struct Resident {
+    size: u64,
+    device: Option<Arc<dyn HardwareDevice>>,
+    replicas: HashSet<String>,
+}
+
+/// Where the data objects tasks read are held
+#[derive(Default)]
+pub(crate) struct Locality {
+    objects: HashMap<String, Resident>,
+}
+
+impl Locality {
+    /// Resolve the objects' locations against the registered devices
+    ///
+    /// `replicas` maps an object to the devices holding a copy besides its location.
+    pub fn new(
+        objects: Vec<DataObject>,
+        mut replicas: HashMap<String, HashSet<String>>,
+        devices: &[Arc<dyn HardwareDevice>],
+    ) -> Self {
+        let objects = objects
+            .into_iter()
+            .map(|object| {
+                let device = devices.iter().find(|device| device.id() == object.location).cloned();
+                let replicas = replicas.remove(&object.id).unwrap_or_default();
+                (object.id, Resident { size: object.size, device, replicas })
+            })
+            .collect();
+        Self { objects }
+    }
+
+    /// Copies of the task's inputs that running it on `to` needs
+    ///
+    /// Inputs already on `to`, as their location or a replica, and inputs nobody has
+    /// registered need none.
+    pub fn transfers(&self, task: &Task, to: &dyn HardwareDevice) -> Vec<Transfer> {
+        task.inputs
+            .iter()
+            .filter_map(|data_id| {
+                let resident = self.objects.get(data_id)?;
+                if resident.replicas.contains(to.id()) {
+                    return None;
+                }
+                let (from, estimate) = match &resident.device {
+                    Some(device) if device.id() == to.id() => return None,
+                    Some(device) => (
+                        device.id().to_string(),
+                        device.transfer_time(resident.size) + to.transfer_time(resident.size),
+                    ),
+                    None => (HOST.to_string(), to.transfer_time(resident.size)),
+                };
+                Some(Transfer {
+                    data_id: data_id.clone(),
+                    bytes: resident.size,
+                    from,
+                    to: to.id().to_string(),
+                    estimate,
+                })
+            })
+            .collect()
+    }
+
+    /// Modelled time to gather the task's inputs on `to`
+    pub fn cost(&self, task: &Task, to: &dyn HardwareDevice) -> Duration {
+        self.transfers(task, to).iter().map(|transfer| transfer.estimate).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataFormat;
+    use crate::hardware::abstract_device::{HardwareType, TaskRequirements};
+    use crate::hardware::gpu::GpuDevice;
+    use crate::scheduling::TaskPriority;
+
+    fn object(id: &str, size: u64, location: &str) -> DataObject {
+        DataObject {
+            id: id.to_string(),
+            name: id.to_string(),
+            format: DataFormat::Binary,
+            size,
+            location: location.to_string(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_transfers_skip_inputs_already_on_the_device() {
+        let devices: Vec<Arc<dyn HardwareDevice>> = ["gpu-0", "gpu-1"]
+            .iter()
+            .map(|id| Arc::new(GpuDevice::new(id.to_string(), id.to_string())) as Arc<dyn HardwareDevice>)
+            .collect();
+        let mb = 1024 * 1024;
+        let locality = Locality::new(
+            vec![
+                object("weights", 64 * mb, "gpu-1"),
+                object("batch", 16 * mb, "data/batch.bin"),
+                object("labels", mb, "data/labels.bin"),
+            ],
+            HashMap::from([("labels".to_string(), HashSet::from(["gpu-0".to_string()]))]),
+            &devices,
+        );
+        let requirements = TaskRequirements {
+            timeout: 0,
+            ..TaskRequirements::for_test(HardwareType::Gpu)
+        };
+        let task = Task::new("task-1".to_string(), "Infer".to_string(), requirements, TaskPriority::Normal)
+            .with_inputs(vec!["weights".to_string(), "batch".to_string(), "labels".to_string(), "unknown".to_string()]);
+
+        let on_gpu0 = locality.transfers(&task, devices[0].as_ref());
+        assert_eq!(on_gpu0.len(), 2);
+        assert_eq!((on_gpu0[0].from.as_str(), on_gpu0[0].bytes), ("gpu-1", 64 * mb));
+        assert_eq!(on_gpu0[1].from, HOST);
+        assert_eq!(on_gpu0[0].estimate, devices[1].transfer_time(64 * mb) * 2);
+
+        // The replica on gpu-0 spares a copy there, but gpu-1 reads labels from the host
+        let on_gpu1 = locality.transfers(&task, devices[1].as_ref());
+        assert_eq!(on_gpu1.len(), 2);
+        assert_eq!((on_gpu1[0].data_id.as_str(), on_gpu1[1].data_id.as_str()), ("batch", "labels"));
+        assert!(locality.cost(&task, devices[1].as_ref()) < locality.cost(&task, devices[0].as_ref()));
+    }
+}
@@ -2,7 +2,46 @@
 
 pub mod task_scheduler;
 pub mod resource_allocator;
+pub mod task_graph;
+pub mod list_scheduler;
+pub mod dispatch;
+pub mod scheduler_config;
+pub mod lifecycle;
+pub mod task_handle;
+pub mod fair_share;
+pub mod thermal;
+pub mod locality;
+
+use crate::hardware::abstract_device::HardwareManager;
+use std::sync::Arc;
 
 // Re-export key types
-pub use task_scheduler::{TaskScheduler, Task, SchedulingStrategy};
-pub use resource_allocator::{ResourceAllocator, AllocationStrategy};
\ No newline at end of file
+pub use task_scheduler::{TaskScheduler, Task, TaskPriority, TaskStatus, SchedulingStrategy};
+pub use resource_allocator::{ResourceAllocator, AllocationStrategy, AllocationStats};
+pub use task_graph::{Dependency, GraphHandle, GraphResult, NodeOutcome, TaskGraph};
+pub use list_scheduler::{Assignment, CostModel, ListHeuristic, Schedule};
+pub use scheduler_config::{
+    AgingConfig, BackoffConfig, BackoffKind, EnergyConfig, LifecycleConfig, OverflowPolicy, PlacementObjective,
+    PrioritiesConfig, PriorityLevel, RetryConfig, SchedulerConfig, TaskQueueConfig, ThermalConfig, ThermalThresholds,
+    ThermalThresholdsByType,
+};
+pub use thermal::{MetricsTemperature, TemperatureSample, TemperatureSource, ThermalState};
+pub use locality::{Transfer, HOST};
+pub use lifecycle::{DeadLetter, FailureAction};
+pub use task_handle::{CancelSignal, TaskHandle, TaskProgress};
+
+/// Scheduler settings file, relative to the working directory
+const TASK_SCHEDULING_CONFIG: &str = "config/scheduling/task_scheduling.yaml";
+
+/// Initialize the scheduler
+pub fn init_scheduler(
+    config: &serde_yaml::Value,
+    hardware_manager: Arc<dyn HardwareManager>,
+) -> Result<Arc<TaskScheduler>, Box<dyn std::error::Error>> {
+    let resource_allocator = Arc::new(ResourceAllocator::new(hardware_manager));
+    let scheduler_config = SchedulerConfig::load(TASK_SCHEDULING_CONFIG, config)?;
+
+    let strategy = scheduler_config.algorithm.clone();
+    let scheduler = TaskScheduler::with_config(resource_allocator, strategy, scheduler_config);
+    Ok(Arc::new(scheduler))
+}
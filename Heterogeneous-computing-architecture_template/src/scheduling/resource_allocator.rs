@@ -1,58 +1,320 @@
 // Resource allocator implementation
 
-pub struct ResourceAllocator {
-    strategy: AllocationStrategy,
-}
+use crate::errors::HardwareError;
+use crate::hardware::abstract_device::{HardwareDevice, HardwareManager, HardwareType, Lease, TaskRequirements};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
 
+/// Device selection strategy
+#[derive(Debug, Clone, Copy)]
 pub enum AllocationStrategy {
     FirstFit,
     BestFit,
     WorstFit,
 }
 
+/// Resource allocation statistics, computed from the active leases
+#[derive(Debug, Clone)]
+pub struct AllocationStats {
+    /// Reserved memory in MB
+    pub total_allocated: u64,
+
+    /// Memory of all registered devices in MB
+    pub total_available: u64,
+
+    /// Reserved share of memory
+    pub utilization_percentage: f64,
+
+    /// Reserved compute units
+    pub compute_units_allocated: u64,
+
+    /// Compute units of all registered devices
+    pub compute_units_available: u64,
+
+    /// Number of active leases
+    pub active_leases: usize,
+}
+
+/// Resource allocator implementation
+pub struct ResourceAllocator {
+    hardware_manager: Arc<dyn HardwareManager>,
+    strategy: AllocationStrategy,
+    allocations: Mutex<HashMap<String, Vec<Lease>>>,
+}
+
 impl ResourceAllocator {
-    pub fn new(strategy: AllocationStrategy) -> Self {
-        Self { strategy }
+    /// Create a new resource allocator
+    pub fn new(hardware_manager: Arc<dyn HardwareManager>) -> Self {
+        Self::with_strategy(hardware_manager, AllocationStrategy::BestFit)
     }
 
-    pub fn allocate_resources(&self, requirements: &ResourceRequirements, devices: &[&dyn HardwareDevice]) -> Option<&dyn HardwareDevice> {
+    /// Create a new resource allocator with an explicit device selection strategy
+    pub fn with_strategy(hardware_manager: Arc<dyn HardwareManager>, strategy: AllocationStrategy) -> Self {
+        Self {
+            hardware_manager,
+            strategy,
+            allocations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Hardware manager the allocator reserves capacity through
+    pub fn hardware_manager(&self) -> &Arc<dyn HardwareManager> {
+        &self.hardware_manager
+    }
+
+    /// Select a device with enough free memory (in bytes) according to the allocation strategy
+    ///
+    /// Free memory is what the device reports minus what leases have reserved on it.
+    pub fn select_device(&self, memory: u64, devices: &[Arc<dyn HardwareDevice>]) -> Option<Arc<dyn HardwareDevice>> {
+        let reserved: HashMap<String, u64> = self
+            .hardware_manager
+            .reservations()
+            .into_iter()
+            .map(|reservation| (reservation.device_id, reservation.reserved.memory * 1024 * 1024))
+            .collect();
+        let free = |device: &Arc<dyn HardwareDevice>| {
+            device
+                .available_memory()
+                .saturating_sub(reserved.get(device.id()).copied().unwrap_or(0))
+        };
+        let mut candidates = devices.iter().filter(|device| {
+            device.is_available() && self.hardware_manager.is_schedulable(device.id()) && free(device) >= memory
+        });
+
         match self.strategy {
             AllocationStrategy::FirstFit => {
                 // Allocate to the first device that meets requirements
-                devices.iter().find(|device| {
-                    device.available_memory() >= requirements.memory
-                }).copied()
-            },
+                candidates.next().cloned()
+            }
             AllocationStrategy::BestFit => {
                 // Allocate to the device that best fits the requirements
-                let mut best_fit = None;
-                let mut best_fit_size = u64::MAX;
-                
-                for device in devices {
-                    let available_memory = device.available_memory();
-                    if available_memory >= requirements.memory && available_memory < best_fit_size {
-                        best_fit = Some(*device);
-                        best_fit_size = available_memory;
-                    }
-                }
-                
-                best_fit
-            },
+                candidates.min_by_key(|device| free(device)).cloned()
+            }
             AllocationStrategy::WorstFit => {
                 // Allocate to the device with the most available memory
-                let mut worst_fit = None;
-                let mut worst_fit_size = 0;
-                
-                for device in devices {
-                    let available_memory = device.available_memory();
-                    if available_memory >= requirements.memory && available_memory > worst_fit_size {
-                        worst_fit = Some(*device);
-                        worst_fit_size = available_memory;
-                    }
+                candidates.max_by_key(|device| free(device)).cloned()
+            }
+        }
+    }
+
+    /// Reserve resources for a task on a device picked by the allocation strategy
+    pub fn allocate_resources(&self, task_id: &str, task_requirements: &TaskRequirements) -> Result<Lease, HardwareError> {
+        debug!(
+            "Allocating resources for task {} requiring {:?} with {} MB memory",
+            task_id,
+            task_requirements.hardware_type,
+            task_requirements.memory_required
+        );
+
+        let memory = task_requirements.memory_required * 1024 * 1024;
+        let mut devices = self.hardware_manager.get_devices_by_type(task_requirements.hardware_type);
+        let mut shortfall = None;
+
+        // Another allocator may take the capacity between selection and reservation; try the next pick
+        while let Some(device) = self.select_device(memory, &devices) {
+            match self.hardware_manager.reserve(device.id(), task_id, task_requirements) {
+                Ok(lease) => {
+                    self.allocations
+                        .lock()
+                        .unwrap()
+                        .entry(task_id.to_string())
+                        .or_default()
+                        .push(lease.clone());
+                    return Ok(lease);
+                }
+                Err(e) => {
+                    debug!("Reserving on {} for task {} failed: {}", device.id(), task_id, e);
+                    shortfall.get_or_insert(e);
+                    devices.retain(|candidate| candidate.id() != device.id());
                 }
-                
-                worst_fit
             }
         }
+        Err(shortfall.unwrap_or_else(|| {
+            HardwareError::DeviceUnavailable(format!(
+                "no {} device has {} MB free",
+                task_requirements.hardware_type, task_requirements.memory_required
+            ))
+        }))
     }
-}
\ No newline at end of file
+
+    /// Release leases by id
+    pub fn release_resources(&self, lease_ids: &[String]) -> Result<(), HardwareError> {
+        debug!("Releasing leases: {:?}", lease_ids);
+
+        // Forget the records first so a failed release is not retried by `release_task`
+        {
+            let mut allocations = self.allocations.lock().unwrap();
+            for leases in allocations.values_mut() {
+                leases.retain(|lease| !lease_ids.contains(&lease.id));
+            }
+            allocations.retain(|_, leases| !leases.is_empty());
+        }
+
+        self.hardware_manager.release_resources(lease_ids)
+    }
+
+    /// Release every lease held by a task
+    pub fn release_task(&self, task_id: &str) -> Result<(), HardwareError> {
+        let leases = self.allocations.lock().unwrap().remove(task_id).unwrap_or_default();
+        let ids: Vec<String> = leases.into_iter().map(|lease| lease.id).collect();
+        self.hardware_manager.release_resources(&ids)
+    }
+
+    /// Leases this allocator granted to a task and has not released
+    pub fn task_leases(&self, task_id: &str) -> Vec<Lease> {
+        self.allocations.lock().unwrap().get(task_id).cloned().unwrap_or_default()
+    }
+
+    /// Get allocation statistics
+    pub fn get_stats(&self) -> AllocationStats {
+        let reservations = self.hardware_manager.reservations();
+        let total = reservations.iter().map(|r| r.quota.capacity.memory).sum::<u64>();
+        let allocated = reservations.iter().map(|r| r.reserved.memory).sum::<u64>();
+        let utilization_percentage = if total > 0 {
+            (allocated as f64 / total as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        AllocationStats {
+            total_allocated: allocated,
+            total_available: total,
+            utilization_percentage,
+            compute_units_allocated: reservations.iter().map(|r| r.reserved.compute_units).sum(),
+            compute_units_available: reservations.iter().map(|r| r.quota.capacity.compute_units).sum(),
+            active_leases: reservations.iter().map(|r| r.leases).sum(),
+        }
+    }
+
+    /// Get available resources of a specific type
+    pub fn get_available_resources(&self, hardware_type: HardwareType) -> Vec<String> {
+        self.hardware_manager
+            .get_devices_by_type(hardware_type)
+            .iter()
+            .filter(|device| device.is_available() && self.hardware_manager.is_schedulable(device.id()))
+            .map(|device| device.id().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{DeviceManager, HealthState};
+    use crate::hardware::fpga::FpgaDevice;
+    use crate::hardware::gpu::GpuDevice;
+
+    #[test]
+    fn test_allocation_stats() {
+        let stats = AllocationStats {
+            total_allocated: 100,
+            total_available: 1000,
+            utilization_percentage: 10.0,
+            compute_units_allocated: 2,
+            compute_units_available: 8,
+            active_leases: 1,
+        };
+
+        assert_eq!(stats.total_allocated, 100);
+        assert_eq!(stats.total_available, 1000);
+        assert_eq!(stats.utilization_percentage, 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_select_device_by_strategy() {
+        let small: Arc<dyn HardwareDevice> = Arc::new(FpgaDevice::new("fpga-small".to_string(), 1024, 64));
+        let large: Arc<dyn HardwareDevice> = Arc::new(FpgaDevice::new("fpga-large".to_string(), 4096, 64));
+        small.initialize().await.unwrap();
+        large.initialize().await.unwrap();
+        let devices = vec![small, large];
+        let device_manager = Arc::new(DeviceManager::new());
+        for device in &devices {
+            device_manager.register_device(device.clone()).unwrap();
+        }
+        let manager: Arc<dyn HardwareManager> = device_manager.clone();
+
+        let best = ResourceAllocator::with_strategy(manager.clone(), AllocationStrategy::BestFit);
+        assert_eq!(best.select_device(512, &devices).unwrap().id(), "fpga-small");
+
+        let worst = ResourceAllocator::with_strategy(manager.clone(), AllocationStrategy::WorstFit);
+        assert_eq!(worst.select_device(512, &devices).unwrap().id(), "fpga-large");
+
+        let first = ResourceAllocator::with_strategy(manager, AllocationStrategy::FirstFit);
+        assert_eq!(first.select_device(2048, &devices).unwrap().id(), "fpga-large");
+        assert!(first.select_device(8192, &devices).is_none());
+
+        // Quarantined devices are skipped
+        device_manager
+            .set_health("fpga-small", HealthState::Quarantined, "maintenance")
+            .unwrap();
+        assert_eq!(best.select_device(512, &devices).unwrap().id(), "fpga-large");
+        assert_eq!(best.get_available_resources(HardwareType::Fpga), vec!["fpga-large".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_leases_account_capacity() {
+        let device_manager = Arc::new(DeviceManager::new());
+        let gpu = GpuDevice::new("gpu-0".to_string(), "Test GPU".to_string());
+        gpu.initialize_gpu().unwrap();
+        device_manager.register_device(Arc::new(gpu)).unwrap();
+        let allocator = ResourceAllocator::new(device_manager.clone());
+
+        let requirements = |memory, compute_units| TaskRequirements {
+            memory_required: memory,
+            compute_units,
+            timeout: 0,
+            ..TaskRequirements::for_test(HardwareType::Gpu)
+        };
+        let first = allocator.allocate_resources("task-1", &requirements(12288, 10)).unwrap();
+        assert_eq!(first.device_id, "gpu-0");
+        assert_eq!(first.task_id, "task-1");
+
+        // Memory left on the device is 4096 MB, so a second large task would over-commit it
+        let error = allocator.allocate_resources("task-2", &requirements(8192, 10)).unwrap_err();
+        assert!(matches!(error, HardwareError::DeviceUnavailable(_)));
+        let error = device_manager.reserve("gpu-0", "task-2", &requirements(8192, 10)).unwrap_err();
+        assert!(matches!(error, HardwareError::InsufficientMemory { required: 8192, available: 4096 }));
+        let second = allocator.allocate_resources("task-2", &requirements(4096, 30)).unwrap();
+
+        let stats = allocator.get_stats();
+        assert_eq!((stats.total_allocated, stats.total_available), (16384, 16384));
+        assert_eq!(stats.utilization_percentage, 100.0);
+        assert_eq!((stats.compute_units_allocated, stats.compute_units_available), (40, 40));
+        assert_eq!(stats.active_leases, 2);
+
+        allocator.release_task("task-1").unwrap();
+        assert!(allocator.task_leases("task-1").is_empty());
+        assert!(matches!(
+            allocator.release_resources(std::slice::from_ref(&first.id)),
+            Err(HardwareError::DoubleRelease(_))
+        ));
+        allocator.release_resources(&[second.id]).unwrap();
+        assert_eq!(allocator.get_stats().total_allocated, 0);
+        assert_eq!(allocator.get_stats().active_leases, 0);
+    }
+
+    #[tokio::test]
+    async fn test_leases_expire_after_task_timeout() {
+        let device_manager = Arc::new(DeviceManager::new());
+        let gpu = GpuDevice::new("gpu-0".to_string(), "Test GPU".to_string());
+        gpu.initialize_gpu().unwrap();
+        device_manager.register_device(Arc::new(gpu)).unwrap();
+
+        let requirements = TaskRequirements {
+            memory_required: 16384,
+            timeout: 20,
+            ..TaskRequirements::for_test(HardwareType::Gpu)
+        };
+        let lease = device_manager.allocate_resources("task-1", &requirements).unwrap().remove(0);
+        assert!(device_manager.allocate_resources("task-2", &requirements).is_err());
+
+        tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+        let replacement = device_manager.allocate_resources("task-2", &requirements).unwrap();
+        assert_eq!(replacement[0].task_id, "task-2");
+        assert!(matches!(
+            device_manager.release_resources(&[lease.id]),
+            Err(HardwareError::LeaseExpired(_))
+        ));
+    }
+}
@@ -0,0 +1,474 @@
+//! Scheduler settings
+//!
+//! Queue limits and the task lifecycle come from `config/scheduling/task_scheduling.yaml`;
+//! the cap on tasks running at once is `scheduling.max_concurrent_tasks` in application.yaml.
+
+use crate::errors::SchedulingError;
+use crate::hardware::abstract_device::HardwareType;
+use crate::scheduling::task_scheduler::{SchedulingStrategy, TaskPriority};
+use rand::Rng;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// What happens to a submission when every run queue it could join is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowPolicy {
+    /// Refuse the new task with `SchedulingError::QueueFull`
+    Reject,
+    /// Evict the oldest queued task to make room
+    Drop,
+    /// Make async submitters wait for room; synchronous submissions are refused
+    Block,
+}
+
+/// `scheduling.task_queue` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TaskQueueConfig {
+    /// Most tasks waiting in one device's run queue
+    pub max_size: usize,
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for TaskQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 1000,
+            overflow_policy: OverflowPolicy::Reject,
+        }
+    }
+}
+
+/// How the delay between attempts grows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackoffKind {
+    /// Double the delay after every attempt
+    Exponential,
+    /// Wait `base_delay` before every retry
+    Fixed,
+}
+
+/// `scheduling.lifecycle.retry.backoff` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackoffConfig {
+    #[serde(rename = "type")]
+    pub kind: BackoffKind,
+    /// Delay before the first retry, in milliseconds
+    pub base_delay: u64,
+    /// Longest delay between attempts, in milliseconds
+    pub max_delay: u64,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            kind: BackoffKind::Exponential,
+            base_delay: 1000,
+            max_delay: 30000,
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Delay before retry number `retry`, counting from 1
+    ///
+    /// Half of the delay is random so that tasks failing together do not all come
+    /// back at the same moment.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let ceiling = match self.kind {
+            BackoffKind::Exponential => self
+                .base_delay
+                .saturating_mul(2u64.saturating_pow(retry.saturating_sub(1))),
+            BackoffKind::Fixed => self.base_delay,
+        }
+        .min(self.max_delay);
+        let fixed = ceiling / 2;
+        Duration::from_millis(fixed + rand::thread_rng().gen_range(0..=ceiling - fixed))
+    }
+}
+
+/// `scheduling.lifecycle.retry` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Most times a task is run, counting the first attempt
+    pub max_attempts: u32,
+    pub backoff: BackoffConfig,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: BackoffConfig::default(),
+        }
+    }
+}
+
+/// `scheduling.lifecycle` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LifecycleConfig {
+    /// Timeout in milliseconds for tasks that do not set their own; 0 disables it
+    pub timeout: u64,
+    pub retry: RetryConfig,
+    /// How long finished tasks stay queryable, in milliseconds
+    pub retention: u64,
+}
+
+impl Default for LifecycleConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 300000,
+            retry: RetryConfig::default(),
+            retention: 3600000,
+        }
+    }
+}
+
+/// One entry of `scheduling.priorities.levels` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriorityLevel {
+    /// Lower-case `TaskPriority` name, e.g. `background`
+    pub name: String,
+    /// Share of dispatches the level gets while every level has work waiting
+    pub weight: u64,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// `scheduling.priorities.aging` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AgingConfig {
+    /// Milliseconds of waiting that move a task ahead by one turn of its level; 0 disables aging
+    pub interval: u64,
+}
+
+impl Default for AgingConfig {
+    fn default() -> Self {
+        Self { interval: 5000 }
+    }
+}
+
+/// `scheduling.priorities` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PrioritiesConfig {
+    pub levels: Vec<PriorityLevel>,
+    pub aging: AgingConfig,
+}
+
+impl Default for PrioritiesConfig {
+    fn default() -> Self {
+        let levels = TaskPriority::ALL
+            .iter()
+            .map(|priority| PriorityLevel {
+                name: priority.name().to_string(),
+                weight: *priority as u64,
+                description: String::new(),
+            })
+            .collect();
+        Self {
+            levels,
+            aging: AgingConfig::default(),
+        }
+    }
+}
+
+impl PrioritiesConfig {
+    /// Configured weight of a priority, falling back to its numeric value
+    pub fn weight(&self, priority: TaskPriority) -> u64 {
+        self.levels
+            .iter()
+            .find(|level| level.name == priority.name())
+            .map_or(priority as u64, |level| level.weight)
+    }
+}
+
+/// What decides the device a task is queued on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlacementObjective {
+    /// The device with the shortest run queue
+    Performance,
+    /// The device modelled to spend the least energy on the task
+    Energy,
+}
+
+/// `scheduling.energy` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EnergyConfig {
+    pub objective: PlacementObjective,
+    /// Most watts all devices together may draw; 0 for no cap
+    pub power_cap: f64,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            objective: PlacementObjective::Performance,
+            power_cap: 0.0,
+        }
+    }
+}
+
+/// Temperatures in Celsius at which a device is hot or critical
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ThermalThresholds {
+    /// From here on the device gets new work last and runs one task at a time
+    pub warning: f64,
+    /// From here on the device starts nothing and its queued work moves elsewhere
+    pub critical: f64,
+}
+
+/// `scheduling.thermal.thresholds` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThermalThresholdsByType {
+    pub cpu: ThermalThresholds,
+    pub gpu: ThermalThresholds,
+    pub fpga: ThermalThresholds,
+}
+
+impl Default for ThermalThresholdsByType {
+    fn default() -> Self {
+        Self {
+            cpu: ThermalThresholds { warning: 85.0, critical: 95.0 },
+            gpu: ThermalThresholds { warning: 83.0, critical: 90.0 },
+            fpga: ThermalThresholds { warning: 85.0, critical: 100.0 },
+        }
+    }
+}
+
+impl ThermalThresholdsByType {
+    /// Thresholds of a device type
+    pub fn get(&self, hardware_type: HardwareType) -> ThermalThresholds {
+        match hardware_type {
+            HardwareType::Cpu => self.cpu,
+            HardwareType::Gpu => self.gpu,
+            HardwareType::Fpga => self.fpga,
+        }
+    }
+}
+
+/// `scheduling.thermal` in task_scheduling.yaml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThermalConfig {
+    /// Milliseconds between temperature readings
+    pub sample_interval: u64,
+    /// Readings kept per device for diagnostics
+    pub history: usize,
+    pub thresholds: ThermalThresholdsByType,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval: 1000,
+            history: 300,
+            thresholds: ThermalThresholdsByType::default(),
+        }
+    }
+}
+
+/// Settings the task scheduler enforces
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Strategy `init_scheduler` builds the scheduler with
+    pub algorithm: SchedulingStrategy,
+
+    pub task_queue: TaskQueueConfig,
+    pub lifecycle: LifecycleConfig,
+    pub priorities: PrioritiesConfig,
+    pub energy: EnergyConfig,
+    pub thermal: ThermalConfig,
+
+    /// Most tasks dispatched and not yet reported finished
+    pub max_concurrent_tasks: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: SchedulingStrategy::Priority,
+            task_queue: TaskQueueConfig::default(),
+            lifecycle: LifecycleConfig::default(),
+            priorities: PrioritiesConfig::default(),
+            energy: EnergyConfig::default(),
+            thermal: ThermalConfig::default(),
+            max_concurrent_tasks: 50,
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// Build the settings from a task_scheduling.yaml document and the application config
+    pub fn from_yaml(task_scheduling: &serde_yaml::Value, application: &serde_yaml::Value) -> Result<Self, SchedulingError> {
+        let mut config = Self::default();
+        let scheduling = task_scheduling.get("scheduling");
+        if let Some(value) = scheduling.and_then(|s| s.get("algorithm")) {
+            config.algorithm = serde_yaml::from_value(value.clone())
+                .map_err(|e| SchedulingError::InvalidConfiguration(format!("scheduling.algorithm: {}", e)))?;
+        }
+        if let Some(section) = scheduling.and_then(|s| s.get("task_queue")) {
+            config.task_queue = serde_yaml::from_value(section.clone())
+                .map_err(|e| SchedulingError::InvalidConfiguration(format!("scheduling.task_queue: {}", e)))?;
+        }
+        if let Some(section) = scheduling.and_then(|s| s.get("priorities")) {
+            config.priorities = serde_yaml::from_value(section.clone())
+                .map_err(|e| SchedulingError::InvalidConfiguration(format!("scheduling.priorities: {}", e)))?;
+        }
+        if let Some(section) = scheduling.and_then(|s| s.get("lifecycle")) {
+            config.lifecycle = serde_yaml::from_value(section.clone())
+                .map_err(|e| SchedulingError::InvalidConfiguration(format!("scheduling.lifecycle: {}", e)))?;
+        }
+        if let Some(section) = scheduling.and_then(|s| s.get("energy")) {
+            config.energy = serde_yaml::from_value(section.clone())
+                .map_err(|e| SchedulingError::InvalidConfiguration(format!("scheduling.energy: {}", e)))?;
+        }
+        if let Some(section) = scheduling.and_then(|s| s.get("thermal")) {
+            config.thermal = serde_yaml::from_value(section.clone())
+                .map_err(|e| SchedulingError::InvalidConfiguration(format!("scheduling.thermal: {}", e)))?;
+        }
+        if let Some(value) = application.get("scheduling").and_then(|s| s.get("max_concurrent_tasks")) {
+            config.max_concurrent_tasks = value.as_u64().ok_or_else(|| {
+                SchedulingError::InvalidConfiguration("scheduling.max_concurrent_tasks must be a number".to_string())
+            })? as usize;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a task_scheduling.yaml file, using defaults when it does not exist
+    pub fn load(path: impl AsRef<Path>, application: &serde_yaml::Value) -> Result<Self, SchedulingError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Self::from_yaml(&serde_yaml::Value::Null, application);
+        }
+        let document = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_yaml::from_str(&content).map_err(|e| e.to_string()))
+            .map_err(|e| SchedulingError::InvalidConfiguration(format!("{}: {}", path.display(), e)))?;
+        Self::from_yaml(&document, application)
+    }
+
+    /// Check for limits that would stop the scheduler from dispatching anything
+    pub fn validate(&self) -> Result<(), SchedulingError> {
+        let mut problems = Vec::new();
+        if self.task_queue.max_size == 0 {
+            problems.push("scheduling.task_queue.max_size must be at least 1".to_string());
+        }
+        for level in &self.priorities.levels {
+            if !TaskPriority::ALL.iter().any(|priority| priority.name() == level.name) {
+                problems.push(format!("scheduling.priorities: unknown level {}", level.name));
+            }
+            if level.weight == 0 {
+                problems.push(format!("scheduling.priorities: level {} needs a weight of at least 1", level.name));
+            }
+        }
+        if self.max_concurrent_tasks == 0 {
+            problems.push("scheduling.max_concurrent_tasks must be at least 1".to_string());
+        }
+        if self.lifecycle.retry.max_attempts == 0 {
+            problems.push("scheduling.lifecycle.retry.max_attempts must be at least 1".to_string());
+        }
+        if !(self.energy.power_cap >= 0.0 && self.energy.power_cap.is_finite()) {
+            problems.push(format!("scheduling.energy.power_cap must be 0 or a number of watts, got {}", self.energy.power_cap));
+        }
+        for (name, thresholds) in [
+            ("cpu", self.thermal.thresholds.cpu),
+            ("gpu", self.thermal.thresholds.gpu),
+            ("fpga", self.thermal.thresholds.fpga),
+        ] {
+            if thresholds.warning >= thresholds.critical {
+                problems.push(format!(
+                    "scheduling.thermal.thresholds.{}: warning ({}) must be below critical ({})",
+                    name, thresholds.warning, thresholds.critical
+                ));
+            }
+        }
+        if self.thermal.history == 0 {
+            problems.push("scheduling.thermal.history must be at least 1".to_string());
+        }
+        let backoff = &self.lifecycle.retry.backoff;
+        if backoff.max_delay < backoff.base_delay {
+            problems.push("scheduling.lifecycle.retry.backoff.max_delay must not be below base_delay".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SchedulingError::InvalidConfiguration(problems.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduling::list_scheduler::ListHeuristic;
+
+    #[test]
+    fn test_repository_config() {
+        let application: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config/application.yaml")).unwrap())
+                .unwrap();
+        let config = SchedulerConfig::load(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/config/scheduling/task_scheduling.yaml"),
+            &application,
+        )
+        .unwrap();
+        assert_eq!(config.algorithm, SchedulingStrategy::Priority);
+        assert_eq!(config.task_queue.max_size, 1000);
+        assert_eq!(config.task_queue.overflow_policy, OverflowPolicy::Reject);
+        assert_eq!(config.max_concurrent_tasks, 50);
+        assert_eq!(config.lifecycle.timeout, 300000);
+        assert_eq!(config.lifecycle.retry.max_attempts, 3);
+        assert_eq!(config.lifecycle.retention, 3600000);
+        assert_eq!(config.priorities.weight(TaskPriority::Critical), 100);
+        assert_eq!(config.priorities.weight(TaskPriority::Background), 10);
+        assert_eq!(config.priorities.aging.interval, 5000);
+        assert_eq!(config.lifecycle.retry.backoff.kind, BackoffKind::Exponential);
+        assert_eq!(config.energy.objective, PlacementObjective::Performance);
+        assert_eq!(config.energy.power_cap, 0.0);
+        assert_eq!(config.thermal.sample_interval, 1000);
+        assert_eq!(config.thermal.thresholds.get(HardwareType::Gpu), ThermalThresholds { warning: 83.0, critical: 90.0 });
+
+        let heft: serde_yaml::Value = serde_yaml::from_str("scheduling:\n  algorithm: heft\n").unwrap();
+        let config = SchedulerConfig::from_yaml(&heft, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(config.algorithm.list_heuristic(), Some(ListHeuristic::Heft));
+        let unknown: serde_yaml::Value = serde_yaml::from_str("scheduling:\n  algorithm: lottery\n").unwrap();
+        assert!(SchedulerConfig::from_yaml(&unknown, &serde_yaml::Value::Null).is_err());
+
+        let bad: serde_yaml::Value =
+            serde_yaml::from_str("scheduling:\n  task_queue:\n    max_size: 0\n    overflow_policy: spill\n").unwrap();
+        assert!(matches!(
+            SchedulerConfig::from_yaml(&bad, &serde_yaml::Value::Null),
+            Err(SchedulingError::InvalidConfiguration(_))
+        ));
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_jitter_up_to_the_cap() {
+        let backoff = BackoffConfig {
+            kind: BackoffKind::Exponential,
+            base_delay: 100,
+            max_delay: 1000,
+        };
+        for _ in 0..20 {
+            let first = backoff.delay(1).as_millis();
+            let third = backoff.delay(3).as_millis();
+            let tenth = backoff.delay(10).as_millis();
+            assert!((50..=100).contains(&first), "{}", first);
+            assert!((200..=400).contains(&third), "{}", third);
+            assert!((500..=1000).contains(&tenth), "{}", tenth);
+        }
+        assert!(backoff.delay(u32::MAX) <= Duration::from_millis(1000));
+    }
+}
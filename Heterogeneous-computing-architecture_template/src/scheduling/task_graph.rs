@@ -0,0 +1,573 @@
+// Task dependency graphs
+
+use crate::errors::{HardwareError, SchedulingError};
+use crate::hardware::abstract_device::{TaskResult, Tensor};
+use crate::scheduling::task_scheduler::{Task, TaskStatus};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::info;
+
+/// Edge from a task to a task it waits for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    /// Task that must complete first
+    pub task_id: String,
+
+    /// Whether the predecessor's output becomes part of this task's input
+    pub passes_data: bool,
+}
+
+#[derive(Debug, Clone)]
+struct GraphNode {
+    task: Task,
+    dependencies: Vec<Dependency>,
+}
+
+/// Tasks and the dependencies between them
+///
+/// A data dependency appends the predecessor's output to the dependent's input. A task
+/// with no payload of its own and a single data dependency receives the output bytes
+/// unchanged. Otherwise both are decoded as tensor payloads, its own tensors first
+/// followed by each predecessor's in the order the dependencies were added.
+#[derive(Debug, Clone, Default)]
+pub struct TaskGraph {
+    nodes: Vec<GraphNode>,
+    index: HashMap<String, usize>,
+}
+
+impl TaskGraph {
+    /// Create an empty graph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a task to the graph
+    pub fn add_task(&mut self, task: Task) -> Result<(), SchedulingError> {
+        if self.index.contains_key(&task.id) {
+            return Err(SchedulingError::DuplicateTask(task.id));
+        }
+        self.index.insert(task.id.clone(), self.nodes.len());
+        self.nodes.push(GraphNode {
+            task,
+            dependencies: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Make `task_id` wait for `depends_on` to complete
+    pub fn add_dependency(&mut self, task_id: &str, depends_on: &str) -> Result<(), SchedulingError> {
+        self.depend(task_id, depends_on, false)
+    }
+
+    /// Make `task_id` wait for `depends_on` and take its output as input
+    pub fn add_data_dependency(&mut self, task_id: &str, depends_on: &str) -> Result<(), SchedulingError> {
+        self.depend(task_id, depends_on, true)
+    }
+
+    fn depend(&mut self, task_id: &str, depends_on: &str, passes_data: bool) -> Result<(), SchedulingError> {
+        let unknown = |missing: &str| SchedulingError::UnknownDependency {
+            task: task_id.to_string(),
+            dependency: missing.to_string(),
+        };
+        let node = *self.index.get(task_id).ok_or_else(|| unknown(task_id))?;
+        if !self.index.contains_key(depends_on) {
+            return Err(unknown(depends_on));
+        }
+
+        let dependencies = &mut self.nodes[node].dependencies;
+        match dependencies.iter_mut().find(|dependency| dependency.task_id == depends_on) {
+            Some(existing) => existing.passes_data |= passes_data,
+            None => dependencies.push(Dependency {
+                task_id: depends_on.to_string(),
+                passes_data,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Number of tasks
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check whether the graph has no tasks
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Ids of the tasks in the order they were added
+    pub fn task_ids(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(|node| node.task.id.as_str())
+    }
+
+    /// A task in the graph
+    pub fn task(&self, task_id: &str) -> Option<&Task> {
+        self.index.get(task_id).map(|&node| &self.nodes[node].task)
+    }
+
+    pub(crate) fn task_mut(&mut self, task_id: &str) -> Option<&mut Task> {
+        self.index.get(task_id).map(|&node| &mut self.nodes[node].task)
+    }
+
+    /// Dependencies of a task
+    pub fn dependencies(&self, task_id: &str) -> Option<&[Dependency]> {
+        self.index.get(task_id).map(|&node| self.nodes[node].dependencies.as_slice())
+    }
+
+    /// Order in which the tasks can run, or the cycle that prevents one
+    pub fn topological_order(&self) -> Result<Vec<String>, SchedulingError> {
+        let mut waiting: Vec<usize> = self.nodes.iter().map(|node| node.dependencies.len()).collect();
+        let dependents = self.dependents();
+        let mut ready: VecDeque<usize> = (0..self.nodes.len()).filter(|&node| waiting[node] == 0).collect();
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(node) = ready.pop_front() {
+            order.push(self.nodes[node].task.id.clone());
+            for &dependent in &dependents[node] {
+                waiting[dependent] -= 1;
+                if waiting[dependent] == 0 {
+                    ready.push_back(dependent);
+                }
+            }
+        }
+
+        if order.len() == self.nodes.len() {
+            Ok(order)
+        } else {
+            Err(SchedulingError::DependencyCycle(self.find_cycle(&waiting)))
+        }
+    }
+
+    /// Indices of the tasks that depend on each task
+    fn dependents(&self) -> Vec<Vec<usize>> {
+        let mut dependents = vec![Vec::new(); self.nodes.len()];
+        for (node, graph_node) in self.nodes.iter().enumerate() {
+            for dependency in &graph_node.dependencies {
+                dependents[self.index[&dependency.task_id]].push(node);
+            }
+        }
+        dependents
+    }
+
+    /// Walk dependencies among the tasks Kahn's algorithm could not order until one repeats
+    fn find_cycle(&self, waiting: &[usize]) -> Vec<String> {
+        let Some(start) = (0..self.nodes.len()).find(|&node| waiting[node] > 0) else {
+            return Vec::new();
+        };
+        let mut path = vec![start];
+        let mut seen = HashSet::from([start]);
+        let mut node = start;
+        loop {
+            // Every unordered task still waits on at least one unordered task
+            node = self.nodes[node]
+                .dependencies
+                .iter()
+                .map(|dependency| self.index[&dependency.task_id])
+                .find(|&dependency| waiting[dependency] > 0)
+                .unwrap_or(start);
+            if !seen.insert(node) {
+                break;
+            }
+            path.push(node);
+        }
+
+        // Keep the loop itself and list it in execution order
+        let begin = path.iter().position(|&n| n == node).unwrap_or(0);
+        let mut cycle: Vec<String> = path[begin..].iter().rev().map(|&n| self.nodes[n].task.id.clone()).collect();
+        cycle.push(cycle[0].clone());
+        cycle
+    }
+}
+
+/// How a task in a graph ended
+#[derive(Debug, Clone)]
+pub enum NodeOutcome {
+    Completed(TaskResult),
+    Failed(String),
+    /// Never ran, e.g. because a task it depends on failed
+    Cancelled(String),
+}
+
+impl NodeOutcome {
+    /// Matching task status
+    pub fn status(&self) -> TaskStatus {
+        match self {
+            NodeOutcome::Completed(_) => TaskStatus::Completed,
+            NodeOutcome::Failed(_) => TaskStatus::Failed,
+            NodeOutcome::Cancelled(_) => TaskStatus::Cancelled,
+        }
+    }
+
+    /// Result of a completed task
+    pub fn result(&self) -> Option<&TaskResult> {
+        match self {
+            NodeOutcome::Completed(result) => Some(result),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of every task in a finished graph
+#[derive(Debug, Clone)]
+pub struct GraphResult {
+    pub outcomes: HashMap<String, NodeOutcome>,
+
+    /// Time from submission until the last task finished
+    pub makespan: Duration,
+
+    /// Makespan predicted by the plan, for graphs submitted under a list-scheduling strategy
+    pub predicted_makespan: Option<Duration>,
+}
+
+impl GraphResult {
+    /// Outcome of one task
+    pub fn outcome(&self, task_id: &str) -> Option<&NodeOutcome> {
+        self.outcomes.get(task_id)
+    }
+
+    /// Result of one completed task
+    pub fn result(&self, task_id: &str) -> Option<&TaskResult> {
+        self.outcome(task_id).and_then(NodeOutcome::result)
+    }
+
+    /// Check whether every task completed
+    pub fn is_success(&self) -> bool {
+        self.outcomes.values().all(|outcome| outcome.status() == TaskStatus::Completed)
+    }
+}
+
+/// Progress of a submitted graph
+struct RunState {
+    /// Tasks not yet released to the queue
+    pending: HashMap<String, GraphNode>,
+    waiting_on: HashMap<String, usize>,
+    dependents: HashMap<String, Vec<String>>,
+    outcomes: HashMap<String, NodeOutcome>,
+    total: usize,
+    finished_at: Option<Instant>,
+}
+
+/// A graph being executed by the scheduler
+pub(crate) struct GraphRun {
+    id: String,
+    started: Instant,
+    predicted_makespan: Option<Duration>,
+    state: Mutex<RunState>,
+    finished: watch::Sender<bool>,
+}
+
+/// What a completion or failure changed in a graph
+#[derive(Default)]
+pub(crate) struct GraphProgress {
+    /// Tasks whose dependencies are now all complete
+    pub ready: Vec<Task>,
+
+    /// Tasks that will never run
+    pub finished: Vec<String>,
+}
+
+impl GraphRun {
+    /// Validate a graph and start it, returning the tasks that can run straight away
+    pub(crate) fn start(
+        id: String,
+        graph: TaskGraph,
+        predicted_makespan: Option<Duration>,
+    ) -> Result<(Arc<Self>, Vec<Task>), SchedulingError> {
+        graph.topological_order()?;
+
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        let mut waiting_on = HashMap::new();
+        for node in &graph.nodes {
+            waiting_on.insert(node.task.id.clone(), node.dependencies.len());
+            for dependency in &node.dependencies {
+                dependents
+                    .entry(dependency.task_id.clone())
+                    .or_default()
+                    .push(node.task.id.clone());
+            }
+        }
+        let total = graph.nodes.len();
+        // Roots are released in the order they were added
+        let roots: Vec<String> = graph
+            .nodes
+            .iter()
+            .filter(|node| node.dependencies.is_empty())
+            .map(|node| node.task.id.clone())
+            .collect();
+        let mut pending: HashMap<String, GraphNode> =
+            graph.nodes.into_iter().map(|node| (node.task.id.clone(), node)).collect();
+        let ready = roots.iter().filter_map(|id| pending.remove(id)).map(|node| node.task).collect();
+
+        let started = Instant::now();
+        let run = Arc::new(Self {
+            id,
+            started,
+            predicted_makespan,
+            state: Mutex::new(RunState {
+                pending,
+                waiting_on,
+                dependents,
+                outcomes: HashMap::new(),
+                total,
+                finished_at: (total == 0).then_some(started),
+            }),
+            finished: watch::Sender::new(total == 0),
+        });
+        Ok((run, ready))
+    }
+
+    /// Graph identifier
+    pub(crate) fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Record a completed task and release the tasks that were waiting only on it
+    pub(crate) fn complete(&self, task_id: &str, result: TaskResult) -> GraphProgress {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let mut progress = GraphProgress::default();
+        if state.outcomes.contains_key(task_id) {
+            return progress;
+        }
+        state.outcomes.insert(task_id.to_string(), NodeOutcome::Completed(result));
+
+        for dependent in state.dependents.get(task_id).cloned().unwrap_or_default() {
+            let Some(waiting) = state.waiting_on.get_mut(&dependent) else {
+                continue;
+            };
+            *waiting -= 1;
+            if *waiting > 0 || !state.pending.contains_key(&dependent) {
+                continue;
+            }
+            let node = state.pending.remove(&dependent).unwrap();
+            match bind_inputs(node, &state.outcomes) {
+                Ok(task) => progress.ready.push(task),
+                Err(e) => {
+                    let reason = format!("cannot bind inputs: {}", e);
+                    Self::finish(state, &dependent, NodeOutcome::Failed(reason), &mut progress);
+                }
+            }
+        }
+        self.notify_if_done(state);
+        progress
+    }
+
+    /// Record a failed or cancelled task and cancel everything downstream of it
+    pub(crate) fn fail(&self, task_id: &str, outcome: NodeOutcome) -> GraphProgress {
+        let mut state = self.state.lock().unwrap();
+        let mut progress = GraphProgress::default();
+        if !state.outcomes.contains_key(task_id) {
+            state.pending.remove(task_id);
+            Self::finish(&mut state, task_id, outcome, &mut progress);
+            // The task itself was already handed out, only its dependents never ran
+            progress.finished.retain(|id| id != task_id);
+        }
+        self.notify_if_done(&mut state);
+        progress
+    }
+
+    /// Check whether a task has not been released to the queue yet
+    pub(crate) fn is_pending(&self, task_id: &str) -> bool {
+        self.state.lock().unwrap().pending.contains_key(task_id)
+    }
+
+    /// Check whether every task has an outcome
+    pub(crate) fn is_finished(&self) -> bool {
+        *self.finished.borrow()
+    }
+
+    /// Outcome of one task, if it has finished
+    pub(crate) fn outcome(&self, task_id: &str) -> Option<NodeOutcome> {
+        self.state.lock().unwrap().outcomes.get(task_id).cloned()
+    }
+
+    /// Makespan predicted by the plan the graph was submitted with
+    pub(crate) fn predicted_makespan(&self) -> Option<Duration> {
+        self.predicted_makespan
+    }
+
+    /// Wait until every task has an outcome
+    pub(crate) async fn wait(&self) -> GraphResult {
+        let mut finished = self.finished.subscribe();
+        let _ = finished.wait_for(|done| *done).await;
+        let state = self.state.lock().unwrap();
+        GraphResult {
+            outcomes: state.outcomes.clone(),
+            makespan: state.finished_at.unwrap_or(self.started) - self.started,
+            predicted_makespan: self.predicted_makespan,
+        }
+    }
+
+    /// Give a task its outcome and cancel its transitive dependents
+    fn finish(state: &mut RunState, task_id: &str, outcome: NodeOutcome, progress: &mut GraphProgress) {
+        state.outcomes.insert(task_id.to_string(), outcome);
+        progress.finished.push(task_id.to_string());
+
+        let mut queue: VecDeque<String> = state.dependents.get(task_id).cloned().unwrap_or_default().into();
+        while let Some(dependent) = queue.pop_front() {
+            if state.outcomes.contains_key(&dependent) {
+                continue;
+            }
+            state.pending.remove(&dependent);
+            let reason = format!("dependency {} did not complete", task_id);
+            state.outcomes.insert(dependent.clone(), NodeOutcome::Cancelled(reason));
+            progress.finished.push(dependent.clone());
+            queue.extend(state.dependents.get(&dependent).cloned().unwrap_or_default());
+        }
+    }
+
+    fn notify_if_done(&self, state: &mut RunState) {
+        if state.outcomes.len() < state.total || state.finished_at.is_some() {
+            return;
+        }
+        let makespan = self.started.elapsed();
+        state.finished_at = Some(self.started + makespan);
+        match self.predicted_makespan {
+            Some(predicted) => info!("Graph {} finished in {:?}, predicted {:?}", self.id, makespan, predicted),
+            None => info!("Graph {} finished in {:?}", self.id, makespan),
+        }
+        self.finished.send_replace(true);
+    }
+}
+
+/// Build a released task's input from its own payload and its predecessors' outputs
+fn bind_inputs(node: GraphNode, outcomes: &HashMap<String, NodeOutcome>) -> Result<Task, HardwareError> {
+    let mut task = node.task;
+    let inputs: Vec<&[u8]> = node
+        .dependencies
+        .iter()
+        .filter(|dependency| dependency.passes_data)
+        .filter_map(|dependency| outcomes.get(&dependency.task_id).and_then(NodeOutcome::result))
+        .map(|result| result.data.as_slice())
+        .collect();
+
+    match inputs.as_slice() {
+        [] => {}
+        [only] if task.data.is_empty() => task.data = only.to_vec(),
+        _ => {
+            let mut tensors = if task.data.is_empty() {
+                Vec::new()
+            } else {
+                Tensor::decode_all(&task.data)?
+            };
+            for input in inputs {
+                tensors.extend(Tensor::decode_all(input)?);
+            }
+            task.data = Tensor::encode_all(&tensors);
+        }
+    }
+    Ok(task)
+}
+
+/// Handle for awaiting a submitted graph
+#[derive(Clone)]
+pub struct GraphHandle {
+    run: Arc<GraphRun>,
+}
+
+impl GraphHandle {
+    pub(crate) fn new(run: Arc<GraphRun>) -> Self {
+        Self { run }
+    }
+
+    /// Graph identifier
+    pub fn id(&self) -> &str {
+        self.run.id()
+    }
+
+    /// Check whether every task has an outcome
+    pub fn is_finished(&self) -> bool {
+        self.run.is_finished()
+    }
+
+    /// Outcome of one task, if it has finished
+    pub fn outcome(&self, task_id: &str) -> Option<NodeOutcome> {
+        self.run.outcome(task_id)
+    }
+
+    /// Makespan predicted by the plan, for graphs submitted under a list-scheduling strategy
+    pub fn predicted_makespan(&self) -> Option<Duration> {
+        self.run.predicted_makespan()
+    }
+
+    /// Wait for every task to complete, fail or be cancelled
+    pub async fn wait(&self) -> GraphResult {
+        self.run.wait().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{HardwareType, TaskRequirements};
+    use crate::scheduling::TaskPriority;
+
+    fn task(id: &str) -> Task {
+        let requirements = TaskRequirements::for_test(HardwareType::Cpu);
+        Task::new(id.to_string(), id.to_string(), requirements, TaskPriority::Normal)
+    }
+
+    fn graph(ids: &[&str], edges: &[(&str, &str)]) -> TaskGraph {
+        let mut graph = TaskGraph::new();
+        for id in ids {
+            graph.add_task(task(id)).unwrap();
+        }
+        for (task_id, depends_on) in edges {
+            graph.add_dependency(task_id, depends_on).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn test_topological_order_and_validation() {
+        let pipeline = graph(&["post", "infer", "pre"], &[("infer", "pre"), ("post", "infer")]);
+        assert_eq!(pipeline.topological_order().unwrap(), vec!["pre", "infer", "post"]);
+
+        let mut invalid = pipeline.clone();
+        assert_eq!(invalid.add_task(task("pre")), Err(SchedulingError::DuplicateTask("pre".to_string())));
+        assert!(matches!(
+            invalid.add_dependency("post", "missing"),
+            Err(SchedulingError::UnknownDependency { .. })
+        ));
+
+        let cyclic = graph(&["a", "b", "c", "d"], &[("b", "a"), ("c", "b"), ("b", "c"), ("d", "c")]);
+        let error = cyclic.topological_order().unwrap_err();
+        assert_eq!(error, SchedulingError::DependencyCycle(vec!["c".into(), "b".into(), "c".into()]));
+        assert_eq!(error.to_string(), "Dependency cycle: c -> b -> c");
+    }
+
+    #[tokio::test]
+    async fn test_release_bind_and_cascade() {
+        let mut diamond = graph(&["a", "b", "c", "d", "e"], &[("b", "a"), ("c", "a"), ("d", "b"), ("d", "c")]);
+        diamond.add_data_dependency("b", "a").unwrap();
+        let (run, roots) = GraphRun::start("graph-1".to_string(), diamond, None).unwrap();
+        let roots: Vec<_> = roots.into_iter().map(|task| task.id).collect();
+        assert_eq!(roots, vec!["a", "e"]);
+
+        let output = TaskResult {
+            task_id: "a".to_string(),
+            data: vec![1, 2, 3],
+            execution_time: 1,
+            energy: 0.0,
+        };
+        let progress = run.complete("a", output);
+        let mut ready: Vec<_> = progress.ready.iter().map(|task| (task.id.as_str(), task.data.clone())).collect();
+        ready.sort();
+        assert_eq!(ready, vec![("b", vec![1, 2, 3]), ("c", vec![])]);
+
+        // c fails, so d never runs even though b completes
+        let progress = run.fail("c", NodeOutcome::Failed("device fault".to_string()));
+        assert_eq!(progress.finished, vec!["d".to_string()]);
+        assert!(run.complete("b", TaskResult { task_id: "b".into(), data: vec![], execution_time: 1, energy: 0.0 }).ready.is_empty());
+        assert!(!run.is_finished());
+        run.complete("e", TaskResult { task_id: "e".into(), data: vec![], execution_time: 1, energy: 0.0 });
+
+        let result = run.wait().await;
+        assert!(!result.is_success());
+        assert_eq!(result.outcome("c").unwrap().status(), TaskStatus::Failed);
+        assert_eq!(result.outcome("d").unwrap().status(), TaskStatus::Cancelled);
+        assert_eq!(result.result("b").unwrap().task_id, "b");
+    }
+}
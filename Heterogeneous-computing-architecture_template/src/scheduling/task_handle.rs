@@ -0,0 +1,391 @@
+//! Tracking of submitted tasks
+//!
+//! Every task the scheduler accepts gets a `TaskRecord`, which the scheduler updates as
+//! the task is dispatched, retried and finished. `TaskHandle`s and status queries read
+//! it. Records of finished tasks are forgotten once the retention period has passed,
+//! and until then their ids cannot be reused; handles that are still held keep working.
+
+use crate::errors::SchedulingError;
+use crate::scheduling::task_graph::NodeOutcome;
+use crate::scheduling::task_scheduler::{Task, TaskStatus};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{watch, Notify};
+
+/// Status and progress of a submitted task
+#[derive(Debug, Clone)]
+pub struct TaskProgress {
+    pub task_id: String,
+    pub status: TaskStatus,
+
+    /// Runs started so far, counting retries
+    pub attempts: u32,
+
+    /// Device of the current or last run
+    pub device_id: Option<String>,
+
+    /// Bytes of input copied to the task's devices, over every run
+    pub bytes_moved: u64,
+
+    pub submitted_at: SystemTime,
+    /// When the first run started
+    pub started_at: Option<SystemTime>,
+    pub finished_at: Option<SystemTime>,
+    pub outcome: Option<NodeOutcome>,
+}
+
+/// Scheduler-side state of one submitted task
+pub(crate) struct TaskRecord {
+    progress: Mutex<TaskProgress>,
+    finished: watch::Sender<bool>,
+    cancel: watch::Sender<bool>,
+}
+
+impl TaskRecord {
+    fn new(task: &Task) -> Self {
+        Self {
+            progress: Mutex::new(TaskProgress {
+                task_id: task.id.clone(),
+                status: TaskStatus::Pending,
+                attempts: task.attempts,
+                device_id: task.device_id.clone(),
+                bytes_moved: 0,
+                submitted_at: SystemTime::now(),
+                started_at: None,
+                finished_at: None,
+                outcome: None,
+            }),
+            finished: watch::Sender::new(false),
+            cancel: watch::Sender::new(false),
+        }
+    }
+
+    pub fn progress(&self) -> TaskProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        *self.finished.borrow()
+    }
+
+    /// Record a dispatch, unless the task already finished
+    pub fn dispatched(&self, task: &Task) -> bool {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.outcome.is_some() {
+            return false;
+        }
+        progress.status = TaskStatus::Scheduled;
+        progress.attempts = task.attempts;
+        progress.device_id = task.device_id.clone();
+        progress.bytes_moved += task.transfers.iter().map(|transfer| transfer.bytes).sum::<u64>();
+        true
+    }
+
+    /// Record that the executor started the task
+    pub fn started(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.status == TaskStatus::Scheduled {
+            progress.status = TaskStatus::Running;
+            progress.started_at.get_or_insert_with(SystemTime::now);
+        }
+    }
+
+    /// Record that the task is waiting for another attempt
+    pub fn requeued(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.outcome.is_none() {
+            progress.status = TaskStatus::Pending;
+        }
+    }
+
+    /// Give the task its outcome; the first outcome wins
+    pub fn finish(&self, outcome: NodeOutcome) -> bool {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.outcome.is_some() {
+            return false;
+        }
+        progress.status = outcome.status();
+        progress.outcome = Some(outcome);
+        progress.finished_at = Some(SystemTime::now());
+        drop(progress);
+        self.finished.send_replace(true);
+        true
+    }
+
+    /// Ask for the task to be cancelled
+    ///
+    /// A task that has not been dispatched is cancelled at once. A dispatched task
+    /// keeps its status until the executor stops it and reports back.
+    pub fn request_cancel(&self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        self.cancel.send_replace(true);
+        if self.progress.lock().unwrap().status == TaskStatus::Pending {
+            self.finish(NodeOutcome::Cancelled("cancelled".to_string()));
+        }
+        true
+    }
+
+    pub fn cancel_requested(&self) -> bool {
+        *self.cancel.borrow()
+    }
+
+    pub fn cancel_signal(&self) -> CancelSignal {
+        CancelSignal(Some(self.cancel.subscribe()))
+    }
+
+    /// Wait for the task's outcome
+    pub async fn wait(&self) -> NodeOutcome {
+        let mut finished = self.finished.subscribe();
+        let _ = finished.wait_for(|done| *done).await;
+        self.progress.lock().unwrap().outcome.clone().expect("finished task has an outcome")
+    }
+}
+
+/// Resolves once cancellation of a running task is requested
+pub struct CancelSignal(Option<watch::Receiver<bool>>);
+
+impl CancelSignal {
+    /// A signal that never fires, for tasks the scheduler does not track
+    pub fn never() -> Self {
+        Self(None)
+    }
+
+    /// Wait until the task is cancelled
+    pub async fn cancelled(self) {
+        if let Some(mut cancel) = self.0 {
+            if cancel.wait_for(|cancelled| *cancelled).await.is_ok() {
+                return;
+            }
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+/// Records of every task the scheduler accepted
+pub(crate) struct TaskRegistry {
+    records: Mutex<HashMap<String, Arc<TaskRecord>>>,
+    /// How long the record of a finished task is kept
+    retention: Duration,
+    /// Tasks a handle cancelled, for the scheduler to take out of its queues
+    cancelled: Mutex<Vec<String>>,
+    /// Wakes blocked submitters, whose next attempt processes the cancellations
+    space: Arc<Notify>,
+}
+
+impl TaskRegistry {
+    pub fn new(retention: Duration, space: Arc<Notify>) -> Self {
+        Self {
+            records: Mutex::new(HashMap::new()),
+            retention,
+            cancelled: Mutex::new(Vec::new()),
+            space,
+        }
+    }
+
+    /// Start tracking a task, refusing the id of a task that has not finished
+    pub fn register(&self, task: &Task) -> Result<Arc<TaskRecord>, SchedulingError> {
+        Ok(self.register_all(&[task])?.remove(0))
+    }
+
+    /// Start tracking several tasks, or none of them if an id is taken
+    ///
+    /// An id stays taken until its task has finished and its record has expired, so
+    /// whoever polls the earlier task can still read its outcome.
+    pub fn register_all(&self, tasks: &[&Task]) -> Result<Vec<Arc<TaskRecord>>, SchedulingError> {
+        let mut records = self.records.lock().unwrap();
+        self.prune(&mut records);
+        let taken = |task: &&&Task| records.contains_key(&task.id);
+        if let Some(task) = tasks.iter().find(taken) {
+            return Err(SchedulingError::DuplicateTask(task.id.clone()));
+        }
+        Ok(tasks
+            .iter()
+            .map(|task| {
+                let record = Arc::new(TaskRecord::new(task));
+                records.insert(task.id.clone(), record.clone());
+                record
+            })
+            .collect())
+    }
+
+    /// Stop tracking a task that was never queued
+    pub fn forget(&self, task_id: &str) {
+        self.records.lock().unwrap().remove(task_id);
+    }
+
+    pub fn get(&self, task_id: &str) -> Option<Arc<TaskRecord>> {
+        let records = self.records.lock().unwrap();
+        records
+            .get(task_id)
+            .filter(|record| !self.expired(record, SystemTime::now()))
+            .cloned()
+    }
+
+    /// Cancel a task, leaving its removal from the queues to the scheduler
+    pub fn cancel(&self, task_id: &str) -> bool {
+        match self.get(task_id) {
+            Some(record) => self.cancel_record(task_id, &record),
+            None => false,
+        }
+    }
+
+    fn cancel_record(&self, task_id: &str, record: &TaskRecord) -> bool {
+        if !record.request_cancel() {
+            return false;
+        }
+        self.cancelled.lock().unwrap().push(task_id.to_string());
+        self.space.notify_waiters();
+        true
+    }
+
+    /// Tasks cancelled since the last call
+    pub fn take_cancelled(&self) -> Vec<String> {
+        std::mem::take(&mut *self.cancelled.lock().unwrap())
+    }
+
+    /// Drop the records of tasks that finished more than `retention` ago
+    fn prune(&self, records: &mut HashMap<String, Arc<TaskRecord>>) {
+        let now = SystemTime::now();
+        records.retain(|_, record| !self.expired(record, now));
+    }
+
+    fn expired(&self, record: &TaskRecord, now: SystemTime) -> bool {
+        let finished_at = record.progress.lock().unwrap().finished_at;
+        finished_at.is_some_and(|at| at + self.retention <= now)
+    }
+}
+
+/// Handle for following and cancelling a submitted task
+#[derive(Clone)]
+pub struct TaskHandle {
+    task_id: String,
+    record: Arc<TaskRecord>,
+    registry: Arc<TaskRegistry>,
+}
+
+impl fmt::Debug for TaskHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskHandle")
+            .field("task_id", &self.task_id)
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl TaskHandle {
+    pub(crate) fn new(task_id: String, record: Arc<TaskRecord>, registry: Arc<TaskRegistry>) -> Self {
+        Self {
+            task_id,
+            record,
+            registry,
+        }
+    }
+
+    /// Id of the task
+    pub fn id(&self) -> &str {
+        &self.task_id
+    }
+
+    pub fn status(&self) -> TaskStatus {
+        self.record.progress().status
+    }
+
+    /// Status, attempts and timings of the task
+    pub fn progress(&self) -> TaskProgress {
+        self.record.progress()
+    }
+
+    /// Check whether the task completed, failed or was cancelled
+    pub fn is_finished(&self) -> bool {
+        self.record.is_finished()
+    }
+
+    /// Outcome of the task, if it has finished
+    pub fn outcome(&self) -> Option<NodeOutcome> {
+        self.record.progress().outcome
+    }
+
+    /// Wait for the task to complete, fail or be cancelled
+    pub async fn wait(&self) -> NodeOutcome {
+        self.record.wait().await
+    }
+
+    /// Cancel the task, stopping it if it is already running
+    ///
+    /// Returns false if the task had already finished.
+    pub fn cancel(&self) -> bool {
+        self.registry.cancel_record(&self.task_id, &self.record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::{HardwareType, TaskRequirements, TaskResult};
+    use crate::scheduling::TaskPriority;
+
+    fn task(id: &str) -> Task {
+        let requirements = TaskRequirements::for_test(HardwareType::Cpu);
+        Task::new(id.to_string(), id.to_string(), requirements, TaskPriority::Normal)
+    }
+
+    #[tokio::test]
+    async fn test_records_track_status_and_expire_after_retention() {
+        let registry = Arc::new(TaskRegistry::new(Duration::from_millis(20), Arc::new(Notify::new())));
+        let first = task("a");
+        let record = registry.register(&first).unwrap();
+        assert_eq!(registry.register(&first).err(), Some(SchedulingError::DuplicateTask("a".to_string())));
+        let handle = TaskHandle::new("a".to_string(), record.clone(), registry.clone());
+
+        let mut dispatched = first.clone();
+        dispatched.attempts = 1;
+        dispatched.device_id = Some("cpu-0".to_string());
+        assert!(record.dispatched(&dispatched));
+        record.started();
+        let progress = handle.progress();
+        assert_eq!(progress.status, TaskStatus::Running);
+        assert_eq!(progress.device_id.as_deref(), Some("cpu-0"));
+        assert!(progress.started_at.is_some());
+
+        let result = TaskResult {
+            task_id: "a".to_string(),
+            data: vec![1],
+            execution_time: 0,
+            energy: 0.0,
+        };
+        assert!(record.finish(NodeOutcome::Completed(result)));
+        assert!(!handle.cancel());
+        assert_eq!(handle.wait().await.result().unwrap().data, vec![1]);
+        assert_eq!(registry.register(&first).err(), Some(SchedulingError::DuplicateTask("a".to_string())));
+        assert_eq!(registry.get("a").unwrap().progress().status, TaskStatus::Completed);
+
+        // The id is free again once the record has expired, the handle still works
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(registry.get("a").is_none());
+        assert!(registry.register(&first).is_ok());
+        assert_eq!(handle.status(), TaskStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_finishes_queued_tasks_and_signals_running_ones() {
+        let registry = TaskRegistry::new(Duration::from_secs(60), Arc::new(Notify::new()));
+        let queued = registry.register(&task("queued")).unwrap();
+        let running = registry.register(&task("running")).unwrap();
+
+        assert!(registry.cancel("queued"));
+        assert_eq!(queued.progress().status, TaskStatus::Cancelled);
+
+        let mut dispatched = task("running");
+        dispatched.attempts = 1;
+        running.dispatched(&dispatched);
+        let signal = running.cancel_signal();
+        assert!(registry.cancel("running"));
+        tokio::time::timeout(Duration::from_secs(1), signal.cancelled()).await.unwrap();
+        assert_eq!(running.progress().status, TaskStatus::Scheduled);
+        assert_eq!(registry.take_cancelled(), vec!["queued", "running"]);
+    }
+}
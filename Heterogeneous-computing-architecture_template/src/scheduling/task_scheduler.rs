@@ -1,84 +1,934 @@
 // Task scheduler implementation
 
-use std::collections::VecDeque;
-use crate::hardware::abstract_device::HardwareDevice;
+use crate::data::DataManager;
+use crate::errors::SchedulingError;
+use crate::hardware::abstract_device::{self, Capacity, DeviceQuota, HardwareType, TaskRequirements, TaskResult};
+use crate::scheduling::dispatch::{Admission, DeviceSlot, OrderKey, RunQueues};
+use crate::scheduling::fair_share::FairShare;
+use crate::scheduling::lifecycle::{DeadLetter, FailureAction, RetryQueue};
+use crate::scheduling::locality::{Locality, Transfer};
+use crate::scheduling::list_scheduler::{self, CostModel, ListHeuristic, Schedule};
+use crate::scheduling::resource_allocator::ResourceAllocator;
+use crate::scheduling::scheduler_config::{OverflowPolicy, SchedulerConfig};
+use crate::scheduling::task_graph::{GraphHandle, GraphProgress, GraphRun, NodeOutcome, TaskGraph};
+use crate::scheduling::task_handle::{CancelSignal, TaskHandle, TaskProgress, TaskRegistry};
+use crate::scheduling::thermal::{TemperatureSample, TemperatureSource, ThermalMonitor, ThermalState};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Notify;
+use tracing::{debug, warn};
 
-pub struct TaskScheduler {
-    tasks: VecDeque<Task>,
-    strategy: SchedulingStrategy,
+/// Task priority levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TaskPriority {
+    Critical = 100,
+    High = 75,
+    Normal = 50,
+    Low = 25,
+    Background = 10,
+}
+
+impl TaskPriority {
+    /// Every level, highest first
+    pub const ALL: [TaskPriority; 5] = [
+        TaskPriority::Critical,
+        TaskPriority::High,
+        TaskPriority::Normal,
+        TaskPriority::Low,
+        TaskPriority::Background,
+    ];
+
+    /// Name of the level in task_scheduling.yaml
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskPriority::Critical => "critical",
+            TaskPriority::High => "high",
+            TaskPriority::Normal => "normal",
+            TaskPriority::Low => "low",
+            TaskPriority::Background => "background",
+        }
+    }
 }
 
-#[derive(Clone)]
+/// Scheduling strategy, named in `scheduling.algorithm` of task_scheduling.yaml
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SchedulingStrategy {
     RoundRobin,
     Priority,
     ShortestJobFirst,
+    /// Plan graphs with HEFT and dispatch in planned order
+    Heft,
+    /// Plan graphs with PEFT and dispatch in planned order
+    Peft,
 }
 
+impl SchedulingStrategy {
+    /// List-scheduling heuristic used to plan graphs, if any
+    pub fn list_heuristic(&self) -> Option<ListHeuristic> {
+        match self {
+            SchedulingStrategy::Heft => Some(ListHeuristic::Heft),
+            SchedulingStrategy::Peft => Some(ListHeuristic::Peft),
+            _ => None,
+        }
+    }
+}
+
+/// Task structure
+#[derive(Debug, Clone)]
 pub struct Task {
     pub id: String,
-    pub priority: u8,
+    pub name: String,
+    pub requirements: TaskRequirements,
+    pub priority: TaskPriority,
+    pub submission_time: u64,
+    pub status: TaskStatus,
     pub estimated_duration: u64,
-    pub required_resources: ResourceRequirements,
+    pub operation: String,
+    pub data: Vec<u8>,
+
+    /// Device the task must run on, set when a plan places it
+    pub device_id: Option<String>,
+
+    /// Times the task has been dispatched
+    pub attempts: u32,
+
+    /// Ids of the data objects the task reads
+    pub inputs: Vec<String>,
+
+    /// Ids of the data objects the task writes, held by its device once it completes
+    pub outputs: Vec<String>,
+
+    /// Copies of inputs to make before the task runs, set when it is dispatched
+    pub transfers: Vec<Transfer>,
 }
 
-pub struct ResourceRequirements {
-    pub cpu_cores: usize,
-    pub memory: u64,
-    pub device_type: String,
+/// Task status enumeration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskStatus {
+    Pending,
+    Scheduled,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl Task {
+    /// Create a new task
+    pub fn new(
+        id: String,
+        name: String,
+        requirements: TaskRequirements,
+        priority: TaskPriority,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            requirements,
+            priority,
+            submission_time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            status: TaskStatus::Pending,
+            estimated_duration: 0,
+            operation: "compute".to_string(),
+            data: Vec::new(),
+            device_id: None,
+            attempts: 0,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            transfers: Vec::new(),
+        }
+    }
+
+    /// Set the operation and input payload executed by this task
+    pub fn with_operation(mut self, operation: &str, data: Vec<u8>) -> Self {
+        self.operation = operation.to_string();
+        self.data = data;
+        self
+    }
+
+    /// Read the given data objects, preferring devices that already hold them
+    pub fn with_inputs(mut self, inputs: Vec<String>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    /// Write the given data objects
+    pub fn with_outputs(mut self, outputs: Vec<String>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// Build the device-level task dispatched to a hardware device
+    pub fn to_device_task(&self) -> abstract_device::Task {
+        abstract_device::Task {
+            id: self.id.clone(),
+            data: self.data.clone(),
+            operation: self.operation.clone(),
+            kernel: self.requirements.kernel.clone(),
+            devices: Vec::new(),
+            cancel: abstract_device::CancelToken::new(),
+            limit: None,
+        }
+    }
+}
+
+/// Outcome of one attempt to queue a task
+enum Submission {
+    Accepted(TaskHandle),
+    /// The task is handed back so a blocked submitter can retry it
+    Refused(Box<Task>, SchedulingError),
+}
+
+/// Task scheduler implementation
+///
+/// Submitted tasks wait in bounded per-device run queues (see `dispatch`). Every task
+/// handed out by `schedule_next_task` counts against `max_concurrent_tasks` until it
+/// is reported with `complete_task` or `fail_task`.
+pub struct TaskScheduler {
+    queues: Mutex<RunQueues>,
+    resource_allocator: Arc<ResourceAllocator>,
+    strategy: SchedulingStrategy,
+    config: SchedulerConfig,
+    /// Woken whenever a run queue gains room
+    space: Arc<Notify>,
+    /// Status and outcome of every submitted task
+    registry: Arc<TaskRegistry>,
+    /// Failed tasks waiting for another attempt, and those out of attempts
+    retries: Mutex<RetryQueue>,
+    /// Graph each unfinished graph task belongs to
+    graph_tasks: Mutex<HashMap<String, Arc<GraphRun>>>,
+    next_graph_id: AtomicU64,
+    /// Planned start of each queued task placed by a list-scheduling plan
+    planned_starts: Mutex<HashMap<String, Duration>>,
+    /// Weighted fair order of the queued tasks, used by the `Priority` strategy
+    fair: Mutex<FairShare>,
+    /// Execution estimates that override the devices' own models
+    estimates: Mutex<HashMap<(String, HardwareType), Duration>>,
+    /// Device temperatures; locked and released before the run queues
+    thermal: Mutex<ThermalMonitor>,
+    /// Where the data objects tasks read and write are held
+    data: Option<Arc<DataManager>>,
+    /// Device, copied inputs and outputs of each dispatched task that moves data
+    staged: Mutex<HashMap<String, Staged>>,
+    /// Devices holding a copy of a data object besides its location
+    replicas: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+/// Data a dispatched task copies to its device and writes there
+struct Staged {
+    device_id: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
 }
 
 impl TaskScheduler {
-    pub fn new(strategy: SchedulingStrategy) -> Self {
+    /// Create a new task scheduler
+    pub fn new(resource_allocator: Arc<ResourceAllocator>) -> Self {
+        Self::with_strategy(resource_allocator, SchedulingStrategy::Priority)
+    }
+
+    /// Create a new task scheduler with an explicit strategy
+    pub fn with_strategy(resource_allocator: Arc<ResourceAllocator>, strategy: SchedulingStrategy) -> Self {
+        Self::with_config(resource_allocator, strategy, SchedulerConfig::default())
+    }
+
+    /// Create a new task scheduler with explicit queue limits
+    ///
+    /// `config` is used as given; `SchedulerConfig::from_yaml` and `load` validate it.
+    pub fn with_config(
+        resource_allocator: Arc<ResourceAllocator>,
+        strategy: SchedulingStrategy,
+        config: SchedulerConfig,
+    ) -> Self {
+        let space = Arc::new(Notify::new());
+        let retention = Duration::from_millis(config.lifecycle.retention);
         Self {
-            tasks: VecDeque::new(),
+            queues: Mutex::new(RunQueues::new(config.task_queue.max_size).with_energy(config.energy.clone())),
+            retries: Mutex::new(RetryQueue::new(config.task_queue.max_size)),
+            fair: Mutex::new(FairShare::new(&config.priorities)),
+            thermal: Mutex::new(ThermalMonitor::new(config.thermal.clone())),
+            resource_allocator,
             strategy,
+            config,
+            registry: Arc::new(TaskRegistry::new(retention, space.clone())),
+            space,
+            graph_tasks: Mutex::new(HashMap::new()),
+            next_graph_id: AtomicU64::new(1),
+            planned_starts: Mutex::new(HashMap::new()),
+            estimates: Mutex::new(HashMap::new()),
+            data: None,
+            staged: Mutex::new(HashMap::new()),
+            replicas: Mutex::new(HashMap::new()),
         }
     }
 
-    pub fn add_task(&mut self, task: Task) {
-        self.tasks.push_back(task);
+    /// Read device temperatures from `source` instead of the devices' metrics
+    pub fn with_temperature_source(self, source: Arc<dyn TemperatureSource>) -> Self {
+        self.thermal.lock().unwrap().set_source(source);
+        self
     }
 
-    pub fn get_next_task(&mut self) -> Option<Task> {
-        match self.strategy {
-            SchedulingStrategy::RoundRobin => self.tasks.pop_front(),
-            SchedulingStrategy::Priority => {
-                // Find the task with the highest priority
-                let mut max_priority = 0;
-                let mut max_index = None;
-                
-                for (i, task) in self.tasks.iter().enumerate() {
-                    if task.priority > max_priority {
-                        max_priority = task.priority;
-                        max_index = Some(i);
-                    }
+    /// Place tasks near their inputs and track their data objects in `data`
+    pub fn with_data_manager(mut self, data: Arc<DataManager>) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// Get the resource allocator used by this scheduler
+    pub fn resource_allocator(&self) -> &Arc<ResourceAllocator> {
+        &self.resource_allocator
+    }
+
+    /// Queue limits this scheduler enforces
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// Submit a new task to the scheduler
+    ///
+    /// A full run queue is handled by the overflow policy. Under `Block` the task is
+    /// refused, since only `submit` can wait for room. The id of a task that has not
+    /// finished yet cannot be reused.
+    pub fn submit_task(&self, task: Task) -> Result<TaskHandle, Box<dyn std::error::Error>> {
+        debug!("Submitting task: {}", task.name);
+        match self.try_submit(task) {
+            Submission::Accepted(handle) => Ok(handle),
+            Submission::Refused(_, error) => Err(error.into()),
+        }
+    }
+
+    /// Submit a task, waiting for room under the `Block` overflow policy
+    pub async fn submit(&self, mut task: Task) -> Result<TaskHandle, SchedulingError> {
+        debug!("Submitting task: {}", task.name);
+        loop {
+            // Register for wake-ups before trying, so room made in between is not missed
+            let space = self.space.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+
+            match self.try_submit(task) {
+                Submission::Accepted(handle) => return Ok(handle),
+                Submission::Refused(refused, _) if self.config.task_queue.overflow_policy == OverflowPolicy::Block => {
+                    task = *refused;
+                    space.await;
                 }
-                
-                if let Some(index) = max_index {
-                    self.tasks.remove(index)
-                } else {
-                    self.tasks.pop_front()
+                Submission::Refused(_, error) => return Err(error),
+            }
+        }
+    }
+
+    fn try_submit(&self, mut task: Task) -> Submission {
+        self.process_cancellations();
+        self.apply_lifecycle(&mut task);
+        let task_id = task.id.clone();
+        let record = match self.registry.register(&task) {
+            Ok(record) => record,
+            Err(error) => return Submission::Refused(Box::new(task), error),
+        };
+
+        self.fair.lock().unwrap().enqueue(&task, Instant::now());
+        let admission = self.queues().admit(task, self.config.task_queue.overflow_policy);
+        match admission {
+            Admission::Queued => {}
+            Admission::Evicted(evicted) => self.dropped(evicted),
+            Admission::Full { task, queue } => {
+                self.registry.forget(&task_id);
+                self.fair.lock().unwrap().remove(&task_id);
+                let capacity = self.config.task_queue.max_size;
+                return Submission::Refused(Box::new(task), SchedulingError::QueueFull { queue, capacity });
+            }
+        }
+        Submission::Accepted(TaskHandle::new(task_id, record, self.registry.clone()))
+    }
+
+    /// Handle of a task submitted on its own or as part of a graph
+    ///
+    /// Finished tasks are found until `lifecycle.retention` has passed.
+    pub fn task_handle(&self, task_id: &str) -> Option<TaskHandle> {
+        let record = self.registry.get(task_id)?;
+        Some(TaskHandle::new(task_id.to_string(), record, self.registry.clone()))
+    }
+
+    /// Status and progress of a submitted task
+    pub fn task_progress(&self, task_id: &str) -> Option<TaskProgress> {
+        self.registry.get(task_id).map(|record| record.progress())
+    }
+
+    /// Use a fixed execution time for an operation on a device type when planning
+    pub fn set_execution_estimate(&self, operation: &str, device_type: HardwareType, estimate: Duration) {
+        self.estimates
+            .lock()
+            .unwrap()
+            .insert((operation.to_string(), device_type), estimate);
+    }
+
+    /// Plan a graph over the devices currently accepting work
+    pub fn plan_graph(&self, graph: &TaskGraph, heuristic: ListHeuristic) -> Result<Schedule, SchedulingError> {
+        let hardware_manager = self.resource_allocator.hardware_manager();
+        let devices = hardware_manager
+            .get_all_devices()
+            .into_iter()
+            .filter(|device| device.is_available() && hardware_manager.is_schedulable(device.id()))
+            .collect();
+        let model = CostModel::new(devices).with_estimates(self.estimates.lock().unwrap().clone());
+        list_scheduler::plan(graph, &model, heuristic)
+    }
+
+    /// Submit a dependency graph
+    ///
+    /// Tasks without dependencies are queued straight away; the rest are queued as
+    /// `complete_task` reports their dependencies done. Under `Heft` and `Peft` every
+    /// task is first pinned to the device the plan chose for it. The graph counts as
+    /// admitted as a whole, so its tasks wait in a backlog rather than being refused
+    /// when their queue is full. Every task in the graph can be followed with
+    /// `task_handle`.
+    pub fn submit_graph(&self, mut graph: TaskGraph) -> Result<GraphHandle, SchedulingError> {
+        let schedule = match self.strategy.list_heuristic() {
+            Some(heuristic) => Some(self.plan_graph(&graph, heuristic)?),
+            None => None,
+        };
+        if let Some(schedule) = &schedule {
+            self.pin(&mut graph, schedule);
+        }
+        let task_ids: Vec<String> = graph.task_ids().map(str::to_string).collect();
+        for task_id in &task_ids {
+            if let Some(task) = graph.task_mut(task_id) {
+                self.apply_lifecycle(task);
+            }
+        }
+
+        graph.topological_order()?;
+        let tasks: Vec<&Task> = task_ids.iter().filter_map(|id| graph.task(id)).collect();
+        self.registry.register_all(&tasks)?;
+
+        let graph_id = format!("graph-{}", self.next_graph_id.fetch_add(1, Ordering::Relaxed));
+        let (run, ready) = GraphRun::start(graph_id, graph, schedule.as_ref().map(|schedule| schedule.makespan))?;
+        let mut graph_tasks = self.graph_tasks.lock().unwrap();
+        if let Some(schedule) = &schedule {
+            let mut planned_starts = self.planned_starts.lock().unwrap();
+            for assignment in &schedule.assignments {
+                planned_starts.insert(assignment.task_id.clone(), assignment.start);
+            }
+        }
+        debug!("Submitting graph {} with {} tasks", run.id(), task_ids.len());
+        for task_id in task_ids {
+            graph_tasks.insert(task_id, run.clone());
+        }
+        drop(graph_tasks);
+
+        let mut queues = self.queues();
+        for task in ready {
+            self.hold(&mut queues, task);
+        }
+        Ok(GraphHandle::new(run))
+    }
+
+    /// Report a task as completed, queueing graph tasks that were waiting on it
+    pub fn complete_task(&self, task_id: &str, result: TaskResult) {
+        self.queues.lock().unwrap().finish(task_id);
+        let staged = self.staged.lock().unwrap().remove(task_id);
+        if let (Some(data), Some(staged)) = (&self.data, staged) {
+            // The task ran, so its inputs made it to the device; its outputs now live there
+            let mut replicas = self.replicas.lock().unwrap();
+            for data_id in staged.inputs {
+                replicas.entry(data_id).or_default().insert(staged.device_id.clone());
+            }
+            for data_id in staged.outputs {
+                replicas.remove(&data_id);
+                if let Err(e) = data.move_data(&data_id, staged.device_id.clone()) {
+                    warn!("Task {} wrote unknown data object {}: {}", task_id, data_id, e);
                 }
-            },
-            SchedulingStrategy::ShortestJobFirst => {
-                // Find the task with the shortest estimated duration
-                let mut min_duration = u64::MAX;
-                let mut min_index = None;
-                
-                for (i, task) in self.tasks.iter().enumerate() {
-                    if task.estimated_duration < min_duration {
-                        min_duration = task.estimated_duration;
-                        min_index = Some(i);
+            }
+        }
+        if let Some(record) = self.registry.get(task_id) {
+            record.finish(NodeOutcome::Completed(result.clone()));
+        }
+        if let Some(run) = self.graph_run(task_id) {
+            let progress = run.complete(task_id, result);
+            self.apply(&run, task_id, progress);
+        }
+    }
+
+    /// Mark a dispatched task as running, returning the signal that cancels it
+    pub fn start_task(&self, task_id: &str) -> CancelSignal {
+        match self.registry.get(task_id) {
+            Some(record) => {
+                record.started();
+                record.cancel_signal()
+            }
+            None => CancelSignal::never(),
+        }
+    }
+
+    /// Report a failed attempt, retrying the task if the error allows it
+    ///
+    /// A retryable failure puts the task back in the queue after a jittered backoff
+    /// delay, on any device of its type, until `lifecycle.retry.max_attempts` runs are
+    /// used up. It then moves to the dead-letter queue and fails like a permanent error.
+    /// A task that was cancelled while running is never retried.
+    pub fn report_failure(&self, mut task: Task, reason: &str, retryable: bool) -> FailureAction {
+        let retry = &self.config.lifecycle.retry;
+        let record = self.registry.get(&task.id);
+        if record.as_ref().is_some_and(|record| record.cancel_requested()) {
+            self.fail_task(&task.id, reason);
+            return FailureAction::Cancelled;
+        }
+        if !retryable {
+            self.fail_task(&task.id, reason);
+            return FailureAction::Failed;
+        }
+        if task.attempts < retry.max_attempts {
+            self.queues.lock().unwrap().finish(&task.id);
+            let delay = retry.backoff.delay(task.attempts);
+            debug!("Retrying task {} in {:?} after attempt {}: {}", task.id, delay, task.attempts, reason);
+            task.device_id = None;
+            task.status = TaskStatus::Pending;
+            if let Some(record) = record {
+                record.requeued();
+            }
+            self.retries.lock().unwrap().delay(task, Instant::now() + delay);
+            return FailureAction::Retried { delay };
+        }
+
+        warn!("Task {} failed {} times, moving it to the dead-letter queue: {}", task.id, task.attempts, reason);
+        let task_id = task.id.clone();
+        let attempts = task.attempts;
+        self.retries.lock().unwrap().bury(DeadLetter {
+            task,
+            attempts,
+            error: reason.to_string(),
+            failed_at: SystemTime::now(),
+        });
+        self.fail_task(&task_id, &format!("gave up after {} attempts: {}", attempts, reason));
+        FailureAction::DeadLettered
+    }
+
+    /// Time until the next task waiting for a retry is due
+    pub fn next_retry_in(&self) -> Option<Duration> {
+        let due = self.retries.lock().unwrap().next_due()?;
+        Some(due.saturating_duration_since(Instant::now()))
+    }
+
+    /// Tasks that failed on every attempt, oldest first
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.retries.lock().unwrap().dead_letters()
+    }
+
+    /// Empty the dead-letter queue, returning its tasks
+    pub fn take_dead_letters(&self) -> Vec<DeadLetter> {
+        self.retries.lock().unwrap().take_dead_letters()
+    }
+
+    /// Report a task as failed, cancelling graph tasks that depend on it
+    ///
+    /// A task whose cancellation was requested ends up cancelled rather than failed.
+    pub fn fail_task(&self, task_id: &str, reason: &str) {
+        self.queues.lock().unwrap().finish(task_id);
+        self.staged.lock().unwrap().remove(task_id);
+        let record = self.registry.get(task_id);
+        let outcome = match &record {
+            Some(record) if record.cancel_requested() => NodeOutcome::Cancelled(reason.to_string()),
+            _ => NodeOutcome::Failed(reason.to_string()),
+        };
+        if let Some(record) = record {
+            record.finish(outcome.clone());
+        }
+        if let Some(run) = self.graph_run(task_id) {
+            let progress = run.fail(task_id, outcome);
+            self.apply(&run, task_id, progress);
+        }
+    }
+
+    /// Pin every task in a graph to its planned device
+    fn pin(&self, graph: &mut TaskGraph, schedule: &Schedule) {
+        for assignment in &schedule.assignments {
+            let Some(task) = graph.task_mut(&assignment.task_id) else {
+                continue;
+            };
+            // A gang gets its devices together when it runs
+            if task.requirements.is_gang() {
+                continue;
+            }
+            task.device_id = Some(assignment.device_id.clone());
+        }
+    }
+
+    fn graph_run(&self, task_id: &str) -> Option<Arc<GraphRun>> {
+        self.graph_tasks.lock().unwrap().get(task_id).cloned()
+    }
+
+    fn apply(&self, run: &GraphRun, task_id: &str, progress: GraphProgress) {
+        let mut graph_tasks = self.graph_tasks.lock().unwrap();
+        let mut planned_starts = self.planned_starts.lock().unwrap();
+        graph_tasks.remove(task_id);
+        for finished in &progress.finished {
+            graph_tasks.remove(finished);
+            planned_starts.remove(finished);
+        }
+        drop(planned_starts);
+        drop(graph_tasks);
+
+        if !progress.finished.is_empty() {
+            debug!("Task {} ended {} dependent tasks", task_id, progress.finished.len());
+        }
+        for finished in &progress.finished {
+            if let (Some(record), Some(outcome)) = (self.registry.get(finished), run.outcome(finished)) {
+                record.finish(outcome);
+            }
+        }
+        let mut queues = self.queues();
+        for task in progress.ready {
+            self.hold(&mut queues, task);
+        }
+    }
+
+    /// Give a task without its own timeout the lifecycle default
+    fn apply_lifecycle(&self, task: &mut Task) {
+        if task.requirements.timeout == 0 {
+            task.requirements.timeout = self.config.lifecycle.timeout;
+        }
+    }
+
+    /// Handle a task evicted by the `Drop` overflow policy
+    fn dropped(&self, task: Task) {
+        warn!("Run queue full, dropped task {}", task.id);
+        self.planned_starts.lock().unwrap().remove(&task.id);
+        self.fair.lock().unwrap().remove(&task.id);
+        let outcome = NodeOutcome::Cancelled("dropped from a full run queue".to_string());
+        if let Some(record) = self.registry.get(&task.id) {
+            record.finish(outcome.clone());
+        }
+        if let Some(run) = self.graph_run(&task.id) {
+            let progress = run.fail(&task.id, outcome);
+            self.apply(&run, &task.id, progress);
+        }
+    }
+
+    /// Take tasks cancelled through their handles out of the queues
+    fn process_cancellations(&self) {
+        for task_id in self.registry.take_cancelled() {
+            // Remove the task from its run queue
+            let removed = self.queues.lock().unwrap().remove(&task_id).is_some();
+            if removed {
+                self.space.notify_waiters();
+            }
+            let removed = removed || self.retries.lock().unwrap().remove(&task_id).is_some();
+            self.planned_starts.lock().unwrap().remove(&task_id);
+            self.fair.lock().unwrap().remove(&task_id);
+
+            // A graph task that has not started yet takes its dependents with it
+            if let Some(run) = self.graph_run(&task_id) {
+                if removed || run.is_pending(&task_id) {
+                    let progress = run.fail(&task_id, NodeOutcome::Cancelled("cancelled".to_string()));
+                    self.apply(&run, &task_id, progress);
+                }
+            }
+        }
+    }
+
+    /// Queue a task that was already admitted, such as a graph task or a retry
+    fn hold(&self, queues: &mut RunQueues, task: Task) {
+        self.fair.lock().unwrap().enqueue(&task, Instant::now());
+        queues.admit_or_hold(task);
+    }
+
+    /// Note the copies a dispatched task makes to its device and where its outputs will land
+    ///
+    /// Inputs keep their location; the device only counts as holding a replica once the
+    /// task completes there. Gang tasks have no single device and read their inputs
+    /// where they are.
+    fn stage(&self, task: &Task) {
+        let (Some(_), Some(device_id)) = (&self.data, &task.device_id) else {
+            return;
+        };
+        for transfer in &task.transfers {
+            debug!(
+                "Task {} copies {} ({} bytes) from {} to {}",
+                task.id, transfer.data_id, transfer.bytes, transfer.from, transfer.to
+            );
+        }
+        if !task.transfers.is_empty() || !task.outputs.is_empty() {
+            let staged = Staged {
+                device_id: device_id.clone(),
+                inputs: task.transfers.iter().map(|transfer| transfer.data_id.clone()).collect(),
+                outputs: task.outputs.clone(),
+            };
+            self.staged.lock().unwrap().insert(task.id.clone(), staged);
+        }
+    }
+
+    /// Devices holding a replica of a data object, besides the object's location
+    pub fn data_replicas(&self, data_id: &str) -> Vec<String> {
+        let mut devices: Vec<String> = self
+            .replicas
+            .lock()
+            .unwrap()
+            .get(data_id)
+            .map(|devices| devices.iter().cloned().collect())
+            .unwrap_or_default();
+        devices.sort();
+        devices
+    }
+
+    /// Read every device's temperature now, rather than when the sample interval has passed
+    pub fn sample_temperatures(&self) {
+        let devices = self.resource_allocator.hardware_manager().get_all_devices();
+        self.thermal.lock().unwrap().sample(&devices, Instant::now());
+    }
+
+    /// Thermal state of a device as of its last reading
+    pub fn thermal_state(&self, device_id: &str) -> ThermalState {
+        self.thermal.lock().unwrap().state(device_id)
+    }
+
+    /// Recent temperature readings of a device, oldest first
+    pub fn temperature_history(&self, device_id: &str) -> Vec<TemperatureSample> {
+        self.thermal.lock().unwrap().history(device_id)
+    }
+
+    /// Lock the run queues after bringing their device set and temperatures up to date
+    fn queues(&self) -> MutexGuard<'_, RunQueues> {
+        let hardware_manager = self.resource_allocator.hardware_manager();
+        let mut quotas: HashMap<String, DeviceQuota> = hardware_manager
+            .reservations()
+            .into_iter()
+            .map(|reservation| (reservation.device_id, reservation.quota))
+            .collect();
+        let devices = hardware_manager.get_all_devices();
+        let locality = self.data.as_ref().map(|data| {
+            let replicas = self.replicas.lock().unwrap().clone();
+            Locality::new(data.get_all_data_objects(), replicas, &devices)
+        });
+        let mut thermal = self.thermal.lock().unwrap();
+        thermal.sample_due(&devices, Instant::now());
+        let slots = devices
+            .into_iter()
+            .map(|device| DeviceSlot {
+                quota: quotas.remove(device.id()).unwrap_or_else(|| DeviceQuota::whole(Capacity::default())),
+                schedulable: device.is_available() && hardware_manager.is_schedulable(device.id()),
+                thermal: thermal.state(device.id()),
+                device,
+            })
+            .collect();
+        drop(thermal);
+
+        let mut queues = self.queues.lock().unwrap();
+        if let Some(locality) = locality {
+            queues.set_locality(locality);
+        }
+        queues.sync(slots);
+        queues
+    }
+
+    /// Schedule the next available task
+    ///
+    /// The task comes out pinned to the device whose queue it was taken from, or that
+    /// stole it. Returns `None` once `max_concurrent_tasks` tasks are in flight.
+    pub fn schedule_next_task(&self) -> Option<Task> {
+        debug!("Attempting to schedule next task");
+
+        self.process_cancellations();
+        let due = self.retries.lock().unwrap().due(Instant::now());
+        let mut queues = self.queues();
+        for task in due {
+            self.hold(&mut queues, task);
+        }
+        let mut planned_starts = self.planned_starts.lock().unwrap();
+        let mut fair = self.fair.lock().unwrap();
+        let now = Instant::now();
+        let mut skipped = Vec::new();
+        let next = {
+            let planned_starts = &planned_starts;
+            let fair = &fair;
+            let key = |task: &Task, seq: u64| -> OrderKey {
+                match self.strategy {
+                    SchedulingStrategy::RoundRobin => (0, seq),
+                    // Weighted fair share between levels, with aging
+                    SchedulingStrategy::Priority => (fair.key(task, now), seq),
+                    SchedulingStrategy::ShortestJobFirst => (task.estimated_duration, seq),
+                    // Planned tasks go in order of planned start, anything else after them
+                    SchedulingStrategy::Heft | SchedulingStrategy::Peft => {
+                        let start = planned_starts.get(&task.id).map_or(u64::MAX, |start| start.as_nanos() as u64);
+                        (start, seq)
                     }
                 }
-                
-                if let Some(index) = min_index {
-                    self.tasks.remove(index)
-                } else {
-                    self.tasks.pop_front()
+            };
+            loop {
+                let Some(mut task) = queues.next(key, self.config.max_concurrent_tasks) else {
+                    break None;
+                };
+                task.attempts += 1;
+                task.status = TaskStatus::Scheduled;
+                task.transfers = queues.transfers(&task);
+                // Skip tasks a handle cancelled after the last sweep
+                match self.registry.get(&task.id) {
+                    Some(record) if !record.dispatched(&task) => {
+                        queues.finish(&task.id);
+                        skipped.push(task.id);
+                    }
+                    _ => break Some(task),
                 }
             }
+        };
+        for task_id in &skipped {
+            planned_starts.remove(task_id);
+            fair.remove(task_id);
+        }
+        let task = next?;
+        planned_starts.remove(&task.id);
+        fair.dispatched(&task.id);
+        drop(fair);
+        drop(planned_starts);
+        drop(queues);
+        self.stage(&task);
+
+        self.space.notify_waiters();
+        Some(task)
+    }
+
+    /// Get pending tasks count, including tasks waiting for a retry
+    pub fn get_pending_tasks_count(&self) -> usize {
+        let queued = self.queues.lock().unwrap().len();
+        queued + self.retries.lock().unwrap().len()
+    }
+
+    /// Tasks handed out and not yet reported finished
+    pub fn tasks_in_flight(&self) -> usize {
+        self.queues.lock().unwrap().in_flight()
+    }
+
+    /// Modelled draw of every device in watts, counting those running tasks at their active draw
+    pub fn power_draw(&self) -> f64 {
+        self.queues().power_draw()
+    }
+
+    /// Length of every device run queue, plus the queue of tasks no device can run
+    pub fn queue_lengths(&self) -> Vec<(String, usize)> {
+        self.queues().lengths()
+    }
+
+    /// Cancel a task by ID
+    ///
+    /// A queued task is cancelled at once. A running task is stopped by the executor,
+    /// which then reports it cancelled.
+    pub fn cancel_task(&self, task_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("Cancelling task: {}", task_id);
+
+        if self.registry.get(task_id).is_none() {
+            return Err(SchedulingError::UnknownTask(task_id.to_string()).into());
         }
+        self.registry.cancel(task_id);
+        self.process_cancellations();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::abstract_device::HardwareType;
+
+    #[test]
+    fn test_task_creation() {
+        let requirements = TaskRequirements {
+            memory_required: 1024,
+            timeout: 300000,
+            ..TaskRequirements::for_test(HardwareType::Cpu)
+        };
+
+        let task = Task::new(
+            "task-1".to_string(),
+            "Test task".to_string(),
+            requirements,
+            TaskPriority::Normal,
+        );
+
+        assert_eq!(task.id, "task-1");
+        assert_eq!(task.name, "Test task");
+        assert_eq!(task.requirements.hardware_type, HardwareType::Cpu);
+        assert_eq!(task.priority, TaskPriority::Normal);
+    }
+
+    #[test]
+    fn test_task_prioritization() {
+        let requirements1 = TaskRequirements {
+            memory_required: 1024,
+            timeout: 300000,
+            ..TaskRequirements::for_test(HardwareType::Cpu)
+        };
+
+        let requirements2 = TaskRequirements {
+            memory_required: 2048,
+            compute_units: 2,
+            priority: 75,
+            timeout: 300000,
+            ..TaskRequirements::for_test(HardwareType::Gpu)
+        };
+
+        let task1 = Task::new(
+            "task-1".to_string(),
+            "Low priority task".to_string(),
+            requirements1,
+            TaskPriority::Low,
+        );
+
+        let task2 = Task::new(
+            "task-2".to_string(),
+            "High priority task".to_string(),
+            requirements2,
+            TaskPriority::High,
+        );
+
+        assert!(task2.priority > task1.priority);
+    }
+
+    #[test]
+    fn test_tasks_cancelled_between_sweeps_leave_no_bookkeeping() {
+        use crate::hardware::abstract_device::DeviceManager;
+        use crate::hardware::cpu::CpuDevice;
+
+        let manager = Arc::new(DeviceManager::new());
+        manager
+            .register_device(Arc::new(CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string())))
+            .unwrap();
+        let scheduler = TaskScheduler::new(Arc::new(ResourceAllocator::new(manager)));
+        let requirements = TaskRequirements {
+            timeout: 0,
+            ..TaskRequirements::for_test(HardwareType::Cpu)
+        };
+        let task = Task::new("task-1".to_string(), "Cancelled".to_string(), requirements, TaskPriority::Normal);
+        let handle = scheduler.submit_task(task.clone()).unwrap();
+        scheduler.planned_starts.lock().unwrap().insert(task.id.clone(), Duration::ZERO);
+
+        // Cancelled after the sweep has run, so only the dispatch loop sees it
+        assert!(handle.cancel());
+        scheduler.registry.take_cancelled();
+        assert!(scheduler.schedule_next_task().is_none());
+        assert!(scheduler.planned_starts.lock().unwrap().is_empty());
+        assert_eq!(scheduler.fair.lock().unwrap().key(&task, Instant::now()), u64::MAX);
+    }
+
+    #[test]
+    fn test_to_device_task() {
+        let requirements = TaskRequirements {
+            memory_required: 1024,
+            timeout: 300000,
+            ..TaskRequirements::for_test(HardwareType::Cpu)
+        };
+
+        let task = Task::new("task-1".to_string(), "Add".to_string(), requirements, TaskPriority::Normal)
+            .with_operation("add", vec![1, 2]);
+        let device_task = task.to_device_task();
+
+        assert_eq!(device_task.id, "task-1");
+        assert_eq!(device_task.operation, "add");
+        assert_eq!(device_task.data, vec![1, 2]);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,177 @@
+//! Thermal state of the devices
+//!
+//! The scheduler reads every device's temperature through a `TemperatureSource`,
+//! at most once per `thermal.sample_interval`, and keeps the last `thermal.history`
+//! readings of each device. A reading at or above the warning threshold of the
+//! device's type makes it hot, and one at or above the critical threshold makes it
+//! critical. The run queues place new work on hot devices last and run one task at
+//! a time there; critical devices start nothing and hand their queued work on.
+
+use crate::hardware::abstract_device::HardwareDevice;
+use crate::scheduling::scheduler_config::ThermalConfig;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+/// Where device temperatures come from
+pub trait TemperatureSource: Send + Sync {
+    /// Current temperature of a device in Celsius; `None` if it cannot be read
+    fn temperature(&self, device: &dyn HardwareDevice) -> Option<f64>;
+}
+
+/// Reads the temperature each device reports in its metrics
+pub struct MetricsTemperature;
+
+impl TemperatureSource for MetricsTemperature {
+    fn temperature(&self, device: &dyn HardwareDevice) -> Option<f64> {
+        let temperature = device.get_metrics().temperature;
+        // Devices without a sensor report 0
+        (temperature > 0.0).then_some(temperature)
+    }
+}
+
+/// How hot a device is relative to the thresholds of its type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThermalState {
+    #[default]
+    Normal,
+    /// At or above the warning threshold
+    Hot,
+    /// At or above the critical threshold
+    Critical,
+}
+
+/// One temperature reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureSample {
+    pub at: SystemTime,
+    /// Temperature in Celsius
+    pub celsius: f64,
+    /// State the reading put the device in
+    pub state: ThermalState,
+}
+
+/// Latest state and recent readings of every device
+pub(crate) struct ThermalMonitor {
+    config: ThermalConfig,
+    source: Arc<dyn TemperatureSource>,
+    history: HashMap<String, VecDeque<TemperatureSample>>,
+    last_sample: Option<Instant>,
+}
+
+impl ThermalMonitor {
+    pub fn new(config: ThermalConfig) -> Self {
+        Self {
+            config,
+            source: Arc::new(MetricsTemperature),
+            history: HashMap::new(),
+            last_sample: None,
+        }
+    }
+
+    pub fn set_source(&mut self, source: Arc<dyn TemperatureSource>) {
+        self.source = source;
+        self.last_sample = None;
+    }
+
+    /// Read every device unless the last reading is more recent than the interval
+    pub fn sample_due(&mut self, devices: &[Arc<dyn HardwareDevice>], now: Instant) {
+        let interval = Duration::from_millis(self.config.sample_interval);
+        if self.last_sample.is_none_or(|last| now.duration_since(last) >= interval) {
+            self.sample(devices, now);
+        }
+    }
+
+    /// Read every device now
+    pub fn sample(&mut self, devices: &[Arc<dyn HardwareDevice>], now: Instant) {
+        self.last_sample = Some(now);
+        self.history.retain(|id, _| devices.iter().any(|device| device.id() == id));
+        for device in devices {
+            let Some(celsius) = self.source.temperature(device.as_ref()) else {
+                continue;
+            };
+            let thresholds = self.config.thresholds.get(device.device_type());
+            let state = if celsius >= thresholds.critical {
+                ThermalState::Critical
+            } else if celsius >= thresholds.warning {
+                ThermalState::Hot
+            } else {
+                ThermalState::Normal
+            };
+            let history = self.history.entry(device.id().to_string()).or_default();
+            let previous = history.back().map_or(ThermalState::Normal, |sample| sample.state);
+            if state > previous {
+                warn!("Device {} is {:?} at {:.1} C", device.id(), state, celsius);
+            }
+            if history.len() == self.config.history {
+                history.pop_front();
+            }
+            history.push_back(TemperatureSample {
+                at: SystemTime::now(),
+                celsius,
+                state,
+            });
+        }
+    }
+
+    /// State of a device as of its last reading
+    pub fn state(&self, device_id: &str) -> ThermalState {
+        self.history
+            .get(device_id)
+            .and_then(|history| history.back())
+            .map_or(ThermalState::Normal, |sample| sample.state)
+    }
+
+    /// Readings of a device, oldest first
+    pub fn history(&self, device_id: &str) -> Vec<TemperatureSample> {
+        self.history
+            .get(device_id)
+            .map(|history| history.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::gpu::GpuDevice;
+    use std::sync::Mutex;
+
+    struct Script(Mutex<VecDeque<f64>>);
+
+    impl TemperatureSource for Script {
+        fn temperature(&self, _device: &dyn HardwareDevice) -> Option<f64> {
+            self.0.lock().unwrap().pop_front()
+        }
+    }
+
+    #[test]
+    fn test_readings_set_state_and_keep_bounded_history() {
+        let config = ThermalConfig {
+            sample_interval: 1000,
+            history: 3,
+            ..ThermalConfig::default()
+        };
+        let mut monitor = ThermalMonitor::new(config);
+        monitor.set_source(Arc::new(Script(Mutex::new(VecDeque::from([40.0, 85.0, 95.0, 60.0])))));
+        let devices: Vec<Arc<dyn HardwareDevice>> =
+            vec![Arc::new(GpuDevice::new("gpu-0".to_string(), "Test GPU".to_string()))];
+
+        let start = Instant::now();
+        monitor.sample_due(&devices, start);
+        monitor.sample_due(&devices, start + Duration::from_millis(10));
+        assert_eq!(monitor.history("gpu-0").len(), 1, "the interval has not passed");
+
+        monitor.sample(&devices, start);
+        assert_eq!(monitor.state("gpu-0"), ThermalState::Hot);
+        monitor.sample(&devices, start);
+        assert_eq!(monitor.state("gpu-0"), ThermalState::Critical);
+        monitor.sample(&devices, start);
+        assert_eq!(monitor.state("gpu-0"), ThermalState::Normal);
+
+        let celsius: Vec<f64> = monitor.history("gpu-0").iter().map(|sample| sample.celsius).collect();
+        assert_eq!(celsius, vec![85.0, 95.0, 60.0]);
+        assert_eq!(monitor.state("gpu-1"), ThermalState::Normal);
+    }
+}
@@ -2,26 +2,66 @@
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use crate::hardware::cpu::CpuDevice;
-    use crate::hardware::abstract_device::HardwareDevice;
+    use heterogeneous_computing_architecture::errors::HardwareError;
+    use heterogeneous_computing_architecture::hardware::abstract_device::{
+        CancelToken, DeviceCapabilities, DeviceManager, DeviceStatus, GangMember, HardwareDevice, HardwareManager, HardwareMetrics,
+        HardwareType, HealthPolicy, HealthState, Task, TaskResult,
+    };
+    use heterogeneous_computing_architecture::hardware::abstract_device::TaskRequirements;
+    use heterogeneous_computing_architecture::hardware::cpu::{CpuDevice, HostTelemetry};
+    use heterogeneous_computing_architecture::hardware::fpga::{
+        Bitstream, BitstreamCacheConfig, BitstreamPolicy, FpgaConfig, FpgaDevice,
+    };
+    use heterogeneous_computing_architecture::computation::{ComputationEngine, TaskExecutor};
+    use heterogeneous_computing_architecture::hardware::abstract_device::Tensor;
+    use heterogeneous_computing_architecture::hardware::gpu::{GpuConfig, GpuDevice, GpuSharingConfig};
+    use heterogeneous_computing_architecture::scheduling::{
+        self, BackoffConfig, BackoffKind, EnergyConfig, HOST, LifecycleConfig, ListHeuristic, OverflowPolicy,
+        PlacementObjective, ResourceAllocator, RetryConfig, SchedulerConfig, SchedulingStrategy, TaskGraph, TaskPriority,
+        TaskQueueConfig, TaskScheduler, TaskStatus, TemperatureSource, ThermalState,
+    };
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+    use heterogeneous_computing_architecture::errors::SchedulingError;
+    use heterogeneous_computing_architecture::data::{DataFormat, DataManager};
+    use heterogeneous_computing_architecture::errors::DataError;
+    use heterogeneous_computing_architecture::models::ModelLoader;
     use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A single-device task needing 16 MB and one compute unit, with a 1 s timeout
+    fn requirements(hardware_type: HardwareType) -> TaskRequirements {
+        TaskRequirements {
+            hardware_type,
+            memory_required: 16,
+            compute_units: 1,
+            priority: 50,
+            timeout: 1000,
+            kernel: None,
+            gang: Vec::new(),
+        }
+    }
 
     #[tokio::test]
     async fn test_cpu_device_execution() {
         // Create a CPU device
         let cpu = Arc::new(CpuDevice::new(
             "test-cpu".to_string(),
-            4,      // 4 cores
-            3200,   // 3.2 GHz
-            8192,   // 8 GB memory
+            "Test CPU".to_string(),
         ));
+        cpu.initialize().await.unwrap();
 
         // Create a test task
-        let task = crate::hardware::abstract_device::Task {
+        let task = Task {
             id: "test-task".to_string(),
             data: vec![1, 2, 3, 4],
-            operation: "add".to_string(),
+            operation: "compute".to_string(),
+            kernel: None,
+            devices: Vec::new(),
+            cancel: CancelToken::new(),
+            limit: None,
         };
 
         // Execute the task
@@ -29,30 +69,893 @@ mod tests {
 
         // Verify the result
         assert_eq!(result.task_id, "test-task");
-        assert_eq!(result.execution_time, 100);
+        assert_eq!(result.data, vec![1, 2, 3, 4]);
     }
 
     #[tokio::test]
     async fn test_device_manager() {
         // Create a device manager
-        let device_manager = crate::hardware::abstract_device::DeviceManager::new();
+        let device_manager = DeviceManager::new();
 
         // Create a CPU device
         let cpu = Arc::new(CpuDevice::new(
             "test-cpu".to_string(),
-            4,      // 4 cores
-            3200,   // 3.2 GHz
-            8192,   // 8 GB memory
+            "Test CPU".to_string(),
         ));
 
         // Add the device to the manager
-        device_manager.add_device(cpu.clone()).await;
+        device_manager.register_device(cpu.clone()).unwrap();
 
         // Retrieve the device
-        let retrieved_device = device_manager.get_device("test-cpu").await.unwrap();
+        let retrieved_device = device_manager.get_device_by_id("test-cpu").unwrap();
 
         // Verify the device
         assert_eq!(retrieved_device.id(), "test-cpu");
-        assert_eq!(retrieved_device.device_type(), "CPU");
+        assert_eq!(retrieved_device.device_type(), HardwareType::Cpu);
+        assert_eq!(retrieved_device.device_type().to_string(), "CPU");
+    }
+
+    #[tokio::test]
+    async fn test_device_manager_reports_host_telemetry() {
+        // Fake procfs/sysfs tree
+        let root = std::env::temp_dir().join(format!("hetero-integration-sysfs-{}", std::process::id()));
+        let thermal = root.join("sys/class/thermal/thermal_zone0");
+        std::fs::create_dir_all(root.join("proc")).unwrap();
+        std::fs::create_dir_all(&thermal).unwrap();
+        std::fs::write(root.join("proc/stat"), "cpu  100 0 100 200 0 0 0 0\n").unwrap();
+        std::fs::write(root.join("proc/meminfo"), "MemTotal: 2048000 kB\nMemAvailable: 512000 kB\n").unwrap();
+        std::fs::write(thermal.join("type"), "x86_pkg_temp\n").unwrap();
+        std::fs::write(thermal.join("temp"), "61000\n").unwrap();
+
+        let telemetry = Arc::new(HostTelemetry::with_root(&root));
+        let cpu = CpuDevice::new("test-cpu".to_string(), "Test CPU".to_string())
+            .with_telemetry(telemetry, Duration::from_millis(10));
+        cpu.initialize().await.unwrap();
+
+        let device_manager = DeviceManager::new();
+        device_manager.register_device(Arc::new(cpu)).unwrap();
+
+        let metrics = device_manager.get_hardware_metrics();
+        assert_eq!(metrics.len(), 1);
+        let cpu_metrics = &metrics[0];
+        assert_eq!(cpu_metrics.utilization, 50.0);
+        assert_eq!(cpu_metrics.temperature, 61.0);
+        assert_eq!(cpu_metrics.memory_total, 2000);
+        assert_eq!(cpu_metrics.memory_used, 1500);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_allocation_prefers_configured_fpga() {
+        let dir = std::env::temp_dir().join(format!("hetero-integration-bitstreams-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("gemm_v1.bit"), Bitstream::encode("gemm", 128, &[0; 4096])).unwrap();
+
+        // Both boards know the bitstream, only the preloaded one has it programmed
+        let device_manager = DeviceManager::new();
+        for (id, policy) in [("fpga-cold", BitstreamPolicy::Dynamic), ("fpga-warm", BitstreamPolicy::Preloaded)] {
+            let mut config = FpgaConfig::default();
+            config.programming.bitstream_path = dir.to_string_lossy().into_owned();
+            let cache = BitstreamCacheConfig { policy, cache_size: 4 };
+            let fpga = FpgaDevice::with_config(id.to_string(), config, cache);
+            fpga.initialize().await.unwrap();
+            device_manager.register_device(Arc::new(fpga)).unwrap();
+        }
+
+        let mut requirements = TaskRequirements {
+            kernel: Some("gemm".to_string()),
+            ..requirements(HardwareType::Fpga)
+        };
+        let allocated = device_manager.allocate_resources("task-1", &requirements).unwrap();
+        assert_eq!(allocated[0].device_id, "fpga-warm");
+
+        requirements.kernel = Some("fft".to_string());
+        assert!(device_manager.allocate_resources("task-2", &requirements).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failing_device_is_quarantined_and_recovers_on_probation() {
+        let policy = HealthPolicy {
+            probe_interval: 10,
+            failure_threshold: 2,
+            quarantine_period: 0,
+            probation_successes: 1,
+        };
+        let device_manager = DeviceManager::with_health_policy(policy);
+        for id in ["cpu-a", "cpu-b"] {
+            let cpu = CpuDevice::new(id.to_string(), "Test CPU".to_string());
+            cpu.initialize().await.unwrap();
+            device_manager.register_device(Arc::new(cpu)).unwrap();
+        }
+        let mut events = device_manager.subscribe();
+
+        // Task-level errors do not count against the device
+        let bad_payload = HardwareError::InvalidTask("truncated task payload".to_string());
+        device_manager.report_task_outcome("cpu-a", Err(&bad_payload));
+        assert!(events.try_recv().is_err());
+
+        let fault = HardwareError::ExecutionError("worker crashed".to_string());
+        device_manager.report_task_outcome("cpu-a", Err(&fault));
+        device_manager.report_task_outcome("cpu-a", Err(&fault));
+        let transitions: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).map(|e| e.to).collect();
+        assert_eq!(transitions, vec![HealthState::Degraded, HealthState::Quarantined]);
+        assert!(!device_manager.is_schedulable("cpu-a"));
+
+        // Schedulers skip the quarantined device and metrics report the error
+        let requirements = requirements(HardwareType::Cpu);
+        for task in 0..4 {
+            let leases = device_manager.allocate_resources(&format!("task-{}", task), &requirements).unwrap();
+            assert_eq!(leases[0].device_id, "cpu-b");
+            device_manager.release_resources(&[leases[0].id.clone()]).unwrap();
+        }
+        let statuses: Vec<_> = device_manager.get_hardware_metrics().iter().map(|m| m.status).collect();
+        assert!(statuses.contains(&DeviceStatus::Error));
+
+        // A successful probe puts it back on probation, and a task success clears it
+        device_manager.probe_devices().await;
+        assert_eq!(events.try_recv().unwrap().to, HealthState::Probation);
+        assert!(device_manager.is_schedulable("cpu-a"));
+        device_manager.report_task_outcome("cpu-a", Ok(()));
+        assert_eq!(events.try_recv().unwrap().to, HealthState::Healthy);
+        assert_eq!(device_manager.device_health("cpu-a").unwrap().quarantines(), 1);
+
+        // Shutting a device down takes it offline at the next probe
+        device_manager.get_device_by_id("cpu-b").unwrap().shutdown().await.unwrap();
+        device_manager.probe_devices().await;
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.device_id.as_str(), event.to), ("cpu-b", HealthState::Offline));
+        assert!(device_manager.set_health("cpu-b", HealthState::Healthy, "operator").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_lookups_and_draining_unregister() {
+        let device_manager = Arc::new(DeviceManager::new());
+        for id in ["cpu-0", "cpu-1"] {
+            let cpu = CpuDevice::new(id.to_string(), "Test CPU".to_string());
+            cpu.initialize().await.unwrap();
+            device_manager.register_device(Arc::new(cpu)).unwrap();
+        }
+        let duplicate = CpuDevice::new("cpu-0".to_string(), "Duplicate".to_string());
+        assert!(device_manager.register_device(Arc::new(duplicate)).is_err());
+
+        // Many schedulers look devices up and run tasks at the same time
+        let mut workers = Vec::new();
+        for worker in 0..16 {
+            let manager = device_manager.clone();
+            workers.push(tokio::spawn(async move {
+                for round in 0..20 {
+                    let id = if (worker + round) % 2 == 0 { "cpu-0" } else { "cpu-1" };
+                    assert_eq!(manager.get_all_devices().len(), 2);
+                    let device = manager.acquire_device(id).unwrap();
+                    let task = Task {
+                        id: format!("task-{}-{}", worker, round),
+                        data: vec![worker as u8, round as u8],
+                        operation: "compute".to_string(),
+                        kernel: None,
+                        devices: Vec::new(),
+                        cancel: CancelToken::new(),
+                        limit: None,
+                    };
+                    assert_eq!(device.execute_task(&task).await.unwrap().data, task.data);
+                }
+            }));
+        }
+        for worker in workers {
+            worker.await.unwrap();
+        }
+        assert_eq!(device_manager.tasks_in_flight("cpu-0"), 0);
+
+        // Draining waits for the task in flight and rejects new ones meanwhile
+        let running = device_manager.acquire_device("cpu-1").unwrap();
+        let drain = tokio::spawn({
+            let manager = device_manager.clone();
+            async move { manager.drain_device("cpu-1", Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!drain.is_finished());
+        assert!(device_manager.acquire_device("cpu-1").is_err());
+        assert!(!device_manager.is_schedulable("cpu-1"));
+        drop(running);
+        drain.await.unwrap().unwrap();
+        assert!(device_manager.get_device_by_id("cpu-1").is_none());
+
+        // A drain that cannot finish in time leaves the device registered and closed
+        let stuck = device_manager.acquire_device("cpu-0").unwrap();
+        let error = device_manager.drain_device("cpu-0", Duration::from_millis(10)).await.unwrap_err();
+        assert!(matches!(error, HardwareError::DrainTimeout { in_flight: 1, .. }));
+        assert!(device_manager.get_device_by_id("cpu-0").is_some());
+        assert!(device_manager.acquire_device("cpu-0").is_err());
+
+        // Unregistering rejects new work at once while the running task finishes
+        device_manager.unregister_device("cpu-0").unwrap();
+        assert!(device_manager.acquire_device("cpu-0").is_err());
+        let task = Task {
+            id: "late".to_string(),
+            data: vec![7],
+            operation: "compute".to_string(),
+            kernel: None,
+            devices: Vec::new(),
+            cancel: CancelToken::new(),
+            limit: None,
+        };
+        assert_eq!(stuck.execute_task(&task).await.unwrap().data, vec![7]);
+    }
+
+    fn graph_task(id: &str, hardware_type: HardwareType, operation: &str, inputs: &[Tensor]) -> scheduling::Task {
+        let requirements = TaskRequirements {
+            timeout: 10_000,
+            ..requirements(hardware_type)
+        };
+        scheduling::Task::new(id.to_string(), id.to_string(), requirements, TaskPriority::Normal)
+            .with_operation(operation, Tensor::encode_all(inputs))
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_graph_passes_data_and_cascades_failures() {
+        let manager = DeviceManager::new();
+        let cpu = CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string());
+        cpu.initialize_cpu().unwrap();
+        manager.register_device(Arc::new(cpu)).unwrap();
+        let gpu = GpuDevice::new("gpu-0".to_string(), "Test GPU".to_string());
+        gpu.initialize_gpu().unwrap();
+        manager.register_device(Arc::new(gpu)).unwrap();
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+
+        let scheduler = TaskScheduler::new(Arc::new(ResourceAllocator::new(manager.clone())));
+        let executor = TaskExecutor::new(Arc::new(ComputationEngine::new(manager.clone())), manager);
+
+        // preprocess (CPU) -> multiply by weights (GPU) -> reduce (CPU)
+        let a = Tensor::new(vec![2, 2], vec![1.0, 2.0, 3.0, 4.0]).unwrap();
+        let weights = Tensor::new(vec![2, 2], vec![1.0, 0.0, 0.0, 2.0]).unwrap();
+        let mut graph = TaskGraph::new();
+        graph.add_task(graph_task("preprocess", HardwareType::Cpu, "add", &[a.clone(), a])).unwrap();
+        graph.add_task(graph_task("infer", HardwareType::Gpu, "gemm", &[weights])).unwrap();
+        graph.add_task(graph_task("postprocess", HardwareType::Cpu, "reduce_sum", &[])).unwrap();
+        graph.add_data_dependency("infer", "preprocess").unwrap();
+        graph.add_data_dependency("postprocess", "infer").unwrap();
+
+        // An independent branch whose first step fails
+        graph.add_task(graph_task("broken", HardwareType::Cpu, "fft", &[])).unwrap();
+        graph.add_task(graph_task("after-broken", HardwareType::Cpu, "compute", &[])).unwrap();
+        graph.add_dependency("after-broken", "broken").unwrap();
+
+        let handle = scheduler.submit_graph(graph).unwrap();
+        assert_eq!(scheduler.get_pending_tasks_count(), 2);
+        assert_eq!(executor.run_until_idle(&scheduler).await, 4);
+        assert!(handle.is_finished());
+
+        let result = handle.wait().await;
+        assert!(!result.is_success());
+        // [[2, 4], [6, 8]] scaled row-wise by diag(1, 2) sums to 2 + 4 + 12 + 16
+        let output = Tensor::decode_all(&result.result("postprocess").unwrap().data).unwrap();
+        assert_eq!(output[0].data, vec![34.0]);
+        assert_eq!(result.outcome("broken").unwrap().status(), TaskStatus::Failed);
+        assert_eq!(result.outcome("after-broken").unwrap().status(), TaskStatus::Cancelled);
+        assert_eq!(scheduler.get_pending_tasks_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_heft_plan_pins_tasks_and_reports_makespan() {
+        let manager = DeviceManager::new();
+        let cpu = CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string());
+        cpu.initialize_cpu().unwrap();
+        manager.register_device(Arc::new(cpu)).unwrap();
+        let gpu = GpuDevice::new("gpu-0".to_string(), "Test GPU".to_string());
+        gpu.initialize_gpu().unwrap();
+        manager.register_device(Arc::new(gpu)).unwrap();
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+
+        let allocator = Arc::new(ResourceAllocator::new(manager.clone()));
+        let scheduler = TaskScheduler::with_strategy(allocator, SchedulingStrategy::Heft);
+        let executor = TaskExecutor::new(Arc::new(ComputationEngine::new(manager.clone())), manager);
+        let ms = Duration::from_millis;
+        for (operation, cpu_time, gpu_time) in [("add", 1, 3), ("gemm", 20, 2), ("reduce_sum", 1, 3)] {
+            scheduler.set_execution_estimate(operation, HardwareType::Cpu, ms(cpu_time));
+            scheduler.set_execution_estimate(operation, HardwareType::Gpu, ms(gpu_time));
+        }
+
+        // The GEMM asks for the GPU and the steps around it for the CPU
+        let a = Tensor::new(vec![2, 2], vec![1.0, 2.0, 3.0, 4.0]).unwrap();
+        let weights = Tensor::new(vec![2, 2], vec![1.0, 0.0, 0.0, 2.0]).unwrap();
+        let mut graph = TaskGraph::new();
+        graph.add_task(graph_task("preprocess", HardwareType::Cpu, "add", &[a.clone(), a])).unwrap();
+        graph.add_task(graph_task("infer", HardwareType::Gpu, "gemm", &[weights])).unwrap();
+        graph.add_task(graph_task("postprocess", HardwareType::Cpu, "reduce_sum", &[])).unwrap();
+        graph.add_data_dependency("infer", "preprocess").unwrap();
+        graph.add_data_dependency("postprocess", "infer").unwrap();
+
+        let plan = scheduler.plan_graph(&graph, ListHeuristic::Heft).unwrap();
+        let devices: Vec<&str> = plan.assignments.iter().map(|a| a.device_id.as_str()).collect();
+        assert_eq!(devices, vec!["cpu-0", "gpu-0", "cpu-0"]);
+        // 4 ms of compute plus the copies to and from the GPU
+        assert!(plan.makespan > ms(4) && plan.makespan < ms(5), "{:?}", plan.makespan);
+
+        let handle = scheduler.submit_graph(graph).unwrap();
+        assert_eq!(handle.predicted_makespan(), Some(plan.makespan));
+        assert_eq!(executor.run_until_idle(&scheduler).await, 3);
+
+        let result = handle.wait().await;
+        assert!(result.is_success());
+        assert_eq!(result.predicted_makespan, Some(plan.makespan));
+        assert!(result.makespan > Duration::ZERO);
+        let output = Tensor::decode_all(&result.result("postprocess").unwrap().data).unwrap();
+        assert_eq!(output[0].data, vec![34.0]);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_submitters_wait_for_queue_space() {
+        let manager = DeviceManager::new();
+        let cpu = CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string());
+        cpu.initialize_cpu().unwrap();
+        manager.register_device(Arc::new(cpu)).unwrap();
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+
+        let config = SchedulerConfig {
+            task_queue: TaskQueueConfig {
+                max_size: 2,
+                overflow_policy: OverflowPolicy::Block,
+            },
+            max_concurrent_tasks: 1,
+            ..SchedulerConfig::default()
+        };
+        let allocator = Arc::new(ResourceAllocator::new(manager));
+        let scheduler = Arc::new(TaskScheduler::with_config(allocator, SchedulingStrategy::RoundRobin, config));
+        for id in ["t1", "t2"] {
+            scheduler.submit_task(graph_task(id, HardwareType::Cpu, "compute", &[])).unwrap();
+        }
+
+        // A synchronous submission cannot wait, so it is refused
+        let error = scheduler.submit_task(graph_task("t3", HardwareType::Cpu, "compute", &[])).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<SchedulingError>(),
+            Some(&SchedulingError::QueueFull { queue: "cpu-0".to_string(), capacity: 2 })
+        );
+
+        // An async submission waits until a task leaves the queue
+        let blocked = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.submit(graph_task("t3", HardwareType::Cpu, "compute", &[])).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+
+        let first = scheduler.schedule_next_task().unwrap();
+        assert_eq!(first.id, "t1");
+        assert_eq!(first.device_id.as_deref(), Some("cpu-0"));
+        tokio::time::timeout(Duration::from_secs(1), blocked).await.unwrap().unwrap().unwrap();
+        assert_eq!(scheduler.get_pending_tasks_count(), 2);
+
+        // Only one task may run at a time
+        assert!(scheduler.schedule_next_task().is_none());
+        scheduler.fail_task("t1", "aborted");
+        assert_eq!(scheduler.schedule_next_task().unwrap().id, "t2");
+        assert_eq!(scheduler.tasks_in_flight(), 1);
+    }
+
+    /// CPU-typed device that fails its first few runs and never finishes a `hang`
+    struct FlakyDevice {
+        failures_left: AtomicU32,
+    }
+
+    #[async_trait]
+    impl HardwareDevice for FlakyDevice {
+        fn id(&self) -> &str {
+            "flaky-0"
+        }
+
+        fn name(&self) -> &str {
+            "Flaky device"
+        }
+
+        fn device_type(&self) -> HardwareType {
+            HardwareType::Cpu
+        }
+
+        fn properties(&self) -> HashMap<String, String> {
+            HashMap::new()
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            DeviceCapabilities {
+                compute_units: 4,
+                operations: vec!["compute".to_string(), "hang".to_string()],
+                max_clock_speed: 1000,
+            }
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        async fn initialize(&self) -> Result<(), HardwareError> {
+            Ok(())
+        }
+
+        async fn shutdown(&self) -> Result<(), HardwareError> {
+            Ok(())
+        }
+
+        fn get_metrics(&self) -> HardwareMetrics {
+            HardwareMetrics {
+                utilization: 0.0,
+                temperature: 0.0,
+                memory_used: 0,
+                memory_total: self.total_memory(),
+                clock_speed: 1000,
+                status: DeviceStatus::Idle,
+                core_reservations: HashMap::new(),
+            }
+        }
+
+        fn total_memory(&self) -> u64 {
+            1 << 30
+        }
+
+        fn available_memory(&self) -> u64 {
+            self.total_memory()
+        }
+
+        async fn execute_task(&self, task: &Task) -> Result<TaskResult, HardwareError> {
+            if !self.capabilities().supports(&task.operation) {
+                return Err(HardwareError::UnsupportedOperation(task.operation.clone()));
+            }
+            if task.operation == "hang" {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(10)) => {}
+                    _ = task.cancel.cancelled() => return Err(HardwareError::Cancelled(task.id.clone())),
+                }
+            }
+            let left = self.failures_left.load(Ordering::SeqCst);
+            if left > 0 {
+                self.failures_left.store(left - 1, Ordering::SeqCst);
+                return Err(HardwareError::ExecutionError("transient fault".to_string()));
+            }
+            Ok(TaskResult {
+                task_id: task.id.clone(),
+                data: task.data.clone(),
+                execution_time: 0,
+                energy: 0.0,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_timeouts_and_dead_letters() {
+        let manager = DeviceManager::new();
+        manager.register_device(Arc::new(FlakyDevice { failures_left: AtomicU32::new(2) })).unwrap();
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+
+        let config = SchedulerConfig {
+            lifecycle: LifecycleConfig {
+                timeout: 50,
+                retry: RetryConfig {
+                    max_attempts: 3,
+                    backoff: BackoffConfig {
+                        kind: BackoffKind::Exponential,
+                        base_delay: 1,
+                        max_delay: 5,
+                    },
+                },
+                ..LifecycleConfig::default()
+            },
+            ..SchedulerConfig::default()
+        };
+        let allocator = Arc::new(ResourceAllocator::new(manager.clone()));
+        let scheduler = TaskScheduler::with_config(allocator, SchedulingStrategy::RoundRobin, config);
+        let executor = TaskExecutor::new(Arc::new(ComputationEngine::new(manager.clone())), manager);
+
+        // Fails twice, then succeeds on its last attempt
+        let mut graph = TaskGraph::new();
+        graph.add_task(graph_task("flaky", HardwareType::Cpu, "compute", &[])).unwrap();
+        let handle = scheduler.submit_graph(graph).unwrap();
+
+        // Takes the lifecycle timeout and runs past it every time
+        let mut hang = graph_task("hang", HardwareType::Cpu, "hang", &[]);
+        hang.requirements.timeout = 0;
+        scheduler.submit_task(hang).unwrap();
+
+        // An unsupported operation is not worth retrying
+        let mut graph = TaskGraph::new();
+        graph.add_task(graph_task("invalid", HardwareType::Cpu, "fft", &[])).unwrap();
+        let invalid = scheduler.submit_graph(graph).unwrap();
+
+        let started = std::time::Instant::now();
+        assert_eq!(executor.run_until_idle(&scheduler).await, 3 + 3 + 1);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(scheduler.get_pending_tasks_count(), 0);
+
+        assert!(handle.wait().await.is_success());
+        assert_eq!(invalid.wait().await.outcome("invalid").unwrap().status(), TaskStatus::Failed);
+
+        let dead = scheduler.take_dead_letters();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].task.id, "hang");
+        assert_eq!(dead[0].attempts, 3);
+        assert!(dead[0].error.contains("timed out"), "{}", dead[0].error);
+        assert!(scheduler.dead_letters().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_task_handles_report_progress_and_cancel_running_tasks() {
+        let manager = DeviceManager::new();
+        manager.register_device(Arc::new(FlakyDevice { failures_left: AtomicU32::new(0) })).unwrap();
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+
+        let config = SchedulerConfig {
+            max_concurrent_tasks: 1,
+            ..SchedulerConfig::default()
+        };
+        let allocator = Arc::new(ResourceAllocator::new(manager.clone()));
+        let scheduler = TaskScheduler::with_config(allocator, SchedulingStrategy::RoundRobin, config);
+        let executor = TaskExecutor::new(Arc::new(ComputationEngine::new(manager.clone())), manager);
+
+        let hang = scheduler.submit_task(graph_task("hang", HardwareType::Cpu, "hang", &[])).unwrap();
+        let queued = scheduler.submit_task(graph_task("queued", HardwareType::Cpu, "compute", &[])).unwrap();
+        assert_eq!(hang.id(), "hang");
+        assert_eq!(hang.status(), TaskStatus::Pending);
+        let duplicate = scheduler.submit_task(graph_task("hang", HardwareType::Cpu, "compute", &[])).unwrap_err();
+        assert_eq!(
+            duplicate.downcast_ref::<SchedulingError>(),
+            Some(&SchedulingError::DuplicateTask("hang".to_string()))
+        );
+
+        // A queued task is cancelled at once
+        assert!(queued.cancel());
+        assert_eq!(queued.status(), TaskStatus::Cancelled);
+
+        // A running task stops when cancelled
+        let (executed, outcome) = tokio::join!(executor.run_until_idle(&scheduler), async {
+            while hang.status() != TaskStatus::Running {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            let progress = hang.progress();
+            assert_eq!(progress.attempts, 1);
+            assert_eq!(progress.device_id.as_deref(), Some("flaky-0"));
+            assert!(progress.started_at.is_some());
+            assert!(hang.cancel());
+            hang.wait().await
+        });
+        assert_eq!(executed, 1);
+        assert_eq!(outcome.status(), TaskStatus::Cancelled);
+        assert!(!hang.cancel());
+
+        // Finished tasks stay queryable, and their ids stay taken while they do
+        let progress = scheduler.task_progress("queued").unwrap();
+        assert_eq!(progress.status, TaskStatus::Cancelled);
+        assert!(progress.finished_at.is_some());
+        assert_eq!(scheduler.task_handle("hang").unwrap().status(), TaskStatus::Cancelled);
+        assert!(scheduler.submit_task(graph_task("hang", HardwareType::Cpu, "compute", &[])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_background_work_is_not_starved_under_steady_load() {
+        let manager = DeviceManager::new();
+        let cpu = CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string());
+        cpu.initialize_cpu().unwrap();
+        manager.register_device(Arc::new(cpu)).unwrap();
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+        let scheduler = TaskScheduler::new(Arc::new(ResourceAllocator::new(manager)));
+
+        let mut background = graph_task("background", HardwareType::Cpu, "compute", &[]);
+        background.priority = TaskPriority::Background;
+        let background = scheduler.submit_task(background).unwrap();
+
+        // Keep critical work queued at all times and count dispatches until the background task runs
+        let mut submitted = 0;
+        let mut dispatches = 0;
+        while background.status() == TaskStatus::Pending {
+            while scheduler.get_pending_tasks_count() < 5 {
+                let mut critical = graph_task(&format!("critical-{}", submitted), HardwareType::Cpu, "compute", &[]);
+                critical.priority = TaskPriority::Critical;
+                scheduler.submit_task(critical).unwrap();
+                submitted += 1;
+            }
+            let task = scheduler.schedule_next_task().unwrap();
+            dispatches += 1;
+            let result = TaskResult {
+                task_id: task.id.clone(),
+                data: Vec::new(),
+                execution_time: 0,
+                energy: 0.0,
+            };
+            scheduler.complete_task(&task.id, result);
+            assert!(dispatches <= 11, "background task still waiting after {} dispatches", dispatches);
+        }
+        assert_eq!(background.status(), TaskStatus::Completed);
+        // Critical work still got the nine turns before it
+        assert_eq!(dispatches, 10);
+    }
+
+    fn member(hardware_type: HardwareType, count: usize, compute_units: u64) -> GangMember {
+        GangMember {
+            hardware_type,
+            count,
+            memory_required: 16,
+            compute_units,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gang_tasks_get_every_device_or_none() {
+        let manager = DeviceManager::new();
+        let cpu = CpuDevice::new("cpu-0".to_string(), "Test CPU".to_string());
+        cpu.initialize_cpu().unwrap();
+        manager.register_device(Arc::new(cpu)).unwrap();
+        for id in ["gpu-0", "gpu-1"] {
+            let gpu = GpuDevice::new(id.to_string(), "Test GPU".to_string());
+            gpu.initialize_gpu().unwrap();
+            manager.register_device(Arc::new(gpu)).unwrap();
+        }
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+
+        // Two whole GPUs and a CPU core
+        let mut gang = graph_task("gang", HardwareType::Gpu, "add", &[]);
+        gang.requirements.gang = vec![member(HardwareType::Gpu, 2, 40), member(HardwareType::Cpu, 1, 1)];
+        let leases = manager.allocate_resources("gang", &gang.requirements).unwrap();
+        let mut devices: Vec<&str> = leases.iter().map(|lease| lease.device_id.as_str()).collect();
+        devices.sort();
+        assert_eq!(devices, vec!["cpu-0", "gpu-0", "gpu-1"]);
+
+        // A competing gang finds no GPU free and reserves nothing, not even the CPU
+        let mut rival = gang.requirements.clone();
+        rival.gang = vec![member(HardwareType::Cpu, 1, 1), member(HardwareType::Gpu, 1, 1)];
+        assert!(matches!(
+            manager.allocate_resources("rival", &rival),
+            Err(HardwareError::InsufficientComputeUnits { required: 1, available: 0 })
+        ));
+        let cpu_leases = manager.reservations().into_iter().find(|r| r.device_id == "cpu-0").unwrap().leases;
+        assert_eq!(cpu_leases, 1);
+        let lease_ids: Vec<String> = leases.into_iter().map(|lease| lease.id).collect();
+        manager.release_resources(&lease_ids).unwrap();
+
+        // Run through the scheduler; every device adds the same tensors and the outputs come back in order
+        let scheduler = TaskScheduler::new(Arc::new(ResourceAllocator::new(manager.clone())));
+        let executor = TaskExecutor::new(Arc::new(ComputationEngine::new(manager.clone())), manager.clone());
+        let a = Tensor::new(vec![2], vec![1.0, 2.0]).unwrap();
+        let gang = gang.with_operation("add", Tensor::encode_all(&[a.clone(), a]));
+        let handle = scheduler.submit_task(gang).unwrap();
+        assert_eq!(scheduler.queue_lengths().into_iter().find(|(queue, _)| queue == "unplaced").unwrap().1, 1);
+        assert_eq!(executor.run_until_idle(&scheduler).await, 1);
+
+        let outcome = handle.wait().await;
+        let data = &outcome.result().unwrap().data;
+        for output in data.chunks(data.len() / 3) {
+            assert_eq!(Tensor::decode_all(output).unwrap()[0].data, vec![2.0, 4.0]);
+        }
+        assert!(manager.reservations().iter().all(|r| r.leases == 0));
+    }
+
+    #[tokio::test]
+    async fn test_gang_members_are_placed_largest_first() {
+        let manager = DeviceManager::new();
+        for (id, memory) in [("gpu-large", 16384), ("gpu-small", 1024)] {
+            let mut config = GpuConfig::default();
+            config.memory.total = memory;
+            config.memory.max_allocation = memory;
+            let gpu = GpuDevice::with_config(id.to_string(), "Test GPU".to_string(), config);
+            gpu.initialize_gpu().unwrap();
+            manager.register_device(Arc::new(gpu)).unwrap();
+        }
+        // The small GPU is ranked last, so a member-order greedy pick gives the large one away
+        manager.set_health("gpu-small", HealthState::Degraded, "test").unwrap();
+
+        let mut requirements = graph_task("gang", HardwareType::Gpu, "add", &[]).requirements;
+        let mut large = member(HardwareType::Gpu, 1, 1);
+        large.memory_required = 8192;
+        requirements.gang = vec![member(HardwareType::Gpu, 1, 1), large];
+        let leases = manager.allocate_resources("gang", &requirements).unwrap();
+        let devices: Vec<&str> = leases.iter().map(|lease| lease.device_id.as_str()).collect();
+        assert_eq!(devices, vec!["gpu-small", "gpu-large"]);
+    }
+
+    #[tokio::test]
+    async fn test_small_tasks_share_a_gpu_within_their_quota() {
+        let manager = DeviceManager::new();
+        let sharing = GpuSharingConfig::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/config/scheduling/resource_allocation.yaml"
+        ))
+        .unwrap();
+        for id in ["gpu-0", "gpu-1"] {
+            let gpu = GpuDevice::new(id.to_string(), "Test GPU".to_string()).with_sharing(sharing.clone());
+            gpu.initialize_gpu().unwrap();
+            manager.register_device(Arc::new(gpu)).unwrap();
+        }
+
+        // 10% of the 16 GB is held back and one task may take at most half of the rest
+        let gpu_0 = manager.reservations().into_iter().find(|r| r.device_id == "gpu-0").unwrap();
+        assert_eq!(gpu_0.quota.capacity.memory, 14746);
+        let mut requirements = graph_task("slice", HardwareType::Gpu, "gemm", &[]).requirements;
+        requirements.compute_units = 25;
+        assert!(matches!(
+            manager.allocate_resources("too-big", &requirements),
+            Err(HardwareError::InsufficientComputeUnits { required: 25, available: 20 })
+        ));
+
+        // Small tasks fill one GPU before spilling onto the next
+        requirements.memory_required = 4096;
+        requirements.compute_units = 10;
+        let placed: Vec<String> = (0..4)
+            .map(|i| manager.allocate_resources(&format!("small-{}", i), &requirements).unwrap()[0].device_id.clone())
+            .collect();
+        assert_eq!(placed[1], placed[0]);
+        assert_eq!(placed[2], placed[0]);
+        assert_ne!(placed[3], placed[0]);
+    }
+
+    #[tokio::test]
+    async fn test_energy_objective_prefers_the_frugal_gpu() {
+        let manager = DeviceManager::new();
+        for (id, limit) in [("gpu-0", 300), ("gpu-1", 150)] {
+            let mut config = GpuConfig::default();
+            config.performance.power.limit = limit;
+            let gpu = GpuDevice::with_config(id.to_string(), "Test GPU".to_string(), config);
+            gpu.initialize_gpu().unwrap();
+            manager.register_device(Arc::new(gpu)).unwrap();
+        }
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+        let config = SchedulerConfig {
+            energy: EnergyConfig {
+                objective: PlacementObjective::Energy,
+                power_cap: 0.0,
+            },
+            ..SchedulerConfig::default()
+        };
+        let scheduler = TaskScheduler::with_config(
+            Arc::new(ResourceAllocator::new(manager.clone())),
+            SchedulingStrategy::Priority,
+            config,
+        );
+
+        let a = Tensor::new(vec![4096], vec![1.0; 4096]).unwrap();
+        scheduler
+            .submit_task(graph_task("sum", HardwareType::Gpu, "add", &[a.clone(), a]))
+            .unwrap();
+        assert_eq!(scheduler.power_draw(), 60.0);
+        let task = scheduler.schedule_next_task().unwrap();
+        assert_eq!(task.device_id.as_deref(), Some("gpu-1"));
+        assert_eq!(scheduler.power_draw(), 180.0);
+
+        let device = manager.get_device_by_id("gpu-1").unwrap();
+        let result = ComputationEngine::execute_on_device(&*device, &task).await.unwrap();
+        assert!(result.energy > 0.0);
+        scheduler.complete_task(&task.id, result);
+        assert_eq!(scheduler.power_draw(), 60.0);
+    }
+
+    /// Temperatures the test sets by hand
+    #[derive(Default)]
+    struct ScriptedTemperatures(Mutex<HashMap<String, f64>>);
+
+    impl ScriptedTemperatures {
+        fn set(&self, device_id: &str, celsius: f64) {
+            self.0.lock().unwrap().insert(device_id.to_string(), celsius);
+        }
+    }
+
+    impl TemperatureSource for ScriptedTemperatures {
+        fn temperature(&self, device: &dyn HardwareDevice) -> Option<f64> {
+            self.0.lock().unwrap().get(device.id()).copied()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_work_leaves_a_device_that_overheats() {
+        let manager = DeviceManager::new();
+        for id in ["gpu-0", "gpu-1"] {
+            let gpu = GpuDevice::new(id.to_string(), "Test GPU".to_string());
+            gpu.initialize_gpu().unwrap();
+            manager.register_device(Arc::new(gpu)).unwrap();
+        }
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+        let temperatures = Arc::new(ScriptedTemperatures::default());
+        temperatures.set("gpu-0", 60.0);
+        temperatures.set("gpu-1", 60.0);
+        let scheduler = TaskScheduler::new(Arc::new(ResourceAllocator::new(manager.clone())))
+            .with_temperature_source(temperatures.clone());
+
+        for i in 0..4 {
+            scheduler
+                .submit_task(graph_task(&format!("task-{}", i), HardwareType::Gpu, "compute", &[]))
+                .unwrap();
+        }
+        let lengths = scheduler.queue_lengths();
+        assert_eq!((lengths[0].1, lengths[1].1), (2, 2));
+
+        // gpu-0 warms up past its warning threshold, then past critical
+        temperatures.set("gpu-0", 85.0);
+        scheduler.sample_temperatures();
+        assert_eq!(scheduler.thermal_state("gpu-0"), ThermalState::Hot);
+        temperatures.set("gpu-0", 92.0);
+        scheduler.sample_temperatures();
+        assert_eq!(scheduler.thermal_state("gpu-0"), ThermalState::Critical);
+        let lengths = scheduler.queue_lengths();
+        assert_eq!((lengths[0].1, lengths[1].1), (0, 4));
+        while let Some(task) = scheduler.schedule_next_task() {
+            assert_eq!(task.device_id.as_deref(), Some("gpu-1"));
+        }
+
+        let history: Vec<f64> = scheduler.temperature_history("gpu-0").iter().map(|sample| sample.celsius).collect();
+        assert_eq!(history[history.len() - 3..], [60.0, 85.0, 92.0]);
+    }
+
+    #[tokio::test]
+    async fn test_tasks_follow_their_data() {
+        let manager = DeviceManager::new();
+        for id in ["gpu-0", "gpu-1"] {
+            let gpu = GpuDevice::new(id.to_string(), "Test GPU".to_string());
+            gpu.initialize_gpu().unwrap();
+            manager.register_device(Arc::new(gpu)).unwrap();
+        }
+        let manager: Arc<dyn HardwareManager> = Arc::new(manager);
+        let data = Arc::new(DataManager::new("data"));
+        let mb = 1024 * 1024;
+        let weights = data
+            .create_data_object("weights".to_string(), DataFormat::Binary, 64 * mb, "gpu-1".to_string(), HashMap::new())
+            .unwrap();
+        let batch = data
+            .create_data_object("batch".to_string(), DataFormat::Binary, 8 * mb, "batch.bin".to_string(), HashMap::new())
+            .unwrap();
+        let scores = data
+            .create_data_object("scores".to_string(), DataFormat::Binary, mb, "scores.bin".to_string(), HashMap::new())
+            .unwrap();
+        let scheduler =
+            TaskScheduler::new(Arc::new(ResourceAllocator::new(manager.clone()))).with_data_manager(data.clone());
+
+        // Both GPUs are idle, so only the weights decide where inference runs
+        let infer = graph_task("infer", HardwareType::Gpu, "compute", &[])
+            .with_inputs(vec![weights.clone(), batch.clone()])
+            .with_outputs(vec![scores.clone()]);
+        scheduler.submit_task(infer).unwrap();
+        let lengths = scheduler.queue_lengths();
+        assert_eq!((lengths[0].1, lengths[1].1), (0, 1));
+
+        let task = scheduler.schedule_next_task().unwrap();
+        assert_eq!(task.device_id.as_deref(), Some("gpu-1"));
+        assert_eq!(task.transfers.len(), 1);
+        assert_eq!((task.transfers[0].data_id.as_str(), task.transfers[0].from.as_str()), (batch.as_str(), HOST));
+        assert_eq!(scheduler.task_progress("infer").unwrap().bytes_moved, 8 * mb);
+
+        // The batch keeps its host path; gpu-1 holds a replica only once the task has run there
+        assert_eq!(data.get_data_object(&batch).unwrap().location, "batch.bin");
+        assert!(scheduler.data_replicas(&batch).is_empty());
+        let result = TaskResult { task_id: "infer".to_string(), data: vec![], execution_time: 1, energy: 0.0 };
+        scheduler.complete_task("infer", result.clone());
+        assert_eq!(data.get_data_object(&batch).unwrap().location, "batch.bin");
+        assert_eq!(scheduler.data_replicas(&batch), vec!["gpu-1".to_string()]);
+        assert_eq!(data.get_data_object(&scores).unwrap().location, "gpu-1");
+
+        // A second read of the batch on gpu-1 copies nothing
+        scheduler
+            .submit_task(graph_task("again", HardwareType::Gpu, "compute", &[]).with_inputs(vec![batch.clone()]))
+            .unwrap();
+        let again = scheduler.schedule_next_task().unwrap();
+        assert_eq!((again.device_id.as_deref(), again.transfers.len()), (Some("gpu-1"), 0));
+        scheduler.complete_task("again", result);
+
+        // A task without inputs is placed as before
+        scheduler.submit_task(graph_task("other", HardwareType::Gpu, "compute", &[])).unwrap();
+        assert_eq!(scheduler.schedule_next_task().unwrap().device_id.as_deref(), Some("gpu-0"));
+    }
+
+    #[test]
+    fn test_models_load_from_stored_files() {
+        let dir = std::env::temp_dir().join(format!("hca-models-{}", std::process::id()));
+        let storage = DataManager::new(dir.to_str().unwrap());
+        let config = br#"{"model_type": "bert", "hidden_size": 768, "num_layers": 12, "num_heads": 12}"#;
+        storage.save_data("bert/config.json", config).unwrap();
+        storage.save_stream("bert/model.bin", &[1u8; 3 * 1024 * 1024][..]).unwrap();
+
+        let loader = ModelLoader::new(DataManager::new(dir.to_str().unwrap()));
+        let model = loader.load_model("bert").unwrap();
+        assert_eq!((model.config.model_type.as_str(), model.config.num_layers), ("bert", 12));
+        assert_eq!(model.weights.len(), 3 * 1024 * 1024);
+        assert!(model.tokenizer.is_empty(), "the tokenizer is optional");
+
+        assert!(matches!(storage.load_data("../escape"), Err(DataError::InvalidPath { .. })));
+        std::fs::remove_dir_all(dir).unwrap();
     }
-}
\ No newline at end of file
+}
@@ -2,14 +2,17 @@
 
 #[cfg(test)]
 mod tests {
-    use crate::hardware::abstract_device::{HardwareDevice, Task, TaskResult};
-    use crate::errors::HardwareError;
+    use heterogeneous_computing_architecture::errors::HardwareError;
+    use heterogeneous_computing_architecture::hardware::abstract_device::{
+        CancelToken, DeviceCapabilities, DeviceStatus, HardwareDevice, HardwareMetrics, HardwareType, Task, TaskResult,
+    };
     use async_trait::async_trait;
+    use std::collections::HashMap;
 
     // Mock hardware device for testing
     struct MockDevice {
         id: String,
-        device_type: String,
+        device_type: HardwareType,
         memory: u64,
         available: bool,
     }
@@ -20,8 +23,48 @@ mod tests {
             &self.id
         }
 
-        fn device_type(&self) -> &str {
-            &self.device_type
+        fn name(&self) -> &str {
+            "Mock device"
+        }
+
+        fn device_type(&self) -> HardwareType {
+            self.device_type
+        }
+
+        fn properties(&self) -> HashMap<String, String> {
+            HashMap::new()
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            DeviceCapabilities {
+                compute_units: 1,
+                operations: vec!["test".to_string()],
+                max_clock_speed: 1000,
+            }
+        }
+
+        fn is_available(&self) -> bool {
+            self.available
+        }
+
+        async fn initialize(&self) -> Result<(), HardwareError> {
+            Ok(())
+        }
+
+        async fn shutdown(&self) -> Result<(), HardwareError> {
+            Ok(())
+        }
+
+        fn get_metrics(&self) -> HardwareMetrics {
+            HardwareMetrics {
+                utilization: 0.0,
+                temperature: 0.0,
+                memory_used: 0,
+                memory_total: self.memory,
+                clock_speed: 1000,
+                status: DeviceStatus::Idle,
+                core_reservations: HashMap::new(),
+            }
         }
 
         fn available_memory(&self) -> u64 {
@@ -38,36 +81,38 @@ mod tests {
                     task_id: "mock-task".to_string(),
                     data: vec![],
                     execution_time: 50,
+                    energy: 0.0,
                 })
             } else {
                 Err(HardwareError::DeviceUnavailable("Device is not available".to_string()))
             }
         }
-
-        fn is_available(&self) -> bool {
-            self.available
-        }
     }
 
     #[tokio::test]
     async fn test_hardware_device_trait() {
         let device = MockDevice {
             id: "mock-1".to_string(),
-            device_type: "MOCK".to_string(),
+            device_type: HardwareType::Cpu,
             memory: 1024,
             available: true,
         };
 
         assert_eq!(device.id(), "mock-1");
-        assert_eq!(device.device_type(), "MOCK");
+        assert_eq!(device.device_type(), HardwareType::Cpu);
         assert_eq!(device.available_memory(), 1024);
         assert_eq!(device.total_memory(), 1024);
         assert!(device.is_available());
+        assert!(device.capabilities().supports("test"));
 
         let task = Task {
             id: "test-task".to_string(),
             data: vec![],
             operation: "test".to_string(),
+            kernel: None,
+            devices: Vec::new(),
+            cancel: CancelToken::new(),
+            limit: None,
         };
 
         let result = device.execute_task(&task).await;
@@ -81,7 +126,7 @@ mod tests {
     async fn test_hardware_device_unavailable() {
         let device = MockDevice {
             id: "mock-2".to_string(),
-            device_type: "MOCK".to_string(),
+            device_type: HardwareType::Cpu,
             memory: 1024,
             available: false,
         };
@@ -92,6 +137,10 @@ mod tests {
             id: "test-task".to_string(),
             data: vec![],
             operation: "test".to_string(),
+            kernel: None,
+            devices: Vec::new(),
+            cancel: CancelToken::new(),
+            limit: None,
         };
 
         let result = device.execute_task(&task).await;
@@ -103,4 +152,4 @@ mod tests {
             _ => panic!("Expected DeviceUnavailable error"),
         }
     }
-}
\ No newline at end of file
+}